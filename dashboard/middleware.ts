@@ -3,8 +3,16 @@ import { NextRequest, NextResponse } from 'next/server';
 const DEFAULT_ADMIN_USER = 'admin';
 const AUTH_REALM = 'Fitness Dashboard Settings';
 
-function isProtectedPath(pathname: string): boolean {
-  return pathname.startsWith('/settings') || pathname === '/api/profiles';
+function isProtectedPath(pathname: string, method: string): boolean {
+  return (
+    pathname.startsWith('/settings') ||
+    (pathname === '/api/chat' && method === 'DELETE') ||
+    pathname === '/api/profiles' ||
+    pathname === '/api/profiles/active' ||
+    pathname === '/api/config' ||
+    pathname === '/api/reload_exercises' ||
+    pathname === '/api/maintenance'
+  );
 }
 
 function parseBasicAuth(authHeader: string): { username: string; password: string } | null {
@@ -43,7 +51,7 @@ function unauthorizedResponse(): NextResponse {
 }
 
 export function middleware(request: NextRequest): NextResponse {
-  if (!isProtectedPath(request.nextUrl.pathname)) {
+  if (!isProtectedPath(request.nextUrl.pathname, request.method)) {
     return NextResponse.next();
   }
 
@@ -77,5 +85,5 @@ export function middleware(request: NextRequest): NextResponse {
 }
 
 export const config = {
-  matcher: ['/settings/:path*', '/api/profiles'],
+  matcher: ['/settings/:path*', '/api/profiles', '/api/profiles/active', '/api/config', '/api/reload_exercises', '/api/maintenance', '/api/chat'],
 };
@@ -3,14 +3,14 @@
 import React, { useState } from 'react';
 import { useRouter } from 'next/navigation';
 
-export default function ForcePullButton() {
+export default function ForcePullButton({ deep = false }: { deep?: boolean }) {
     const [loading, setLoading] = useState(false);
     const router = useRouter();
 
     const handlePull = async () => {
         setLoading(true);
         try {
-            await fetch('/api/force-pull', { method: 'POST' });
+            await fetch(deep ? '/api/force-pull?deep=true' : '/api/force-pull', { method: 'POST' });
             router.refresh();
         } catch (err) {
             console.error(err);
@@ -27,12 +27,12 @@ export default function ForcePullButton() {
             {loading ? (
                 <>
                     <span className="w-4 h-4 rounded-full border-2 border-white border-t-transparent animate-spin"></span>
-                    Syncing...
+                    {deep ? 'Re-analyzing...' : 'Syncing...'}
                 </>
             ) : (
                 <>
                     <svg xmlns="http://www.w3.org/2000/svg" width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2" strokeLinecap="round" strokeLinejoin="round"><path d="M21 2v6h-6M3 12a9 9 0 1 0 2-7.7L2 6" /><path d="M21 8A9 9 0 0 0 4.3 3.3L8 2" /></svg>
-                    Force Sync
+                    {deep ? 'Sync & Re-analyze' : 'Force Sync'}
                 </>
             )}
         </button>
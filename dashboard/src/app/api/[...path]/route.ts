@@ -1,22 +1,53 @@
 import { NextRequest, NextResponse } from 'next/server';
 
 const ALLOWED_PATHS = new Set([
+  'dashboard',
   'progression',
   'progression/deltas',
+  'exercise_resolutions',
+  'exercise_resolutions/clear',
   'recovery',
   'recovery/history',
   'workouts/today',
   'workouts/upcoming',
+  'workouts/next',
+  'races',
+  'plan.ics',
+  'plan.csv',
+  'activities',
   'muscle_heatmap',
+  'muscle_volume',
+  'muscle_recovery',
+  'readiness_recommendation',
+  'zones',
+  'weekly_summary',
+  'weekly_summary/review',
   'chat',
   'generate',
+  'generate/undo',
   'profiles',
+  'profiles/active',
+  'config',
+  'reload_exercises',
+  'maintenance',
   'analyze',
   'analyze/upcoming',
   'force-pull',
   'course/create',
 ]);
 
+function isAllowedPath(segments: string[]): boolean {
+  if (ALLOWED_PATHS.has(segments.join('/'))) {
+    return true;
+  }
+  // analysis/{activity_id}
+  if (segments.length === 2 && segments[0] === 'analysis' && /^\d+$/.test(segments[1])) {
+    return true;
+  }
+  // exercise/{name}/history
+  return segments.length === 3 && segments[0] === 'exercise' && segments[2] === 'history';
+}
+
 export const dynamic = 'force-dynamic';
 export const runtime = 'nodejs';
 
@@ -26,11 +57,10 @@ function backendApiBaseUrl(): string {
 
 async function forwardRequest(
   request: NextRequest,
-  method: 'GET' | 'POST' | 'PUT',
+  method: 'GET' | 'POST' | 'PUT' | 'DELETE',
   pathSegments: string[],
 ): Promise<NextResponse> {
-  const path = pathSegments.join('/');
-  if (!ALLOWED_PATHS.has(path)) {
+  if (!isAllowedPath(pathSegments)) {
     return NextResponse.json(
       { status: 'error', message: 'Unknown API route' },
       { status: 404 },
@@ -56,7 +86,8 @@ async function forwardRequest(
     }
   }
 
-  const targetUrl = `${backendApiBaseUrl()}/api/${path}`;
+  const path = pathSegments.map(encodeURIComponent).join('/');
+  const targetUrl = `${backendApiBaseUrl()}/api/${path}${request.nextUrl.search}`;
 
   try {
     const response = await fetch(targetUrl, {
@@ -70,11 +101,21 @@ async function forwardRequest(
     const responseContentType =
       response.headers.get('content-type') || 'application/json; charset=utf-8';
 
+    const responseHeaders: Record<string, string> = {
+      'content-type': responseContentType,
+    };
+    const contentDisposition = response.headers.get('content-disposition');
+    if (contentDisposition) {
+      responseHeaders['content-disposition'] = contentDisposition;
+    }
+    const requestId = response.headers.get('x-request-id');
+    if (requestId) {
+      responseHeaders['x-request-id'] = requestId;
+    }
+
     return new NextResponse(responseBody, {
       status: response.status,
-      headers: {
-        'content-type': responseContentType,
-      },
+      headers: responseHeaders,
     });
   } catch {
     return NextResponse.json(
@@ -111,3 +152,11 @@ export async function PUT(
   const { path } = await context.params;
   return forwardRequest(request, 'PUT', path || []);
 }
+
+export async function DELETE(
+  request: NextRequest,
+  context: { params: Promise<RouteParams> },
+): Promise<NextResponse> {
+  const { path } = await context.params;
+  return forwardRequest(request, 'DELETE', path || []);
+}
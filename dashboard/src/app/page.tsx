@@ -26,6 +26,7 @@ type ProgressionItem = {
   reps: number;
   date: string;
   history?: { weight: number; reps: number; date: string }[];
+  unit?: string;
 };
 
 type CompletedWorkout = {
@@ -190,6 +191,7 @@ type WeeklyDelta = {
   this_week_reps: number;
   last_week_weight: number;
   last_week_reps: number;
+  unit?: string;
 };
 
 async function fetchWeeklyDeltas(): Promise<WeeklyDelta[]> {
@@ -223,6 +225,20 @@ type RecoveryItem = {
   hrv_weekly_avg: number | null;
   hrv_last_night_avg: number | null;
   rhr_trend: number[];
+  daily_summary?: {
+    steps: number | null;
+    floors: number | null;
+    intensity_minutes: number | null;
+    stress_avg: number | null;
+    active_calories: number | null;
+  } | null;
+  sleep_detail?: {
+    total_seconds: number | null;
+    deep_seconds: number | null;
+    rem_seconds: number | null;
+    light_seconds: number | null;
+    awake_seconds: number | null;
+  } | null;
 };
 
 async function fetchRecovery(): Promise<RecoveryItem> {
@@ -378,6 +394,7 @@ export default async function Dashboard() {
           </div>
           <div className="flex items-center gap-2">
             <ForcePullButton />
+            <ForcePullButton deep />
             <Link href="/settings" className="px-3 py-2 glass-panel text-white rounded-xl hover:bg-white/10 transition-all flex items-center gap-2 h-[38px] text-xs font-medium">
               <svg xmlns="http://www.w3.org/2000/svg" width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2" strokeLinecap="round" strokeLinejoin="round"><path d="M12.22 2h-.44a2 2 0 0 0-2 2v.18a2 2 0 0 1-1 1.73l-.43.25a2 2 0 0 1-2 0l-.15-.08a2 2 0 0 0-2.73.73l-.22.38a2 2 0 0 0 .73 2.73l.15.1a2 2 0 0 1 1 1.72v.51a2 2 0 0 1-1 1.74l-.15.09a2 2 0 0 0-.73 2.73l.22.38a2 2 0 0 0 2.73.73l.15-.08a2 2 0 0 1 2 0l.43.25a2 2 0 0 1 1 1.73V20a2 2 0 0 0 2 2h.44a2 2 0 0 0 2-2v-.18a2 2 0 0 1 1-1.73l.43-.25a2 2 0 0 1 2 0l.15.08a2 2 0 0 0 2.73-.73l.22-.39a2 2 0 0 0-.73-2.73l-.15-.08a2 2 0 0 1-1-1.74v-.5a2 2 0 0 1 1-1.74l.15-.09a2 2 0 0 0 .73-2.73l-.22-.38a2 2 0 0 0-2.73-.73l-.15.08a2 2 0 0 1-2 0l-.43-.25a2 2 0 0 1-1-1.73V4a2 2 0 0 0-2-2z"></path><circle cx="12" cy="12" r="3"></circle></svg>
               Settings
@@ -626,7 +643,7 @@ export default async function Dashboard() {
                       <div className="mt-2 flex items-center justify-between">
                         <div className="flex items-baseline gap-1.5">
                           <span className="text-2xl font-extrabold tracking-tight text-white">{delta.this_week_weight.toFixed(1)}</span>
-                          <span className="text-gray-500 text-xs">kg × {delta.this_week_reps}</span>
+                          <span className="text-gray-500 text-xs">{delta.unit ?? 'kg'} × {delta.this_week_reps}</span>
                         </div>
                         <span className={`text-lg font-bold ${isUp ? 'text-emerald-400' : isDown ? 'text-red-400' : 'text-gray-500'}`} style={{ filter: isUp ? 'drop-shadow(0 0 6px rgba(52,211,153,0.4))' : isDown ? 'drop-shadow(0 0 6px rgba(248,113,113,0.4))' : 'none' }}>
                           {isUp ? '↑' : isDown ? '↓' : '='}
@@ -634,10 +651,10 @@ export default async function Dashboard() {
                       </div>
                       {delta.last_week_weight > 0 && (
                         <div className="mt-1.5 pt-1.5 border-t border-white/5 text-[11px] text-gray-500">
-                          Last: {delta.last_week_weight.toFixed(1)}kg × {delta.last_week_reps}
+                          Last: {delta.last_week_weight.toFixed(1)}{delta.unit ?? 'kg'} × {delta.last_week_reps}
                           {weightDiff !== 0 && (
                             <span className={`ml-1.5 ${weightDiff > 0 ? 'text-emerald-400' : 'text-red-400'}`}>
-                              ({weightDiff > 0 ? '+' : ''}{weightDiff.toFixed(1)}kg)
+                              ({weightDiff > 0 ? '+' : ''}{weightDiff.toFixed(1)}{delta.unit ?? 'kg'})
                             </span>
                           )}
                         </div>
@@ -666,7 +683,7 @@ export default async function Dashboard() {
                   <div className="mt-2 flex items-center justify-between">
                     <div className="flex items-baseline gap-1.5">
                       <span className="text-3xl font-extrabold tracking-tight text-white group-hover:text-red-100 transition-colors duration-300">{item.max_weight.toFixed(1)}</span>
-                      <span className="text-gray-500 text-xs font-medium">kg</span>
+                      <span className="text-gray-500 text-xs font-medium">{item.unit ?? 'kg'}</span>
                     </div>
                     {item.history && item.history.length > 0 && (
                       <div className="w-16 h-8">
@@ -7,6 +7,10 @@ type Profile = {
     goals: string[];
     constraints: string[];
     available_equipment: string[];
+    rest_days?: string[];
+    coach_persona?: string;
+    planning_horizon_days?: number;
+    units?: 'metric' | 'imperial';
 };
 
 type ProfilesData = {
@@ -51,8 +55,13 @@ export default function SettingsPage() {
             if (!res.ok) {
                 let message = 'Failed to save profiles';
                 try {
-                    const response = await res.json() as { message?: string };
-                    if (response?.message) {
+                    const response = await res.json() as {
+                        message?: string;
+                        errors?: { field: string; profile: string | null; message: string }[];
+                    };
+                    if (response?.errors?.length) {
+                        message = response.errors.map(e => e.message).join(' ');
+                    } else if (response?.message) {
                         message = response.message;
                     }
                 } catch {
@@ -2,7 +2,7 @@ use anyhow::{anyhow, Context, Result};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use tracing::info;
+use tracing::{info, warn};
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -16,6 +16,40 @@ struct GeminiRequest {
 #[serde(rename_all = "camelCase")]
 struct GenerationConfig {
     max_output_tokens: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f64>,
+}
+
+impl From<GenerationSettings> for GenerationConfig {
+    fn from(settings: GenerationSettings) -> Self {
+        Self {
+            max_output_tokens: settings.max_output_tokens,
+            temperature: settings.temperature,
+            top_p: settings.top_p,
+        }
+    }
+}
+
+/// Sampling settings for one kind of Gemini request. Unset `temperature` and
+/// `top_p` leave Gemini's model defaults in place.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct GenerationSettings {
+    pub max_output_tokens: i32,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+}
+
+impl Default for GenerationSettings {
+    fn default() -> Self {
+        Self {
+            max_output_tokens: 8192,
+            temperature: None,
+            top_p: None,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -67,22 +101,74 @@ struct ContentResponse {
     parts: Vec<Part>,
 }
 
+/// Production Gemini REST API root.
+pub const GEMINI_API_BASE: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+/// Opening line of every system instruction unless `coach_persona` overrides it.
+pub const DEFAULT_COACH_PERSONA: &str = "You are an elite Multi-Sport Coach.";
+
 pub struct AiClient {
     client: Client,
     api_key: String,
     model: String,
+    base_url: String,
+    system_prompt: String,
+    /// Used by `generate_workout` (plans, analyses and other one-shot prompts).
+    workout_generation: GenerationSettings,
+    /// Used by `chat_with_history`.
+    chat_generation: GenerationSettings,
 }
 
 impl AiClient {
     pub fn new(api_key: String, model: String) -> Self {
+        Self::new_with_base_url(api_key, model, GEMINI_API_BASE)
+    }
+
+    /// Client for the configured Gemini key and `GEMINI_MODEL` (default
+    /// `gemini-3-flash-preview`), speaking as the active coach persona.
+    pub fn from_config(config: &crate::config::AppConfig) -> Self {
+        let model =
+            std::env::var("GEMINI_MODEL").unwrap_or_else(|_| "gemini-3-flash-preview".to_string());
+        Self::new(config.gemini_api_key.clone(), model)
+            .with_system_prompt(crate::profiles::coach_persona(config))
+            .with_generation_config(config)
+    }
+
+    /// Same as `new`, but targets `base_url` (e.g. a local mock server)
+    /// instead of the production Gemini API.
+    pub fn new_with_base_url(api_key: String, model: String, base_url: &str) -> Self {
         info!("Initialized AiClient with model: {}", model);
         AiClient {
             client: Client::new(),
             api_key,
             model,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            system_prompt: DEFAULT_COACH_PERSONA.to_string(),
+            workout_generation: GenerationSettings::default(),
+            chat_generation: GenerationSettings::default(),
         }
     }
 
+    /// Replaces the coaching persona that opens the system instruction.
+    pub fn with_system_prompt(mut self, system_prompt: impl Into<String>) -> Self {
+        self.system_prompt = system_prompt.into();
+        self
+    }
+
+    /// Applies the configured `workout_generation` and `chat_generation` settings.
+    pub fn with_generation_config(mut self, config: &crate::config::AppConfig) -> Self {
+        self.workout_generation = config.workout_generation;
+        self.chat_generation = config.chat_generation;
+        self
+    }
+
+    fn generate_content_url(&self) -> String {
+        format!(
+            "{}/models/{}:generateContent?key={}",
+            self.base_url, self.model, self.api_key
+        )
+    }
+
     fn get_valid_exercises_string() -> String {
         let mut names = Vec::new();
         if let Ok(content) = std::fs::read_to_string("Garmin Exercises Database - Exercises.csv") {
@@ -103,8 +189,7 @@ impl AiClient {
     }
 
     pub async fn generate_workout(&self, prompt: &str) -> Result<String> {
-        let mut sys_text =
-            "You are an elite Multi-Sport Coach. Follow instructions precisely. When creating a structured workout, incorporate supersets whenever possible. Use 'sets' and 'reps' for multiple iterations of an exercise. To represent a superset, group the multiple exercises into an 'exercises' array inside the workout step, specifying 'reps' and 'weight' for each sub-exercise, and 'sets' at the top step level.".to_string();
+        let mut sys_text = format!("{} Follow instructions precisely. When creating a structured workout, incorporate supersets whenever possible. Use 'sets' and 'reps' for multiple iterations of an exercise. To represent a superset, group the multiple exercises into an 'exercises' array inside the workout step, specifying 'reps' and 'weight' for each sub-exercise, and 'sets' at the top step level.", self.system_prompt);
         sys_text.push_str(&Self::get_valid_exercises_string());
 
         let request_body = GeminiRequest {
@@ -117,23 +202,21 @@ impl AiClient {
                     text: prompt.to_string(),
                 }],
             }],
-            generation_config: Some(GenerationConfig {
-                max_output_tokens: 8192,
-            }),
+            generation_config: Some(self.workout_generation.into()),
         };
 
-        let url = format!(
-            "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
-            self.model, self.api_key
-        );
+        let url = self.generate_content_url();
 
+        let started = std::time::Instant::now();
         let response = self
             .client
             .post(&url)
             .header("Content-Type", "application/json")
             .json(&request_body)
             .send()
-            .await?;
+            .await;
+        crate::metrics::record_gemini_call(started.elapsed());
+        let response = response?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -189,7 +272,7 @@ impl AiClient {
             });
         }
 
-        let mut sys_instruction = "You are an elite Multi-Sport Coach. Follow instructions precisely. The user is asking questions about the generated workout plan, their Garmin health metrics, or fitness in general. You will respond as the coach in a friendly and conversational, yet brief manner.\nIf you decide to actively add or reschedule a workout for the athlete, YOU MUST output a raw JSON codeblock starting with ```json containing an array of Garmin workout objects. Use the exact formats expected representing phase, exercise, weight, sets, reps etc.\nWhen creating a structured workout, incorporate supersets whenever possible. Use 'sets' and 'reps' for multiple iterations of an exercise. To represent a superset, group the multiple exercises into an 'exercises' array inside the workout step, specifying 'reps' and 'weight' for each sub-exercise, and 'sets' at the top step level.\nALWAYS reply with natural conversation along with the json block if adding a workout.".to_string();
+        let mut sys_instruction = format!("{} Follow instructions precisely. The user is asking questions about the generated workout plan, their Garmin health metrics, or fitness in general. You will respond as the coach in a friendly and conversational, yet brief manner.\nIf you decide to actively add or reschedule a workout for the athlete, YOU MUST output a raw JSON codeblock starting with ```json containing an array of Garmin workout objects. Use the exact formats expected representing phase, exercise, weight, sets, reps etc.\nWhen creating a structured workout, incorporate supersets whenever possible. Use 'sets' and 'reps' for multiple iterations of an exercise. To represent a superset, group the multiple exercises into an 'exercises' array inside the workout step, specifying 'reps' and 'weight' for each sub-exercise, and 'sets' at the top step level.\nALWAYS reply with natural conversation along with the json block if adding a workout.", self.system_prompt);
 
         if let Some(ctx) = context {
             sys_instruction.push_str("\n\n=== LIVE ATHLETE CONTEXT ===\n");
@@ -205,23 +288,21 @@ impl AiClient {
                 }],
             }),
             contents,
-            generation_config: Some(GenerationConfig {
-                max_output_tokens: 8192,
-            }),
+            generation_config: Some(self.chat_generation.into()),
         };
 
-        let url = format!(
-            "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
-            self.model, self.api_key
-        );
+        let url = self.generate_content_url();
 
+        let started = std::time::Instant::now();
         let response = self
             .client
             .post(&url)
             .header("Content-Type", "application/json")
             .json(&request_body)
             .send()
-            .await?;
+            .await;
+        crate::metrics::record_gemini_call(started.elapsed());
+        let response = response?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -258,30 +339,72 @@ impl AiClient {
         Err(anyhow!("No valid content returned from Gemini"))
     }
 
+    /// The first valid ```json block only; the pipeline and chat use
+    /// `extract_workouts` so a second block isn't lost.
+    #[allow(dead_code)]
     pub fn extract_json_block(markdown: &str) -> Result<String> {
+        Self::extract_all_json_blocks(markdown)
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Could not extract JSON block from LLM response"))
+    }
+
+    /// Every ```json block in `markdown` that holds valid JSON, in order; blocks
+    /// that don't parse are skipped. A response without blocks that is itself
+    /// valid JSON counts as one block.
+    pub fn extract_all_json_blocks(markdown: &str) -> Vec<String> {
         let start_marker = "```json";
         let end_marker = "```";
 
-        if let Some(start_idx) = markdown.find(start_marker) {
+        let mut blocks = Vec::new();
+        let mut found_marker = false;
+        let mut rest = markdown;
+        while let Some(start_idx) = rest.find(start_marker) {
+            found_marker = true;
             let json_start = start_idx + start_marker.len();
-            if let Some(end_idx) = markdown[json_start..].find(end_marker) {
-                let json_content = &markdown[json_start..json_start + end_idx];
-                return Ok(json_content.trim().to_string());
+            let Some(end_idx) = rest[json_start..].find(end_marker) else {
+                break;
+            };
+            let json_content = rest[json_start..json_start + end_idx].trim();
+            if serde_json::from_str::<Value>(json_content).is_ok() {
+                blocks.push(json_content.to_string());
+            } else {
+                warn!("Skipping a ```json block that is not valid JSON");
             }
+            rest = &rest[json_start + end_idx + end_marker.len()..];
         }
 
-        // If no markers, maybe the raw string is just valid JSON
-        if serde_json::from_str::<Value>(markdown).is_ok() {
-            return Ok(markdown.trim().to_string());
+        if !found_marker && serde_json::from_str::<Value>(markdown).is_ok() {
+            blocks.push(markdown.trim().to_string());
+        }
+        blocks
+    }
+
+    /// Workouts from all JSON blocks in `markdown`: arrays are concatenated and
+    /// a lone object counts as one workout.
+    pub fn extract_workouts(markdown: &str) -> Result<Vec<Value>> {
+        let blocks = Self::extract_all_json_blocks(markdown);
+        if blocks.is_empty() {
+            return Err(anyhow!("Could not extract JSON block from LLM response"));
         }
 
-        Err(anyhow!("Could not extract JSON block from LLM response"))
+        let mut workouts = Vec::new();
+        for block in blocks {
+            match serde_json::from_str::<Value>(&block)? {
+                Value::Array(items) => workouts.extend(items),
+                other => workouts.push(other),
+            }
+        }
+        Ok(workouts)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::AiClient;
+    use super::{AiClient, GenerationSettings};
+    use crate::config::AppConfig;
+    use wiremock::matchers::{body_string_contains, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
 
     #[test]
     fn extract_json_block_from_markdown() {
@@ -290,6 +413,47 @@ mod tests {
         assert_eq!(extracted, "[{\"workoutName\":\"FJ-AI:Test\"}]");
     }
 
+    #[test]
+    fn json_block_is_extracted_from_markdown() {
+        let markdown = "Here is your plan:\n```json\n[{\"workoutName\":\"FJ-AI:Test\"}]\n```";
+        assert_eq!(
+            AiClient::extract_all_json_blocks(markdown),
+            vec!["[{\"workoutName\":\"FJ-AI:Test\"}]".to_string()]
+        );
+    }
+
+    #[test]
+    fn workouts_from_every_json_block_are_merged() {
+        let markdown = "Moved your run:\n```json\n[{\"workoutName\":\"A\"}]\n```\nAlso added:\n```json\n{\"workoutName\":\"B\"}\n```\nEnjoy!";
+        assert_eq!(AiClient::extract_all_json_blocks(markdown).len(), 2);
+
+        let names: Vec<_> = AiClient::extract_workouts(markdown)
+            .unwrap()
+            .iter()
+            .map(|w| w["workoutName"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(names, ["A", "B"]);
+    }
+
+    #[test]
+    fn invalid_json_blocks_are_skipped() {
+        let markdown = "```json\n[{\"workoutName\": \"A\",]\n```\nthen\n```json\n[{\"workoutName\":\"B\"}]\n```";
+        assert_eq!(
+            AiClient::extract_all_json_blocks(markdown),
+            vec!["[{\"workoutName\":\"B\"}]".to_string()]
+        );
+        assert_eq!(AiClient::extract_workouts(markdown).unwrap().len(), 1);
+
+        assert!(AiClient::extract_all_json_blocks("```json\nnot json\n```").is_empty());
+        assert!(AiClient::extract_workouts("No plan today.").is_err());
+    }
+
+    #[test]
+    fn extract_json_block_returns_the_first_valid_block() {
+        let markdown = "```json\nnot json\n```\n```json\n{\"a\":1}\n```\n```json\n{\"b\":2}\n```";
+        assert_eq!(AiClient::extract_json_block(markdown).unwrap(), "{\"a\":1}");
+    }
+
     #[test]
     fn extract_json_block_from_raw_json() {
         let raw = "{\"ok\":true}";
@@ -302,4 +466,113 @@ mod tests {
         let invalid = "not json";
         assert!(AiClient::extract_json_block(invalid).is_err());
     }
+
+    #[test]
+    fn raw_json_counts_as_one_block() {
+        let raw = "{\"ok\":true}";
+        assert_eq!(
+            AiClient::extract_all_json_blocks(raw),
+            vec!["{\"ok\":true}".to_string()]
+        );
+    }
+
+    #[test]
+    fn invalid_payload_yields_no_blocks() {
+        let invalid = "not json";
+        assert!(AiClient::extract_all_json_blocks(invalid).is_empty());
+    }
+
+    #[tokio::test]
+    async fn generate_workout_reads_first_candidate_from_base_url() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/models/test-model:generateContent"))
+            .and(query_param("key", "test-key"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "candidates": [{ "content": { "parts": [{ "text": "Rest today." }] } }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = AiClient::new_with_base_url(
+            "test-key".to_string(),
+            "test-model".to_string(),
+            &server.uri(),
+        );
+        let text = client.generate_workout("plan my week").await.unwrap();
+        assert_eq!(text, "Rest today.");
+    }
+
+    #[tokio::test]
+    async fn configured_persona_is_sent_as_system_instruction() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/models/test-model:generateContent"))
+            .and(body_string_contains(
+                "\"systemInstruction\":{\"parts\":[{\"text\":\"You are a gentle rehab coach. Follow",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "candidates": [{ "content": { "parts": [{ "text": "Take it easy." }] } }]
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let client = AiClient::new_with_base_url(
+            "test-key".to_string(),
+            "test-model".to_string(),
+            &server.uri(),
+        )
+        .with_system_prompt("You are a gentle rehab coach.");
+        client.generate_workout("plan my week").await.unwrap();
+        let history = vec![("user".to_string(), "How is my knee?".to_string(), 0)];
+        client.chat_with_history(&history, None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn workout_and_chat_requests_use_their_own_generation_settings() {
+        let server = MockServer::start().await;
+        let reply = serde_json::json!({
+            "candidates": [{ "content": { "parts": [{ "text": "ok" }] } }]
+        });
+        Mock::given(method("POST"))
+            .and(body_string_contains(
+                "\"generationConfig\":{\"maxOutputTokens\":16384,\"temperature\":0.2,\"topP\":0.8}",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(reply.clone()))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_string_contains(
+                "\"generationConfig\":{\"maxOutputTokens\":8192,\"temperature\":0.9}",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(reply))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = AppConfig {
+            workout_generation: GenerationSettings {
+                max_output_tokens: 16384,
+                temperature: Some(0.2),
+                top_p: Some(0.8),
+            },
+            chat_generation: GenerationSettings {
+                temperature: Some(0.9),
+                ..GenerationSettings::default()
+            },
+            ..AppConfig::default()
+        };
+        let client = AiClient::new_with_base_url(
+            "test-key".to_string(),
+            "test-model".to_string(),
+            &server.uri(),
+        )
+        .with_generation_config(&config);
+        client.generate_workout("plan my week").await.unwrap();
+        let history = vec![("user".to_string(), "Ready?".to_string(), 0)];
+        client.chat_with_history(&history, None).await.unwrap();
+    }
 }
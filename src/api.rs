@@ -8,7 +8,7 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{BTreeMap, VecDeque},
+    collections::VecDeque,
     net::SocketAddr,
     path::Path,
     sync::Arc,
@@ -16,20 +16,24 @@ use std::{
 };
 use tokio::sync::Mutex;
 use tower_http::cors::CorsLayer;
-use tracing::{error, info};
+use tracing::{debug, error, info, Instrument};
 
 use crate::coaching::Coach;
 use crate::db::Database;
 use crate::garmin_client::GarminClient;
+use crate::profiles::{
+    load_profiles, profiles_path, save_profiles, set_active_profile, validate_profiles_payload,
+    ProfilesPayload,
+};
 
 const MAX_CHAT_INPUT_LEN: usize = 65_536;
-const MAX_PROFILE_NAME_LEN: usize = 64;
-const MAX_PROFILE_ITEMS: usize = 64;
-const MAX_PROFILE_ITEM_LEN: usize = 256;
-fn profiles_path() -> String {
-    std::env::var("PROFILES_PATH").unwrap_or_else(|_| "data/profiles.json".to_string())
-}
-
+const MUSCLE_HEATMAP_DAYS: u32 = 14;
+/// Progression lifts included in `/api/dashboard`.
+const DASHBOARD_TOP_LIFTS: usize = 5;
+/// Default window and page size of `/api/activities`.
+const ACTIVITIES_DEFAULT_DAYS: i64 = 30;
+const ACTIVITIES_DEFAULT_LIMIT: usize = 50;
+const ACTIVITIES_MAX_LIMIT: usize = 200;
 #[derive(Serialize)]
 pub struct ChatMessage {
     pub role: String,
@@ -42,9 +46,17 @@ pub struct ChatInput {
     pub content: String,
 }
 
+#[derive(Deserialize)]
+pub struct ActiveProfileInput {
+    pub name: String,
+}
+
 #[derive(Deserialize)]
 pub struct AnalyzeActivityInput {
     pub activity: serde_json::Value,
+    /// Re-run the analysis even when one is stored, replacing it.
+    #[serde(default)]
+    pub force: bool,
 }
 
 #[derive(Deserialize)]
@@ -60,42 +72,33 @@ pub struct AnalyzeUpcomingInput {
 }
 
 #[derive(Deserialize)]
-pub struct CreateCourseInput {
-    pub workout: crate::models::ScheduledWorkout,
-    pub start_latitude: Option<f64>,
-    pub start_longitude: Option<f64>,
+pub struct GarminLoginInput {
+    pub email: String,
+    pub password: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(deny_unknown_fields)]
-struct ProfileConfigPayload {
-    #[serde(default)]
-    goals: Vec<String>,
-    #[serde(default)]
-    constraints: Vec<String>,
-    #[serde(default)]
-    available_equipment: Vec<String>,
-    #[serde(default)]
-    auto_analyze_sports: Vec<String>,
+#[derive(Deserialize)]
+pub struct GarminMfaInput {
+    pub session_id: String,
+    pub code: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(deny_unknown_fields)]
-struct ProfilesPayload {
-    active_profile: String,
-    #[serde(default)]
-    profiles: BTreeMap<String, ProfileConfigPayload>,
+#[derive(Deserialize)]
+pub struct CreateCourseInput {
+    pub workout: crate::models::ScheduledWorkout,
+    pub start_latitude: Option<f64>,
+    pub start_longitude: Option<f64>,
 }
 
 #[derive(Debug)]
-struct SlidingWindowLimiter {
+pub(crate) struct SlidingWindowLimiter {
     max_requests: usize,
     window: Duration,
     hits: VecDeque<Instant>,
 }
 
 impl SlidingWindowLimiter {
-    fn new(max_requests: usize, window: Duration) -> Self {
+    pub(crate) fn new(max_requests: usize, window: Duration) -> Self {
         Self {
             max_requests,
             window,
@@ -103,7 +106,7 @@ impl SlidingWindowLimiter {
         }
     }
 
-    fn allow(&mut self) -> bool {
+    pub(crate) fn allow(&mut self) -> bool {
         let now = Instant::now();
         while let Some(oldest) = self.hits.front() {
             if now.duration_since(*oldest) > self.window {
@@ -130,6 +133,9 @@ pub struct ApiState {
     coach: Arc<Coach>,
     chat_limiter: Arc<Mutex<SlidingWindowLimiter>>,
     generate_limiter: Arc<Mutex<SlidingWindowLimiter>>,
+    login_limiter: Arc<Mutex<SlidingWindowLimiter>>,
+    pending_logins: Arc<Mutex<crate::garmin_login::PendingLogins>>,
+    generation_guard: crate::generation::GenerationGuard,
 }
 
 #[derive(Serialize)]
@@ -146,6 +152,8 @@ pub struct ProgressionResponse {
     pub reps: i32,
     pub date: String,
     pub history: Vec<TrendPoint>,
+    /// Unit of every weight in this response (`kg` or `lb`, per the active profile).
+    pub unit: &'static str,
 }
 
 #[derive(Serialize)]
@@ -155,6 +163,7 @@ pub struct WeeklyDeltaResponse {
     pub this_week_reps: i32,
     pub last_week_weight: f64,
     pub last_week_reps: i32,
+    pub unit: &'static str,
 }
 
 #[derive(Serialize)]
@@ -163,6 +172,26 @@ pub struct TodayWorkoutsResponse {
     pub planned: Vec<crate::models::ScheduledWorkout>,
 }
 
+#[derive(Serialize)]
+pub struct DashboardResponse {
+    pub recovery: RecoveryResponse,
+    pub today: TodayWorkoutsResponse,
+    pub upcoming_races: Vec<crate::models::ScheduledWorkout>,
+    pub top_progression: Vec<ProgressionResponse>,
+    pub muscle_heatmap: Vec<crate::models::ExerciseMuscleMap>,
+}
+
+/// An upcoming race or event with its countdown, for `/api/races`.
+#[derive(Debug, Serialize)]
+pub struct RaceCountdown {
+    pub title: Option<String>,
+    pub date: String,
+    pub sport: Option<String>,
+    /// Planned distance in meters.
+    pub distance: Option<f64>,
+    pub days_until: i64,
+}
+
 #[derive(Serialize)]
 pub struct RecoveryResponse {
     pub body_battery: Option<i32>,
@@ -172,6 +201,8 @@ pub struct RecoveryResponse {
     pub hrv_weekly_avg: Option<i32>,
     pub hrv_last_night_avg: Option<i32>,
     pub rhr_trend: Vec<i32>,
+    pub daily_summary: Option<crate::models::DailySummary>,
+    pub sleep_detail: Option<crate::models::SleepDetail>,
 }
 
 fn cors_origins(raw_origins: &str) -> Vec<HeaderValue> {
@@ -224,129 +255,10 @@ fn error_response(status: StatusCode, message: &str) -> (StatusCode, Json<serde_
     )
 }
 
-fn normalize_profile_list(
-    values: &[String],
-    profile_name: &str,
-    field_name: &str,
-) -> Result<Vec<String>, String> {
-    if values.len() > MAX_PROFILE_ITEMS {
-        return Err(format!(
-            "Profile '{}' has too many '{}' entries (max {}).",
-            profile_name, field_name, MAX_PROFILE_ITEMS
-        ));
-    }
-
-    let mut normalized = Vec::new();
-    for value in values {
-        let trimmed = value.trim();
-        if trimmed.is_empty() {
-            continue;
-        }
-
-        if trimmed.chars().count() > MAX_PROFILE_ITEM_LEN {
-            return Err(format!(
-                "Profile '{}' has an entry in '{}' that exceeds {} characters.",
-                profile_name, field_name, MAX_PROFILE_ITEM_LEN
-            ));
-        }
-
-        normalized.push(trimmed.to_string());
-    }
-
-    Ok(normalized)
-}
-
-fn validate_profiles_payload(payload: ProfilesPayload) -> Result<ProfilesPayload, String> {
-    let active_profile = payload.active_profile.trim();
-    if active_profile.is_empty() {
-        return Err("active_profile cannot be empty.".to_string());
-    }
-    if active_profile.chars().count() > MAX_PROFILE_NAME_LEN {
-        return Err(format!(
-            "active_profile exceeds {} characters.",
-            MAX_PROFILE_NAME_LEN
-        ));
-    }
-    if payload.profiles.is_empty() {
-        return Err("profiles must include at least one profile.".to_string());
-    }
-
-    let mut normalized_profiles = BTreeMap::new();
-    for (raw_name, profile) in payload.profiles {
-        let profile_name = raw_name.trim();
-        if profile_name.is_empty() {
-            return Err("Profile names cannot be empty.".to_string());
-        }
-        if profile_name.chars().count() > MAX_PROFILE_NAME_LEN {
-            return Err(format!(
-                "Profile name '{}' exceeds {} characters.",
-                profile_name, MAX_PROFILE_NAME_LEN
-            ));
-        }
-        if normalized_profiles.contains_key(profile_name) {
-            return Err(format!("Duplicate profile name '{}'.", profile_name));
-        }
-
-        let normalized_profile = ProfileConfigPayload {
-            goals: normalize_profile_list(&profile.goals, profile_name, "goals")?,
-            constraints: normalize_profile_list(&profile.constraints, profile_name, "constraints")?,
-            available_equipment: normalize_profile_list(
-                &profile.available_equipment,
-                profile_name,
-                "available_equipment",
-            )?,
-            auto_analyze_sports: normalize_profile_list(
-                &profile.auto_analyze_sports,
-                profile_name,
-                "auto_analyze_sports",
-            )?,
-        };
-
-        normalized_profiles.insert(profile_name.to_string(), normalized_profile);
-    }
-
-    if !normalized_profiles.contains_key(active_profile) {
-        return Err(format!(
-            "active_profile '{}' must reference an existing profile.",
-            active_profile
-        ));
-    }
-
-    Ok(ProfilesPayload {
-        active_profile: active_profile.to_string(),
-        profiles: normalized_profiles,
-    })
-}
-
-fn write_file_atomically(path: &Path, content: &str) -> std::io::Result<()> {
-    let mut tmp_path = path.to_path_buf();
-    tmp_path.set_extension("json.tmp");
-
-    std::fs::write(&tmp_path, content)?;
-    if let Err(err) = std::fs::rename(&tmp_path, path) {
-        // Docker file bind mounts can reject atomic replace with EBUSY/EXDEV.
-        // In that case we fall back to direct write to preserve functionality.
-        let needs_fallback = matches!(err.raw_os_error(), Some(16 | 18));
-        if needs_fallback {
-            error!(
-                "Atomic replace failed for {} ({}). Falling back to direct write.",
-                path.display(),
-                err
-            );
-            std::fs::write(path, content)?;
-            let _ = std::fs::remove_file(&tmp_path);
-            return Ok(());
-        }
-
-        let _ = std::fs::remove_file(&tmp_path);
-        return Err(err);
-    }
-
-    Ok(())
-}
-
 async fn auth_middleware(State(state): State<ApiState>, request: Request, next: Next) -> Response {
-    if request.method() == Method::OPTIONS {
+    if request.method() == Method::OPTIONS
+        || matches!(request.uri().path(), "/healthz" | "/metrics")
+    {
         return next.run(request).await;
     }
 
@@ -366,11 +278,52 @@ async fn auth_middleware(State(state): State<ApiState>, request: Request, next:
     next.run(request).await
 }
 
+/// Short random id correlating a request's log lines and its `X-Request-Id` header.
+fn new_request_id() -> String {
+    use std::hash::{BuildHasher, Hasher};
+    let hash = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    format!("{:08x}", hash as u32)
+}
+
+/// Runs every request inside a span carrying its trace id (so handler logs, including
+/// a `run_coach_pipeline` started by `/api/generate`, can be correlated), logs
+/// method/path/status/latency and returns the id as `X-Request-Id`.
+async fn trace_request(request: Request, next: Next) -> Response {
+    let request_id = new_request_id();
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let span = tracing::info_span!("request", id = %request_id);
+
+    let started = Instant::now();
+    let mut response = next.run(request).instrument(span.clone()).await;
+    let elapsed_ms = started.elapsed().as_millis();
+    let status = response.status().as_u16();
+
+    span.in_scope(|| {
+        // Probes hit these constantly; keep them out of the default log level.
+        if matches!(path.as_str(), "/healthz" | "/metrics") {
+            debug!("{} {} -> {} in {}ms", method, path, status, elapsed_ms);
+        } else {
+            info!("{} {} -> {} in {}ms", method, path, status, elapsed_ms);
+        }
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response
+            .headers_mut()
+            .insert(HeaderName::from_static("x-request-id"), value);
+    }
+    response
+}
+
 pub async fn run_server(
     config: Arc<crate::config::AppConfig>,
     database: Arc<Mutex<Database>>,
     garmin_client: Arc<GarminClient>,
     coach: Arc<Coach>,
+    generation_guard: crate::generation::GenerationGuard,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let state = ApiState {
         chat_limiter: Arc::new(Mutex::new(SlidingWindowLimiter::new(
@@ -381,36 +334,69 @@ pub async fn run_server(
             config.generate_rate_limit_per_hour,
             Duration::from_secs(60 * 60),
         ))),
+        login_limiter: Arc::new(Mutex::new(SlidingWindowLimiter::new(
+            config.garmin_login_rate_limit_per_hour,
+            Duration::from_secs(60 * 60),
+        ))),
+        pending_logins: Arc::new(Mutex::new(crate::garmin_login::PendingLogins::new(
+            crate::garmin_login::MFA_SESSION_TTL,
+        ))),
         config: config.clone(),
         database,
         garmin_client,
         coach,
+        generation_guard,
     };
 
     let cors = CorsLayer::new()
         .allow_origin(cors_origins(&config.cors_allowed_origins))
-        .allow_methods([Method::GET, Method::POST, Method::PUT])
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
         .allow_headers([
             header::CONTENT_TYPE,
             header::AUTHORIZATION,
             HeaderName::from_static("x-api-token"),
-        ]);
+        ])
+        .expose_headers([HeaderName::from_static("x-request-id")]);
 
     let app = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/metrics", get(get_metrics))
         .route("/api/progression", get(get_progression))
         .route("/api/progression/deltas", get(get_weekly_deltas))
+        .route("/api/exercise/:name/history", get(get_exercise_history))
+        .route("/api/exercise_resolutions", get(get_exercise_resolutions))
+        .route(
+            "/api/exercise_resolutions/clear",
+            axum::routing::post(clear_exercise_resolutions),
+        )
+        .route("/api/dashboard", get(get_dashboard))
         .route("/api/recovery", get(get_recovery))
         .route("/api/recovery/history", get(get_recovery_history))
         .route("/api/workouts/today", get(get_today_workouts))
         .route("/api/workouts/upcoming", get(get_upcoming_workouts))
+        .route("/api/workouts/next", get(get_next_workout))
+        .route("/api/races", get(get_races))
+        .route("/api/plan.ics", get(get_plan_ics))
+        .route("/api/plan.csv", get(get_plan_csv))
+        .route("/api/activities", get(get_activities))
         .route("/api/activities/week", get(get_week_activities))
         .route("/api/force-pull", axum::routing::post(force_pull_data))
         .route("/api/generate", axum::routing::post(trigger_generate))
+        .route("/api/generate/undo", axum::routing::post(undo_generation))
+        .route("/api/garmin/login", axum::routing::post(garmin_login))
+        .route(
+            "/api/garmin/login/mfa",
+            axum::routing::post(garmin_login_mfa),
+        )
         .route(
             "/api/predict_duration",
             axum::routing::post(predict_duration),
         )
         .route("/api/analyze", axum::routing::post(analyze_activity))
+        .route(
+            "/api/analysis/:activity_id",
+            axum::routing::delete(delete_activity_analysis),
+        )
         .route(
             "/api/analyze/upcoming",
             axum::routing::post(analyze_upcoming_event),
@@ -420,12 +406,39 @@ pub async fn run_server(
             axum::routing::post(create_course),
         )
         .route("/api/muscle_heatmap", get(get_muscle_heatmap))
-        .route("/api/chat", get(get_chat).post(post_chat))
+        .route("/api/muscle_volume", get(get_muscle_volume))
+        .route("/api/muscle_recovery", get(get_muscle_recovery))
+        .route(
+            "/api/readiness_recommendation",
+            get(get_readiness_recommendation),
+        )
+        .route("/api/zones", get(get_zones))
+        .route("/api/weekly_summary", get(get_weekly_summary))
+        .route(
+            "/api/weekly_summary/review",
+            axum::routing::post(review_weekly_summary),
+        )
+        .route(
+            "/api/chat",
+            get(get_chat).post(post_chat).delete(clear_chat),
+        )
         .route("/api/profiles", get(get_profiles).put(update_profiles))
+        .route(
+            "/api/profiles/active",
+            get(get_active_profile).post(switch_active_profile),
+        )
+        .route("/api/config", get(get_config))
+        .route("/api/maintenance", axum::routing::post(run_maintenance))
+        .route(
+            "/api/reload_exercises",
+            axum::routing::post(reload_exercises),
+        )
+        .route_layer(middleware::from_fn(crate::metrics::track_request))
         .with_state(state.clone())
         .layer(DefaultBodyLimit::max(16 * 1024))
         .layer(middleware::from_fn_with_state(state, auth_middleware))
-        .layer(cors);
+        .layer(cors)
+        .layer(middleware::from_fn(trace_request));
 
     let addr: SocketAddr = config.api_bind_addr.parse().map_err(|e| {
         std::io::Error::new(
@@ -442,9 +455,22 @@ pub async fn run_server(
     Ok(())
 }
 
+fn try_acquire_generation(
+    guard: &crate::generation::GenerationGuard,
+) -> Result<crate::generation::GenerationPermit, (StatusCode, Json<serde_json::Value>)> {
+    guard.try_start().ok_or_else(|| {
+        error_response(
+            StatusCode::CONFLICT,
+            "A generation is already running, please wait",
+        )
+    })
+}
+
 async fn trigger_generate(
     State(state): State<ApiState>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let _generation = try_acquire_generation(&state.generation_guard)?;
+
     if !state.generate_limiter.lock().await.allow() {
         return Err((
             StatusCode::TOO_MANY_REQUESTS,
@@ -461,6 +487,7 @@ async fn trigger_generate(
         state.coach.clone(),
         state.database.clone(),
         true,
+        false,
     )
     .await
     {
@@ -468,18 +495,160 @@ async fn trigger_generate(
             "status": "success",
             "message": "Workouts generated and pushed to Garmin"
         }))),
-        Err(e) => Err((
+        Err(e) => {
+            // Upstream failures are 502 so the dashboard doesn't mistake an expired
+            // Garmin session for its own API token being rejected.
+            let status = match e {
+                crate::error::AppError::GarminAuth(_)
+                | crate::error::AppError::GarminApi(_)
+                | crate::error::AppError::Ai(_) => StatusCode::BAD_GATEWAY,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            Err((
+                status,
+                Json(serde_json::json!({
+                    "status": "error",
+                    "message": e.user_message()
+                })),
+            ))
+        }
+    }
+}
+
+async fn undo_generation(
+    State(state): State<ApiState>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let _generation = try_acquire_generation(&state.generation_guard)?;
+
+    match state.garmin_client.undo_last_generation().await {
+        Ok(None) => Ok(Json(serde_json::json!({
+            "status": "success",
+            "removed": 0,
+            "message": "No recorded generation to undo"
+        }))),
+        Ok(Some(removed)) => {
+            let _ = state.database.lock().await.clear_garmin_cache();
+            Ok(Json(serde_json::json!({
+                "status": "success",
+                "removed": removed
+            })))
+        }
+        Err(e) => Err(error_response(
+            StatusCode::BAD_GATEWAY,
+            &format!("Failed to undo the last generation: {:#}", e),
+        )),
+    }
+}
+
+/// Garmin logins through the API need a configured `api_auth_token` (the auth
+/// middleware lets everything through without one) and are rate limited.
+async fn check_garmin_login_allowed(
+    state: &ApiState,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if state
+        .config
+        .api_auth_token
+        .as_deref()
+        .is_none_or(|t| t.is_empty())
+    {
+        return Err(error_response(
+            StatusCode::FORBIDDEN,
+            "Garmin login through the API requires api_auth_token to be set",
+        ));
+    }
+    if !state.login_limiter.lock().await.allow() {
+        return Err(error_response(
+            StatusCode::TOO_MANY_REQUESTS,
+            "Rate limit exceeded for Garmin login",
+        ));
+    }
+    Ok(())
+}
+
+/// Persists the new tokens for the configured account and hands them to the
+/// running Garmin client.
+async fn finish_garmin_login(
+    state: &ApiState,
+    oauth1: crate::garmin_api::OAuth1Token,
+    oauth2: crate::garmin_api::OAuth2Token,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let saved = crate::garmin_api::token_dir_for_account(state.config.garmin_account.as_deref())
+        .and_then(|dir| crate::garmin_login::save_tokens(&dir, &oauth1, &oauth2));
+    if let Err(e) = saved {
+        return Err(error_response(
             StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "status": "error",
-                "message": e.to_string()
-            })),
+            &format!("Failed to save Garmin tokens: {:#}", e),
+        ));
+    }
+    state.garmin_client.api.replace_tokens(oauth1, oauth2).await;
+    info!("Garmin login through the API succeeded");
+    Ok(Json(serde_json::json!({ "status": "success" })))
+}
+
+async fn garmin_login(
+    State(state): State<ApiState>,
+    Json(input): Json<GarminLoginInput>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    check_garmin_login_allowed(&state).await?;
+    if input.email.trim().is_empty() || input.password.is_empty() {
+        return Err(error_response(
+            StatusCode::BAD_REQUEST,
+            "email and password are required",
+        ));
+    }
+
+    match crate::garmin_login::login_step_1(input.email.trim(), &input.password).await {
+        Ok(crate::garmin_login::LoginResult::Success(oauth1, oauth2)) => {
+            finish_garmin_login(&state, oauth1, *oauth2).await
+        }
+        Ok(crate::garmin_login::LoginResult::MfaRequired(session)) => {
+            let session_id = state
+                .pending_logins
+                .lock()
+                .await
+                .insert(session)
+                .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+            Ok(Json(serde_json::json!({
+                "status": "success",
+                "mfa_required": true,
+                "session_id": session_id
+            })))
+        }
+        Err(e) => Err(error_response(
+            StatusCode::BAD_GATEWAY,
+            &format!("Garmin login failed: {:#}", e),
+        )),
+    }
+}
+
+async fn garmin_login_mfa(
+    State(state): State<ApiState>,
+    Json(input): Json<GarminMfaInput>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    check_garmin_login_allowed(&state).await?;
+    let Some(session) = state.pending_logins.lock().await.take(&input.session_id) else {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            "Unknown or expired login session, please log in again",
+        ));
+    };
+
+    match crate::garmin_login::login_step_2_mfa(session, input.code.trim()).await {
+        Ok((oauth1, oauth2)) => finish_garmin_login(&state, oauth1, oauth2).await,
+        Err(e) => Err(error_response(
+            StatusCode::BAD_GATEWAY,
+            &format!("Garmin MFA verification failed: {:#}", e),
         )),
     }
 }
 
 async fn get_chat(State(state): State<ApiState>) -> Json<Vec<ChatMessage>> {
     let db = state.database.lock().await;
+    Json(chat_messages(&db))
+}
+
+/// The dashboard chat thread from `coach_briefs`, one user/model pair per brief.
+fn chat_messages(db: &Database) -> Vec<ChatMessage> {
     let history = db.get_coach_briefs().unwrap_or_default();
     let mut resp = Vec::with_capacity(history.len() * 2);
     for (prompt, response, created_at) in history {
@@ -494,7 +663,24 @@ async fn get_chat(State(state): State<ApiState>) -> Json<Vec<ChatMessage>> {
             created_at,
         });
     }
-    Json(resp)
+    resp
+}
+
+/// Resets the dashboard chat: clears `coach_briefs`, the table `/api/chat`
+/// reads from, including the plan memory fed into the next brief.
+async fn clear_chat(
+    State(state): State<ApiState>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    match state.database.lock().await.clear_coach_briefs() {
+        Ok(deleted) => Ok(Json(serde_json::json!({
+            "status": "success",
+            "deleted": deleted
+        }))),
+        Err(e) => Err(error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("Failed to clear chat history: {}", e),
+        )),
+    }
 }
 
 async fn post_chat(
@@ -543,23 +729,7 @@ async fn post_chat(
         ));
     }
 
-    let gemini_model =
-        std::env::var("GEMINI_MODEL").unwrap_or_else(|_| "gemini-3-flash-preview".to_string());
-
-    {
-        let db = state.database.lock().await;
-        if let Err(e) = db.add_ai_chat_message("user", content) {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "status": "error",
-                    "message": format!("Failed to save input: {}", e)
-                })),
-            ));
-        }
-    }
-
-    let ai_client = crate::ai_client::AiClient::new(gemini_key.clone(), gemini_model);
+    let ai_client = crate::ai_client::AiClient::from_config(&state.config);
 
     let history_pairs = state
         .database
@@ -583,7 +753,7 @@ async fn post_chat(
     match ai_client.chat_with_history(&history, None).await {
         Ok(response) => {
             let db = state.database.lock().await;
-            if let Err(e) = db.add_coach_brief(content, &response) {
+            if let Err(e) = db.add_chat_exchange(content, &response) {
                 return Err((
                     StatusCode::INTERNAL_SERVER_ERROR,
                     Json(serde_json::json!({
@@ -608,42 +778,351 @@ async fn post_chat(
     }
 }
 
-async fn get_progression(State(state): State<ApiState>) -> Json<Vec<ProgressionResponse>> {
-    let db = state.database.lock().await;
-    let history = db.get_progression_history_raw().unwrap_or_default();
+async fn reload_exercises(
+    State(state): State<ApiState>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let loaded = state
+        .garmin_client
+        .reload_exercises()
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()))?;
+
+    Ok(Json(serde_json::json!({
+        "status": "success",
+        "loaded": loaded
+    })))
+}
+
+async fn run_maintenance(
+    State(state): State<ApiState>,
+) -> Result<Json<crate::db::PruneReport>, (StatusCode, Json<serde_json::Value>)> {
+    let report = state
+        .database
+        .lock()
+        .await
+        .vacuum_old_data(
+            state.config.retention_days,
+            state.config.exercise_history_retention_days,
+            state.config.now().date_naive(),
+        )
+        .map_err(|e| {
+            error!("Maintenance failed: {}", e);
+            error_response(StatusCode::INTERNAL_SERVER_ERROR, "Maintenance failed.")
+        })?;
+    Ok(Json(report))
+}
+
+async fn healthz(State(state): State<ApiState>) -> Json<serde_json::Value> {
+    let pending = state
+        .database
+        .lock()
+        .await
+        .count_pending_messages()
+        .unwrap_or(0);
+    Json(serde_json::json!({
+        "status": "ok",
+        "pending_messages": pending
+    }))
+}
+
+async fn get_metrics() -> impl IntoResponse {
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        crate::metrics::render(),
+    )
+}
+
+async fn get_config(State(state): State<ApiState>) -> Json<serde_json::Value> {
+    Json(state.config.redacted())
+}
+
+async fn get_progression(State(state): State<ApiState>) -> Json<Vec<ProgressionResponse>> {
+    let units = crate::profiles::active_units();
+    let db = state.database.lock().await;
+    Json(progression(&db, units))
+}
+
+fn progression(db: &Database, units: crate::models::Units) -> Vec<ProgressionResponse> {
+    let history = db.get_progression_history_raw().unwrap_or_default();
+
+    let mut response = Vec::with_capacity(history.len());
+    for (name, weight, reps, date, trend_history) in history {
+        let history_points = trend_history
+            .into_iter()
+            .map(|(w, r, d)| TrendPoint {
+                weight: units.weight_from_kg(w),
+                reps: r,
+                date: d,
+            })
+            .collect();
+
+        response.push(ProgressionResponse {
+            exercise_name: name,
+            max_weight: units.weight_from_kg(weight),
+            reps,
+            date,
+            history: history_points,
+            unit: units.weight_label(),
+        });
+    }
+
+    response
+}
+
+async fn get_exercise_history(
+    State(state): State<ApiState>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+) -> Result<Json<Vec<crate::db::ExerciseSet>>, (StatusCode, Json<serde_json::Value>)> {
+    let category = state
+        .garmin_client
+        .workout_builder()
+        .resolve_exercise(&name)
+        .0;
+
+    let db = state.database.lock().await;
+    if let Some(category) = category {
+        let sets = db.get_exercise_sets(&category).map_err(|e| {
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &format!("Failed to load exercise history: {}", e),
+            )
+        })?;
+        if !sets.is_empty() {
+            return Ok(Json(sets));
+        }
+    }
+
+    let available = db.get_exercise_names().unwrap_or_default();
+    Err(error_response(
+        StatusCode::NOT_FOUND,
+        &format!(
+            "No history for exercise '{}'. Available: {}",
+            name,
+            available.join(", ")
+        ),
+    ))
+}
+
+async fn get_muscle_volume(
+    State(state): State<ApiState>,
+) -> Json<Vec<crate::coaching::MuscleVolume>> {
+    if let Ok(data) = state.garmin_client.fetch_data().await {
+        return Json(crate::coaching::weekly_muscle_volume(
+            &data.activities,
+            state.config.now(),
+        ));
+    }
+
+    Json(Vec::new())
+}
+
+#[derive(Deserialize)]
+pub struct ExerciseResolutionsQuery {
+    pub limit: Option<u32>,
+}
+
+async fn get_exercise_resolutions(
+    State(state): State<ApiState>,
+    axum::extract::Query(query): axum::extract::Query<ExerciseResolutionsQuery>,
+) -> Result<Json<Vec<crate::db::ExerciseResolutionEntry>>, (StatusCode, Json<serde_json::Value>)> {
+    let limit = query.limit.unwrap_or(100);
+    state
+        .database
+        .lock()
+        .await
+        .get_exercise_resolutions(limit)
+        .map(Json)
+        .map_err(|e| {
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &format!("Failed to load exercise resolutions: {}", e),
+            )
+        })
+}
+
+async fn clear_exercise_resolutions(
+    State(state): State<ApiState>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    match state.database.lock().await.clear_exercise_resolutions() {
+        Ok(removed) => Ok(Json(serde_json::json!({
+            "status": "success",
+            "removed": removed
+        }))),
+        Err(e) => Err(error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("Failed to clear exercise resolutions: {}", e),
+        )),
+    }
+}
+
+async fn get_muscle_recovery(
+    State(state): State<ApiState>,
+) -> Json<Vec<crate::coaching::MuscleRecovery>> {
+    if let Ok(data) = state.garmin_client.fetch_data().await {
+        return Json(crate::coaching::muscle_recovery(
+            &data.activities,
+            state.config.now().naive_local(),
+            state.config.min_muscle_recovery_hours,
+        ));
+    }
+
+    Json(Vec::new())
+}
+
+#[derive(Serialize)]
+pub struct ReadinessRecommendation {
+    pub intensity: crate::coaching::Intensity,
+    pub body_battery: Option<i32>,
+    pub sleep_score: Option<i32>,
+    pub training_readiness: Option<i32>,
+    pub thresholds: crate::coaching::IntensityThresholds,
+}
+
+async fn get_readiness_recommendation(
+    State(state): State<ApiState>,
+) -> Result<Json<ReadinessRecommendation>, (StatusCode, Json<serde_json::Value>)> {
+    let data = state.garmin_client.fetch_data().await.map_err(|e| {
+        error!("Readiness check failed to fetch Garmin data: {}", e);
+        error_response(StatusCode::BAD_GATEWAY, "Failed to fetch Garmin data.")
+    })?;
+
+    let thresholds = state.config.effective_intensity_thresholds();
+    let recovery = data.recovery_metrics.as_ref();
+    Ok(Json(ReadinessRecommendation {
+        intensity: recovery
+            .map(|r| crate::coaching::recommended_intensity(r, &thresholds))
+            .unwrap_or(crate::coaching::Intensity::Moderate),
+        body_battery: recovery.and_then(|r| r.current_body_battery),
+        sleep_score: recovery.and_then(|r| r.sleep_score),
+        training_readiness: recovery.and_then(|r| r.training_readiness),
+        thresholds,
+    }))
+}
+
+#[derive(Serialize)]
+pub struct HrZone {
+    pub zone: &'static str,
+    pub min_bpm: u32,
+    pub max_bpm: u32,
+}
+
+#[derive(Serialize)]
+pub struct ZonesResponse {
+    pub max_hr: f64,
+    pub source: crate::coaching::MaxHrSource,
+    pub zones: Vec<HrZone>,
+}
+
+async fn get_zones(
+    State(state): State<ApiState>,
+) -> Result<Json<ZonesResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let data = state.garmin_client.fetch_data().await.map_err(|e| {
+        error!("Zones failed to fetch Garmin data: {}", e);
+        error_response(StatusCode::BAD_GATEWAY, "Failed to fetch Garmin data.")
+    })?;
+
+    let (max_hr, source) = crate::coaching::resolve_max_hr(
+        &data.user_profile,
+        &data.activities,
+        state.config.now().date_naive(),
+        state.config.max_hr_formula,
+    )
+    .ok_or_else(|| {
+        error_response(
+            StatusCode::NOT_FOUND,
+            "No birth date or recorded max HR available.",
+        )
+    })?;
+
+    let zones = crate::coaching::hr_zones(max_hr)
+        .into_iter()
+        .map(|(zone, min_bpm, max_bpm)| HrZone {
+            zone,
+            min_bpm,
+            max_bpm,
+        })
+        .collect();
+
+    Ok(Json(ZonesResponse {
+        max_hr,
+        source,
+        zones,
+    }))
+}
+
+async fn get_weekly_summary(
+    State(state): State<ApiState>,
+) -> Result<Json<crate::coaching::WeeklySummary>, (StatusCode, Json<serde_json::Value>)> {
+    let data = state.garmin_client.fetch_data().await.map_err(|e| {
+        error!("Weekly summary failed to fetch Garmin data: {}", e);
+        error_response(StatusCode::BAD_GATEWAY, "Failed to fetch Garmin data.")
+    })?;
 
-    let mut response = Vec::with_capacity(history.len());
-    for (name, weight, reps, date, trend_history) in history {
-        let history_points = trend_history
-            .into_iter()
-            .map(|(w, r, d)| TrendPoint {
-                weight: w,
-                reps: r,
-                date: d,
-            })
-            .collect();
+    let mut summary = crate::coaching::build_weekly_summary(&data, state.config.now());
+    summary.personal_records = state
+        .database
+        .lock()
+        .await
+        .get_personal_records_since(&summary.start_date)
+        .unwrap_or_default();
 
-        response.push(ProgressionResponse {
-            exercise_name: name,
-            max_weight: weight,
-            reps,
-            date,
-            history: history_points,
-        });
+    Ok(Json(summary))
+}
+
+async fn review_weekly_summary(
+    State(state): State<ApiState>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let gemini_key = &state.config.gemini_api_key;
+    if gemini_key.is_empty() {
+        return Err(error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "GEMINI_API_KEY not configured",
+        ));
     }
 
-    Json(response)
+    let data = state.garmin_client.fetch_data().await.map_err(|e| {
+        error!("Weekly review failed to fetch Garmin data: {}", e);
+        error_response(StatusCode::BAD_GATEWAY, "Failed to fetch Garmin data.")
+    })?;
+    let mut summary = crate::coaching::build_weekly_summary(&data, state.config.now());
+    summary.personal_records = state
+        .database
+        .lock()
+        .await
+        .get_personal_records_since(&summary.start_date)
+        .unwrap_or_default();
+
+    let ai_client = crate::ai_client::AiClient::from_config(&state.config);
+    let prompt = crate::coaching::render_weekly_review_prompt(&summary);
+
+    match ai_client.generate_workout(&prompt).await {
+        Ok(review) => Ok(Json(serde_json::json!({
+            "summary": summary,
+            "review": review
+        }))),
+        Err(e) => Err(error_response(StatusCode::BAD_GATEWAY, &e.to_string())),
+    }
 }
 
 async fn get_muscle_heatmap(
     State(state): State<ApiState>,
 ) -> Json<Vec<crate::models::ExerciseMuscleMap>> {
     let db = state.database.lock().await;
-    let heatmap = db.get_recent_muscle_heatmap(14).unwrap_or_default();
-    Json(heatmap)
+    Json(muscle_heatmap(&db))
+}
+
+fn muscle_heatmap(db: &Database) -> Vec<crate::models::ExerciseMuscleMap> {
+    db.get_recent_muscle_heatmap(MUSCLE_HEATMAP_DAYS).unwrap_or_default()
 }
 
 async fn get_recovery(State(state): State<ApiState>) -> Json<RecoveryResponse> {
+    let data = state.garmin_client.fetch_data().await.ok();
+    Json(recovery(data.as_ref()))
+}
+
+fn recovery(data: Option<&crate::models::GarminResponse>) -> RecoveryResponse {
     let mut response = RecoveryResponse {
         body_battery: None,
         sleep_score: None,
@@ -652,21 +1131,23 @@ async fn get_recovery(State(state): State<ApiState>) -> Json<RecoveryResponse> {
         hrv_weekly_avg: None,
         hrv_last_night_avg: None,
         rhr_trend: Vec::new(),
+        daily_summary: None,
+        sleep_detail: None,
     };
 
-    if let Ok(data) = state.garmin_client.fetch_data().await {
-        if let Some(metrics) = data.recovery_metrics {
-            response.body_battery = metrics.current_body_battery;
-            response.sleep_score = metrics.sleep_score;
-            response.training_readiness = metrics.training_readiness;
-            response.hrv_status = metrics.hrv_status;
-            response.hrv_weekly_avg = metrics.hrv_weekly_avg;
-            response.hrv_last_night_avg = metrics.hrv_last_night_avg;
-            response.rhr_trend = metrics.rhr_trend;
-        }
+    if let Some(metrics) = data.and_then(|d| d.recovery_metrics.clone()) {
+        response.body_battery = metrics.current_body_battery;
+        response.sleep_score = metrics.sleep_score;
+        response.training_readiness = metrics.training_readiness;
+        response.hrv_status = metrics.hrv_status;
+        response.hrv_weekly_avg = metrics.hrv_weekly_avg;
+        response.hrv_last_night_avg = metrics.hrv_last_night_avg;
+        response.rhr_trend = metrics.rhr_trend;
+        response.daily_summary = metrics.daily_summary;
+        response.sleep_detail = metrics.sleep_detail;
     }
 
-    Json(response)
+    response
 }
 
 async fn get_recovery_history(
@@ -679,35 +1160,121 @@ async fn get_recovery_history(
 }
 
 async fn get_today_workouts(State(state): State<ApiState>) -> Json<TodayWorkoutsResponse> {
+    let data = state.garmin_client.fetch_data().await.ok();
+    Json(today_workouts(
+        data.as_ref(),
+        state.config.now().date_naive(),
+    ))
+}
+
+fn today_workouts(
+    data: Option<&crate::models::GarminResponse>,
+    today: chrono::NaiveDate,
+) -> TodayWorkoutsResponse {
+    let today_prefix = today.format("%Y-%m-%d").to_string();
     let mut response = TodayWorkoutsResponse {
         done: Vec::new(),
         planned: Vec::new(),
     };
 
-    let today_prefix = chrono::Local::now().format("%Y-%m-%d").to_string();
-
-    if let Ok(data) = state.garmin_client.fetch_data().await {
+    if let Some(data) = data {
         response.done = data
             .activities
-            .into_iter()
-            .filter(|a| a.start_time.starts_with(&today_prefix))
+            .iter()
+            .filter(|a| a.start_date() == Some(today))
+            .cloned()
             .collect();
 
         response.planned = data
             .scheduled_workouts
-            .into_iter()
+            .iter()
             .filter(|w| w.date.starts_with(&today_prefix))
+            .cloned()
             .collect();
     }
 
-    Json(response)
+    response
 }
 
-async fn get_upcoming_workouts(
-    State(state): State<ApiState>,
-) -> Json<Vec<crate::models::ScheduledWorkout>> {
+/// Races and events from today on, soonest first.
+fn upcoming_races(
+    data: Option<&crate::models::GarminResponse>,
+    today_prefix: &str,
+) -> Vec<crate::models::ScheduledWorkout> {
+    let Some(data) = data else {
+        return Vec::new();
+    };
+    let mut races: Vec<_> = data
+        .scheduled_workouts
+        .iter()
+        .filter(|w| crate::models::is_race_item(w) && w.date.as_str() >= today_prefix)
+        .cloned()
+        .collect();
+    races.sort_by(|a, b| a.date.cmp(&b.date));
+    races
+}
+
+/// Upcoming races with the days left until each; items with an unparsable date are left out.
+fn race_countdowns(
+    data: Option<&crate::models::GarminResponse>,
+    today: chrono::NaiveDate,
+) -> Vec<RaceCountdown> {
+    let today_prefix = today.format("%Y-%m-%d").to_string();
+    upcoming_races(data, &today_prefix)
+        .into_iter()
+        .filter_map(|race| {
+            let date = chrono::NaiveDate::parse_from_str(race.date.get(..10)?, "%Y-%m-%d").ok()?;
+            Some(RaceCountdown {
+                days_until: (date - today).num_days(),
+                title: race.title,
+                date: race.date,
+                sport: race.sport,
+                distance: race.distance,
+            })
+        })
+        .collect()
+}
+
+async fn get_races(State(state): State<ApiState>) -> Json<Vec<RaceCountdown>> {
+    let data = state.garmin_client.fetch_data().await.ok();
+    let today = state.config.now().date_naive();
+    Json(race_countdowns(data.as_ref(), today))
+}
+
+/// Lifts with the most recorded sessions first, heaviest first on ties.
+fn top_progression(mut lifts: Vec<ProgressionResponse>, limit: usize) -> Vec<ProgressionResponse> {
+    lifts.sort_by(|a, b| {
+        b.history
+            .len()
+            .cmp(&a.history.len())
+            .then(b.max_weight.total_cmp(&a.max_weight))
+    });
+    lifts.truncate(limit);
+    lifts
+}
+
+/// Everything the dashboard home screen renders, from a single Garmin fetch.
+async fn get_dashboard(State(state): State<ApiState>) -> Json<DashboardResponse> {
+    let today_prefix = state.config.now().format("%Y-%m-%d").to_string();
+    let data = state.garmin_client.fetch_data().await.ok();
+    let units = crate::profiles::active_units();
+    let (lifts, heatmap) = {
+        let db = state.database.lock().await;
+        (progression(&db, units), muscle_heatmap(&db))
+    };
+
+    Json(DashboardResponse {
+        recovery: recovery(data.as_ref()),
+        today: today_workouts(data.as_ref(), state.config.now().date_naive()),
+        upcoming_races: upcoming_races(data.as_ref(), &today_prefix),
+        top_progression: top_progression(lifts, DASHBOARD_TOP_LIFTS),
+        muscle_heatmap: heatmap,
+    })
+}
+
+async fn upcoming_scheduled_workouts(state: &ApiState) -> Vec<crate::models::ScheduledWorkout> {
     let mut planned = Vec::new();
-    let today_prefix = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let today_prefix = state.config.now().format("%Y-%m-%d").to_string();
 
     if let Ok(data) = state.garmin_client.fetch_data().await {
         planned = data
@@ -718,22 +1285,82 @@ async fn get_upcoming_workouts(
     }
 
     planned.sort_by(|a, b| a.date.cmp(&b.date));
-    Json(planned)
+    planned
+}
+
+async fn get_upcoming_workouts(
+    State(state): State<ApiState>,
+) -> Json<Vec<crate::models::ScheduledWorkout>> {
+    Json(upcoming_scheduled_workouts(&state).await)
+}
+
+#[derive(Serialize)]
+pub struct NextWorkoutResponse {
+    /// `None` when nothing is scheduled from today on.
+    pub workout: Option<crate::models::ScheduledWorkout>,
+    /// The `/next` Signal rendering of `workout`.
+    pub details: Option<String>,
+}
+
+async fn get_next_workout(
+    State(state): State<ApiState>,
+) -> Result<Json<NextWorkoutResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let data = state.garmin_client.fetch_data().await.map_err(|e| {
+        error!("Next workout failed to fetch Garmin data: {}", e);
+        error_response(StatusCode::BAD_GATEWAY, "Failed to fetch Garmin data.")
+    })?;
+    let today = state.config.now().format("%Y-%m-%d").to_string();
+    let workout = crate::bot::next_scheduled_workout(&data.scheduled_workouts, &today).cloned();
+    let details = workout.as_ref().map(|w| {
+        crate::bot::scheduled_workout_details(
+            w,
+            &crate::bot::load_generated_workouts(),
+            state.garmin_client.ai_tags(),
+            crate::profiles::active_units(),
+        )
+    });
+    Ok(Json(NextWorkoutResponse { workout, details }))
+}
+
+async fn get_plan_ics(State(state): State<ApiState>) -> impl IntoResponse {
+    let planned = upcoming_scheduled_workouts(&state).await;
+    (
+        [
+            (header::CONTENT_TYPE, "text/calendar; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"training-plan.ics\"",
+            ),
+        ],
+        crate::plan_export::render_ics(&planned, chrono::Utc::now()),
+    )
+}
+
+async fn get_plan_csv(State(state): State<ApiState>) -> impl IntoResponse {
+    let planned = upcoming_scheduled_workouts(&state).await;
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"training-plan.csv\"",
+            ),
+        ],
+        crate::plan_export::render_csv(&planned),
+    )
 }
 
 async fn get_week_activities(
     State(state): State<ApiState>,
 ) -> Json<Vec<crate::models::GarminActivity>> {
-    let today = chrono::Local::now().date_naive();
-    let week_ago = (today - chrono::Duration::days(7))
-        .format("%Y-%m-%d")
-        .to_string();
+    let today = state.config.now().date_naive();
+    let week_ago = today - chrono::Duration::days(7);
 
     if let Ok(data) = state.garmin_client.fetch_data().await {
         let mut activities: Vec<_> = data
             .activities
             .into_iter()
-            .filter(|a| a.start_time >= week_ago)
+            .filter(|a| a.start_date().is_some_and(|d| d >= week_ago))
             .collect();
         activities.sort_by(|a, b| a.start_time.cmp(&b.start_time));
         return Json(activities);
@@ -742,26 +1369,98 @@ async fn get_week_activities(
     Json(Vec::new())
 }
 
+#[derive(Deserialize)]
+pub struct ActivitiesQuery {
+    pub sport: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct ActivitiesPage {
+    /// Matching activities before `limit`/`offset` are applied.
+    pub total: usize,
+    pub limit: usize,
+    pub offset: usize,
+    pub activities: Vec<crate::models::GarminActivity>,
+}
+
+/// Activities of `sport` (any type key or bucket name such as `run` or
+/// `cycling`) started between `from` and `to` inclusive, newest first.
+fn filter_activities(
+    activities: Vec<crate::models::GarminActivity>,
+    sport: Option<crate::models::Sport>,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+) -> Vec<crate::models::GarminActivity> {
+    let mut matching: Vec<_> = activities
+        .into_iter()
+        .filter(|a| a.start_date().is_some_and(|d| d >= from && d <= to))
+        .filter(|a| sport.is_none_or(|s| a.normalized_sport() == s))
+        .collect();
+    matching.sort_by(|a, b| b.start_time.cmp(&a.start_time));
+    matching
+}
+
+async fn get_activities(
+    State(state): State<ApiState>,
+    axum::extract::Query(query): axum::extract::Query<ActivitiesQuery>,
+) -> Result<Json<ActivitiesPage>, (StatusCode, Json<serde_json::Value>)> {
+    let parse_date = |name: &str, value: Option<&str>| {
+        value
+            .map(|v| {
+                chrono::NaiveDate::parse_from_str(v, "%Y-%m-%d").map_err(|_| {
+                    error_response(
+                        StatusCode::BAD_REQUEST,
+                        &format!("'{}' must be a YYYY-MM-DD date.", name),
+                    )
+                })
+            })
+            .transpose()
+    };
+    let to =
+        parse_date("to", query.to.as_deref())?.unwrap_or_else(|| state.config.now().date_naive());
+    let from = parse_date("from", query.from.as_deref())?
+        .unwrap_or(to - chrono::Duration::days(ACTIVITIES_DEFAULT_DAYS - 1));
+    let sport = query
+        .sport
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(crate::models::Sport::from_type_key);
+    let limit = query
+        .limit
+        .unwrap_or(ACTIVITIES_DEFAULT_LIMIT)
+        .min(ACTIVITIES_MAX_LIMIT);
+    let offset = query.offset.unwrap_or(0);
+
+    let data = state.garmin_client.fetch_data().await.map_err(|e| {
+        error!("Activity history failed to fetch Garmin data: {}", e);
+        error_response(StatusCode::BAD_GATEWAY, "Failed to fetch Garmin data.")
+    })?;
+    let matching = filter_activities(data.activities, sport, from, to);
+    Ok(Json(ActivitiesPage {
+        total: matching.len(),
+        limit,
+        offset,
+        activities: matching.into_iter().skip(offset).take(limit).collect(),
+    }))
+}
+
 async fn get_profiles() -> Result<Json<ProfilesPayload>, (StatusCode, Json<serde_json::Value>)> {
     let path = profiles_path();
-    let data = std::fs::read_to_string(&path).map_err(|err| {
-        error!("Failed to read {}: {}", path, err);
-        error_response(
+    if !Path::new(&path).exists() {
+        error!("Profiles file {} does not exist", path);
+        return Err(error_response(
             StatusCode::INTERNAL_SERVER_ERROR,
             "Profiles configuration is unavailable.",
-        )
-    })?;
-
-    let parsed = serde_json::from_str::<ProfilesPayload>(&data).map_err(|err| {
-        error!("Failed to parse {}: {}", path, err);
-        error_response(
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Profiles configuration is invalid.",
-        )
-    })?;
+        ));
+    }
 
-    let validated = validate_profiles_payload(parsed).map_err(|err| {
-        error!("Validation failed for {}: {}", path, err);
+    let validated = load_profiles(Path::new(&path)).map_err(|err| {
+        error!("Failed to load {}: {:#}", path, err);
         error_response(
             StatusCode::INTERNAL_SERVER_ERROR,
             "Profiles configuration is invalid.",
@@ -779,44 +1478,97 @@ async fn update_profiles(
         error_response(StatusCode::BAD_REQUEST, "Invalid profiles payload.")
     })?;
 
-    let validated = validate_profiles_payload(payload)
-        .map_err(|err| error_response(StatusCode::BAD_REQUEST, &err))?;
+    let validated = validate_profiles_payload(payload).map_err(|errors| {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({
+                "status": "error",
+                "message": crate::profiles::describe_validation_errors(&errors),
+                "errors": errors
+            })),
+        )
+    })?;
 
     let path = profiles_path();
-    let mut json_str = serde_json::to_string_pretty(&validated).map_err(|err| {
-        error!("Failed to serialize {} payload: {}", path, err);
+    save_profiles(Path::new(&path), validated).map_err(|err| {
+        error!("Failed to save {}: {:#}", path, err);
         error_response(
             StatusCode::INTERNAL_SERVER_ERROR,
             "Failed to persist profiles configuration.",
         )
     })?;
-    json_str.push('\n');
 
-    write_file_atomically(Path::new(&path), &json_str).map_err(|err| {
-        error!("Failed to atomically write {}: {}", path, err);
+    Ok(Json(serde_json::json!({
+        "status": "success",
+        "message": "Profiles updated"
+    })))
+}
+
+async fn get_active_profile(
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let Json(profiles) = get_profiles().await?;
+    Ok(Json(serde_json::json!({
+        "active_profile": profiles.active_profile
+    })))
+}
+
+async fn switch_active_profile(
+    payload: Result<Json<ActiveProfileInput>, JsonRejection>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let Json(input) = payload.map_err(|err| {
+        error!("Rejected invalid active profile payload: {}", err);
+        error_response(
+            StatusCode::BAD_REQUEST,
+            "Expected {\"name\": \"<profile>\"}.",
+        )
+    })?;
+
+    let path = profiles_path();
+    let switched = set_active_profile(Path::new(&path), &input.name).map_err(|err| {
+        error!("Failed to switch active profile in {}: {:#}", path, err);
         error_response(
             StatusCode::INTERNAL_SERVER_ERROR,
             "Failed to persist profiles configuration.",
         )
     })?;
+    if !switched {
+        return Err(error_response(
+            StatusCode::NOT_FOUND,
+            &format!("No profile named '{}'.", input.name.trim()),
+        ));
+    }
 
     Ok(Json(serde_json::json!({
         "status": "success",
-        "message": "Profiles updated"
+        "active_profile": input.name.trim()
     })))
 }
 
+/// `title|sport` key for cached duration predictions. Either part may be empty,
+/// but a request with neither has nothing to key on and is not cached.
+fn predicted_duration_cache_key(title: &str, sport: &str) -> Option<String> {
+    let (title, sport) = (title.trim(), sport.trim());
+    if title.is_empty() && sport.is_empty() {
+        return None;
+    }
+    Some(format!("{}|{}", title, sport))
+}
+
 async fn predict_duration(
     State(state): State<ApiState>,
     Json(input): Json<PredictDurationInput>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
     let title = input.title.unwrap_or_default();
     let sport = input.sport.unwrap_or_default();
-    let cache_key = format!("{}|{}", title, sport);
+    let cache_key = predicted_duration_cache_key(&title, &sport);
+    let max_age_secs = match state.config.predicted_duration_ttl_days {
+        0 => None,
+        days => Some(days as u64 * 86_400),
+    };
 
-    {
+    if let Some(cache_key) = &cache_key {
         let db = state.database.lock().await;
-        if let Ok(Some(duration)) = db.get_predicted_duration(&cache_key) {
+        if let Ok(Some(duration)) = db.get_predicted_duration(cache_key, max_age_secs) {
             return Ok(Json(serde_json::json!({ "duration": duration })));
         }
     }
@@ -832,9 +1584,7 @@ async fn predict_duration(
         ));
     }
 
-    let gemini_model =
-        std::env::var("GEMINI_MODEL").unwrap_or_else(|_| "gemini-3-flash-preview".to_string());
-    let ai_client = crate::ai_client::AiClient::new(gemini_key.clone(), gemini_model);
+    let ai_client = crate::ai_client::AiClient::from_config(&state.config);
     let prompt = format!(
         "Predict the duration in minutes for this workout. Take into account conventional durations for these types of workouts. Return only a plain integer representing minutes, and nothing else (no units, no markdown). If you cannot predict or it's unknown, return 45.\nTitle: {}\nSport: {}\nDescription: {}",
         title, sport, input.description.unwrap_or_default()
@@ -843,9 +1593,9 @@ async fn predict_duration(
     match ai_client.generate_workout(&prompt).await {
         Ok(text) => {
             let parsed = text.trim().parse::<i32>().unwrap_or(45);
-            {
+            if let Some(cache_key) = &cache_key {
                 let db = state.database.lock().await;
-                let _ = db.set_predicted_duration(&cache_key, parsed);
+                let _ = db.set_predicted_duration(cache_key, parsed);
             }
 
             Ok(Json(serde_json::json!({
@@ -875,13 +1625,14 @@ async fn analyze_activity(
         .unwrap_or_default();
 
     // Check DB first
-    if let Some(id) = activity_id {
+    let cached = {
         let db = state.database.lock().await;
-        if let Ok(Some(existing_analysis)) = db.get_activity_analysis(id) {
-            return Ok(Json(serde_json::json!({
-                "analysis": existing_analysis
-            })));
-        }
+        cached_activity_analysis(&db, activity_id, input.force)
+    };
+    if let Some(existing_analysis) = cached {
+        return Ok(Json(serde_json::json!({
+            "analysis": existing_analysis
+        })));
     }
 
     let gemini_key = &state.config.gemini_api_key;
@@ -895,11 +1646,10 @@ async fn analyze_activity(
         ));
     }
 
-    let gemini_model =
-        std::env::var("GEMINI_MODEL").unwrap_or_else(|_| "gemini-3-flash-preview".to_string());
-    let ai_client = crate::ai_client::AiClient::new(gemini_key.clone(), gemini_model);
+    let ai_client = crate::ai_client::AiClient::from_config(&state.config);
     let prompt = format!(
-        "Please provide an in-depth analysis of this completed fitness activity. Be encouraging but highly analytical.\n\nYou have been provided with the complete, raw JSON payload direct from Garmin. It contains many undocumented fields, extra metrics, recovery data, elevation, stress, cadence, temperatures, or detailed exercise sets.\n\nPlease actively hunt through this raw JSON and surface interesting insights, anomalies, or performance correlations that wouldn't be obvious from just the basic time/distance metrics. Explain what these deeper metrics mean for the athlete's progress.\n\nHere is the raw Garmin activity data in JSON format:\n\n{}",
+        "Please provide an in-depth analysis of this completed fitness activity. Be encouraging but highly analytical.\n\nYou have been provided with the complete, raw JSON payload direct from Garmin. It contains many undocumented fields, extra metrics, recovery data, elevation, stress, cadence, temperatures, or detailed exercise sets.\n\nPlease actively hunt through this raw JSON and surface interesting insights, anomalies, or performance correlations that wouldn't be obvious from just the basic time/distance metrics. Explain what these deeper metrics mean for the athlete's progress.\n\n{}Here is the raw Garmin activity data in JSON format:\n\n{}",
+        crate::coaching::terrain_note(garmin_act.as_ref()),
         serde_json::to_string(&input.activity).unwrap_or_default()
     );
 
@@ -908,7 +1658,9 @@ async fn analyze_activity(
             // Save to DB
             if let Some(id) = activity_id {
                 let db = state.database.lock().await;
-                let _ = db.save_activity_analysis(id, &start_time, &text);
+                if let Err(e) = store_activity_analysis(&db, id, &start_time, &text, input.force) {
+                    error!("Failed to save analysis of activity {}: {}", id, e);
+                }
             }
             Ok(Json(serde_json::json!({
                 "analysis": text
@@ -924,27 +1676,104 @@ async fn analyze_activity(
     }
 }
 
-async fn force_pull_data(
+/// The stored analysis to return instead of calling the AI; `force` always re-runs.
+fn cached_activity_analysis(
+    db: &Database,
+    activity_id: Option<i64>,
+    force: bool,
+) -> Option<String> {
+    if force {
+        return None;
+    }
+    db.get_activity_analysis(activity_id?).ok().flatten()
+}
+
+/// Persists a fresh analysis; a forced re-run overwrites the stored one.
+fn store_activity_analysis(
+    db: &Database,
+    activity_id: i64,
+    start_time: &str,
+    text: &str,
+    force: bool,
+) -> rusqlite::Result<()> {
+    if force {
+        db.replace_activity_analysis(activity_id, start_time, text)
+    } else {
+        db.save_activity_analysis(activity_id, start_time, text)
+    }
+}
+
+async fn delete_activity_analysis(
     State(state): State<ApiState>,
+    axum::extract::Path(activity_id): axum::extract::Path<i64>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    match state
+        .database
+        .lock()
+        .await
+        .delete_activity_analysis(activity_id)
     {
-        let db = state.database.lock().await;
-        if let Err(e) = db.clear_garmin_cache() {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "status": "error",
-                    "message": format!("Failed to clear database garmin cache: {}", e)
-                })),
-            ));
-        }
+        Ok(true) => Ok(Json(serde_json::json!({
+            "status": "success",
+            "message": format!("Analysis of activity {} deleted", activity_id)
+        }))),
+        Ok(false) => Err(error_response(
+            StatusCode::NOT_FOUND,
+            &format!("No stored analysis for activity {}", activity_id),
+        )),
+        Err(e) => Err(error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("Failed to delete analysis: {}", e),
+        )),
     }
+}
 
-    match state.garmin_client.fetch_data().await {
-        Ok(_) => Ok(Json(serde_json::json!({
+#[derive(Deserialize)]
+pub struct ForcePullQuery {
+    /// Also re-run auto-analysis for today's activities after the pull.
+    #[serde(default)]
+    pub deep: bool,
+}
+
+async fn force_pull_data(
+    State(state): State<ApiState>,
+    axum::extract::Query(query): axum::extract::Query<ForcePullQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    if query.deep && state.config.gemini_api_key.is_empty() {
+        return Err(error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "No API key",
+        ));
+    }
+
+    match state.garmin_client.fetch_data_with_opts(true).await {
+        Ok(_) if !query.deep => Ok(Json(serde_json::json!({
             "status": "success",
             "message": "Data successfully force-pulled from Garmin."
         }))),
+        Ok(data) => {
+            let ai_client = crate::ai_client::AiClient::from_config(&state.config);
+            let (_, auto_analysis) = crate::profiles::load_active_context();
+
+            let analyzed = crate::auto_analyze_activities(
+                &data.activities,
+                &state.garmin_client,
+                &ai_client,
+                &auto_analysis,
+                &state.database,
+                &state.config,
+                crate::AnalysisRun::TodayForced,
+            )
+            .await;
+            Ok(Json(serde_json::json!({
+                "status": "success",
+                "message": format!(
+                    "Data force-pulled from Garmin; {} of today's activities (re)analyzed.",
+                    analyzed
+                ),
+                "analyzed": analyzed
+            })))
+        }
         Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(serde_json::json!({
@@ -959,7 +1788,7 @@ async fn get_weekly_deltas(State(state): State<ApiState>) -> Json<Vec<WeeklyDelt
     use chrono::Datelike;
 
     let week_start_chrono = crate::config::parse_weekday(&state.config.week_start_day);
-    let today = chrono::Local::now().date_naive();
+    let today = state.config.now().date_naive();
 
     let days_since_start = (today.weekday().num_days_from_monday() as i64
         - week_start_chrono.num_days_from_monday() as i64
@@ -971,6 +1800,7 @@ async fn get_weekly_deltas(State(state): State<ApiState>) -> Json<Vec<WeeklyDelt
     let this_week_start_str = this_week_start.format("%Y-%m-%d").to_string();
     let last_week_start_str = last_week_start.format("%Y-%m-%d").to_string();
 
+    let units = crate::profiles::active_units();
     let db = state.database.lock().await;
     let deltas = db
         .get_weekly_progression_deltas(&this_week_start_str, &last_week_start_str)
@@ -980,10 +1810,11 @@ async fn get_weekly_deltas(State(state): State<ApiState>) -> Json<Vec<WeeklyDelt
         .into_iter()
         .map(|(name, tw, tr, lw, lr)| WeeklyDeltaResponse {
             exercise_name: name,
-            this_week_weight: tw,
+            this_week_weight: units.weight_from_kg(tw),
             this_week_reps: tr,
-            last_week_weight: lw,
+            last_week_weight: units.weight_from_kg(lw),
             last_week_reps: lr,
+            unit: units.weight_label(),
         })
         .collect();
 
@@ -1006,9 +1837,7 @@ async fn analyze_upcoming_event(
         ));
     }
 
-    let gemini_model =
-        std::env::var("GEMINI_MODEL").unwrap_or_else(|_| "gemini-3-flash-preview".to_string());
-    let ai_client = crate::ai_client::AiClient::new(gemini_key.clone(), gemini_model);
+    let ai_client = crate::ai_client::AiClient::from_config(&state.config);
 
     // Provide context
     let mut context_str = String::new();
@@ -1044,6 +1873,18 @@ async fn analyze_upcoming_event(
         }
     }
 
+    let (profile_ctx, _) = crate::profiles::load_active_context();
+    context_str.push_str(&format!(
+        "Athlete Goals: {}\n",
+        profile_ctx.goals.join("; ")
+    ));
+    if !profile_ctx.constraints.is_empty() {
+        context_str.push_str(&format!(
+            "Athlete Constraints: {}\n",
+            profile_ctx.constraints.join("; ")
+        ));
+    }
+
     let workout_json = serde_json::to_string(&workout).unwrap_or_default();
 
     let cache_key = format!(
@@ -1126,11 +1967,11 @@ async fn create_course(
         )
     })?;
 
-    let course_name = format!(
-        "FJ-AI: {} {:.1}km Loop",
+    let course_name = state.garmin_client.ai_tags().tag_name(&format!(
+        "{} {:.1}km Loop",
         workout.title.as_deref().unwrap_or("Run"),
         distance_m / 1000.0
-    );
+    ));
 
     match state
         .garmin_client
@@ -1182,9 +2023,7 @@ async fn resolve_course_distance(
         return Err("No distance available and GEMINI_API_KEY not configured".to_string());
     }
 
-    let gemini_model =
-        std::env::var("GEMINI_MODEL").unwrap_or_else(|_| "gemini-3-flash-preview".to_string());
-    let ai_client = crate::ai_client::AiClient::new(gemini_key.clone(), gemini_model);
+    let ai_client = crate::ai_client::AiClient::from_config(&state.config);
 
     let workout_json = serde_json::to_string(workout).unwrap_or_default();
     let prompt = format!(
@@ -1235,3 +2074,258 @@ async fn resolve_course_coordinates(
 
     Err("No start coordinates available. Provide start_latitude/start_longitude, configure default_start_latitude/longitude, or complete a GPS run activity.".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        cached_activity_analysis, chat_messages, filter_activities, new_request_id,
+        predicted_duration_cache_key, race_countdowns, store_activity_analysis, top_progression,
+        try_acquire_generation, upcoming_races, update_profiles, ProgressionResponse,
+    };
+    use axum::http::StatusCode;
+    use std::time::Duration;
+
+    #[test]
+    fn concurrent_generation_is_rejected_with_conflict() {
+        let guard = crate::generation::GenerationGuard::new(
+            crate::db::memory_db(),
+            Duration::from_secs(600),
+        );
+
+        let first = try_acquire_generation(&guard).expect("first generation should start");
+        let (status, body) = try_acquire_generation(&guard).unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(
+            body.0["message"],
+            "A generation is already running, please wait"
+        );
+
+        drop(first);
+        assert!(try_acquire_generation(&guard).is_ok());
+    }
+
+    fn lift(name: &str, max_weight: f64, sessions: usize) -> ProgressionResponse {
+        ProgressionResponse {
+            exercise_name: name.to_string(),
+            max_weight,
+            reps: 5,
+            date: "2026-03-01".to_string(),
+            history: (0..sessions)
+                .map(|_| super::TrendPoint {
+                    weight: max_weight,
+                    reps: 5,
+                    date: "2026-03-01".to_string(),
+                })
+                .collect(),
+            unit: "kg",
+        }
+    }
+
+    #[test]
+    fn top_progression_prefers_most_trained_then_heaviest_lifts() {
+        let lifts = vec![
+            lift("CURL", 15.0, 2),
+            lift("SQUAT", 120.0, 6),
+            lift("BENCH_PRESS", 85.0, 6),
+            lift("DEADLIFT", 150.0, 4),
+        ];
+        let names: Vec<_> = top_progression(lifts, 3)
+            .into_iter()
+            .map(|l| l.exercise_name)
+            .collect();
+        assert_eq!(names, ["SQUAT", "BENCH_PRESS", "DEADLIFT"]);
+    }
+
+    #[test]
+    fn upcoming_races_skip_past_events_and_plain_workouts() {
+        let data: crate::models::GarminResponse = serde_json::from_value(serde_json::json!({
+            "activities": [],
+            "plans": [],
+            "scheduled_workouts": [
+                { "title": "Marathon", "date": "2026-05-01", "itemType": "race" },
+                { "title": "Old 10k", "date": "2026-02-01", "itemType": "race" },
+                { "title": "Tempo", "date": "2026-03-12", "itemType": "workout" },
+                { "title": "Parkrun", "date": "2026-03-14", "itemType": "event" }
+            ]
+        }))
+        .unwrap();
+
+        let titles: Vec<_> = upcoming_races(Some(&data), "2026-03-10")
+            .into_iter()
+            .filter_map(|r| r.title)
+            .collect();
+        assert_eq!(titles, ["Parkrun", "Marathon"]);
+        assert!(upcoming_races(None, "2026-03-10").is_empty());
+    }
+
+    #[test]
+    fn race_countdowns_list_only_upcoming_races_by_date() {
+        let data: crate::models::GarminResponse = serde_json::from_value(serde_json::json!({
+            "activities": [],
+            "plans": [],
+            "scheduled_workouts": [
+                { "title": "Spring Marathon", "date": "2026-05-01", "itemType": "race",
+                  "sportTypeKey": "running", "distance": 42195.0 },
+                { "title": "Long Run", "date": "2026-03-15", "itemType": "workout" },
+                { "title": "Club Time Trial", "date": "2026-03-12", "itemType": "event",
+                  "sportTypeKey": "cycling" },
+                { "title": "Old 10k", "date": "2026-02-01", "itemType": "race" },
+                { "title": "A Race", "date": "2026-04-05", "itemType": "primaryEvent" },
+                { "title": "Easy Spin", "date": "2026-03-11", "itemType": "fbtAdaptiveWorkout" }
+            ]
+        }))
+        .unwrap();
+        let today = chrono::NaiveDate::from_ymd_opt(2026, 3, 10).unwrap();
+
+        let races = race_countdowns(Some(&data), today);
+        let summary: Vec<_> = races
+            .iter()
+            .map(|r| (r.title.as_deref().unwrap(), r.days_until))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("Club Time Trial", 2),
+                ("A Race", 26),
+                ("Spring Marathon", 52)
+            ]
+        );
+        assert_eq!(races[2].sport.as_deref(), Some("running"));
+        assert_eq!(races[2].distance, Some(42195.0));
+        assert!(race_countdowns(None, today).is_empty());
+    }
+
+    #[tokio::test]
+    async fn invalid_profiles_are_rejected_with_every_field_error() {
+        let payload = crate::profiles::ProfilesPayload {
+            active_profile: " ".to_string(),
+            profiles: Default::default(),
+        };
+
+        let (status, body) = update_profiles(Ok(axum::Json(payload))).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            body.0["errors"],
+            serde_json::json!([
+                {
+                    "field": "active_profile",
+                    "profile": null,
+                    "message": "active_profile cannot be empty."
+                },
+                {
+                    "field": "profiles",
+                    "profile": null,
+                    "message": "profiles must include at least one profile."
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn clearing_the_chat_empties_the_thread() {
+        let db = crate::db::memory_db();
+        db.add_coach_brief("brief", "plan").unwrap();
+        db.add_chat_exchange("Swap Friday?", "Moved to Saturday.")
+            .unwrap();
+        db.add_ai_chat_message("user", "Signal chat").unwrap();
+        assert_eq!(chat_messages(&db).len(), 4);
+
+        assert_eq!(db.clear_coach_briefs().unwrap(), 2);
+        assert!(chat_messages(&db).is_empty());
+        assert_eq!(db.get_ai_chat_history().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn forced_analysis_bypasses_and_replaces_the_cache() {
+        let db = crate::db::memory_db();
+        let date = "2026-03-14 07:00:00";
+
+        assert_eq!(cached_activity_analysis(&db, Some(42), false), None);
+        store_activity_analysis(&db, 42, date, "First take.", false).unwrap();
+        assert_eq!(
+            cached_activity_analysis(&db, Some(42), false).as_deref(),
+            Some("First take.")
+        );
+        assert_eq!(cached_activity_analysis(&db, None, false), None);
+
+        // A forced run skips the cache and its result replaces the stored one.
+        assert_eq!(cached_activity_analysis(&db, Some(42), true), None);
+        store_activity_analysis(&db, 42, date, "Second take.", true).unwrap();
+        assert_eq!(
+            cached_activity_analysis(&db, Some(42), false).as_deref(),
+            Some("Second take.")
+        );
+
+        // Unforced saves never clobber an existing analysis.
+        store_activity_analysis(&db, 42, date, "Late duplicate.", false).unwrap();
+        assert_eq!(
+            db.get_activity_analysis(42).unwrap().as_deref(),
+            Some("Second take.")
+        );
+
+        assert!(db.delete_activity_analysis(42).unwrap());
+        assert!(!db.delete_activity_analysis(42).unwrap());
+        assert_eq!(cached_activity_analysis(&db, Some(42), false), None);
+    }
+
+    #[test]
+    fn request_ids_are_short_hex_and_vary() {
+        let id = new_request_id();
+        assert_eq!(id.len(), 8);
+        assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+        let ids: std::collections::HashSet<_> = (0..16).map(|_| new_request_id()).collect();
+        assert!(ids.len() > 1);
+    }
+
+    #[test]
+    fn predicted_duration_cache_key_tolerates_empty_parts() {
+        assert_eq!(
+            predicted_duration_cache_key(" Long Run ", "running").as_deref(),
+            Some("Long Run|running")
+        );
+        assert_eq!(
+            predicted_duration_cache_key("", "running").as_deref(),
+            Some("|running")
+        );
+        assert_eq!(
+            predicted_duration_cache_key("Long Run", "").as_deref(),
+            Some("Long Run|")
+        );
+        assert_eq!(predicted_duration_cache_key(" ", ""), None);
+    }
+
+    fn activities() -> Vec<crate::models::GarminActivity> {
+        serde_json::from_value(serde_json::json!([
+            { "activityName": "Old Run", "startTimeLocal": "2026-02-27 07:00:00", "activityType": { "typeKey": "running" } },
+            { "activityName": "Trail", "startTimeLocal": "2026-03-02 07:00:00", "activityType": { "typeKey": "trail_running" } },
+            { "activityName": "Legs", "startTimeLocal": "2026-03-03 18:00:00", "activityType": { "typeKey": "strength_training" } },
+            { "activityName": "Tempo", "startTimeLocal": "2026-03-05 07:00:00", "activityType": { "typeKey": "running" } },
+            { "activityName": "Future Ride", "startTimeLocal": "2026-03-09 07:00:00", "activityType": { "typeKey": "cycling" } }
+        ]))
+        .unwrap()
+    }
+
+    fn names(activities: Vec<crate::models::GarminActivity>) -> Vec<String> {
+        activities.into_iter().filter_map(|a| a.name).collect()
+    }
+
+    #[test]
+    fn activities_filter_by_sport_newest_first() {
+        let day = |d| chrono::NaiveDate::from_ymd_opt(2026, 3, d).unwrap();
+        let runs = filter_activities(
+            activities(),
+            Some(crate::models::Sport::from_type_key("run")),
+            day(1),
+            day(31),
+        );
+        assert_eq!(names(runs), ["Tempo", "Trail"]);
+    }
+
+    #[test]
+    fn activities_filter_by_inclusive_date_range() {
+        let day = |d| chrono::NaiveDate::from_ymd_opt(2026, 3, d).unwrap();
+        let all = filter_activities(activities(), None, day(2), day(5));
+        assert_eq!(names(all), ["Tempo", "Legs", "Trail"]);
+        assert!(filter_activities(activities(), None, day(6), day(8)).is_empty());
+    }
+}
@@ -1,18 +1,50 @@
 use futures_util::StreamExt;
 use serde::Serialize;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 use tokio_tungstenite::{connect_async, tungstenite::protocol::Message as WsMessage};
 use tracing::{error, info};
 
+use crate::api::SlidingWindowLimiter;
 use crate::coaching::Coach;
 use crate::db::Database;
 use crate::garmin_client::GarminClient;
+use crate::models::Sport;
 pub struct BotController {
     pub database: Arc<Mutex<Database>>,
     pub config: Arc<crate::config::AppConfig>,
     pub garmin_client: Arc<GarminClient>,
     pub coach: Arc<Coach>,
+    pub generation_guard: crate::generation::GenerationGuard,
+    conversation_limiters: Mutex<HashMap<String, SlidingWindowLimiter>>,
+    live_context: Mutex<ContextCache>,
+}
+
+/// The live Garmin part of the conversation context, reused for `ttl` so
+/// back-to-back messages don't re-fetch everything.
+#[derive(Debug)]
+struct ContextCache {
+    ttl: Duration,
+    entry: Option<(Instant, String)>,
+}
+
+impl ContextCache {
+    fn new(ttl: Duration) -> Self {
+        Self { ttl, entry: None }
+    }
+
+    fn get(&self, now: Instant) -> Option<&str> {
+        self.entry
+            .as_ref()
+            .filter(|(built_at, _)| now.duration_since(*built_at) < self.ttl)
+            .map(|(_, context)| context.as_str())
+    }
+
+    fn store(&mut self, now: Instant, context: String) {
+        self.entry = Some((now, context));
+    }
 }
 
 // Structs removed in favor of serde_json::Value
@@ -30,15 +62,36 @@ impl BotController {
         garmin_client: Arc<GarminClient>,
         coach: Arc<Coach>,
         database: Arc<Mutex<Database>>,
+        generation_guard: crate::generation::GenerationGuard,
     ) -> Self {
         Self {
+            conversation_limiters: Mutex::new(HashMap::new()),
+            live_context: Mutex::new(ContextCache::new(Duration::from_secs(
+                config.bot_context_cache_secs,
+            ))),
             config,
             garmin_client,
             coach,
             database,
+            generation_guard,
         }
     }
 
+    /// Whether `sender` may start another conversational Gemini call now.
+    async fn allow_conversation(&self, sender: &str) -> bool {
+        self.conversation_limiters
+            .lock()
+            .await
+            .entry(sender.to_string())
+            .or_insert_with(|| {
+                SlidingWindowLimiter::new(
+                    self.config.bot_chat_rate_limit_per_minute,
+                    Duration::from_secs(60),
+                )
+            })
+            .allow()
+    }
+
     pub async fn run(&self) {
         info!("Starting Signal Bot... connecting to signal-cli-rest-api WS...");
 
@@ -165,6 +218,13 @@ impl BotController {
 
                             let response = self.handle_command(cmd, args).await;
                             self.send_reply(&msg_sender, &response).await;
+                        } else if !self.allow_conversation(&msg_sender).await {
+                            info!("Conversation rate limit hit for {}", msg_sender);
+                            self.send_reply(
+                                &msg_sender,
+                                "You're messaging faster than I can think! Give me a minute, then ask again.",
+                            )
+                            .await;
                         } else {
                             // Conversational Logic
                             let response = self.handle_conversation(text_trim).await;
@@ -176,16 +236,15 @@ impl BotController {
         }
     }
 
-    async fn handle_conversation(&self, text: &str) -> String {
-        let gemini_key = &self.config.gemini_api_key;
-        if gemini_key.is_empty() {
-            return "I cannot respond contextually without a GEMINI_API_KEY.".to_string();
+    /// Recovery, today's plan, recent workouts and upcoming events from
+    /// Garmin, cached for `bot_context_cache_secs`.
+    async fn garmin_context(&self) -> String {
+        let mut cache = self.live_context.lock().await;
+        if let Some(context) = cache.get(Instant::now()) {
+            return context.to_string();
         }
 
-        // 1. Fetch live context silently
-        let now = chrono::Local::now();
-        let mut context_str = format!("Current Date: {}", now.format("%a, %Y-%m-%d %H:%M"));
-
+        let mut context = String::new();
         if let Ok(data) = self.garmin_client.fetch_data().await {
             let bb = data
                 .recovery_metrics
@@ -200,7 +259,7 @@ impl BotController {
                 .map(|v: i32| v.to_string())
                 .unwrap_or_else(|| "N/A".to_string());
 
-            let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+            let today = self.config.now().format("%Y-%m-%d").to_string();
             let today_workouts: Vec<_> = data
                 .scheduled_workouts
                 .iter()
@@ -223,85 +282,91 @@ impl BotController {
                     .join(", ")
             };
 
-            context_str.push_str(&format!(
+            context.push_str(&format!(
                 "\nBody Battery: {}\nSleep Score: {}\nToday's Planned Workouts: {}",
                 bb, sleep, planned_str
             ));
 
             // Add recent activities to context
-            let seven_days_ago = (chrono::Local::now() - chrono::Duration::days(7))
-                .format("%Y-%m-%d")
-                .to_string();
+            let seven_days_ago = (self.config.now() - chrono::Duration::days(7)).date_naive();
             let recent_activities: Vec<_> = data
                 .activities
                 .iter()
-                .filter(|a| a.start_time >= seven_days_ago)
+                .filter(|a| a.start_date().is_some_and(|d| d >= seven_days_ago))
                 .collect();
 
             if !recent_activities.is_empty() {
-                context_str.push_str("\n\nRecent Workouts (Last 7 Days):\n");
+                context.push_str("\n\nRecent Workouts (Last 7 Days):\n");
                 for act in recent_activities {
                     let name = act.name.as_deref().unwrap_or("Untitled");
                     let sport = act.get_activity_type().unwrap_or("Unknown");
                     let date = act.start_time.split('T').next().unwrap_or(&act.start_time);
                     let dist = act.distance.unwrap_or(0.0) / 1000.0;
                     let dur_mins = act.duration.unwrap_or(0.0) / 60.0;
-                    context_str.push_str(&format!(
+                    context.push_str(&format!(
                         "- {} ({}) | {}: {:.1}km in {:.0} mins\n",
                         name, sport, date, dist, dur_mins
                     ));
                 }
             }
-        }
-
-        // Add recent analyses to context
-        {
-            let db = self.database.lock().await;
-            if let Ok(analyses) = db.get_recent_activity_analyses(7) {
-                if !analyses.is_empty() {
-                    context_str.push_str("\n\nRecent AI Coach Feedback (Last 7 Days):\n");
-                    for (date, summary) in analyses {
-                        context_str.push_str(&format!("- On {}:\n  {}\n", date, summary));
-                    }
-                }
-            }
-        }
 
-        // Add upcoming races & events
-        if let Ok(data) = self.garmin_client.fetch_data().await {
-            let today_str = chrono::Local::now().format("%Y-%m-%d").to_string();
+            // Add upcoming races & events
             let upcoming_events: Vec<_> = data
                 .scheduled_workouts
                 .iter()
-                .filter(|w| {
-                    if let Some(ref it) = w.item_type {
-                        (it == "race" || it == "event" || it == "primaryEvent")
-                            && w.date >= today_str
-                    } else {
-                        false
-                    }
-                })
+                .filter(|w| crate::models::is_race_item(w) && w.date >= today)
                 .collect();
 
             if !upcoming_events.is_empty() {
-                context_str.push_str("\n\nUpcoming Races & Events:\n");
+                context.push_str("\n\nUpcoming Races & Events:\n");
                 for ev in &upcoming_events {
                     let title = ev.title.as_deref().unwrap_or("Untitled Event");
                     let sport = ev.sport.as_deref().unwrap_or("Unknown");
-                    if let Ok(race_date) =
-                        chrono::NaiveDate::parse_from_str(&ev.date, "%Y-%m-%d")
-                    {
-                        let today_date = chrono::Local::now().naive_local().date();
+                    if let Ok(race_date) = chrono::NaiveDate::parse_from_str(&ev.date, "%Y-%m-%d") {
+                        let today_date = self.config.now().naive_local().date();
                         let days_until = (race_date - today_date).num_days();
-                        context_str.push_str(&format!(
+                        context.push_str(&format!(
                             "- {} ({}) on {} — {} days away\n",
                             title, sport, ev.date, days_until
                         ));
                     } else {
-                        context_str.push_str(&format!(
-                            "- {} ({}) on {}\n",
-                            title, sport, ev.date
-                        ));
+                        context.push_str(&format!("- {} ({}) on {}\n", title, sport, ev.date));
+                    }
+                }
+            }
+            // Failed fetches are not cached, so the next message retries.
+            cache.store(Instant::now(), context.clone());
+        }
+        context
+    }
+
+    async fn handle_conversation(&self, text: &str) -> String {
+        let gemini_key = &self.config.gemini_api_key;
+        if gemini_key.is_empty() {
+            return "I cannot respond contextually without a GEMINI_API_KEY.".to_string();
+        }
+
+        // 1. Fetch live context silently
+        let now = self.config.now();
+        let mut context_str = format!("Current Date: {}", now.format("%a, %Y-%m-%d %H:%M"));
+        context_str.push_str(&self.garmin_context().await);
+
+        // Add recent analyses and RPE logs to context
+        {
+            let db = self.database.lock().await;
+            if let Ok(rpe_logs) = db.get_recent_rpe(7) {
+                if !rpe_logs.is_empty() {
+                    context_str.push_str("\n\nLogged RPE (Last 7 Days):\n");
+                    for (date, exercise, rpe) in rpe_logs {
+                        context_str.push_str(&format!("- {}: {} @ RPE {}\n", date, exercise, rpe));
+                    }
+                }
+            }
+            if let Ok(analyses) = db.get_recent_activity_analyses(7) {
+                if !analyses.is_empty() {
+                    context_str.push_str("\n\nRecent AI Coach Feedback (Last 7 Days):\n");
+                    for (date, summary) in analyses {
+                        context_str.push_str(&format!("- On {}:\n  {}\n", date, summary));
                     }
                 }
             }
@@ -309,7 +374,7 @@ impl BotController {
 
         // Add profile goals, constraints, and equipment
         {
-            let (profile_ctx, _) = crate::load_profile_context();
+            let (profile_ctx, _) = crate::profiles::load_active_context();
             if !profile_ctx.goals.is_empty() {
                 context_str.push_str("\n\nAthlete Goals:\n");
                 for goal in &profile_ctx.goals {
@@ -343,9 +408,7 @@ impl BotController {
             }
         }
 
-        let gemini_model =
-            std::env::var("GEMINI_MODEL").unwrap_or_else(|_| "gemini-3-flash-preview".to_string());
-        let ai_client = crate::ai_client::AiClient::new(gemini_key.to_string(), gemini_model);
+        let ai_client = crate::ai_client::AiClient::from_config(&self.config);
 
         {
             let db = self.database.lock().await;
@@ -367,41 +430,18 @@ impl BotController {
                     let _ = db.add_ai_chat_message("model", &response);
                 }
 
-                // Scan for JSON code block indicating a reschedule
-                if let Ok(json_str) = crate::ai_client::AiClient::extract_json_block(&response) {
-                    if let Ok(workouts) = serde_json::from_str::<Vec<serde_json::Value>>(&json_str)
-                    {
-                        for workout_spec in workouts {
-                            let _ = crate::workout_builder::WorkoutBuilder::new()
-                                .build_workout_payload(&workout_spec, true);
-                            info!("Conversational Coach Scheduled Workout");
-                        }
+                // Scan every JSON code block for workouts indicating a reschedule
+                if let Ok(workouts) = crate::ai_client::AiClient::extract_workouts(&response) {
+                    for workout_spec in workouts {
+                        let _ = self
+                            .garmin_client
+                            .workout_builder()
+                            .build_workout_payload(&workout_spec, true);
+                        info!("Conversational Coach Scheduled Workout");
                     }
                 }
 
-                // Strip the exact markdown json block from the response before sending it
-                let clean_response = if let Some(start_idx) = response.find("```json") {
-                    if let Some(end_idx) = response[start_idx..]
-                        .find("```\n")
-                        .or_else(|| response[start_idx..].find("```"))
-                    {
-                        let full_end = start_idx + end_idx + 3;
-                        let mut cleaned = response.clone();
-                        // Also remove a trailing newline if it exists right after the block
-                        if cleaned.len() > full_end && cleaned.as_bytes()[full_end] == b'\n' {
-                            cleaned.replace_range(start_idx..=full_end, "");
-                        } else {
-                            cleaned.replace_range(start_idx..full_end, "");
-                        }
-                        cleaned.trim().to_string()
-                    } else {
-                        response
-                    }
-                } else {
-                    response
-                };
-
-                clean_response
+                strip_json_blocks(response)
             }
             Err(e) => format!("My coaching brain failed to connect: {}", e),
         }
@@ -423,7 +463,7 @@ impl BotController {
                         .and_then(|m| m.sleep_score)
                         .map(|v| v.to_string())
                         .unwrap_or_else(|| "N/A".to_string());
-                    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+                    let today = self.config.now().format("%Y-%m-%d").to_string();
                     let today_workouts: Vec<_> = data
                         .scheduled_workouts
                         .iter()
@@ -435,13 +475,7 @@ impl BotController {
                     } else {
                         today_workouts
                             .iter()
-                            .map(|w| {
-                                format!(
-                                    "{} ({})",
-                                    w.title.as_deref().unwrap_or("Untitled"),
-                                    w.sport.as_deref().unwrap_or("Unknown")
-                                )
-                            })
+                            .map(|w| format_planned_workout(w))
                             .collect::<Vec<_>>()
                             .join(", ")
                     };
@@ -451,18 +485,23 @@ impl BotController {
                 Err(e) => format!("Failed to fetch status from Garmin: {}", e),
             },
             "/generate" => {
+                let Some(_generation) = self.generation_guard.try_start() else {
+                    return "⏳ A generation is already running, please wait.".to_string();
+                };
                 match crate::run_coach_pipeline(
                     self.config.clone(),
                     self.garmin_client.clone(),
                     self.coach.clone(),
                     self.database.clone(),
                     true,
+                    false,
                 )
                 .await
                 {
                     Ok(_) => {
                         "✅ Successfully generated and scheduled the week's workouts!".to_string()
                     }
+                    Err(e @ crate::error::AppError::GarminAuth(_)) => e.user_message(),
                     Err(e) => format!("Failed to generate workout: {}", e),
                 }
             }
@@ -479,7 +518,7 @@ impl BotController {
                         if let (Ok(kcal), Ok(protein)) =
                             (kcal_str.parse::<i32>(), protein_str.parse::<i32>())
                         {
-                            let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+                            let today = self.config.now().format("%Y-%m-%d").to_string();
                             let db = self.database.lock().await;
                             if let Err(e) = db.log_nutrition(&today, kcal, protein) {
                                 format!("Failed to log macros: {}", e)
@@ -497,61 +536,351 @@ impl BotController {
             "/readiness" => match self.garmin_client.fetch_data().await {
                 Ok(data) => {
                     if !self.config.gemini_api_key.is_empty() {
-                        crate::bot::generate_race_readiness_assessment(
-                            &data,
-                            &self.config.gemini_api_key,
-                        )
-                        .await
+                        crate::bot::generate_race_readiness_assessment(&data, &self.config).await
                     } else {
                         "GEMINI_API_KEY is not set. Cannot run readiness assessment.".to_string()
                     }
                 }
                 Err(e) => format!("Failed to fetch Garmin data: {}", e),
             },
-            _ => "Command not recognized. Use /status, /generate, /readiness, or /macros."
+            "/injury" => {
+                let description = args.trim();
+                if description.is_empty() {
+                    return "Please describe the injury. Example: /injury sore left knee, no deep squats"
+                        .to_string();
+                }
+
+                let path = crate::profiles::profiles_path();
+                let constraint = format!("Injury: {}", description);
+                let constraints = match crate::profiles::add_active_constraint(
+                    std::path::Path::new(&path),
+                    &constraint,
+                ) {
+                    Ok(c) => c,
+                    Err(e) => return format!("Failed to save injury to profile: {}", e),
+                };
+
+                let mut reply = format!(
+                    "🩹 Added constraint #{}: {}\nUse /healed {} once it has recovered.",
+                    constraints.len(),
+                    constraint,
+                    constraints.len()
+                );
+                if !self.config.gemini_api_key.is_empty() {
+                    let advice = generate_injury_advice(description, &constraints, &self.config)
+                    .await;
+                    reply.push_str("\n\n");
+                    reply.push_str(&advice);
+                }
+                reply
+            }
+            "/healed" => {
+                let path = crate::profiles::profiles_path();
+                let path = std::path::Path::new(&path);
+                match args.trim().parse::<usize>() {
+                    Ok(index) => match crate::profiles::remove_active_constraint(path, index) {
+                        Ok(removed) => format!("💪 Removed constraint: {}", removed),
+                        Err(e) => format!("Failed to remove constraint: {}", e),
+                    },
+                    Err(_) => match crate::profiles::load_profiles(path) {
+                        Ok(payload) => {
+                            let constraints = payload
+                                .profiles
+                                .get(&payload.active_profile)
+                                .map(|p| p.constraints.clone())
+                                .unwrap_or_default();
+                            if constraints.is_empty() {
+                                "No active constraints.".to_string()
+                            } else {
+                                let list = constraints
+                                    .iter()
+                                    .enumerate()
+                                    .map(|(i, c)| format!("{}. {}", i + 1, c))
+                                    .collect::<Vec<_>>()
+                                    .join("\n");
+                                format!(
+                                    "Usage: /healed <number>\n\nActive constraints:\n{}",
+                                    list
+                                )
+                            }
+                        }
+                        Err(e) => format!("Failed to load profiles: {}", e),
+                    },
+                }
+            }
+            "/rpe" => {
+                let usage = "Usage: /rpe <exercise> <rpe 1-10>. Example: /rpe bench press 8";
+                let Some((exercise, rpe_str)) = args.trim().rsplit_once(char::is_whitespace)
+                else {
+                    return usage.to_string();
+                };
+                let rpe = match rpe_str.parse::<i32>() {
+                    Ok(rpe) if (1..=10).contains(&rpe) => rpe,
+                    Ok(_) => return "RPE must be between 1 and 10.".to_string(),
+                    Err(_) => return usage.to_string(),
+                };
+
+                let builder = self.garmin_client.workout_builder();
+                let Some(category) = builder.resolve_exercise(exercise).0 else {
+                    return format!("Unknown exercise '{}'.", exercise.trim());
+                };
+
+                let today = self.config.now().format("%Y-%m-%d").to_string();
+                let db = self.database.lock().await;
+                match db.log_rpe(&today, &category, rpe) {
+                    Ok(_) => format!("✅ Logged RPE {} for {} ({}).", rpe, category, today),
+                    Err(e) => format!("Failed to log RPE: {}", e),
+                }
+            }
+            "/next" => match self.garmin_client.fetch_data().await {
+                Ok(data) => {
+                    let today = self.config.now().format("%Y-%m-%d").to_string();
+                    match next_scheduled_workout(&data.scheduled_workouts, &today) {
+                        Some(w) => format!(
+                            "⏭️ Next workout ({}):\n{}",
+                            w.date.get(..10).unwrap_or(&w.date),
+                            scheduled_workout_details(
+                                w,
+                                &load_generated_workouts(),
+                                self.garmin_client.ai_tags(),
+                                crate::profiles::active_units(),
+                            )
+                        ),
+                        None => "Nothing scheduled".to_string(),
+                    }
+                }
+                Err(e) => format!("Failed to fetch the schedule from Garmin: {}", e),
+            },
+            "/pr" => {
+                let builder = self.garmin_client.workout_builder();
+                let units = crate::profiles::active_units();
+                let db = self.database.lock().await;
+                personal_best_reply(&db, &builder, args, units)
+            }
+            "/cleanup-orphans" => match self.garmin_client.remove_orphaned_schedules().await {
+                Ok(removed) if removed.is_empty() => "✅ No orphaned schedule entries found.".to_string(),
+                Ok(removed) => {
+                    let list = removed
+                        .iter()
+                        .map(|o| format!("- {} ({})", o.title, o.date))
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    let _ = self.database.lock().await.clear_garmin_cache();
+                    format!(
+                        "🧹 Removed {} orphaned schedule entries:\n{}",
+                        removed.len(),
+                        list
+                    )
+                }
+                Err(e) => format!("Failed to clean up orphaned schedules: {}", e),
+            },
+            "/undo" => {
+                let Some(_generation) = self.generation_guard.try_start() else {
+                    return "⏳ A generation is already running, please wait.".to_string();
+                };
+                match self.garmin_client.undo_last_generation().await {
+                    Ok(None) => "No recorded generation to undo.".to_string(),
+                    Ok(Some(removed)) => {
+                        let _ = self.database.lock().await.clear_garmin_cache();
+                        format!("↩️ Removed {} workouts from the last generation.", removed)
+                    }
+                    Err(e) => format!("Failed to undo the last generation: {:#}", e),
+                }
+            }
+            _ => "Command not recognized. Use /status, /next, /generate, /undo, /readiness, /macros, /rpe, /pr, /injury, /healed, or /cleanup-orphans."
                 .to_string(),
         }
     }
 
     async fn send_reply(&self, recipient: &str, text: &str) {
-        let phone_number = &self.config.signal_phone_number;
-        if phone_number.trim().is_empty() {
-            error!("Warning: signal_phone_number not set. Cannot send reply.");
+        let recipients = vec![recipient.to_string()];
+        let undelivered = send_chunked(&self.config, &recipients, text).await;
+        if undelivered.is_empty() {
             return;
         }
+        let db = self.database.lock().await;
+        for chunk in &undelivered {
+            if let Err(e) = db.queue_pending_message(recipient, chunk) {
+                error!("Failed to queue undelivered Signal reply: {}", e);
+            }
+        }
+    }
+}
 
-        let send_req = SendMessageReq {
-            message: text.to_string(),
-            number: phone_number.clone(),
-            recipients: vec![recipient.to_string()],
+const SIGNAL_SEND_ATTEMPTS: u32 = 3;
+/// Longer messages are split by `split_message` before sending.
+const SIGNAL_MAX_MESSAGE_LEN: usize = 2000;
+/// Room kept at the end of each chunk for the " (12/34)" counter.
+const CHUNK_SUFFIX_RESERVE: usize = 10;
+
+/// Removes every ```json block (and the newline right after it) from a chat
+/// reply before it is sent over Signal.
+fn strip_json_blocks(response: String) -> String {
+    let mut cleaned = response;
+    while let Some(start_idx) = cleaned.find("```json") {
+        let body_start = start_idx + "```json".len();
+        let Some(end_idx) = cleaned[body_start..].find("```") else {
+            break;
         };
+        let full_end = body_start + end_idx + 3;
+        if cleaned.len() > full_end && cleaned.as_bytes()[full_end] == b'\n' {
+            cleaned.replace_range(start_idx..=full_end, "");
+        } else {
+            cleaned.replace_range(start_idx..full_end, "");
+        }
+    }
+    cleaned.trim().to_string()
+}
 
-        let api_host = &self.config.signal_api_host;
-        let client = reqwest::Client::new();
-        let res = client
-            .post(format!("http://{}:8080/v2/send", api_host))
-            .json(&send_req)
-            .send()
-            .await;
-
-        match res {
-            Ok(r) => {
-                if !r.status().is_success() {
-                    let status = r.status();
-                    if let Ok(body) = r.text().await {
-                        error!("Signal reply failed with status {}: {}", status, body);
-                    } else {
-                        error!("Signal reply failed with status {}", status);
-                    }
-                }
-            }
+/// Splits `text` into chunks of at most `max_len` characters, preferring
+/// paragraph, then sentence, then word boundaries. Multi-part messages get a
+/// " (1/3)" style suffix.
+pub fn split_message(text: &str, max_len: usize) -> Vec<String> {
+    let text = text.trim();
+    if text.chars().count() <= max_len {
+        return vec![text.to_string()];
+    }
+
+    let limit = max_len.saturating_sub(CHUNK_SUFFIX_RESERVE).max(1);
+    let mut chunks = Vec::new();
+    let mut rest = text;
+    while rest.chars().count() > limit {
+        let window_end = rest
+            .char_indices()
+            .nth(limit)
+            .map(|(i, _)| i)
+            .unwrap_or(rest.len());
+        let window = &rest[..window_end];
+
+        let paragraph = window.rfind("\n\n");
+        let sentence = [". ", "! ", "? ", ".\n", "!\n", "?\n"]
+            .iter()
+            .filter_map(|p| window.rfind(p).map(|i| i + 1))
+            .max();
+        // A boundary in the next character still counts as a whole word.
+        let word = rest[window_end..]
+            .starts_with(char::is_whitespace)
+            .then_some(window_end)
+            .or_else(|| window.rfind(char::is_whitespace));
+
+        let cut = [paragraph, sentence, word]
+            .into_iter()
+            .flatten()
+            .find(|&i| i > 0)
+            .unwrap_or(window_end);
+
+        chunks.push(rest[..cut].trim_end().to_string());
+        rest = rest[cut..].trim_start();
+    }
+    if !rest.is_empty() {
+        chunks.push(rest.to_string());
+    }
+
+    let total = chunks.len();
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| format!("{} ({}/{})", chunk, i + 1, total))
+        .collect()
+}
+
+/// Sends `text` as one or more chunks, pausing briefly between them. Returns
+/// the chunks that could not be delivered, in order.
+async fn send_chunked(
+    config: &crate::config::AppConfig,
+    recipients: &[String],
+    text: &str,
+) -> Vec<String> {
+    let chunks = split_message(text, SIGNAL_MAX_MESSAGE_LEN);
+    for (i, chunk) in chunks.iter().enumerate() {
+        if i > 0 {
+            tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+        }
+        if let Err(e) = send_signal_with_retry(config, recipients, chunk).await {
+            error!("Signal send failed, queueing for retry: {}", e);
+            return chunks[i..].to_vec();
+        }
+    }
+    Vec::new()
+}
+
+async fn send_signal(
+    config: &crate::config::AppConfig,
+    recipients: &[String],
+    text: &str,
+) -> anyhow::Result<()> {
+    let phone_number = &config.signal_phone_number;
+    if phone_number.trim().is_empty() {
+        anyhow::bail!("signal_phone_number not set");
+    }
+
+    let send_req = SendMessageReq {
+        message: text.to_string(),
+        number: phone_number.clone(),
+        recipients: recipients.to_vec(),
+    };
+
+    let api_host = &config.signal_api_host;
+    let client = reqwest::Client::new();
+    let r = client
+        .post(format!("http://{}:8080/v2/send", api_host))
+        .json(&send_req)
+        .send()
+        .await?;
+
+    if !r.status().is_success() {
+        let status = r.status();
+        let body = r.text().await.unwrap_or_default();
+        anyhow::bail!("status {}: {}", status, body);
+    }
+    Ok(())
+}
+
+/// Tries a send up to `SIGNAL_SEND_ATTEMPTS` times, backing off 1s, 2s, ...
+async fn send_signal_with_retry(
+    config: &crate::config::AppConfig,
+    recipients: &[String],
+    text: &str,
+) -> anyhow::Result<()> {
+    let mut attempt = 1;
+    loop {
+        match send_signal(config, recipients, text).await {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt >= SIGNAL_SEND_ATTEMPTS => return Err(e),
             Err(e) => {
-                error!("Failed to send Signal reply network error: {}", e);
+                error!(
+                    "Signal send attempt {}/{} failed: {}",
+                    attempt, SIGNAL_SEND_ATTEMPTS, e
+                );
+                tokio::time::sleep(tokio::time::Duration::from_secs(1 << (attempt - 1))).await;
+                attempt += 1;
             }
         }
     }
 }
 
+/// 64-bit FNV-1a; unlike `DefaultHasher` its output never changes between Rust
+/// releases, so stored digests stay comparable.
+fn fnv1a_64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// True when `text` may be broadcast, i.e. it differs from the last broadcast
+/// or that one is at least `window_mins` old (0 disables the check).
+fn claim_broadcast(db: &Database, text: &str, window_mins: u64) -> bool {
+    if window_mins == 0 {
+        return true;
+    }
+    let hash = format!("{:016x}", fnv1a_64(text.trim().as_bytes()));
+    db.record_broadcast(&hash, window_mins * 60)
+        .unwrap_or_else(|e| {
+            error!("Failed to check broadcast history: {}", e);
+            true
+        })
+}
+
 pub async fn broadcast_message(text: &str, config: &crate::config::AppConfig) {
     let subscribers_var = &config.signal_subscribers;
     if subscribers_var.trim().is_empty() {
@@ -568,54 +897,262 @@ pub async fn broadcast_message(text: &str, config: &crate::config::AppConfig) {
         return;
     }
 
-    let phone_number = &config.signal_phone_number;
-    if phone_number.trim().is_empty() {
+    if config.signal_phone_number.trim().is_empty() {
         error!("Warning: signal_phone_number not set. Skipping broadcast.");
         return;
     }
 
-    let send_req = SendMessageReq {
-        message: text.to_string(),
-        number: phone_number.clone(),
-        recipients,
-    };
+    let db = Database::new(config);
+    if let Ok(db) = &db {
+        if !claim_broadcast(db, text, config.broadcast_dedup_mins) {
+            info!(
+                "Suppressed Signal broadcast identical to one sent in the last {} min",
+                config.broadcast_dedup_mins
+            );
+            return;
+        }
+    }
 
-    let api_host = &config.signal_api_host;
-    let client = reqwest::Client::new();
-    let res = client
-        .post(format!("http://{}:8080/v2/send", api_host))
-        .json(&send_req)
-        .send()
-        .await;
-
-    match res {
-        Ok(r) => {
-            if !r.status().is_success() {
-                let status = r.status();
-                if let Ok(body) = r.text().await {
-                    error!("Signal broadcast failed with status {}: {}", status, body);
-                } else {
-                    error!("Signal broadcast failed with status {}", status);
+    let undelivered = send_chunked(config, &recipients, text).await;
+    if undelivered.is_empty() {
+        info!("Signal broadcast succeeded!");
+        return;
+    }
+    match db {
+        Ok(db) => {
+            for recipient in &recipients {
+                for chunk in &undelivered {
+                    if let Err(e) = db.queue_pending_message(recipient, chunk) {
+                        error!("Failed to queue undelivered Signal broadcast: {}", e);
+                    }
                 }
-            } else {
-                info!("Signal broadcast succeeded!");
             }
         }
-        Err(e) => {
-            error!("Failed to broadcast Signal message network error: {}", e);
+        Err(e) => error!("Failed to open database to queue broadcast: {}", e),
+    }
+}
+
+/// Periodically re-sends messages that could not be delivered earlier, oldest
+/// first, stopping at the first failure so ordering is preserved.
+pub fn start_pending_message_flusher(
+    database: Arc<Mutex<Database>>,
+    config: Arc<crate::config::AppConfig>,
+) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+
+            let pending = match database.lock().await.get_pending_messages(20) {
+                Ok(pending) => pending,
+                Err(e) => {
+                    error!("Failed to load pending Signal messages: {}", e);
+                    continue;
+                }
+            };
+
+            for (id, recipient, body) in pending {
+                if let Err(e) = send_signal(&config, &[recipient], &body).await {
+                    error!("Pending Signal message still undeliverable: {}", e);
+                    break;
+                }
+                if let Err(e) = database.lock().await.delete_pending_message(id) {
+                    error!("Failed to remove delivered pending message: {}", e);
+                }
+            }
         }
+    });
+}
+
+/// Icon for a Garmin sport or activity type key; unknown sports get a neutral medal.
+pub fn sport_emoji(sport: &str) -> &'static str {
+    let key = sport.to_lowercase();
+    let has = |parts: &[&str]| parts.iter().any(|p| key.contains(p));
+    if has(&["yoga", "pilates", "breathwork", "meditation"]) {
+        return "🧘";
+    }
+    if has(&["hik"]) {
+        return "🥾";
+    }
+    if has(&["walk"]) {
+        return "🚶";
+    }
+    if has(&["rowing"]) {
+        return "🚣";
+    }
+    match Sport::from_type_key(&key) {
+        Sport::Run => "🏃",
+        Sport::Bike => "🚴",
+        Sport::Swim => "🏊",
+        Sport::Strength => "🏋️",
+        Sport::Cardio => "🤸",
+        Sport::Other => "🏅",
     }
 }
 
-pub fn format_workout_details(workout_spec: &serde_json::Value) -> String {
+/// `"🏃 Tempo Run (running)"` for a scheduled workout.
+fn format_planned_workout(w: &crate::models::ScheduledWorkout) -> String {
+    let sport = w.sport.as_deref().unwrap_or("Unknown");
+    format!(
+        "{} {} ({})",
+        sport_emoji(sport),
+        w.title.as_deref().unwrap_or("Untitled"),
+        sport
+    )
+}
+
+/// The workout `/next` shows: today's when there is one, otherwise the soonest
+/// future one. Races and events are not workouts and are skipped.
+pub fn next_scheduled_workout<'a>(
+    scheduled: &'a [crate::models::ScheduledWorkout],
+    today: &str,
+) -> Option<&'a crate::models::ScheduledWorkout> {
+    scheduled
+        .iter()
+        .filter(|w| !crate::models::is_race_item(w))
+        .filter(|w| w.date.get(..10).unwrap_or(&w.date) >= today)
+        .min_by(|a, b| a.date.cmp(&b.date))
+}
+
+/// The generated specs saved to `GENERATED_WORKOUTS_PATH`, empty when missing.
+pub fn load_generated_workouts() -> Vec<serde_json::Value> {
+    let path = std::env::var("GENERATED_WORKOUTS_PATH")
+        .unwrap_or_else(|_| "generated_workouts.json".to_string());
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// `format_workout_details` of a calendar entry, using its generated spec (same
+/// date and tagged name) for the steps when there is one.
+pub fn scheduled_workout_details(
+    w: &crate::models::ScheduledWorkout,
+    generated: &[serde_json::Value],
+    tags: &crate::garmin_client::AiWorkoutTags,
+    units: crate::models::Units,
+) -> String {
+    let title = w.title.as_deref().unwrap_or("Untitled");
+    let date = w.date.get(..10).unwrap_or(&w.date);
+    let spec = generated.iter().find(|spec| {
+        let name = spec.get("workoutName").and_then(|n| n.as_str());
+        let scheduled = spec.get("scheduledDate").and_then(|d| d.as_str());
+        scheduled == Some(date) && name.is_some_and(|n| n == title || tags.tag_name(n) == title)
+    });
+    match spec {
+        Some(spec) => format_workout_details(spec, units),
+        None => format_workout_details(
+            &serde_json::json!({
+                "workoutName": title,
+                "sport": w.sport.as_deref().unwrap_or("Unknown"),
+                "description": w.description,
+            }),
+            units,
+        ),
+    }
+}
+
+/// Lifts listed by `/pr` without an exercise.
+const PR_TOP_LIFTS: usize = 5;
+
+/// Reply to `/pr [exercise]`: the heaviest recorded set of the resolved
+/// exercise, or the top lifts by estimated 1RM when `query` is empty.
+fn personal_best_reply(
+    db: &Database,
+    builder: &crate::workout_builder::WorkoutBuilder,
+    query: &str,
+    units: crate::models::Units,
+) -> String {
+    let query = query.trim();
+    let category = if query.is_empty() {
+        None
+    } else {
+        match builder.resolve_exercise(query).0 {
+            Some(category) => Some(category),
+            None => return format!("Unknown exercise '{}'.", query),
+        }
+    };
+    let history = match db.get_progression_history_raw() {
+        Ok(history) => history,
+        Err(e) => return format!("Failed to load lift history: {}", e),
+    };
+    // (exercise, weight, reps, date, estimated 1RM) of each lift's heaviest set.
+    let mut bests: Vec<_> = history
+        .iter()
+        .filter(|(_, weight, _, _, _)| *weight > 0.0)
+        .map(|(name, weight, reps, date, _)| {
+            let e1rm = crate::db::estimated_1rm(*weight, *reps);
+            (name, *weight, *reps, date.get(..10).unwrap_or(date), e1rm)
+        })
+        .collect();
+
+    let Some(category) = category else {
+        if bests.is_empty() {
+            return "No strength records yet. Log a lifting session and I'll track your bests."
+                .to_string();
+        }
+        bests.sort_by(|a, b| b.4.total_cmp(&a.4));
+        let lines: Vec<String> = bests
+            .iter()
+            .take(PR_TOP_LIFTS)
+            .enumerate()
+            .map(|(i, (name, weight, reps, date, e1rm))| {
+                format!(
+                    "{}. {}: {} × {} on {} (est. 1RM {})",
+                    i + 1,
+                    name,
+                    units.format_weight(*weight),
+                    reps,
+                    date,
+                    units.format_weight(*e1rm)
+                )
+            })
+            .collect();
+        return format!("🏆 Top lifts by estimated 1RM\n\n{}", lines.join("\n"));
+    };
+
+    match bests.iter().find(|(name, ..)| **name == category) {
+        Some((name, weight, reps, date, e1rm)) => format!(
+            "🏆 {} best: {} × {} on {}\nEstimated 1RM: {}",
+            name,
+            units.format_weight(*weight),
+            reps,
+            date,
+            units.format_weight(*e1rm)
+        ),
+        None => format!(
+            "No records yet for {}. Log a set and I'll start tracking it.",
+            category
+        ),
+    }
+}
+
+/// `" (+2.5kg vs best)"`, or a regression flag when the load is below the record.
+fn format_progression_delta(delta_kg: f64, units: crate::models::Units) -> String {
+    if delta_kg.abs() < 0.05 {
+        " (= best)".to_string()
+    } else if delta_kg > 0.0 {
+        format!(" (+{} vs best)", units.format_weight(delta_kg))
+    } else {
+        format!(" ⬇️ regression ({} vs best)", units.format_weight(delta_kg))
+    }
+}
+
+pub fn format_workout_details(
+    workout_spec: &serde_json::Value,
+    units: crate::models::Units,
+) -> String {
     let mut out = String::new();
     let name = workout_spec
         .get("workoutName")
         .and_then(|v| v.as_str())
         .unwrap_or("Unknown Workout");
+    // Specs without a sport are strength workouts (see `WorkoutBuilder`).
+    let sport = workout_spec
+        .get("sport")
+        .and_then(|v| v.as_str())
+        .unwrap_or("strength_training");
 
-    let display_name = crate::garmin_client::ensure_ai_workout_name(name);
-    out.push_str(&format!("🏋️ {}\n", display_name));
+    out.push_str(&format!("{} {}\n", sport_emoji(sport), name));
 
     if let Some(desc) = workout_spec.get("description").and_then(|v| v.as_str()) {
         out.push_str(&format!("{}\n", desc));
@@ -647,16 +1184,23 @@ pub fn format_workout_details(workout_spec: &serde_json::Value) -> String {
                 if let Some(sets) = step.get("sets") {
                     details.push_str(&format!(" | Sets: {}", sets));
                 }
-                if let Some(weight) = step.get("weight") {
-                    let w = if weight.is_string() {
-                        weight.as_str().unwrap().to_string()
-                    } else {
-                        weight.to_string()
-                    };
-                    if w != "0" && w != "0.0" {
-                        details.push_str(&format!(" | Weight: {}kg", w));
+                if let Some(kg) = step
+                    .get("weight")
+                    .and_then(crate::workout_builder::WorkoutBuilder::parse_weight)
+                {
+                    if kg > 0.0 {
+                        details.push_str(&format!(" | Weight: {}", units.format_weight(kg)));
                     }
                 }
+                if let Some(delta) = step.get("progression_delta").and_then(|v| v.as_f64()) {
+                    details.push_str(&format_progression_delta(delta, units));
+                }
+                if let Some(tempo) = crate::workout_builder::WorkoutBuilder::tempo_text(
+                    step.get("tempo").and_then(|v| v.as_str()),
+                    step.get("tut_seconds").and_then(|v| v.as_i64()),
+                ) {
+                    details.push_str(&format!(" | {}", tempo));
+                }
                 if let Some(note) = step.get("note").and_then(|v| v.as_str()) {
                     details.push_str(&format!("\n  📝 {}", note));
                 }
@@ -676,7 +1220,7 @@ pub fn start_morning_notifier(
         let mut last_sent_date = String::new();
 
         loop {
-            let now = chrono::Local::now();
+            let now = config.now();
             let today = now.format("%Y-%m-%d").to_string();
 
             let time_str = &config.morning_message_time;
@@ -695,13 +1239,7 @@ pub fn start_morning_notifier(
                         if !today_workouts.is_empty() {
                             let planned_str = today_workouts
                                 .iter()
-                                .map(|w| {
-                                    format!(
-                                        "{} ({})",
-                                        w.title.as_deref().unwrap_or("Untitled"),
-                                        w.sport.as_deref().unwrap_or("Unknown")
-                                    )
-                                })
+                                .map(|w| format_planned_workout(w))
                                 .collect::<Vec<_>>()
                                 .join("\n- ");
 
@@ -726,16 +1264,149 @@ pub fn start_morning_notifier(
     });
 }
 
+const GARMIN_AUTH_ALERT_KEY: &str = "garmin_auth_alert";
+const GARMIN_AUTH_ALERT_SENT: &str = "sent";
+
+/// Whether a Garmin-auth alert should go out now: only on a failed cycle, and
+/// only if none was sent since the last successful one.
+pub fn garmin_auth_alert_due(auth_failed: bool, previous: Option<&str>) -> bool {
+    auth_failed && previous != Some(GARMIN_AUTH_ALERT_SENT)
+}
+
+/// Broadcasts a single "please re-run login" alert when a daemon cycle
+/// failed on Garmin auth. The alert is re-armed after the next successful cycle.
+pub async fn report_garmin_auth_status(
+    database: &Arc<Mutex<Database>>,
+    config: &crate::config::AppConfig,
+    result: &crate::error::AppResult<()>,
+) {
+    let auth_error = match result {
+        Ok(()) => None,
+        Err(e @ crate::error::AppError::GarminAuth(_)) => Some(e),
+        Err(_) => return,
+    };
+    let previous = database
+        .lock()
+        .await
+        .get_kv(GARMIN_AUTH_ALERT_KEY)
+        .unwrap_or(None);
+
+    let Some(err) = auth_error else {
+        if previous.as_deref() == Some(GARMIN_AUTH_ALERT_SENT) {
+            if let Err(e) = database.lock().await.set_kv(GARMIN_AUTH_ALERT_KEY, "ok") {
+                error!("Failed to reset Garmin auth alert: {}", e);
+            }
+        }
+        return;
+    };
+
+    if !garmin_auth_alert_due(true, previous.as_deref()) {
+        return;
+    }
+    broadcast_message(&err.user_message(), config).await;
+    if let Err(e) = database
+        .lock()
+        .await
+        .set_kv(GARMIN_AUTH_ALERT_KEY, GARMIN_AUTH_ALERT_SENT)
+    {
+        error!("Failed to record Garmin auth alert: {}", e);
+    }
+}
+
+const STALE_CHECKIN_KEY: &str = "stale_checkin_sent_for";
+
+/// Days since `last_activity` when a check-in is due: the gap has reached
+/// `gap_days` and no check-in was sent yet for this same last activity date
+/// (`already_sent_for`). A new activity resets the cycle.
+pub fn stale_checkin_due(
+    last_activity: Option<chrono::NaiveDate>,
+    today: chrono::NaiveDate,
+    gap_days: u32,
+    already_sent_for: Option<&str>,
+) -> Option<i64> {
+    let last_activity = last_activity?;
+    if gap_days == 0 {
+        return None;
+    }
+    let days = (today - last_activity).num_days();
+    let key = last_activity.format("%Y-%m-%d").to_string();
+    if days >= gap_days as i64 && already_sent_for != Some(key.as_str()) {
+        Some(days)
+    } else {
+        None
+    }
+}
+
+/// Sends a single "everything okay?" message once no activity has been
+/// recorded for `stale_checkin_days`, and stays quiet until a new activity shows up.
+pub fn start_stale_data_checkin_notifier(
+    garmin_client: Arc<GarminClient>,
+    database: Arc<Mutex<Database>>,
+    config: Arc<crate::config::AppConfig>,
+) {
+    if config.stale_checkin_days == 0 {
+        return;
+    }
+
+    tokio::spawn(async move {
+        loop {
+            let now = config.now();
+            let current_time = now.format("%H:%M").to_string();
+
+            if current_time == config.stale_checkin_time {
+                match garmin_client.fetch_data().await {
+                    Ok(data) => {
+                        let last_activity =
+                            data.activities.iter().filter_map(|a| a.start_date()).max();
+                        let already_sent_for = database
+                            .lock()
+                            .await
+                            .get_kv(STALE_CHECKIN_KEY)
+                            .unwrap_or(None);
+
+                        if let Some(days) = stale_checkin_due(
+                            last_activity,
+                            now.date_naive(),
+                            config.stale_checkin_days,
+                            already_sent_for.as_deref(),
+                        ) {
+                            let msg = format!(
+                                "👋 Haven't seen a workout in {} days — everything okay? Let me know if you're sick, injured or just need a lighter week and I'll adjust the plan.",
+                                days
+                            );
+                            broadcast_message(&msg, &config).await;
+                            if let Some(date) = last_activity {
+                                let key = date.format("%Y-%m-%d").to_string();
+                                if let Err(e) =
+                                    database.lock().await.set_kv(STALE_CHECKIN_KEY, &key)
+                                {
+                                    error!("Failed to record stale-data check-in: {}", e);
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        error!("Check-in notifier failed to fetch garmin data: {}", e);
+                    }
+                }
+            }
+
+            // Sleep for roughly a minute
+            tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+        }
+    });
+}
+
 pub fn start_weekly_review_notifier(
     garmin_client: Arc<GarminClient>,
+    database: Arc<Mutex<Database>>,
     config: Arc<crate::config::AppConfig>,
 ) {
     tokio::spawn(async move {
         let mut last_sent_week = String::new();
 
         loop {
-            let now = chrono::Local::now();
-            let today_str = now.format("%Y-%m-%d").to_string();
+            let now = config.now();
             // Get week representation like "2026-W09" to ensure we only send once per week
             let current_week = now.format("%G-W%V").to_string();
 
@@ -751,128 +1422,15 @@ pub fn start_weekly_review_notifier(
             {
                 match garmin_client.fetch_data().await {
                     Ok(data) => {
-                        let gemini_model = std::env::var("GEMINI_MODEL")
-                            .unwrap_or_else(|_| "gemini-3-flash-preview".to_string());
-                        let ai_client = crate::ai_client::AiClient::new(
-                            config.gemini_api_key.clone(),
-                            gemini_model,
-                        );
-                        let seven_days_ago = now - chrono::Duration::days(7);
-                        let seven_days_ago_str = seven_days_ago.format("%Y-%m-%d").to_string();
-
-                        let recent_activities: Vec<_> = data
-                            .activities
-                            .iter()
-                            .filter(|a| a.start_time >= seven_days_ago_str)
-                            .collect();
-
-                        // Calculate volume broken down by activity type
-                        let act_count = recent_activities.len();
-                        info!("Weekly summary: {} activities in range since {}", act_count, seven_days_ago_str);
-                        let mut type_stats: std::collections::HashMap<String, (f64, f64, usize)> =
-                            std::collections::HashMap::new();
-                        for a in &recent_activities {
-                            let atype = a
-                                .get_activity_type()
-                                .unwrap_or("other")
-                                .replace('_', " ");
-                            let dist_m = a.distance.unwrap_or(0.0);
-                            let dur_s = a.duration.unwrap_or(0.0);
-                            info!(
-                                "  Activity: name={:?} type={} date={} distance={:.0}m ({:.2}km) duration={:.0}s ({:.1}min)",
-                                a.name.as_deref().unwrap_or("?"),
-                                atype,
-                                &a.start_time,
-                                dist_m,
-                                dist_m / 1000.0,
-                                dur_s,
-                                dur_s / 60.0
-                            );
-                            let entry = type_stats.entry(atype).or_insert((0.0, 0.0, 0));
-                            entry.0 += dist_m / 1000.0; // km
-                            entry.1 += dur_s / 60.0; // mins
-                            entry.2 += 1;
-                        }
-                        for (atype, (dist, dur, count)) in &type_stats {
-                            info!("  Type totals: {} (×{}): {:.1} km, {:.0} mins", atype, count, dist, dur);
-                        }
-                        let total_distance_km: f64 =
-                            type_stats.values().map(|(d, _, _)| d).sum();
-                        let total_duration_mins: f64 =
-                            type_stats.values().map(|(_, t, _)| t).sum();
-
-                        // Build Prompt Context
-                        let mut context = format!(
-                            "Athlete's Weekly Summary\nTimeframe: {} to {}\nWorkouts Completed: {}\nTotal Duration: {:.1} mins\nTotal Distance: {:.1} km\n",
-                            seven_days_ago_str, today_str, act_count, total_duration_mins, total_distance_km
-                        );
-
-                        // Add per-type breakdown
-                        context.push_str("\nBreakdown by Activity Type:\n");
-                        let mut sorted_types: Vec<_> = type_stats.iter().collect();
-                        sorted_types.sort_by(|a, b| b.1 .1.partial_cmp(&a.1 .1).unwrap_or(std::cmp::Ordering::Equal));
-                        for (atype, (dist, dur, count)) in &sorted_types {
-                            context.push_str(&format!(
-                                "- {} (×{}): {:.1} km, {:.0} mins\n",
-                                atype, count, dist, dur
-                            ));
-                        }
-
-                        if let Some(metrics) = &data.recovery_metrics {
-                            let sleep = metrics
-                                .sleep_score
-                                .map_or("N/A".to_string(), |v| v.to_string());
-                            let bb = metrics
-                                .current_body_battery
-                                .map_or("N/A".to_string(), |v| v.to_string());
-                            let hrv = metrics.hrv_status.as_deref().unwrap_or("N/A");
-                            context.push_str(&format!("\nCurrent Recovery Stats:\nSleep Score: {}\nBody Battery: {}\nHRV Status: {}\n", sleep, bb, hrv));
-                        }
-
-                        let tomorrow = (now + chrono::Duration::days(1))
-                            .format("%Y-%m-%d")
-                            .to_string();
-                        let upcoming: Vec<_> = data
-                            .scheduled_workouts
-                            .iter()
-                            .filter(|w| w.date.starts_with(&tomorrow))
-                            .collect();
-
-                        if !upcoming.is_empty() {
-                            context.push_str("\nTomorrow's Schedule:\n");
-                            for w in upcoming {
-                                context.push_str(&format!(
-                                    "- {} ({})\n",
-                                    w.title.as_deref().unwrap_or("Workout"),
-                                    w.sport.as_deref().unwrap_or("unknown")
-                                ));
-                            }
-                        }
-
-                        // Build the stats header that will be prepended to the message
-                        let mut stats_header = format!(
-                            "📊 Week: {} → {}\n🏋️ {} workouts | ⏱ {:.0} mins | 📏 {:.1} km\n",
-                            seven_days_ago_str, today_str, act_count, total_duration_mins, total_distance_km
-                        );
-                        for (atype, (dist, dur, count)) in &sorted_types {
-                            stats_header.push_str(&format!(
-                                "  • {} (×{}): {:.1} km, {:.0} min\n",
-                                atype, count, dist, dur
-                            ));
-                        }
-
-                        let prompt = format!(
-                            "You are the athlete's elite performance coach. Review the following weekly summary of their Garmin data.\n\
-                            Write a highly encouraging, crisp, 2-3 paragraph weekly review to be sent on Signal.\n\
-                            IMPORTANT: The exact stats (distances, durations, counts) are already shown to the athlete above your text. \
-                            Do NOT restate, paraphrase, or round the numbers. Focus purely on qualitative coaching insights: \
-                            training patterns, consistency, recovery trends, and direction for the upcoming week.\n\
-                            Comment critically but kindly on any recovery data (sleep, body battery), and give them a focal point \
-                            for the upcoming week based on tomorrow's schedule.\n\
-                            Keep the tone professional, motivating, and conversational.\n\n\
-                            === WEEKLY DATA ===\n{}",
-                            context
-                        );
+                        let ai_client = crate::ai_client::AiClient::from_config(&config);
+                        let mut summary = crate::coaching::build_weekly_summary(&data, now);
+                        summary.personal_records = database
+                            .lock()
+                            .await
+                            .get_personal_records_since(&summary.start_date)
+                            .unwrap_or_default();
+                        let stats_header = crate::coaching::format_weekly_stats_header(&summary);
+                        let prompt = crate::coaching::render_weekly_review_prompt(&summary);
 
                         match ai_client.generate_workout(&prompt).await {
                             Ok(review) => {
@@ -895,26 +1453,37 @@ pub fn start_weekly_review_notifier(
     });
 }
 
+/// The soonest race or event on or after `today` (`YYYY-MM-DD`).
+fn next_race<'a>(
+    scheduled: &'a [crate::models::ScheduledWorkout],
+    today: &str,
+) -> Option<&'a crate::models::ScheduledWorkout> {
+    scheduled
+        .iter()
+        .filter(|sw| crate::models::is_race_item(sw) && sw.date.as_str() >= today)
+        .min_by(|a, b| a.date.cmp(&b.date))
+}
+
+/// Whether the next race is exactly one of the configured `offsets` days away.
+pub fn race_readiness_due(
+    scheduled: &[crate::models::ScheduledWorkout],
+    today: chrono::NaiveDate,
+    offsets: &[i64],
+) -> bool {
+    let today_str = today.format("%Y-%m-%d").to_string();
+    next_race(scheduled, &today_str)
+        .and_then(|race| chrono::NaiveDate::parse_from_str(&race.date, "%Y-%m-%d").ok())
+        .is_some_and(|race_date| offsets.contains(&(race_date - today).num_days()))
+}
+
 pub async fn generate_race_readiness_assessment(
     data: &crate::models::GarminResponse,
-    gemini_key: &str,
+    config: &crate::config::AppConfig,
 ) -> String {
-    let now = chrono::Local::now();
+    let now = config.now();
     let today_str = now.format("%Y-%m-%d").to_string();
 
-    let mut upcoming_race: Option<crate::models::ScheduledWorkout> = None;
-    for sw in &data.scheduled_workouts {
-        if let Some(ref it) = sw.item_type {
-            if (it == "race" || it == "event" || it == "primaryEvent")
-                && sw.date >= today_str
-                && (upcoming_race.is_none() || sw.date < upcoming_race.as_ref().unwrap().date)
-            {
-                upcoming_race = Some(sw.clone());
-            }
-        }
-    }
-
-    let race = match upcoming_race {
+    let race = match next_race(&data.scheduled_workouts, &today_str) {
         Some(r) => r,
         None => return "No upcoming races or events found in your Garmin calendar.".to_string(),
     };
@@ -926,13 +1495,12 @@ pub async fn generate_race_readiness_assessment(
     let today_date = now.naive_local().date();
     let days_until = (race_date - today_date).num_days();
 
-    let twelve_weeks_ago = now - chrono::Duration::days(84);
-    let twelve_weeks_ago_str = twelve_weeks_ago.format("%Y-%m-%d").to_string();
+    let twelve_weeks_ago = (now - chrono::Duration::days(84)).date_naive();
 
     let recent_activities: Vec<_> = data
         .activities
         .iter()
-        .filter(|a| a.start_time >= twelve_weeks_ago_str)
+        .filter(|a| a.start_date().is_some_and(|d| d >= twelve_weeks_ago))
         .collect();
 
     let total_dur_min: f64 = recent_activities
@@ -947,21 +1515,15 @@ pub async fn generate_race_readiness_assessment(
         / 1000.0;
     let run_count = recent_activities
         .iter()
-        .filter(|a| a.get_activity_type().unwrap_or("").contains("run"))
+        .filter(|a| a.normalized_sport() == Sport::Run)
         .count();
     let bike_count = recent_activities
         .iter()
-        .filter(|a| {
-            a.get_activity_type().unwrap_or("").contains("biking")
-                || a.get_activity_type().unwrap_or("").contains("cycl")
-        })
+        .filter(|a| a.normalized_sport() == Sport::Bike)
         .count();
     let strength_count = recent_activities
         .iter()
-        .filter(|a| {
-            a.get_activity_type().unwrap_or("").contains("strength")
-                || a.get_activity_type().unwrap_or("").contains("fitness")
-        })
+        .filter(|a| a.normalized_sport() == Sport::Strength)
         .count();
 
     let mut recovery_str = String::new();
@@ -1000,7 +1562,7 @@ pub async fn generate_race_readiness_assessment(
         race.title.as_deref().unwrap_or("Untitled Event"),
         race.date,
         race.sport.as_deref().unwrap_or("Unknown"),
-        race.distance.unwrap_or(0.0),
+        race.distance.unwrap_or(0.0) / 1000.0,
         total_dur_min / 60.0,
         total_dist_km,
         run_count, bike_count, strength_count,
@@ -1008,15 +1570,39 @@ pub async fn generate_race_readiness_assessment(
         days_until
     );
 
-    let gemini_model =
-        std::env::var("GEMINI_MODEL").unwrap_or_else(|_| "gemini-3-flash-preview".to_string());
-    let ai_client = crate::ai_client::AiClient::new(gemini_key.to_string(), gemini_model);
+    let ai_client = crate::ai_client::AiClient::from_config(config);
     match ai_client.generate_workout(&prompt).await {
         Ok(assessment) => format!("🏁 **Race Readiness Assessment**\n\n{}", assessment),
         Err(e) => format!("Failed to generate assessment: {}", e),
     }
 }
 
+async fn generate_injury_advice(
+    injury: &str,
+    constraints: &[String],
+    config: &crate::config::AppConfig,
+) -> String {
+    let prompt = format!(
+        "The athlete just reported a new injury or niggle: \"{}\".\n\
+        Their current training constraints are:\n{}\n\n\
+        As their coach, give short, practical advice on how to modify upcoming training around this injury: \
+        which movements or sessions to avoid, safe substitutions, and warning signs that warrant seeing a professional. \
+        Do not diagnose. Keep it concise enough for a messaging app (max 2 short paragraphs).",
+        injury,
+        constraints
+            .iter()
+            .map(|c| format!("- {}", c))
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+
+    let ai_client = crate::ai_client::AiClient::from_config(config);
+    match ai_client.generate_workout(&prompt).await {
+        Ok(advice) => format!("🩺 **Training Adjustments**\n\n{}", advice),
+        Err(e) => format!("Failed to generate injury advice: {}", e),
+    }
+}
+
 pub fn start_race_readiness_notifier(
     garmin_client: Arc<GarminClient>,
     config: Arc<crate::config::AppConfig>,
@@ -1025,7 +1611,7 @@ pub fn start_race_readiness_notifier(
         let mut last_notified_day = String::new();
 
         loop {
-            let now = chrono::Local::now();
+            let now = config.now();
             let today_str = now.format("%Y-%m-%d").to_string();
 
             let current_time = now.format("%H:%M").to_string();
@@ -1034,35 +1620,13 @@ pub fn start_race_readiness_notifier(
             if current_time == *target_time && last_notified_day != today_str {
                 match garmin_client.fetch_data().await {
                     Ok(data) => {
-                        let mut upcoming_race: Option<crate::models::ScheduledWorkout> = None;
-                        for sw in &data.scheduled_workouts {
-                            if let Some(ref it) = sw.item_type {
-                                if (it == "race" || it == "event" || it == "primaryEvent")
-                                    && sw.date >= today_str
-                                    && (upcoming_race.is_none()
-                                        || sw.date < upcoming_race.as_ref().unwrap().date)
-                                {
-                                    upcoming_race = Some(sw.clone());
-                                }
-                            }
-                        }
-
-                        if let Some(race) = upcoming_race {
-                            if let Ok(race_date) =
-                                chrono::NaiveDate::parse_from_str(&race.date, "%Y-%m-%d")
-                            {
-                                let today_date = now.naive_local().date();
-                                let days_until = (race_date - today_date).num_days();
-
-                                if days_until == 14 || days_until == 7 || days_until == 2 {
-                                    let msg = generate_race_readiness_assessment(
-                                        &data,
-                                        &config.gemini_api_key,
-                                    )
-                                    .await;
-                                    broadcast_message(&msg, &config).await;
-                                }
-                            }
+                        if race_readiness_due(
+                            &data.scheduled_workouts,
+                            now.date_naive(),
+                            &config.race_readiness_offsets_days,
+                        ) {
+                            let msg = generate_race_readiness_assessment(&data, &config).await;
+                            broadcast_message(&msg, &config).await;
                         }
 
                         last_notified_day = today_str;
@@ -1087,7 +1651,7 @@ pub fn start_monthly_debrief_notifier(
         let mut last_sent_month = 0;
 
         loop {
-            let now = chrono::Local::now();
+            let now = config.now();
             let current_day = now.day();
             let target_day = config.monthly_review_day;
 
@@ -1100,12 +1664,7 @@ pub fn start_monthly_debrief_notifier(
             {
                 match garmin_client.fetch_data().await {
                     Ok(data) => {
-                        let gemini_model = std::env::var("GEMINI_MODEL")
-                            .unwrap_or_else(|_| "gemini-3-flash-preview".to_string());
-                        let ai_client = crate::ai_client::AiClient::new(
-                            config.gemini_api_key.clone(),
-                            gemini_model,
-                        );
+                        let ai_client = crate::ai_client::AiClient::from_config(&config);
                         let year = now.year();
                         let month = now.month();
 
@@ -1127,13 +1686,21 @@ pub fn start_monthly_debrief_notifier(
                         let last_month_activities: Vec<_> = data
                             .activities
                             .iter()
-                            .filter(|a| a.start_time.starts_with(&last_month_prefix))
+                            .filter(|a| {
+                                a.start_date().is_some_and(|d| {
+                                    (d.year(), d.month()) == (last_month_year, last_month)
+                                })
+                            })
                             .collect();
 
                         let prev_month_activities: Vec<_> = data
                             .activities
                             .iter()
-                            .filter(|a| a.start_time.starts_with(&prev_month_prefix))
+                            .filter(|a| {
+                                a.start_date().is_some_and(|d| {
+                                    (d.year(), d.month()) == (prev_month_year, prev_month)
+                                })
+                            })
                             .collect();
 
                         // Last month volume
@@ -1190,7 +1757,7 @@ pub fn start_monthly_debrief_notifier(
                             strength_summary.push_str(&format!("- {}: {:.1}kg\n", name, weight));
                         }
 
-                        let (context, _) = crate::load_profile_context();
+                        let (context, _) = crate::profiles::load_active_context();
                         let user_goals = if context.goals.is_empty() {
                             "General Fitness".to_string()
                         } else {
@@ -1250,7 +1817,7 @@ pub fn start_strength_validation_notifier(
         let mut last_validated_date = String::new();
 
         loop {
-            let now = chrono::Local::now();
+            let now = config.now();
             let today = now.format("%Y-%m-%d").to_string();
             let current_time = now.format("%H:%M").to_string();
             let target_time = &config.strength_validation_time;
@@ -1282,3 +1849,311 @@ pub fn start_strength_validation_notifier(
         }
     });
 }
+
+/// Prunes old rows once a day at `maintenance_time` (see `Database::vacuum_old_data`).
+pub fn start_maintenance_task(
+    database: Arc<Mutex<Database>>,
+    config: Arc<crate::config::AppConfig>,
+) {
+    tokio::spawn(async move {
+        let mut last_run_date = String::new();
+
+        loop {
+            let now = config.now();
+            let today = now.format("%Y-%m-%d").to_string();
+            let current_time = now.format("%H:%M").to_string();
+
+            if current_time == config.maintenance_time && last_run_date != today {
+                info!("⏰ Running daily database maintenance...");
+                let result = database.lock().await.vacuum_old_data(
+                    config.retention_days,
+                    config.exercise_history_retention_days,
+                    now.date_naive(),
+                );
+                match result {
+                    Ok(report) => info!("Database maintenance finished: {:?}", report),
+                    Err(e) => error!("Database maintenance failed: {}", e),
+                }
+                last_run_date = today;
+            }
+
+            tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        claim_broadcast, fnv1a_64, format_workout_details, garmin_auth_alert_due,
+        next_scheduled_workout, personal_best_reply, race_readiness_due, scheduled_workout_details,
+        split_message, sport_emoji, stale_checkin_due, strip_json_blocks, ContextCache,
+    };
+    use crate::models::Units;
+    use chrono::NaiveDate;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn every_json_block_is_stripped_from_chat_replies() {
+        let reply = "Moved your run.\n```json\n[{\"workoutName\":\"A\"}]\n```\nRest up!\n```json\n{\"note\":1}\n```";
+        assert_eq!(
+            strip_json_blocks(reply.to_string()),
+            "Moved your run.\nRest up!"
+        );
+        assert_eq!(strip_json_blocks("No changes.".to_string()), "No changes.");
+    }
+
+    #[test]
+    fn pr_resolves_the_exercise_and_reports_its_best_set() {
+        let db = crate::db::memory_db();
+        let builder = crate::workout_builder::WorkoutBuilder::new();
+        assert!(personal_best_reply(&db, &builder, "", Units::Metric)
+            .starts_with("No strength records yet"));
+
+        let sessions = [
+            (1, "2026-03-01 08:00:00", "BENCH_PRESS", 80.0, 5),
+            (2, "2026-03-08 08:00:00", "BENCH_PRESS", 85.0, 3),
+            (3, "2026-03-08 18:00:00", "SQUAT", 100.0, 10),
+            (4, "2026-03-09 08:00:00", "DEADLIFT", 140.0, 1),
+        ];
+        for (id, start, category, kg, reps) in sessions {
+            let activity: crate::models::GarminActivity =
+                serde_json::from_value(serde_json::json!({
+                    "activityId": id,
+                    "startTimeLocal": start,
+                    "activityType": { "typeKey": "strength_training" },
+                    "sets": { "exerciseSets": [{
+                        "setType": "ACTIVE",
+                        "exercises": [{ "category": category }],
+                        "repetitionCount": reps,
+                        "weight": kg * 1000.0
+                    }] }
+                }))
+                .unwrap();
+            db.insert_activity(&activity).unwrap();
+        }
+
+        let bench = personal_best_reply(&db, &builder, "bench press", Units::Metric);
+        assert_eq!(
+            bench,
+            "🏆 BENCH_PRESS best: 85kg × 3 on 2026-03-08\nEstimated 1RM: 93.5kg"
+        );
+        assert!(personal_best_reply(&db, &builder, "plank", Units::Metric)
+            .starts_with("No records yet for PLANK"));
+
+        let top = personal_best_reply(&db, &builder, "  ", Units::Metric);
+        let lines: Vec<&str> = top.lines().collect();
+        assert_eq!(lines[0], "🏆 Top lifts by estimated 1RM");
+        assert_eq!(
+            lines[2..],
+            [
+                "1. DEADLIFT: 140kg × 1 on 2026-03-09 (est. 1RM 140kg)",
+                "2. SQUAT: 100kg × 10 on 2026-03-08 (est. 1RM 133.3kg)",
+                "3. BENCH_PRESS: 85kg × 3 on 2026-03-08 (est. 1RM 93.5kg)",
+            ]
+        );
+    }
+
+    #[test]
+    fn live_context_is_reused_until_the_ttl_expires() {
+        let mut cache = ContextCache::new(Duration::from_secs(300));
+        let built = Instant::now();
+        assert_eq!(cache.get(built), None);
+
+        cache.store(built, "\nBody Battery: 80".to_string());
+        assert_eq!(cache.get(built), Some("\nBody Battery: 80"));
+        assert_eq!(
+            cache.get(built + Duration::from_secs(299)),
+            Some("\nBody Battery: 80")
+        );
+        assert_eq!(cache.get(built + Duration::from_secs(300)), None);
+
+        // A zero TTL never serves from the cache.
+        let mut disabled = ContextCache::new(Duration::ZERO);
+        disabled.store(built, "stale".to_string());
+        assert_eq!(disabled.get(built), None);
+    }
+
+    #[test]
+    fn identical_broadcasts_within_the_window_are_sent_once() {
+        let db = crate::db::memory_db();
+        let morning = "🌅 Good morning! Today: 🏃 Easy Run";
+
+        assert!(claim_broadcast(&db, morning, 10));
+        assert!(!claim_broadcast(&db, morning, 10));
+        assert!(!claim_broadcast(&db, &format!("{}\n", morning), 10));
+        // A different message goes out and becomes the one to compare against.
+        assert!(claim_broadcast(&db, "📈 Weekly Coach Review", 10));
+        assert!(claim_broadcast(&db, morning, 10));
+        // A zero window disables deduplication.
+        assert!(claim_broadcast(&db, morning, 0));
+
+        // Digests are stored, so they must not depend on the toolchain.
+        assert_eq!(fnv1a_64(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a_64(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn sports_map_to_emoji_with_a_neutral_fallback() {
+        assert_eq!(sport_emoji("running"), "🏃");
+        assert_eq!(sport_emoji("trail_running"), "🏃");
+        assert_eq!(sport_emoji("cycling"), "🚴");
+        assert_eq!(sport_emoji("indoor_cycling"), "🚴");
+        assert_eq!(sport_emoji("lap_swimming"), "🏊");
+        assert_eq!(sport_emoji("strength_training"), "🏋️");
+        assert_eq!(sport_emoji("yoga"), "🧘");
+        assert_eq!(sport_emoji("hiking"), "🥾");
+        assert_eq!(sport_emoji("walking"), "🚶");
+        assert_eq!(sport_emoji("indoor_rowing"), "🚣");
+        assert_eq!(sport_emoji("hiit"), "🤸");
+        assert_eq!(sport_emoji("Unknown"), "🏅");
+        assert_eq!(sport_emoji(""), "🏅");
+
+        let run = serde_json::json!({ "workoutName": "Tempo", "sport": "running", "steps": [] });
+        assert!(format_workout_details(&run, Units::Metric).starts_with("🏃 Tempo\n"));
+        let lift = serde_json::json!({ "workoutName": "Push Day", "steps": [] });
+        assert!(format_workout_details(&lift, Units::Metric).starts_with("🏋️ Push Day\n"));
+    }
+
+    #[test]
+    fn workout_details_show_progression_and_flag_regressions() {
+        let spec = serde_json::json!({
+            "workoutName": "Push Day",
+            "steps": [
+                { "phase": "interval", "exercise": "BENCH_PRESS", "weight": 87.5, "reps": 5, "progression_delta": 2.5 },
+                { "phase": "interval", "exercise": "SHOULDER_PRESS", "weight": 30.0, "reps": 8, "progression_delta": -5.0 },
+                { "phase": "interval", "exercise": "DIP", "reps": 10 }
+            ]
+        });
+
+        let details = format_workout_details(&spec, Units::Metric);
+        assert!(details.contains("Weight: 87.5kg (+2.5kg vs best)"));
+        assert!(details.contains("Weight: 30kg ⬇️ regression (-5kg vs best)"));
+        assert!(!details.lines().last().unwrap().contains("best"));
+    }
+
+    #[test]
+    fn workout_details_show_tempo_and_time_under_tension() {
+        let spec = serde_json::json!({
+            "workoutName": "Hypertrophy",
+            "steps": [
+                { "phase": "interval", "exercise": "BENCH_PRESS", "reps": 10, "tempo": "3-1-1", "tut_seconds": 50 }
+            ]
+        });
+
+        let details = format_workout_details(&spec, Units::Metric);
+        assert!(
+            details.contains("Reps: 10 | Tempo 3-1-1 | TUT 50s"),
+            "{details}"
+        );
+    }
+
+    #[test]
+    fn stale_checkin_fires_once_per_gap() {
+        let last = NaiveDate::from_ymd_opt(2026, 3, 10);
+        let day = |d: u32| NaiveDate::from_ymd_opt(2026, 3, d).unwrap();
+
+        assert_eq!(stale_checkin_due(last, day(13), 4, None), None);
+        assert_eq!(stale_checkin_due(last, day(14), 4, None), Some(4));
+        // Already checked in for this silence; stay quiet on the following days.
+        assert_eq!(
+            stale_checkin_due(last, day(15), 4, Some("2026-03-10")),
+            None
+        );
+        assert_eq!(
+            stale_checkin_due(last, day(20), 4, Some("2026-03-10")),
+            None
+        );
+
+        // A new activity starts a new cycle.
+        let newer = NaiveDate::from_ymd_opt(2026, 3, 16);
+        assert_eq!(
+            stale_checkin_due(newer, day(20), 4, Some("2026-03-10")),
+            Some(4)
+        );
+
+        assert_eq!(stale_checkin_due(last, day(20), 0, None), None);
+        assert_eq!(stale_checkin_due(None, day(20), 4, None), None);
+    }
+
+    #[test]
+    fn garmin_auth_alert_fires_once_until_a_successful_cycle() {
+        assert!(garmin_auth_alert_due(true, None));
+        assert!(garmin_auth_alert_due(true, Some("ok")));
+        assert!(!garmin_auth_alert_due(true, Some("sent")));
+        assert!(!garmin_auth_alert_due(false, None));
+    }
+
+    #[test]
+    fn long_messages_split_on_boundaries_without_cutting_words() {
+        let paragraph = "📊 Weekly review. ".to_string()
+            + &"Solid aerobic work this week with consistent easy pacing! ".repeat(12);
+        let text = [paragraph.as_str(); 7].join("\n\n");
+        assert!(text.chars().count() >= 5000);
+
+        let chunks = split_message(&text, 1000);
+        assert!(chunks.len() > 1);
+        let total = chunks.len();
+        let mut words = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert!(chunk.chars().count() <= 1000);
+            let suffix = format!(" ({}/{})", i + 1, total);
+            let body = chunk.strip_suffix(&suffix).expect("missing part counter");
+            words.extend(body.split_whitespace().map(str::to_string));
+        }
+        let original: Vec<String> = text.split_whitespace().map(str::to_string).collect();
+        assert_eq!(words, original);
+
+        assert_eq!(split_message("short", 1000), vec!["short".to_string()]);
+    }
+
+    #[test]
+    fn next_workout_prefers_today_then_the_nearest_future_one() {
+        let scheduled: Vec<crate::models::ScheduledWorkout> =
+            serde_json::from_value(serde_json::json!([
+                { "title": "Yesterday Run", "date": "2026-03-09", "sportTypeKey": "running" },
+                { "title": "FJ-AI: Push Day", "date": "2026-03-12", "sportTypeKey": "strength_training" },
+                { "title": "Race", "date": "2026-03-10", "itemType": "race" },
+                { "title": "Easy Run", "date": "2026-03-11", "sportTypeKey": "running" },
+                { "title": "Today Swim", "date": "2026-03-10", "sportTypeKey": "lap_swimming" }
+            ]))
+            .unwrap();
+        let title = |today| next_scheduled_workout(&scheduled, today).and_then(|w| w.title.clone());
+
+        assert_eq!(title("2026-03-10").as_deref(), Some("Today Swim"));
+        assert_eq!(title("2026-03-11").as_deref(), Some("Easy Run"));
+        assert!(title("2026-03-13").is_none());
+
+        let generated = vec![serde_json::json!({
+            "workoutName": "Push Day",
+            "scheduledDate": "2026-03-12",
+            "steps": [{ "phase": "interval", "exercise": "BENCH_PRESS", "sets": 4, "reps": 6 }]
+        })];
+        let tags = crate::garmin_client::AiWorkoutTags::default();
+        let push = next_scheduled_workout(&scheduled, "2026-03-12").unwrap();
+        let details = scheduled_workout_details(push, &generated, &tags, Units::Metric);
+        assert!(details.contains("BENCH_PRESS | Reps: 6 | Sets: 4"));
+
+        let swim = next_scheduled_workout(&scheduled, "2026-03-10").unwrap();
+        let details = scheduled_workout_details(swim, &generated, &tags, Units::Metric);
+        assert!(details.starts_with("🏊 Today Swim\n"));
+    }
+
+    #[test]
+    fn readiness_fires_only_on_configured_offsets() {
+        let scheduled: Vec<crate::models::ScheduledWorkout> =
+            serde_json::from_value(serde_json::json!([
+                { "title": "Tempo Run", "date": "2026-03-12", "sportTypeKey": "running" },
+                { "title": "City 10k", "date": "2026-03-17", "itemType": "race" },
+                { "title": "Autumn Marathon", "date": "2026-10-04", "itemType": "primaryEvent" }
+            ]))
+            .unwrap();
+        let today = NaiveDate::from_ymd_opt(2026, 3, 10).unwrap();
+
+        assert!(race_readiness_due(&scheduled, today, &[14, 7, 2]));
+        assert!(!race_readiness_due(&scheduled, today, &[21, 14, 3, 1]));
+        assert!(!race_readiness_due(&scheduled, today, &[]));
+        let tomorrow = today.succ_opt().unwrap();
+        assert!(!race_readiness_due(&scheduled, tomorrow, &[14, 7, 2]));
+    }
+}
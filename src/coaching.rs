@@ -1,11 +1,27 @@
-use crate::models::{TrainingPlan, TrainingTarget, WorkoutType};
-use chrono::{Datelike, Duration, Utc};
+use crate::models::{Sport, TrainingPlan, TrainingTarget, WorkoutType};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
 use tracing::info;
 
 pub struct CoachContext {
     pub goals: Vec<String>,
     pub constraints: Vec<String>,
     pub available_equipment: Vec<String>,
+    /// Weekday abbreviations (`Mon`..`Sun`) the athlete keeps workout-free.
+    pub rest_days: Vec<String>,
+    /// Profile override for `AppConfig::planning_horizon_days`.
+    pub planning_horizon_days: Option<u32>,
+}
+
+/// Hard scheduling rule for the athlete's fixed rest days, if any are configured.
+pub fn rest_day_rule(rest_days: &[String]) -> Option<String> {
+    if rest_days.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "**REST DAYS**: Do NOT schedule any workout on these weekdays: {}. Move sessions to other days instead of dropping rest.\n",
+        rest_days.join(", ")
+    ))
 }
 
 pub struct BriefInput<'a> {
@@ -22,10 +38,905 @@ pub struct BriefInput<'a> {
     pub previous_plan_response: &'a Option<String>,
     /// Recent AI activity analyses (date, summary) from the last 7 days.
     pub recent_analyses: &'a [(String, String)],
+    /// Athlete-logged RPE entries (date, exercise category, rpe) from the last 7 days.
+    pub recent_rpe: &'a [(String, String, i32)],
     /// Adherence summary comparing planned vs. actual workouts.
     pub adherence_summary: &'a [String],
     /// Week-over-week progression deltas: (exercise, this_wk_weight, this_wk_reps, last_wk_weight, last_wk_reps).
     pub weekly_deltas: &'a [(String, f64, i32, f64, i32)],
+    /// Daily recovery snapshots (at least the last 14 days) for week-over-week trends.
+    pub recovery_history: &'a [crate::db::RecoveryHistoryEntry],
+    pub max_hr_formula: crate::models::MaxHrFormula,
+    /// Body Battery below which no high-intensity session may be scheduled on the plan's first day (0 disables).
+    pub min_body_battery_for_hard: i32,
+    /// Hours between two sessions training the same muscle group (0 disables).
+    pub min_muscle_recovery_hours: u32,
+    /// Readiness cut-offs deciding today's recommended intensity.
+    pub intensity_thresholds: IntensityThresholds,
+    /// Age in hours of the Garmin data when Garmin was unreachable and the last
+    /// successful fetch is used instead.
+    pub stale_data_hours: Option<u64>,
+    /// Current time in the configured timezone; decides "today" in the brief.
+    pub now: chrono::DateTime<chrono::FixedOffset>,
+    /// Days (starting tomorrow) the requested plan covers.
+    pub planning_horizon_days: u32,
+    /// Workout types the "Required Output" section asks for.
+    pub generation_scope: GenerationScope,
+}
+
+/// Which workout types the AI generates and uploads; the others are left to
+/// e.g. Garmin Coach.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GenerationScope {
+    /// No restriction, as before the scope existed: the brief asks for strength
+    /// workouts (and describes pool swims), and every returned workout is uploaded.
+    #[default]
+    Any,
+    Strength,
+    /// Runs and pool swims.
+    Endurance,
+    Both,
+}
+
+impl GenerationScope {
+    fn includes_strength(self) -> bool {
+        self != GenerationScope::Endurance
+    }
+
+    fn includes_endurance(self) -> bool {
+        matches!(self, GenerationScope::Endurance | GenerationScope::Both)
+    }
+
+    fn describes_swims(self) -> bool {
+        self != GenerationScope::Strength
+    }
+
+    /// Whether a generated workout spec is in scope; specs without a `sport`
+    /// are strength workouts (see `WorkoutBuilder`).
+    pub fn includes(self, spec: &serde_json::Value) -> bool {
+        if self == GenerationScope::Any {
+            return true;
+        }
+        let is_strength = spec
+            .get("sport")
+            .and_then(|s| s.as_str())
+            .is_none_or(|s| Sport::from_type_key(s) == Sport::Strength);
+        if is_strength {
+            self.includes_strength()
+        } else {
+            self.includes_endurance()
+        }
+    }
+
+    fn workouts_label(self) -> &'static str {
+        match self {
+            GenerationScope::Any | GenerationScope::Strength => "Strength Workouts",
+            GenerationScope::Endurance => "Endurance Workouts (runs and pool swims)",
+            GenerationScope::Both => "Strength and Endurance Workouts (runs and pool swims)",
+        }
+    }
+
+    fn completed_session_label(self) -> &'static str {
+        match self {
+            GenerationScope::Any | GenerationScope::Strength => "strength session",
+            GenerationScope::Endurance => "run or swim",
+            GenerationScope::Both => "session of the same type",
+        }
+    }
+}
+
+/// Mean recovery markers over a window of days; `None` when the window has no data.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RecoveryAverages {
+    pub sleep_score: Option<f64>,
+    pub body_battery: Option<f64>,
+    pub training_readiness: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecoveryTrend {
+    /// The 7 days ending today (inclusive).
+    pub this_week: RecoveryAverages,
+    /// The 7 days before that.
+    pub last_week: RecoveryAverages,
+}
+
+fn average(values: impl Iterator<Item = i32>) -> Option<f64> {
+    let (sum, count) = values.fold((0i64, 0u32), |(s, c), v| (s + v as i64, c + 1));
+    if count == 0 {
+        None
+    } else {
+        Some(sum as f64 / count as f64)
+    }
+}
+
+/// Formats a sleep stage duration as `7h05m` / `45m`.
+fn format_sleep_duration(secs: i64) -> String {
+    let minutes = secs.max(0) / 60;
+    if minutes >= 60 {
+        format!("{}h{:02}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+fn recovery_averages(entries: &[&crate::db::RecoveryHistoryEntry]) -> RecoveryAverages {
+    RecoveryAverages {
+        sleep_score: average(entries.iter().filter_map(|e| e.sleep_score)),
+        body_battery: average(entries.iter().filter_map(|e| e.body_battery)),
+        training_readiness: average(entries.iter().filter_map(|e| e.training_readiness)),
+    }
+}
+
+/// Splits the recovery history into two rolling 7-day windows ending at `today`
+/// and averages sleep score, body battery and training readiness in each.
+pub fn weekly_recovery_trend(
+    history: &[crate::db::RecoveryHistoryEntry],
+    today: NaiveDate,
+) -> RecoveryTrend {
+    let this_week_start = today - Duration::days(6);
+    let last_week_start = today - Duration::days(13);
+
+    let mut this_week = Vec::new();
+    let mut last_week = Vec::new();
+    for entry in history {
+        let Ok(date) = NaiveDate::parse_from_str(&entry.date, "%Y-%m-%d") else {
+            continue;
+        };
+        if date >= this_week_start && date <= today {
+            this_week.push(entry);
+        } else if date >= last_week_start && date < this_week_start {
+            last_week.push(entry);
+        }
+    }
+
+    RecoveryTrend {
+        this_week: recovery_averages(&this_week),
+        last_week: recovery_averages(&last_week),
+    }
+}
+
+/// Renders one line per metric present in both windows, e.g.
+/// `- **Sleep Score**: 78.0 -> 71.4 (-6.6)`.
+fn format_recovery_trend(trend: &RecoveryTrend) -> Vec<String> {
+    let metrics = [
+        (
+            "Sleep Score",
+            trend.last_week.sleep_score,
+            trend.this_week.sleep_score,
+        ),
+        (
+            "Body Battery",
+            trend.last_week.body_battery,
+            trend.this_week.body_battery,
+        ),
+        (
+            "Training Readiness",
+            trend.last_week.training_readiness,
+            trend.this_week.training_readiness,
+        ),
+    ];
+    metrics
+        .iter()
+        .filter_map(|(label, last, this)| match (last, this) {
+            (Some(last), Some(this)) => Some(format!(
+                "- **{}**: {:.1} -> {:.1} ({:+.1})",
+                label,
+                last,
+                this,
+                this - last
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+/// True when every day from tomorrow through the next `horizon_days` days already
+/// has at least one Garmin calendar item (Garmin Coach session, workout, rest or event).
+pub fn schedule_covers_horizon(
+    scheduled_workouts: &[crate::models::ScheduledWorkout],
+    today: NaiveDate,
+    horizon_days: u32,
+) -> bool {
+    (1..=horizon_days as i64).all(|offset| {
+        let day = (today + Duration::days(offset))
+            .format("%Y-%m-%d")
+            .to_string();
+        scheduled_workouts
+            .iter()
+            .any(|sw| sw.date.starts_with(&day))
+    })
+}
+
+/// Activities to auto-analyze, newest first: `sports` matches with an id that started
+/// within the last `days` days (today included) and are not yet analyzed. `cap`
+/// limits the count (used on the very first run so a backlog doesn't spam Signal).
+pub fn auto_analyze_candidates<'a>(
+    activities: &'a [crate::models::GarminActivity],
+    sports: &[String],
+    today: NaiveDate,
+    days: u32,
+    cap: Option<usize>,
+    is_analyzed: impl Fn(i64) -> bool,
+) -> Vec<&'a crate::models::GarminActivity> {
+    if days == 0 {
+        return Vec::new();
+    }
+    let oldest = today - Duration::days(days as i64 - 1);
+
+    let mut candidates: Vec<_> = activities
+        .iter()
+        .filter(|act| act.start_date().is_some_and(|d| d >= oldest && d <= today))
+        .filter(|act| {
+            act.get_activity_type()
+                .is_some_and(|t| sports.iter().any(|s| s == t))
+        })
+        .filter(|act| act.id.is_some_and(|id| !is_analyzed(id)))
+        .collect();
+    candidates.sort_by(|a, b| b.start_time.cmp(&a.start_time));
+    if let Some(cap) = cap {
+        candidates.truncate(cap);
+    }
+    candidates
+}
+
+/// Weekly working-set range recommended per muscle group for hypertrophy.
+pub const MIN_WEEKLY_SETS: i32 = 10;
+pub const MAX_WEEKLY_SETS: i32 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeStatus {
+    UnderTrained,
+    Optimal,
+    PossibleOverreach,
+}
+
+impl VolumeStatus {
+    pub fn from_sets(sets: i32) -> Self {
+        if sets < MIN_WEEKLY_SETS {
+            VolumeStatus::UnderTrained
+        } else if sets > MAX_WEEKLY_SETS {
+            VolumeStatus::PossibleOverreach
+        } else {
+            VolumeStatus::Optimal
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MuscleVolume {
+    pub muscle: String,
+    pub sets: i32,
+    pub status: VolumeStatus,
+}
+
+/// Whether `act` started after `since`; unparseable start times never do.
+fn started_after(act: &crate::models::GarminActivity, since: DateTime<FixedOffset>) -> bool {
+    crate::models::parse_garmin_datetime(&act.start_time, since.offset())
+        .is_some_and(|started| started > since)
+}
+
+/// Coarse body region used for weekly volume, keyed by the primary muscle of
+/// the exercise category (see `models::muscle_groups_for`).
+fn muscle_group_for_category(category: &str) -> &'static str {
+    match crate::models::muscle_groups_for(category).first().copied() {
+        Some("chest") => "Chest",
+        Some("upper-back" | "lower-back" | "trapezius") => "Back",
+        Some("front-deltoids" | "back-deltoids") => "Shoulders",
+        Some("biceps" | "triceps" | "forearm") => "Arms",
+        Some("abs" | "obliques") => "Core",
+        Some("quadriceps" | "hamstring" | "gluteal" | "calves" | "adductor" | "abductors") => {
+            "Legs"
+        }
+        _ => "Other",
+    }
+}
+
+/// Exercise category of each active, non-warm-up set in a strength activity.
+fn working_set_categories(
+    activity: &crate::models::GarminActivity,
+) -> impl Iterator<Item = &str> + '_ {
+    let sets = match &activity.sets {
+        Some(crate::models::GarminSetsData::Details(data)) => data.exercise_sets.as_slice(),
+        _ => &[],
+    };
+    sets.iter()
+        .filter(|set| set.set_type == "ACTIVE")
+        .filter_map(|set| set.exercises.first())
+        .map(|ex| ex.category.as_str())
+        .filter(|category| *category != "WARM_UP")
+}
+
+/// Weekly climbing (meters) from which the brief shows an elevation section.
+const HILLY_WEEK_GAIN_METERS: f64 = 200.0;
+
+/// Total elevation gain and loss in meters over the 7 days before `now`.
+/// Flat and indoor activities contribute nothing.
+pub fn weekly_elevation(
+    activities: &[crate::models::GarminActivity],
+    now: DateTime<FixedOffset>,
+) -> (f64, f64) {
+    let week_ago = now - Duration::days(7);
+    activities
+        .iter()
+        .filter(|a| started_after(a, week_ago))
+        .fold((0.0, 0.0), |(gain, loss), a| {
+            (
+                gain + a.elevation_gain.unwrap_or(0.0),
+                loss + a.elevation_loss.unwrap_or(0.0),
+            )
+        })
+}
+
+/// Days of outdoor sessions scanned for heat exposure.
+pub const HEAT_EXPOSURE_DAYS: i64 = 7;
+
+/// Peak session temperature (°C) from which an outdoor session counts as hot.
+const HOT_SESSION_CELSIUS: f64 = 25.0;
+
+/// Brief section listing hot outdoor endurance sessions from the last
+/// `HEAT_EXPOSURE_DAYS` days. Empty when none were hot or no temperatures were recorded.
+pub fn heat_note(
+    activities: &[crate::models::GarminActivity],
+    now: DateTime<FixedOffset>,
+) -> String {
+    let since = now - Duration::days(HEAT_EXPOSURE_DAYS);
+    let hot: Vec<String> = activities
+        .iter()
+        .filter(|a| a.is_outdoor_endurance() && started_after(a, since))
+        .filter_map(|a| {
+            let max = a.max_temperature.filter(|t| *t >= HOT_SESSION_CELSIUS)?;
+            let range = match a.min_temperature {
+                Some(min) => format!("{:.0}-{:.0}°C", min, max),
+                None => format!("up to {:.0}°C", max),
+            };
+            Some(format!(
+                "- **{}** ({}): {}\n",
+                a.name.as_deref().unwrap_or("Untitled"),
+                a.start_date()?,
+                range
+            ))
+        })
+        .collect();
+    if hot.is_empty() {
+        return String::new();
+    }
+
+    let mut note = format!("## Heat Exposure (Last {} Days)\n", HEAT_EXPOSURE_DAYS);
+    note.push_str(&hot.concat());
+    note.push_str("*Recent outdoor sessions were hot. In outdoor endurance workout descriptions, add heat-acclimation and hydration guidance: drink and take electrolytes before and during, ease pace by effort rather than holding targets, and avoid the hottest hours.*\n\n");
+    note
+}
+
+/// Auto-analysis instructions for sports without a profile `analysis_prompts` template.
+pub const GENERIC_ANALYSIS_PROMPT: &str = "Please provide an in-depth analysis of this completed fitness activity. Be encouraging but highly analytical.\n\nYou have been provided with the complete, raw JSON payload direct from Garmin. It contains many undocumented fields, extra metrics, recovery data, elevation, stress, cadence, temperatures, or detailed exercise sets.\n\nPlease actively hunt through this raw JSON and surface interesting insights, anomalies, or performance correlations that wouldn't be obvious from just the basic time/distance metrics. Explain what these deeper metrics mean for the athlete's progress.";
+
+/// Prompt paragraph describing an activity's climbing, so the analysis judges
+/// pace and heart rate against the terrain. Empty for flat or indoor activities.
+pub fn terrain_note(activity: Option<&crate::models::GarminActivity>) -> String {
+    match activity.and_then(|a| a.elevation_summary()) {
+        Some(summary) => format!(
+            "Terrain: elevation {}. Relate pace, heart rate and effort to this climbing instead of judging it as a flat effort.\n\n",
+            summary
+        ),
+        None => String::new(),
+    }
+}
+
+/// Active working sets per muscle group over the 7 days before `now`, sorted by
+/// volume (descending). Warm-up sets are not counted.
+pub fn weekly_muscle_volume(
+    activities: &[crate::models::GarminActivity],
+    now: DateTime<FixedOffset>,
+) -> Vec<MuscleVolume> {
+    let week_ago = now - Duration::days(7);
+    let mut sets_by_group: std::collections::HashMap<&str, i32> = std::collections::HashMap::new();
+
+    for act in activities {
+        if !started_after(act, week_ago) {
+            continue;
+        }
+        for category in working_set_categories(act) {
+            *sets_by_group
+                .entry(muscle_group_for_category(category))
+                .or_insert(0) += 1;
+        }
+    }
+
+    let mut volumes: Vec<MuscleVolume> = sets_by_group
+        .into_iter()
+        .map(|(muscle, sets)| MuscleVolume {
+            muscle: muscle.to_string(),
+            sets,
+            status: VolumeStatus::from_sets(sets),
+        })
+        .collect();
+    volumes.sort_by(|a, b| b.sets.cmp(&a.sets).then_with(|| a.muscle.cmp(&b.muscle)));
+    volumes
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MuscleRecovery {
+    pub muscle: String,
+    pub last_trained_date: String,
+    pub hours_since: i64,
+    /// At least `min_muscle_recovery_hours` have passed since the last session.
+    pub ready: bool,
+}
+
+/// When each muscle group was last trained within the 7 days before `now`, most
+/// recent first. `now` is local wall-clock time, like the activities' start times.
+pub fn muscle_recovery(
+    activities: &[crate::models::GarminActivity],
+    now: chrono::NaiveDateTime,
+    min_hours: u32,
+) -> Vec<MuscleRecovery> {
+    let week_ago = now - Duration::days(7);
+    let mut last_trained: std::collections::HashMap<&str, chrono::NaiveDateTime> =
+        std::collections::HashMap::new();
+
+    for act in activities {
+        let Some(started) = act.start_local() else {
+            continue;
+        };
+        if started <= week_ago || started > now {
+            continue;
+        }
+        for category in working_set_categories(act) {
+            let muscle = muscle_group_for_category(category);
+            if muscle == "Other" {
+                continue;
+            }
+            let last = last_trained.entry(muscle).or_insert(started);
+            *last = (*last).max(started);
+        }
+    }
+
+    let mut recovery: Vec<MuscleRecovery> = last_trained
+        .into_iter()
+        .map(|(muscle, trained)| {
+            let hours_since = (now - trained).num_hours();
+            MuscleRecovery {
+                muscle: muscle.to_string(),
+                last_trained_date: trained.format("%Y-%m-%d %H:%M:%S").to_string(),
+                hours_since,
+                ready: hours_since >= i64::from(min_hours),
+            }
+        })
+        .collect();
+    recovery.sort_by(|a, b| {
+        a.hours_since
+            .cmp(&b.hours_since)
+            .then_with(|| a.muscle.cmp(&b.muscle))
+    });
+    recovery
+}
+
+/// Brief guidance for a muscle group still inside its recovery window.
+fn format_muscle_recovery_guidance(recovery: &MuscleRecovery, min_hours: u32) -> Option<String> {
+    if recovery.ready {
+        return None;
+    }
+    let ago = if recovery.hours_since >= 24 {
+        let days = recovery.hours_since / 24;
+        format!("{} day{} ago", days, if days == 1 { "" } else { "s" })
+    } else {
+        format!("{}h ago", recovery.hours_since)
+    };
+    Some(format!(
+        "{} last trained {} — allow {}h recovery before another {} session",
+        recovery.muscle,
+        ago,
+        min_hours,
+        recovery.muscle.to_lowercase()
+    ))
+}
+
+/// Warning line for a muscle group outside the recommended weekly range.
+/// "Other" is a catch-all bucket and never flagged.
+fn format_volume_flag(volume: &MuscleVolume) -> Option<String> {
+    if volume.muscle == "Other" {
+        return None;
+    }
+    match volume.status {
+        VolumeStatus::UnderTrained => Some(format!(
+            "⚠️ {}: {} sets (under-trained)",
+            volume.muscle, volume.sets
+        )),
+        VolumeStatus::PossibleOverreach => Some(format!(
+            "⚠️ {}: {} sets (possible overreach)",
+            volume.muscle, volume.sets
+        )),
+        VolumeStatus::Optimal => None,
+    }
+}
+
+/// Totals for one activity type within the weekly summary.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ActivityTypeTotals {
+    pub activity_type: String,
+    pub count: usize,
+    pub distance_km: f64,
+    pub duration_mins: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct WeeklyRecoverySnapshot {
+    pub sleep_score: Option<i32>,
+    pub body_battery: Option<i32>,
+    pub hrv_status: Option<String>,
+}
+
+/// Training volume for the last 7 days, as shown in the weekly review.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WeeklySummary {
+    pub start_date: String,
+    pub end_date: String,
+    pub activity_count: usize,
+    pub total_duration_mins: f64,
+    pub total_distance_km: f64,
+    /// Sorted by total duration, longest first.
+    pub by_type: Vec<ActivityTypeTotals>,
+    pub recovery: Option<WeeklyRecoverySnapshot>,
+    /// Tomorrow's scheduled items as "title (sport)".
+    pub tomorrow: Vec<String>,
+    /// Weight PRs set this week. Filled in by the caller from the database.
+    pub personal_records: Vec<crate::db::PersonalRecord>,
+}
+
+pub fn build_weekly_summary(
+    data: &crate::models::GarminResponse,
+    now: chrono::DateTime<chrono::FixedOffset>,
+) -> WeeklySummary {
+    let week_start = (now - Duration::days(7)).date_naive();
+    let start_date = week_start.format("%Y-%m-%d").to_string();
+    let end_date = now.format("%Y-%m-%d").to_string();
+
+    let recent_activities: Vec<_> = data
+        .activities
+        .iter()
+        .filter(|a| a.start_date().is_some_and(|d| d >= week_start))
+        .collect();
+    info!(
+        "Weekly summary: {} activities in range since {}",
+        recent_activities.len(),
+        start_date
+    );
+
+    let mut by_type: Vec<ActivityTypeTotals> = Vec::new();
+    for a in &recent_activities {
+        let atype = a.get_activity_type().unwrap_or("other").replace('_', " ");
+        let dist_m = a.distance.unwrap_or(0.0);
+        let dur_s = a.duration.unwrap_or(0.0);
+        tracing::debug!(
+            "  Activity: name={:?} type={} date={} distance={:.0}m duration={:.0}s",
+            a.name.as_deref().unwrap_or("?"),
+            atype,
+            &a.start_time,
+            dist_m,
+            dur_s
+        );
+        let idx = match by_type.iter().position(|t| t.activity_type == atype) {
+            Some(idx) => idx,
+            None => {
+                by_type.push(ActivityTypeTotals {
+                    activity_type: atype,
+                    count: 0,
+                    distance_km: 0.0,
+                    duration_mins: 0.0,
+                });
+                by_type.len() - 1
+            }
+        };
+        let totals = &mut by_type[idx];
+        totals.distance_km += dist_m / 1000.0;
+        totals.duration_mins += dur_s / 60.0;
+        totals.count += 1;
+    }
+    by_type.sort_by(|a, b| {
+        b.duration_mins
+            .partial_cmp(&a.duration_mins)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let recovery = data
+        .recovery_metrics
+        .as_ref()
+        .map(|m| WeeklyRecoverySnapshot {
+            sleep_score: m.sleep_score,
+            body_battery: m.current_body_battery,
+            hrv_status: m.hrv_status.clone(),
+        });
+
+    let tomorrow_str = (now + Duration::days(1)).format("%Y-%m-%d").to_string();
+    let tomorrow = data
+        .scheduled_workouts
+        .iter()
+        .filter(|w| w.date.starts_with(&tomorrow_str))
+        .map(|w| {
+            format!(
+                "{} ({})",
+                w.title.as_deref().unwrap_or("Workout"),
+                w.sport.as_deref().unwrap_or("unknown")
+            )
+        })
+        .collect();
+
+    WeeklySummary {
+        start_date,
+        end_date,
+        activity_count: recent_activities.len(),
+        total_duration_mins: by_type.iter().map(|t| t.duration_mins).sum(),
+        total_distance_km: by_type.iter().map(|t| t.distance_km).sum(),
+        by_type,
+        recovery,
+        tomorrow,
+        personal_records: Vec::new(),
+    }
+}
+
+/// Stats block shown above the AI's weekly review.
+pub fn format_weekly_stats_header(summary: &WeeklySummary) -> String {
+    let mut header = format!(
+        "📊 Week: {} → {}\n🏋️ {} workouts | ⏱ {:.0} mins | 📏 {:.1} km\n",
+        summary.start_date,
+        summary.end_date,
+        summary.activity_count,
+        summary.total_duration_mins,
+        summary.total_distance_km
+    );
+    for t in &summary.by_type {
+        header.push_str(&format!(
+            "  • {} (×{}): {:.1} km, {:.0} min\n",
+            t.activity_type, t.count, t.distance_km, t.duration_mins
+        ));
+    }
+    header
+}
+
+pub fn render_weekly_review_prompt(summary: &WeeklySummary) -> String {
+    let mut context = format!(
+        "Athlete's Weekly Summary\nTimeframe: {} to {}\nWorkouts Completed: {}\nTotal Duration: {:.1} mins\nTotal Distance: {:.1} km\n",
+        summary.start_date,
+        summary.end_date,
+        summary.activity_count,
+        summary.total_duration_mins,
+        summary.total_distance_km
+    );
+
+    context.push_str("\nBreakdown by Activity Type:\n");
+    for t in &summary.by_type {
+        context.push_str(&format!(
+            "- {} (×{}): {:.1} km, {:.0} mins\n",
+            t.activity_type, t.count, t.distance_km, t.duration_mins
+        ));
+    }
+
+    if let Some(recovery) = &summary.recovery {
+        let sleep = recovery
+            .sleep_score
+            .map_or("N/A".to_string(), |v| v.to_string());
+        let bb = recovery
+            .body_battery
+            .map_or("N/A".to_string(), |v| v.to_string());
+        let hrv = recovery.hrv_status.as_deref().unwrap_or("N/A");
+        context.push_str(&format!(
+            "\nCurrent Recovery Stats:\nSleep Score: {}\nBody Battery: {}\nHRV Status: {}\n",
+            sleep, bb, hrv
+        ));
+    }
+
+    if !summary.personal_records.is_empty() {
+        context.push_str("\nNew Personal Records This Week (celebrate these specifically!):\n");
+        for pr in &summary.personal_records {
+            context.push_str(&format!(
+                "- {}: {}kg x {} on {} (previous best {}kg)\n",
+                pr.exercise_name,
+                pr.weight,
+                pr.reps,
+                pr.date.split(' ').next().unwrap_or(&pr.date),
+                pr.previous_best
+            ));
+        }
+    }
+
+    if !summary.tomorrow.is_empty() {
+        context.push_str("\nTomorrow's Schedule:\n");
+        for item in &summary.tomorrow {
+            context.push_str(&format!("- {}\n", item));
+        }
+    }
+
+    format!(
+        "You are the athlete's elite performance coach. Review the following weekly summary of their Garmin data.\n\
+        Write a highly encouraging, crisp, 2-3 paragraph weekly review to be sent on Signal.\n\
+        IMPORTANT: The exact stats (distances, durations, counts) are already shown to the athlete above your text. \
+        Do NOT restate, paraphrase, or round the numbers. Focus purely on qualitative coaching insights: \
+        training patterns, consistency, recovery trends, and direction for the upcoming week.\n\
+        Comment critically but kindly on any recovery data (sleep, body battery), and give them a focal point \
+        for the upcoming week based on tomorrow's schedule.\n\
+        Keep the tone professional, motivating, and conversational.\n\n\
+        === WEEKLY DATA ===\n{}",
+        context
+    )
+}
+
+/// First day a generated plan may schedule workouts on: tomorrow.
+pub fn plan_start(now: DateTime<FixedOffset>) -> NaiveDate {
+    now.date_naive() + Duration::days(1)
+}
+
+/// Strict brief section forbidding hard sessions on the plan's `first_day` when
+/// this morning's Body Battery is below `floor`; `None` when above the floor,
+/// unknown, or `floor` is 0.
+pub fn low_body_battery_directive(
+    recovery: &Option<crate::models::GarminRecoveryMetrics>,
+    floor: i32,
+    first_day: &str,
+) -> Option<String> {
+    if floor <= 0 {
+        return None;
+    }
+    let bb = recovery.as_ref()?.current_body_battery?;
+    if bb >= floor {
+        return None;
+    }
+    Some(format!(
+        "**⛔ LOW BODY BATTERY GUARDRAIL**: Body Battery is {} / 100, below the hard-session floor of {}. \
+        You MUST NOT schedule any high-intensity session (intervals, tempo, threshold, heavy strength) for the first day of the plan ({}). \
+        Any workout dated {} must be recovery work only: easy Zone 1-2 cardio, mobility, or light technique.\n\n",
+        bb, floor, first_day, first_day
+    ))
+}
+
+/// How hard today's sessions may be, judged from this morning's recovery markers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Intensity {
+    High,
+    Moderate,
+    Recovery,
+}
+
+/// Default Body Battery floor for hard sessions, shared by the low-Body-Battery
+/// guardrail and [`IntensityThresholds::low_body_battery`].
+pub const DEFAULT_MIN_BODY_BATTERY_FOR_HARD: i32 = 20;
+
+/// Cut-offs (0-100) behind [`recommended_intensity`]. Any known marker below its
+/// `low_*` value means recovery work; every known marker at or above its
+/// `high_*` value allows hard sessions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IntensityThresholds {
+    /// Not configured on its own: `AppConfig::effective_intensity_thresholds`
+    /// takes it from `min_body_battery_for_hard`.
+    #[serde(skip_deserializing)]
+    pub low_body_battery: i32,
+    pub low_sleep_score: i32,
+    pub low_training_readiness: i32,
+    pub high_body_battery: i32,
+    pub high_sleep_score: i32,
+    pub high_training_readiness: i32,
+}
+
+impl Default for IntensityThresholds {
+    fn default() -> Self {
+        Self {
+            low_body_battery: DEFAULT_MIN_BODY_BATTERY_FOR_HARD,
+            low_sleep_score: 50,
+            low_training_readiness: 30,
+            high_body_battery: 60,
+            high_sleep_score: 75,
+            high_training_readiness: 60,
+        }
+    }
+}
+
+/// Today's intensity from Body Battery, sleep score and training readiness.
+/// Missing markers are ignored; with none known the answer is `Moderate`.
+pub fn recommended_intensity(
+    recovery: &crate::models::GarminRecoveryMetrics,
+    thresholds: &IntensityThresholds,
+) -> Intensity {
+    let markers: Vec<(i32, i32, i32)> = [
+        (
+            recovery.current_body_battery,
+            thresholds.low_body_battery,
+            thresholds.high_body_battery,
+        ),
+        (
+            recovery.sleep_score,
+            thresholds.low_sleep_score,
+            thresholds.high_sleep_score,
+        ),
+        (
+            recovery.training_readiness,
+            thresholds.low_training_readiness,
+            thresholds.high_training_readiness,
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(value, low, high)| Some((value?, low, high)))
+    .collect();
+
+    if markers.is_empty() {
+        Intensity::Moderate
+    } else if markers.iter().any(|&(value, low, _)| value < low) {
+        Intensity::Recovery
+    } else if markers.iter().all(|&(value, _, high)| value >= high) {
+        Intensity::High
+    } else {
+        Intensity::Moderate
+    }
+}
+
+/// Brief instruction matching today's recommended intensity.
+fn intensity_directive(intensity: Intensity, today: &str) -> String {
+    let (label, instruction) = match intensity {
+        Intensity::High => (
+            "HIGH",
+            "key sessions (intervals, threshold, heavy strength) are appropriate",
+        ),
+        Intensity::Moderate => (
+            "MODERATE",
+            "keep hard efforts controlled and avoid max-effort sets or races",
+        ),
+        Intensity::Recovery => (
+            "LOW",
+            "prescribe technique/volume-reduced work: fewer sets, lighter loads, easy aerobic efforts",
+        ),
+    };
+    format!(
+        "**Today's readiness is {}** — {} for any workout dated {}.\n\n",
+        label, instruction, today
+    )
+}
+
+/// Z1–Z5 heart-rate zones as `(name, min_bpm, max_bpm)`, each spanning 10% of
+/// max HR from 50% up to 100%.
+pub fn hr_zones(max_hr: f64) -> [(&'static str, u32, u32); 5] {
+    let bpm = |pct: f64| (max_hr * pct).round() as u32;
+    [
+        ("Z1", bpm(0.5), bpm(0.6)),
+        ("Z2", bpm(0.6), bpm(0.7)),
+        ("Z3", bpm(0.7), bpm(0.8)),
+        ("Z4", bpm(0.8), bpm(0.9)),
+        ("Z5", bpm(0.9), bpm(1.0)),
+    ]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaxHrSource {
+    /// Estimated from the profile birth date.
+    Age,
+    /// Highest max HR recorded in recent activities.
+    Observed,
+}
+
+/// Max HR for zone calculation: estimated from the profile age when the birth
+/// date is known, otherwise the highest `max_hr` seen in `activities`.
+pub fn resolve_max_hr(
+    profile: &Option<crate::models::GarminProfile>,
+    activities: &[crate::models::GarminActivity],
+    today: NaiveDate,
+    formula: crate::models::MaxHrFormula,
+) -> Option<(f64, MaxHrSource)> {
+    if let Some(max_hr) = profile
+        .as_ref()
+        .and_then(|p| p.estimated_max_hr(today, formula))
+    {
+        return Some((max_hr, MaxHrSource::Age));
+    }
+
+    activities
+        .iter()
+        .filter_map(|a| a.max_hr)
+        .filter(|hr| *hr > 0.0)
+        .max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|hr| (hr, MaxHrSource::Observed))
 }
 
 pub struct Coach;
@@ -52,28 +963,17 @@ impl Coach {
 
         let bike_count = recent_activities
             .iter()
-            .filter(|a| {
-                let s = a.get_activity_type().unwrap_or("unknown").to_lowercase();
-                s.contains("cycling") || s.contains("biking")
-            })
+            .filter(|a| a.normalized_sport() == Sport::Bike)
             .count();
 
         let run_count = recent_activities
             .iter()
-            .filter(|a| {
-                a.get_activity_type()
-                    .unwrap_or("unknown")
-                    .to_lowercase()
-                    .contains("running")
-            })
+            .filter(|a| a.normalized_sport() == Sport::Run)
             .count();
 
         let strength_count = recent_activities
             .iter()
-            .filter(|a| {
-                let s = a.get_activity_type().unwrap_or("unknown").to_lowercase();
-                s.contains("strength") || s.contains("fitness")
-            })
+            .filter(|a| a.normalized_sport() == Sport::Strength)
             .count();
 
         // Analyze Strength Volume from Detailed Data
@@ -198,10 +1098,19 @@ impl Coach {
             week_start_day,
             previous_plan_response,
             recent_analyses,
+            recent_rpe,
             adherence_summary,
             weekly_deltas,
+            recovery_history,
+            max_hr_formula,
+            min_body_battery_for_hard,
+            min_muscle_recovery_hours,
+            intensity_thresholds,
+            stale_data_hours,
+            now,
+            planning_horizon_days,
+            generation_scope,
         } = input;
-        let now = Utc::now();
         let mut brief = String::new();
 
         // 1. Header & Current Context
@@ -210,6 +1119,12 @@ impl Coach {
 
         let today_date_str = now.format("%Y-%m-%d").to_string();
         brief.push_str(&format!("**Current Date**: {}\n\n", today_date_str));
+        if let Some(hours) = stale_data_hours {
+            brief.push_str(&format!(
+                "**⚠️ Using cached data from {} hours ago**: Garmin could not be reached. Activities, scheduled workouts and recovery markers may be out of date; plan conservatively and do not treat missing recent sessions as skipped.\n\n",
+                hours
+            ));
+        }
 
         // Compute week boundaries based on configurable start day
         let week_start_chrono = crate::config::parse_weekday(week_start_day);
@@ -231,7 +1146,7 @@ impl Coach {
         brief.push_str("**Activities Completed Today**:\n");
         let todays_activities: Vec<&crate::models::GarminActivity> = detailed_activities
             .iter()
-            .filter(|a| a.start_time.starts_with(&today_date_str))
+            .filter(|a| a.start_date() == Some(now.date_naive()))
             .collect();
 
         if todays_activities.is_empty() {
@@ -264,6 +1179,49 @@ impl Coach {
             if let Some(ss) = rec.sleep_score {
                 brief.push_str(&format!("- **Sleep Score**: {} / 100\n", ss));
             }
+            if let Some(sleep) = &rec.sleep_detail {
+                let mut parts = Vec::new();
+                if let Some(total) = sleep.total_seconds {
+                    parts.push(format!("{} asleep", format_sleep_duration(total)));
+                }
+                for (label, secs) in [
+                    ("deep", sleep.deep_seconds),
+                    ("REM", sleep.rem_seconds),
+                    ("light", sleep.light_seconds),
+                    ("awake", sleep.awake_seconds),
+                ] {
+                    if let Some(secs) = secs {
+                        parts.push(format!("{} {}", label, format_sleep_duration(secs)));
+                    }
+                }
+                if !parts.is_empty() {
+                    brief.push_str(&format!("- **Last Night's Sleep**: {}\n", parts.join(", ")));
+                }
+            }
+            if let Some(day) = &rec.daily_summary {
+                let mut parts = Vec::new();
+                if let Some(steps) = day.steps {
+                    parts.push(format!("{} steps", steps));
+                }
+                if let Some(floors) = day.floors {
+                    parts.push(format!("{:.0} floors", floors));
+                }
+                if let Some(minutes) = day.intensity_minutes {
+                    parts.push(format!("{} intensity min", minutes));
+                }
+                if let Some(kcal) = day.active_calories {
+                    parts.push(format!("{} active kcal", kcal));
+                }
+                if let Some(stress) = day.stress_avg {
+                    parts.push(format!("avg stress {}", stress));
+                }
+                if !parts.is_empty() {
+                    brief.push_str(&format!(
+                        "- **All-Day Load (non-workout context)**: {}\n",
+                        parts.join(", ")
+                    ));
+                }
+            }
 
             if !rec.recent_sleep_scores.is_empty() {
                 brief.push_str("- **7-Day Sleep Trend**: ");
@@ -285,6 +1243,27 @@ impl Coach {
             brief.push('\n');
         }
 
+        let first_day = plan_start(now).format("%Y-%m-%d").to_string();
+        if let Some(directive) =
+            low_body_battery_directive(recovery_metrics, min_body_battery_for_hard, &first_day)
+        {
+            brief.push_str(&directive);
+        }
+        if let Some(rec) = recovery_metrics {
+            let intensity = recommended_intensity(rec, &intensity_thresholds);
+            brief.push_str(&intensity_directive(intensity, &today_date_str));
+        }
+
+        let trend_lines =
+            format_recovery_trend(&weekly_recovery_trend(recovery_history, now.date_naive()));
+        if !trend_lines.is_empty() {
+            brief.push_str("**Recovery Trend (Last 7 Days vs. Previous 7 Days, averages)**:\n");
+            for line in &trend_lines {
+                brief.push_str(&format!("{}\n", line));
+            }
+            brief.push_str("*If recovery is declining week-over-week, reduce intensity or volume accordingly.*\n\n");
+        }
+
         // 2. Athlete Profile
         brief.push_str("## Athlete Profile\n");
         if let Some(p) = profile {
@@ -297,6 +1276,13 @@ impl Coach {
             if let Some(dob) = &p.birth_date {
                 brief.push_str(&format!("- **DOB**: {}\n", dob));
             }
+            if let Some(age) = p.age(now.date_naive()) {
+                brief.push_str(&format!("- **Age**: {}\n", age));
+                brief.push_str(&format!(
+                    "- **Estimated Max HR**: {:.0} bpm\n",
+                    max_hr_formula.max_hr(age)
+                ));
+            }
             if let Some(v) = p.vo2_max_running {
                 brief.push_str(&format!("- **VO2Max (Run)**: {:.1}\n", v));
             }
@@ -311,6 +1297,27 @@ impl Coach {
         }
         brief.push('\n');
 
+        if let Some((max_hr, source)) = resolve_max_hr(
+            profile,
+            detailed_activities,
+            now.date_naive(),
+            max_hr_formula,
+        ) {
+            brief.push_str("## Training Zones\n");
+            let basis = match source {
+                MaxHrSource::Age => "estimated from age",
+                MaxHrSource::Observed => "highest recorded in recent activities",
+            };
+            brief.push_str(&format!(
+                "*Based on max HR {:.0} bpm ({}). Prescribe cardio intensity using these zones.*\n",
+                max_hr, basis
+            ));
+            for (zone, low, high) in hr_zones(max_hr) {
+                brief.push_str(&format!("- **{}**: {}-{} bpm\n", zone, low, high));
+            }
+            brief.push('\n');
+        }
+
         // 3. Goals & Constraints
         brief.push_str("## Goals & Context\n");
         brief.push_str("**Primary Goals**:\n");
@@ -339,12 +1346,8 @@ impl Coach {
         let mut upcoming_workouts = Vec::new();
 
         for sw in scheduled_workouts {
-            if let Some(ref it) = sw.item_type {
-                if it == "race" || it == "event" || it == "primaryEvent" {
-                    upcoming_races.push(sw);
-                } else {
-                    upcoming_workouts.push(sw);
-                }
+            if crate::models::is_race_item(sw) {
+                upcoming_races.push(sw);
             } else {
                 upcoming_workouts.push(sw);
             }
@@ -360,7 +1363,7 @@ impl Coach {
                     race.sport.as_deref().unwrap_or("Unknown")
                 );
                 if let Some(dist) = race.distance {
-                    details.push_str(&format!(", Distance: {:.1}km", dist));
+                    details.push_str(&format!(", Distance: {:.1}km", dist / 1000.0));
                 }
                 details.push_str(")\n");
                 brief.push_str(&details);
@@ -380,10 +1383,10 @@ impl Coach {
                     sw.sport.as_deref().unwrap_or("Unknown")
                 );
                 if let Some(d) = sw.duration {
-                    details.push_str(&format!(", Duration: {:.0}min", d));
+                    details.push_str(&format!(", Duration: {:.0}min", d / 60.0));
                 }
                 if let Some(dist) = sw.distance {
-                    details.push_str(&format!(", Distance: {:.1}km", dist));
+                    details.push_str(&format!(", Distance: {:.1}km", dist / 1000.0));
                 }
                 if let Some(desc) = &sw.description {
                     details.push_str(&format!(", Focus: '{}'", desc));
@@ -401,11 +1404,9 @@ impl Coach {
 
         // 4. Status Update (30 Days)
         let thirty_days_ago = now - Duration::days(30);
-        let thirty_days_ago_str = thirty_days_ago.format("%Y-%m-%dT%H:%M:%S").to_string();
-
         let recent_30d: Vec<&crate::models::GarminActivity> = detailed_activities
             .iter()
-            .filter(|a| a.start_time >= thirty_days_ago_str)
+            .filter(|a| started_after(a, thirty_days_ago))
             .collect();
 
         let _total_count = recent_30d.len();
@@ -427,37 +1428,31 @@ impl Coach {
 
         let run_count = recent_30d
             .iter()
-            .filter(|a| {
-                a.get_activity_type()
-                    .unwrap_or("unknown")
-                    .to_lowercase()
-                    .contains("run")
-            })
+            .filter(|a| a.normalized_sport() == Sport::Run)
             .count();
         let bike_count = recent_30d
             .iter()
-            .filter(|a| {
-                let s = a.get_activity_type().unwrap_or("unknown").to_lowercase();
-                s.contains("bike") || s.contains("cycl")
-            })
+            .filter(|a| a.normalized_sport() == Sport::Bike)
             .count();
         let strength_count = recent_30d
             .iter()
-            .filter(|a| {
-                a.get_activity_type()
-                    .unwrap_or("unknown")
-                    .to_lowercase()
-                    .contains("strength")
-                    || a.get_activity_type()
-                        .unwrap_or("unknown")
-                        .to_lowercase()
-                        .contains("fitness")
-            })
+            .filter(|a| a.normalized_sport() == Sport::Strength)
             .count();
+        let swims: Vec<_> = recent_30d
+            .iter()
+            .filter(|a| a.normalized_sport() == Sport::Swim)
+            .collect();
         brief.push_str(&format!(
-            "- **Frequency**: {} Runs, {} Rides, {} Strength sessions\n",
-            run_count, bike_count, strength_count
+            "- **Frequency**: {} Runs, {} Rides, {} Swims, {} Strength sessions\n",
+            run_count,
+            bike_count,
+            swims.len(),
+            strength_count
         ));
+        if !swims.is_empty() {
+            let swim_km: f64 = swims.iter().filter_map(|a| a.distance).sum::<f64>() / 1000.0;
+            brief.push_str(&format!("- **Swim Volume**: {:.1} km\n", swim_km));
+        }
 
         // 5. Detailed Recent Log (Last 14 Days for deeper context)
         let cutoff = now - Duration::days(14);
@@ -469,53 +1464,21 @@ impl Coach {
         let two_weeks_ago = now - Duration::days(14);
         let _two_weeks_ago_str = two_weeks_ago.format("%Y-%m-%dT%H:%M:%S").to_string();
 
-        let mut weekly_muscle_volume: std::collections::HashMap<&str, i32> =
-            std::collections::HashMap::new();
-
         // Sort detailed activities by date desc
         let _sorted_activities = detailed_activities.to_vec();
 
         // Take up to 20 most recent activities from the detailed array
         let mut count = 0;
         for act in detailed_activities {
-            let act_time = chrono::DateTime::parse_from_rfc3339(&act.start_time)
-                .map(|dt| dt.with_timezone(&Utc))
-                .unwrap_or_else(|_| {
-                    chrono::NaiveDateTime::parse_from_str(&act.start_time, "%Y-%m-%d %H:%M:%S")
-                        .map(|ndt| chrono::DateTime::<Utc>::from_naive_utc_and_offset(ndt, Utc))
-                        .unwrap_or_default()
-                });
-            if act_time > two_weeks_ago {
+            if started_after(act, two_weeks_ago) {
                 let mut focus_str = String::new();
                 if let Some(crate::models::GarminSetsData::Details(data)) = &act.sets {
                     // Extract unique exercise categories
                     let mut unique_exercises = std::collections::HashSet::new();
 
-                    let is_last_7_days = act_time > (now - Duration::days(7));
-
                     for set in &data.exercise_sets {
                         if let Some(ex) = set.exercises.first() {
                             unique_exercises.insert(ex.category.clone());
-
-                            // Accumulate muscle group volume for the last 7 days
-                            // We only count ACTIVE working sets
-                            if is_last_7_days && set.set_type == "ACTIVE" {
-                                if ex.category == "WARM_UP" {
-                                    continue;
-                                }
-                                let mg = match ex.category.as_str() {
-                                    "BENCH_PRESS" | "PUSH_UP" => "Chest",
-                                    "ROW" | "PULL_UP" | "PULL_DOWN" => "Back",
-                                    "SQUAT" | "DEADLIFT" | "LUNGE" | "CALF_RAISE" => "Legs",
-                                    "SHOULDER_PRESS" | "FRONT_RAISE" | "LATERAL_RAISE" => {
-                                        "Shoulders"
-                                    }
-                                    "TRICEPS_EXTENSION" | "BICEP_CURL" => "Arms",
-                                    "CORE" | "PLANK" | "SIT_UP" => "Core",
-                                    _ => "Other",
-                                };
-                                *weekly_muscle_volume.entry(mg).or_insert(0) += 1;
-                            }
                         }
                     }
                     if !unique_exercises.is_empty() {
@@ -570,26 +1533,60 @@ impl Coach {
             }
         }
         // 5. Muscle Fatigue Heatmap
-        if !weekly_muscle_volume.is_empty() {
+        let weekly_volume = weekly_muscle_volume(detailed_activities, now);
+        if !weekly_volume.is_empty() {
             brief.push_str("## Muscle Fatigue Heatmap (Last 7 Days)\n");
-            brief.push_str("*Number of Active Working Sets performed per muscle group. Aim for 10-20 sets per week for optimal hypertrophy.* \n");
-            let mut sorted_volumes: Vec<_> = weekly_muscle_volume.iter().collect();
-            sorted_volumes.sort_by(|a, b| b.1.cmp(a.1)); // Sort descending by volume
-            for (mg, vol) in sorted_volumes {
-                brief.push_str(&format!("- **{}**: {} sets\n", mg, vol));
+            brief.push_str(&format!("*Number of Active Working Sets performed per muscle group. Aim for {}-{} sets per week for optimal hypertrophy.* \n", MIN_WEEKLY_SETS, MAX_WEEKLY_SETS));
+            for volume in &weekly_volume {
+                brief.push_str(&format!("- **{}**: {} sets\n", volume.muscle, volume.sets));
+            }
+            let flags: Vec<String> = weekly_volume
+                .iter()
+                .filter_map(format_volume_flag)
+                .collect();
+            if !flags.is_empty() {
+                brief.push_str("\n**Volume Flags:**\n");
+                for flag in flags {
+                    brief.push_str(&format!("- {}\n", flag));
+                }
             }
             brief.push('\n');
         }
+        let (elevation_gain, elevation_loss) = weekly_elevation(detailed_activities, now);
+        if elevation_gain >= HILLY_WEEK_GAIN_METERS {
+            brief.push_str("## Elevation (Last 7 Days)\n");
+            brief.push_str(&format!(
+                "- **Total climbing**: +{:.0} m / -{:.0} m\n",
+                elevation_gain, elevation_loss
+            ));
+            brief.push_str("*Hilly terrain adds leg and eccentric load: account for it in lower-body volume and run intensity.*\n\n");
+        }
+        brief.push_str(&heat_note(detailed_activities, now));
+        if min_muscle_recovery_hours > 0 {
+            let guidance: Vec<String> = muscle_recovery(
+                detailed_activities,
+                now.naive_local(),
+                min_muscle_recovery_hours,
+            )
+            .iter()
+            .filter_map(|r| format_muscle_recovery_guidance(r, min_muscle_recovery_hours))
+            .collect();
+            if !guidance.is_empty() {
+                brief.push_str("## Muscle Recovery\n");
+                brief.push_str(&format!("*Leave at least {}h between sessions that train the same muscle group; count from the time below when scheduling.*\n", min_muscle_recovery_hours));
+                for line in guidance {
+                    brief.push_str(&format!("- {}\n", line));
+                }
+                brief.push('\n');
+            }
+        }
 
         // 6. Completed Strength This Week
         {
             let strength_this_week: Vec<&crate::models::GarminActivity> = detailed_activities
                 .iter()
                 .filter(|a| {
-                    let is_strength = a
-                        .get_activity_type()
-                        .map(|t| t.contains("strength") || t.contains("fitness"))
-                        .unwrap_or(false);
+                    let is_strength = a.normalized_sport() == Sport::Strength;
                     let in_week = a.start_time.as_str() >= week_start_str.as_str()
                         && a.start_time.as_str() <= week_end_str.as_str();
                     is_strength && in_week
@@ -716,39 +1713,673 @@ impl Coach {
             }
         }
 
+        // 10b. Athlete-reported effort
+        if !recent_rpe.is_empty() {
+            brief.push_str("## Logged RPE (Last 7 Days)\n");
+            brief.push_str("*Rate of perceived exertion reported by the athlete (1-10). Treat RPE 9-10 as a signal to hold or reduce load for that lift.*\n");
+            for (date, exercise, rpe) in recent_rpe {
+                brief.push_str(&format!("- {}: {} @ RPE {}\n", date, exercise, rpe));
+            }
+            brief.push('\n');
+        }
+
         // 11. Required Output
         brief.push_str("## Required Output\n");
+        let horizon_days = planning_horizon_days.max(1) as i64;
+        let plan_start_str = plan_start(now).format("%Y-%m-%d").to_string();
+        let plan_end_str = (now.date_naive() + Duration::days(horizon_days))
+            .format("%Y-%m-%d")
+            .to_string();
         brief.push_str(&format!(
-            "Based on the Athlete Profile, Goals, and Activity Log, please generate the training plan for the **remaining days of this week** ({} to {}).\n",
-            today_date_str, week_end_str
+            "Based on the Athlete Profile, Goals, and Activity Log, please generate the training plan for the **next {} days** ({} to {}).\n",
+            horizon_days, plan_start_str, plan_end_str
+        ));
+        let strength = generation_scope.includes_strength();
+        let endurance = generation_scope.includes_endurance();
+        brief.push_str(&format!(
+            "You **MUST** output the {} in the following JSON format (inside a json code block). \n",
+            generation_scope.workouts_label()
         ));
-        brief.push_str("You **MUST** output the Strength Workouts in the following JSON format (inside a json code block). \n");
         brief.push_str("**CRITICAL RULES**:\n");
-        brief.push_str(
-            "1. Start every workout with a Dynamic Warmup and end with Static Stretching.\n",
-        );
-        brief.push_str("2. **EXERCISE VOCABULARY**: Our system automatically maps your exercises to the Garmin database. You may use any standard exercise name (e.g. 'Barbell Bench Press', 'Goblet Squat', 'Pull Up', 'Dumbbell Hammer Curl', etc.). The system will find the closest match. Try to be as specific as possible.\n");
-        brief.push_str("3. **REST PERIODS**: For the `rest` field, output an integer in seconds (e.g., `rest: 90`), or the exact string `\"LAP\"` if the rest should remain untimed until the user manually presses the lap button.\n");
-        brief.push_str(&format!("4. **SCHEDULE**: Include a `scheduledDate` field at the top level of each workout, formatted as \"YYYY-MM-DD\". Only schedule workouts between {} (tomorrow at earliest) and {} (end of week). Do NOT regenerate workouts for days that already have a completed strength session listed above.\n", today_date_str, week_end_str));
-        brief.push_str("5. **SKIP COMPLETED**: Review the 'Strength Workouts Already Completed This Week' section above. Do NOT generate workouts that duplicate muscle groups or workout types already completed. Only fill in the MISSING sessions for the rest of the week.\n");
+        let mut rules = Vec::new();
+        if strength {
+            rules.push(
+                "Start every workout with a Dynamic Warmup and end with Static Stretching.\n"
+                    .to_string(),
+            );
+            rules.push("**EXERCISE VOCABULARY**: Our system automatically maps your exercises to the Garmin database. You may use any standard exercise name (e.g. 'Barbell Bench Press', 'Goblet Squat', 'Pull Up', 'Dumbbell Hammer Curl', etc.). The system will find the closest match. Try to be as specific as possible.\n".to_string());
+            rules.push("**REST PERIODS**: For the `rest` field, output an integer in seconds (e.g., `rest: 90`), or the exact string `\"LAP\"` if the rest should remain untimed until the user manually presses the lap button.\n".to_string());
+        } else {
+            rules.push(
+                "Start every workout with an easy warmup and end with a cooldown.\n".to_string(),
+            );
+        }
+        rules.push(format!("**SCHEDULE**: Include a `scheduledDate` field at the top level of each workout, formatted as \"YYYY-MM-DD\". Only schedule workouts between {} (tomorrow) and {} (end of the {}-day planning horizon), distributed across those days. Do NOT regenerate workouts for days that already have a completed {} listed above.\n", plan_start_str, plan_end_str, horizon_days, generation_scope.completed_session_label()));
+        if strength {
+            rules.push("**SKIP COMPLETED**: Review the 'Strength Workouts Already Completed This Week' section above. Do NOT generate workouts that duplicate muscle groups or workout types already completed. Only fill in the MISSING sessions for the rest of the week.\n".to_string());
+            rules.push("**SUPERSETS**: To pair exercises back-to-back (A1/A2), give consecutive steps the same `group` tag (e.g. `\"group\": \"A\"`). They are performed as one circuit for `sets` rounds, and only the `rest` of the last step in the group is taken, after each round.\n".to_string());
+        }
+        if endurance {
+            let mut rule = "**ENDURANCE**: Runs set `\"sport\": \"running\"`; each step ends on `distance` (e.g. \"1km\") or `duration`, may carry a per-km `pace` (\"5:30\" or a range \"5:20-5:40\"), and intervals go in `{\"repeat\": n, \"steps\": [...]}` blocks.".to_string();
+            if !strength {
+                rule.push_str(" Do not output strength workouts.");
+            }
+            rule.push('\n');
+            rules.push(rule);
+        }
+        if let Some(rule) = rest_day_rule(&context.rest_days) {
+            rules.push(rule);
+        }
+        for (i, rule) in rules.iter().enumerate() {
+            brief.push_str(&format!("{}. {}", i + 1, rule));
+        }
 
         brief.push_str("\n```json\n");
         brief.push_str("[\n");
-        brief.push_str("  {\n");
-        brief.push_str("    \"workoutName\": \"Strength A - Push Focus\",\n");
-        brief.push_str("    \"description\": \"Focus on chest and triceps hypertrophy.\",\n");
-        brief.push_str("    \"scheduledDate\": \"2026-02-21\",\n");
-        brief.push_str("    \"steps\": [\n");
-        brief.push_str("      { \"phase\": \"warmup\", \"exercise\": \"ROW\", \"duration\": \"5min\", \"note\": \"Light rowing or cardio.\" },\n");
-        brief.push_str("      { \"phase\": \"interval\", \"exercise\": \"BENCH_PRESS\", \"weight\": 12.5, \"reps\": 10, \"sets\": 4, \"rest\": 120, \"note\": \"Progressive overload from last week.\" },\n");
-        brief.push_str("      { \"phase\": \"interval\", \"exercise\": \"SHOULDER_PRESS\", \"weight\": 10.0, \"reps\": \"AMRAP\", \"sets\": 3, \"rest\": \"LAP\", \"note\": \"Push to near failure.\" },\n");
-        brief.push_str("      { \"phase\": \"cooldown\", \"exercise\": \"YOGA\", \"duration\": \"10min\", \"note\": \"Static stretching for chest and tris.\" }\n");
-        brief.push_str("    ]\n");
-        brief.push_str("  }\n");
+        if strength {
+            brief.push_str("  {\n");
+            brief.push_str("    \"workoutName\": \"Strength A - Push Focus\",\n");
+            brief.push_str("    \"description\": \"Focus on chest and triceps hypertrophy.\",\n");
+            brief.push_str(&format!("    \"scheduledDate\": \"{}\",\n", plan_start_str));
+            brief.push_str("    \"steps\": [\n");
+            brief.push_str("      { \"phase\": \"warmup\", \"exercise\": \"ROW\", \"duration\": \"5min\", \"note\": \"Light rowing or cardio.\" },\n");
+            brief.push_str("      { \"phase\": \"interval\", \"exercise\": \"BENCH_PRESS\", \"weight\": 12.5, \"reps\": 10, \"sets\": 4, \"rest\": 120, \"note\": \"Progressive overload from last week.\" },\n");
+            brief.push_str("      { \"phase\": \"interval\", \"exercise\": \"SHOULDER_PRESS\", \"weight\": 10.0, \"reps\": \"AMRAP\", \"sets\": 3, \"rest\": \"LAP\", \"note\": \"Push to near failure.\" },\n");
+            brief.push_str("      { \"phase\": \"interval\", \"group\": \"A\", \"exercise\": \"DUMBBELL_FLY\", \"weight\": 8.0, \"reps\": 12, \"sets\": 3 },\n");
+            brief.push_str("      { \"phase\": \"interval\", \"group\": \"A\", \"exercise\": \"TRICEPS_EXTENSION\", \"weight\": 10.0, \"reps\": 12, \"sets\": 3, \"rest\": 90, \"note\": \"Superset with the flyes, no rest in between.\" },\n");
+            brief.push_str("      { \"phase\": \"cooldown\", \"exercise\": \"YOGA\", \"duration\": \"10min\", \"note\": \"Static stretching for chest and tris.\" }\n");
+            brief.push_str("    ]\n");
+            brief.push_str(if endurance { "  },\n" } else { "  }\n" });
+        }
+        if endurance {
+            brief.push_str("  {\n");
+            brief.push_str("    \"workoutName\": \"Run - Threshold Intervals\",\n");
+            brief.push_str("    \"description\": \"Build lactate threshold.\",\n");
+            brief.push_str("    \"sport\": \"running\",\n");
+            brief.push_str(&format!("    \"scheduledDate\": \"{}\",\n", plan_start_str));
+            brief.push_str("    \"steps\": [\n");
+            brief.push_str("      { \"phase\": \"warmup\", \"duration\": \"10min\", \"note\": \"Easy jog.\" },\n");
+            brief.push_str("      { \"repeat\": 4, \"steps\": [\n");
+            brief.push_str("        { \"phase\": \"interval\", \"distance\": \"1km\", \"pace\": \"4:50-5:00\" },\n");
+            brief.push_str("        { \"phase\": \"recovery\", \"duration\": \"2min\" }\n");
+            brief.push_str("      ] },\n");
+            brief.push_str("      { \"phase\": \"cooldown\", \"duration\": \"10min\" }\n");
+            brief.push_str("    ]\n");
+            brief.push_str("  }\n");
+        }
         brief.push_str("]\n");
         brief.push_str("```\n");
-        brief.push_str("Use `phase`: 'warmup', 'interval', or 'cooldown'. For 'weight', ensure you propose a specific load (in kg) available in the equipment list. For 'reps', use integers or 'AMRAP'.\n");
+        if strength {
+            brief.push_str("Use `phase`: 'warmup', 'interval', or 'cooldown'. For 'weight', ensure you propose a specific load (in kg) available in the equipment list. For 'reps', use integers or 'AMRAP'.\n");
+            brief.push_str("When a load progresses from a different lift's record (e.g. a paused bench from BENCH_PRESS), set `progress_from` to that exercise. State the week-over-week change in the `note` (e.g. \"+2.5kg vs last week\"); the system checks it against the recorded best.\n");
+            brief.push_str("To finish a set with drop sets add `\"dropsets\": [{\"reps\": 8, \"weight\": \"-20%\"}, {\"reps\": \"AMRAP\", \"weight\": \"-20%\"}]` to that step; each drop follows after a 10s transition, and its `weight` is kg or a percentage of the previous set.\n");
+            brief.push_str("For hypertrophy work you may add `\"tempo\": \"3-1-1\"` (eccentric-pause-concentric seconds) and/or `\"tut_seconds\": 40` (time under tension per set) to a strength step; a step with `tut_seconds` and no `reps` is timed instead of counted.\n");
+        }
+        if generation_scope.describes_swims() {
+            brief.push_str("For a pool swim set `\"sport\": \"swimming\"` and an optional `\"pool_length\"` (meters, default 25); its steps use `distance` (e.g. \"400m\"), `duration` for rests, `repeat` blocks, and an optional `stroke` ('free', 'back', 'breast', 'fly', 'im', 'drill').\n");
+        }
 
         brief
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        auto_analyze_candidates, build_weekly_summary, format_muscle_recovery_guidance,
+        format_recovery_trend, format_volume_flag, heat_note, hr_zones, intensity_directive,
+        low_body_battery_directive, muscle_recovery, recommended_intensity,
+        render_weekly_review_prompt, resolve_max_hr, rest_day_rule, schedule_covers_horizon,
+        terrain_note, weekly_elevation, weekly_recovery_trend, BriefInput, Coach, CoachContext,
+        GenerationScope, Intensity, IntensityThresholds, MaxHrSource, MuscleVolume, VolumeStatus,
+    };
+    use crate::db::RecoveryHistoryEntry;
+    use crate::models::ScheduledWorkout;
+    use chrono::{DateTime, Duration, FixedOffset, NaiveDate};
+
+    fn entry(date: NaiveDate, sleep: i32, bb: i32, readiness: Option<i32>) -> RecoveryHistoryEntry {
+        RecoveryHistoryEntry {
+            date: date.format("%Y-%m-%d").to_string(),
+            body_battery: Some(bb),
+            sleep_score: Some(sleep),
+            training_readiness: readiness,
+            hrv_last_night_avg: None,
+            hrv_status: None,
+            rhr: None,
+        }
+    }
+
+    #[test]
+    fn weekly_recovery_trend_averages_both_windows() {
+        let today = NaiveDate::from_ymd_opt(2026, 3, 14).unwrap();
+        let mut history = Vec::new();
+        for i in 7..14 {
+            history.push(entry(today - Duration::days(i), 80, 70, Some(60)));
+        }
+        for i in 0..7 {
+            history.push(entry(today - Duration::days(i), 70, 50, None));
+        }
+        // Outside both windows, must be ignored
+        history.push(entry(today - Duration::days(20), 10, 10, Some(10)));
+
+        let trend = weekly_recovery_trend(&history, today);
+        assert_eq!(trend.last_week.sleep_score, Some(80.0));
+        assert_eq!(trend.this_week.sleep_score, Some(70.0));
+        assert_eq!(trend.last_week.body_battery, Some(70.0));
+        assert_eq!(trend.this_week.body_battery, Some(50.0));
+        assert_eq!(trend.last_week.training_readiness, Some(60.0));
+        assert_eq!(trend.this_week.training_readiness, None);
+
+        let lines = format_recovery_trend(&trend);
+        assert_eq!(
+            lines,
+            vec![
+                "- **Sleep Score**: 80.0 -> 70.0 (-10.0)".to_string(),
+                "- **Body Battery**: 70.0 -> 50.0 (-20.0)".to_string(),
+            ]
+        );
+    }
+
+    fn scheduled(date: &str, title: &str) -> ScheduledWorkout {
+        serde_json::from_value(serde_json::json!({
+            "title": title,
+            "date": date,
+            "itemType": "workout"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn fully_scheduled_horizon_is_covered() {
+        let today = NaiveDate::from_ymd_opt(2026, 3, 14).unwrap();
+        let week: Vec<ScheduledWorkout> = (1..=7)
+            .map(|i| {
+                let date = today + Duration::days(i);
+                scheduled(&date.format("%Y-%m-%d").to_string(), "Base Run")
+            })
+            .collect();
+        assert!(schedule_covers_horizon(&week, today, 7));
+
+        // A gap on day 4 means the AI still has something to plan
+        let with_gap: Vec<ScheduledWorkout> = week
+            .into_iter()
+            .filter(|w| w.date != "2026-03-18")
+            .collect();
+        assert!(!schedule_covers_horizon(&with_gap, today, 7));
+        assert!(schedule_covers_horizon(&with_gap, today, 3));
+    }
+
+    fn strength_session(start: &str, categories: &[&str]) -> crate::models::GarminActivity {
+        let sets: Vec<_> = categories
+            .iter()
+            .map(|c| serde_json::json!({ "setType": "ACTIVE", "exercises": [{ "category": c }] }))
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "activityType": { "typeKey": "strength_training" },
+            "startTimeLocal": start,
+            "sets": { "exerciseSets": sets }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn weekly_elevation_sums_recent_climbing_and_ignores_indoor_work() {
+        let activity = |start: &str, gain: Option<f64>, loss: Option<f64>| {
+            serde_json::from_value::<crate::models::GarminActivity>(serde_json::json!({
+                "activityType": { "typeKey": "trail_running" },
+                "startTimeLocal": start,
+                "elevationGain": gain,
+                "elevationLoss": loss
+            }))
+            .unwrap()
+        };
+        let activities = vec![
+            activity("2026-03-10 07:00:00", Some(420.0), Some(410.0)),
+            activity("2026-03-11 07:00:00", Some(180.0), Some(200.0)),
+            activity("2026-03-11 18:00:00", None, None),
+            // Outside the 7-day window.
+            activity("2026-03-01 07:00:00", Some(900.0), Some(900.0)),
+        ];
+        let now = NaiveDate::from_ymd_opt(2026, 3, 12)
+            .unwrap()
+            .and_hms_opt(18, 0, 0)
+            .unwrap()
+            .and_utc()
+            .fixed_offset();
+
+        assert_eq!(weekly_elevation(&activities, now), (600.0, 610.0));
+        assert!(terrain_note(Some(&activities[0])).contains("elevation +420 m / -410 m"));
+        assert_eq!(terrain_note(Some(&activities[2])), "");
+        assert_eq!(terrain_note(None), "");
+    }
+
+    #[test]
+    fn heat_note_lists_only_hot_outdoor_sessions_with_temperatures() {
+        let activity = |key: &str, start: &str, min: Option<f64>, max: Option<f64>| {
+            serde_json::from_value::<crate::models::GarminActivity>(serde_json::json!({
+                "activityName": "Lunch Run",
+                "activityType": { "typeKey": key },
+                "startTimeLocal": start,
+                "minTemperature": min,
+                "maxTemperature": max
+            }))
+            .unwrap()
+        };
+        let now = NaiveDate::from_ymd_opt(2026, 7, 12)
+            .unwrap()
+            .and_hms_opt(18, 0, 0)
+            .unwrap()
+            .and_utc()
+            .fixed_offset();
+
+        let hot = vec![
+            activity("running", "2026-07-11 12:30:00", Some(24.0), Some(31.0)),
+            activity("road_biking", "2026-07-10 13:00:00", None, Some(28.4)),
+        ];
+        let note = heat_note(&hot, now);
+        assert!(note.starts_with("## Heat Exposure (Last 7 Days)\n"));
+        assert!(note.contains("- **Lunch Run** (2026-07-11): 24-31°C\n"));
+        assert!(note.contains("(2026-07-10): up to 28°C\n"));
+        assert!(note.contains("hydration"));
+
+        let not_hot = vec![
+            activity("running", "2026-07-11 06:30:00", None, None),
+            activity("running", "2026-07-11 07:30:00", Some(12.0), Some(18.0)),
+            activity("treadmill_running", "2026-07-11 12:00:00", None, Some(30.0)),
+            // Outside the 7-day window.
+            activity("running", "2026-07-01 12:00:00", None, Some(33.0)),
+        ];
+        assert_eq!(heat_note(&not_hot, now), "");
+    }
+
+    #[test]
+    fn muscle_recovery_tracks_last_session_per_group() {
+        let now = NaiveDate::from_ymd_opt(2026, 3, 12)
+            .unwrap()
+            .and_hms_opt(18, 0, 0)
+            .unwrap();
+        let activities = vec![
+            strength_session("2026-03-09 18:00:00", &["BENCH_PRESS", "SQUAT"]),
+            strength_session("2026-03-11 18:00:00", &["BENCH_PRESS", "WARM_UP"]),
+            strength_session("2026-03-12 08:00:00", &["ROW"]),
+            // Outside the 7-day window.
+            strength_session("2026-03-01 18:00:00", &["CURL"]),
+        ];
+
+        let recovery = muscle_recovery(&activities, now, 48);
+        let summary: Vec<_> = recovery
+            .iter()
+            .map(|r| (r.muscle.as_str(), r.hours_since, r.ready))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("Back", 10, false),
+                ("Chest", 24, false),
+                ("Legs", 72, true)
+            ]
+        );
+        assert_eq!(recovery[1].last_trained_date, "2026-03-11 18:00:00");
+
+        assert_eq!(
+            format_muscle_recovery_guidance(&recovery[1], 48).as_deref(),
+            Some("Chest last trained 1 day ago — allow 48h recovery before another chest session")
+        );
+        assert_eq!(
+            format_muscle_recovery_guidance(&recovery[0], 48).as_deref(),
+            Some("Back last trained 10h ago — allow 48h recovery before another back session")
+        );
+        assert_eq!(format_muscle_recovery_guidance(&recovery[2], 48), None);
+
+        // Exactly at the threshold counts as recovered.
+        assert!(muscle_recovery(&activities, now, 24)
+            .iter()
+            .any(|r| r.muscle == "Chest" && r.ready));
+    }
+
+    #[test]
+    fn volume_status_boundaries() {
+        assert_eq!(VolumeStatus::from_sets(9), VolumeStatus::UnderTrained);
+        assert_eq!(VolumeStatus::from_sets(10), VolumeStatus::Optimal);
+        assert_eq!(VolumeStatus::from_sets(20), VolumeStatus::Optimal);
+        assert_eq!(VolumeStatus::from_sets(21), VolumeStatus::PossibleOverreach);
+
+        let flag = |muscle: &str, sets: i32| {
+            format_volume_flag(&MuscleVolume {
+                muscle: muscle.to_string(),
+                sets,
+                status: VolumeStatus::from_sets(sets),
+            })
+        };
+        assert_eq!(
+            flag("Chest", 9).as_deref(),
+            Some("⚠️ Chest: 9 sets (under-trained)")
+        );
+        assert_eq!(flag("Chest", 10), None);
+        assert_eq!(flag("Legs", 20), None);
+        assert_eq!(
+            flag("Legs", 21).as_deref(),
+            Some("⚠️ Legs: 21 sets (possible overreach)")
+        );
+        assert_eq!(flag("Other", 2), None);
+    }
+
+    #[test]
+    fn auto_analyze_candidates_respect_window_analyzed_ids_and_cap() {
+        let activities: Vec<crate::models::GarminActivity> = serde_json::from_value(serde_json::json!([
+            { "activityId": 1, "startTimeLocal": "2026-03-08 07:00:00", "activityType": { "typeKey": "running" } },
+            { "activityId": 2, "startTimeLocal": "2026-03-10 07:00:00", "activityType": { "typeKey": "running" } },
+            { "activityId": 3, "startTimeLocal": "2026-03-12 07:00:00", "activityType": { "typeKey": "running" } },
+            { "activityId": 4, "startTimeLocal": "2026-03-13 18:00:00", "activityType": { "typeKey": "strength_training" } },
+            { "activityId": 5, "startTimeLocal": "2026-03-14 07:00:00", "activityType": { "typeKey": "running" } }
+        ]))
+        .unwrap();
+        let sports = vec!["running".to_string()];
+        let today = NaiveDate::from_ymd_opt(2026, 3, 14).unwrap();
+        let ids = |days, cap, analyzed: &[i64]| {
+            auto_analyze_candidates(&activities, &sports, today, days, cap, |id| {
+                analyzed.contains(&id)
+            })
+            .iter()
+            .filter_map(|a| a.id)
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids(2, None, &[]), vec![5]);
+        assert_eq!(ids(7, None, &[]), vec![5, 3, 2, 1]);
+        assert_eq!(ids(7, None, &[5, 2]), vec![3, 1]);
+        assert_eq!(ids(7, Some(2), &[]), vec![5, 3]);
+        assert!(ids(0, None, &[]).is_empty());
+    }
+
+    /// An empty 7-day strength brief input; tests override the fields they exercise.
+    fn brief_input(context: &CoachContext, now: DateTime<FixedOffset>) -> BriefInput<'_> {
+        BriefInput {
+            detailed_activities: &[],
+            plans: &[],
+            profile: &None,
+            metrics: &None,
+            scheduled_workouts: &[],
+            recovery_metrics: &None,
+            context,
+            progression_history: &[],
+            week_start_day: "Mon",
+            previous_plan_response: &None,
+            recent_analyses: &[],
+            recent_rpe: &[],
+            adherence_summary: &[],
+            weekly_deltas: &[],
+            recovery_history: &[],
+            max_hr_formula: crate::models::MaxHrFormula::Fox,
+            min_body_battery_for_hard: 0,
+            min_muscle_recovery_hours: 0,
+            intensity_thresholds: Default::default(),
+            stale_data_hours: None,
+            now,
+            planning_horizon_days: 7,
+            generation_scope: GenerationScope::default(),
+        }
+    }
+
+    #[test]
+    fn brief_output_spec_follows_the_planning_horizon() {
+        use chrono::TimeZone;
+        let now = chrono::Local
+            .with_ymd_and_hms(2026, 3, 14, 18, 0, 0)
+            .unwrap()
+            .fixed_offset();
+        let context = CoachContext {
+            goals: vec!["Build strength".to_string()],
+            constraints: Vec::new(),
+            available_equipment: Vec::new(),
+            rest_days: Vec::new(),
+            planning_horizon_days: None,
+        };
+        let brief = |planning_horizon_days| {
+            Coach::new().generate_brief(BriefInput {
+                planning_horizon_days,
+                ..brief_input(&context, now)
+            })
+        };
+
+        let two_weeks = brief(14);
+        assert!(two_weeks.contains("**next 14 days** (2026-03-15 to 2026-03-28)"));
+        assert!(two_weeks.contains("end of the 14-day planning horizon"));
+        assert!(two_weeks.contains("\"scheduledDate\": \"2026-03-15\""));
+
+        let one_week = brief(7);
+        assert!(one_week.contains("**next 7 days** (2026-03-15 to 2026-03-21)"));
+    }
+
+    #[test]
+    fn endurance_scope_omits_the_strength_json_spec() {
+        use chrono::TimeZone;
+        let now = chrono::Local
+            .with_ymd_and_hms(2026, 3, 14, 18, 0, 0)
+            .unwrap()
+            .fixed_offset();
+        let context = CoachContext {
+            goals: vec!["Sub 4h marathon".to_string()],
+            constraints: Vec::new(),
+            available_equipment: Vec::new(),
+            rest_days: Vec::new(),
+            planning_horizon_days: None,
+        };
+        let brief = |generation_scope| {
+            Coach::new().generate_brief(BriefInput {
+                generation_scope,
+                ..brief_input(&context, now)
+            })
+        };
+
+        let endurance = brief(GenerationScope::Endurance);
+        assert!(endurance.contains("output the Endurance Workouts (runs and pool swims)"));
+        assert!(endurance.contains("\"sport\": \"running\""));
+        assert!(!endurance.contains("BENCH_PRESS"));
+        assert!(!endurance.contains("EXERCISE VOCABULARY"));
+
+        let strength = brief(GenerationScope::Strength);
+        assert!(strength.contains("output the Strength Workouts"));
+        assert!(strength.contains("6. **SUPERSETS**"));
+        assert!(!strength.contains("Threshold Intervals"));
+
+        assert!(!strength.contains("\"sport\": \"swimming\""));
+
+        // The default keeps the pre-scope brief: strength rules plus pool swims.
+        let default = brief(GenerationScope::default());
+        assert!(default.contains("output the Strength Workouts"));
+        assert!(default.contains("6. **SUPERSETS**"));
+        assert!(default.contains("\"sport\": \"swimming\""));
+        assert!(!default.contains("Threshold Intervals"));
+
+        let both = brief(GenerationScope::Both);
+        assert!(both.contains("BENCH_PRESS") && both.contains("Threshold Intervals"));
+        assert!(!both.contains("Do not output strength workouts"));
+        assert!(endurance.contains("Do not output strength workouts"));
+    }
+
+    #[test]
+    fn generation_scope_filters_specs_by_sport() {
+        let lift = serde_json::json!({ "workoutName": "Push" });
+        let run = serde_json::json!({ "workoutName": "Tempo", "sport": "running" });
+        let swim = serde_json::json!({ "workoutName": "Pool", "sport": "swimming" });
+
+        assert!(GenerationScope::Strength.includes(&lift));
+        assert!(!GenerationScope::Strength.includes(&run));
+        assert!(!GenerationScope::Endurance.includes(&lift));
+        assert!(GenerationScope::Endurance.includes(&swim));
+        assert!(GenerationScope::Both.includes(&run) && GenerationScope::Both.includes(&lift));
+        let default = GenerationScope::default();
+        assert!(default.includes(&lift) && default.includes(&run) && default.includes(&swim));
+    }
+
+    #[test]
+    fn weekly_summary_totals_recent_activities_by_type() {
+        use chrono::TimeZone;
+        let now = chrono::Local
+            .with_ymd_and_hms(2026, 3, 14, 18, 0, 0)
+            .unwrap()
+            .fixed_offset();
+        let data: crate::models::GarminResponse = serde_json::from_value(serde_json::json!({
+            "activities": [
+                { "startTimeLocal": "2026-03-13 07:00:00", "activityType": { "typeKey": "running" }, "distance": 10000.0, "duration": 3000.0 },
+                { "startTimeLocal": "2026-03-10 07:00:00", "activityType": { "typeKey": "running" }, "distance": 5000.0, "duration": 1500.0 },
+                { "startTimeLocal": "2026-03-12 18:00:00", "activityType": { "typeKey": "strength_training" }, "duration": 3600.0 },
+                { "startTimeLocal": "2026-03-01 07:00:00", "activityType": { "typeKey": "running" }, "distance": 42195.0, "duration": 14400.0 }
+            ],
+            "plans": [],
+            "scheduled_workouts": [
+                { "title": "Easy Run", "date": "2026-03-15", "sportTypeKey": "running", "itemType": "workout" }
+            ],
+            "recovery_metrics": {
+                "sleep_score": 82,
+                "recent_sleep_scores": [],
+                "current_body_battery": 64,
+                "hrv_status": "BALANCED"
+            }
+        }))
+        .unwrap();
+
+        let summary = build_weekly_summary(&data, now);
+        assert_eq!(summary.start_date, "2026-03-07");
+        assert_eq!(summary.activity_count, 3);
+        assert_eq!(summary.total_distance_km, 15.0);
+        assert_eq!(summary.total_duration_mins, 135.0);
+        assert_eq!(summary.by_type[0].activity_type, "running");
+        assert_eq!(summary.by_type[0].count, 2);
+        assert_eq!(summary.by_type[1].activity_type, "strength training");
+        assert_eq!(summary.tomorrow, vec!["Easy Run (running)".to_string()]);
+
+        assert!(summary.personal_records.is_empty());
+
+        let mut summary = summary;
+        summary.personal_records.push(crate::db::PersonalRecord {
+            exercise_name: "BENCH_PRESS".to_string(),
+            weight: 85.0,
+            reps: 1,
+            date: "2026-03-10 08:00:00".to_string(),
+            previous_best: 80.0,
+        });
+        let prompt = render_weekly_review_prompt(&summary);
+        assert!(prompt.contains("New Personal Records This Week"));
+        assert!(prompt.contains("- BENCH_PRESS: 85kg x 1 on 2026-03-10 (previous best 80kg)"));
+        assert!(prompt.contains("Workouts Completed: 3"));
+        assert!(prompt.contains("HRV Status: BALANCED"));
+        assert!(prompt.contains("- Easy Run (running)"));
+    }
+
+    #[test]
+    fn hr_zones_split_max_hr_in_ten_percent_bands() {
+        assert_eq!(
+            hr_zones(190.0),
+            [
+                ("Z1", 95, 114),
+                ("Z2", 114, 133),
+                ("Z3", 133, 152),
+                ("Z4", 152, 171),
+                ("Z5", 171, 190),
+            ]
+        );
+    }
+
+    #[test]
+    fn max_hr_falls_back_to_observed_activities() {
+        let today = NaiveDate::from_ymd_opt(2026, 3, 14).unwrap();
+        let activities: Vec<crate::models::GarminActivity> =
+            serde_json::from_value(serde_json::json!([
+                { "startTimeLocal": "2026-03-10 07:00:00", "maxHR": 178.0 },
+                { "startTimeLocal": "2026-03-12 07:00:00", "maxHR": 184.0 },
+                { "startTimeLocal": "2026-03-13 07:00:00" }
+            ]))
+            .unwrap();
+        let formula = crate::models::MaxHrFormula::Fox;
+
+        let with_age: Option<crate::models::GarminProfile> =
+            serde_json::from_value(serde_json::json!({ "birthDate": "1986-01-01" })).unwrap();
+        assert_eq!(
+            resolve_max_hr(&with_age, &activities, today, formula),
+            Some((180.0, MaxHrSource::Age))
+        );
+
+        let without_age: Option<crate::models::GarminProfile> =
+            serde_json::from_value(serde_json::json!({ "weight": 72500.0 })).unwrap();
+        assert_eq!(
+            resolve_max_hr(&without_age, &activities, today, formula),
+            Some((184.0, MaxHrSource::Observed))
+        );
+        assert_eq!(resolve_max_hr(&None, &[], today, formula), None);
+    }
+
+    #[test]
+    fn low_body_battery_forces_recovery_directive() {
+        let recovery = |bb: i32| -> Option<crate::models::GarminRecoveryMetrics> {
+            serde_json::from_value(serde_json::json!({ "current_body_battery": bb })).unwrap()
+        };
+
+        let directive = low_body_battery_directive(&recovery(15), 25, "2026-03-14").unwrap();
+        assert!(directive.contains("Body Battery is 15 / 100"));
+        assert!(directive.contains("MUST NOT schedule any high-intensity session"));
+        assert!(directive.contains("recovery work only"));
+
+        assert_eq!(
+            low_body_battery_directive(&recovery(25), 25, "2026-03-14"),
+            None
+        );
+        assert_eq!(
+            low_body_battery_directive(&recovery(15), 0, "2026-03-14"),
+            None
+        );
+        assert_eq!(low_body_battery_directive(&None, 25, "2026-03-14"), None);
+    }
+
+    #[test]
+    fn intensity_follows_the_weakest_recovery_marker() {
+        let intensity = |bb: Option<i32>, sleep: Option<i32>, readiness: Option<i32>| {
+            let recovery: crate::models::GarminRecoveryMetrics =
+                serde_json::from_value(serde_json::json!({
+                    "current_body_battery": bb,
+                    "sleep_score": sleep,
+                    "training_readiness": readiness,
+                }))
+                .unwrap();
+            recommended_intensity(&recovery, &IntensityThresholds::default())
+        };
+
+        // Every marker at its high threshold allows hard work.
+        assert_eq!(intensity(Some(60), Some(75), Some(60)), Intensity::High);
+        // One marker just under its high threshold drops to moderate.
+        assert_eq!(intensity(Some(59), Some(90), Some(90)), Intensity::Moderate);
+        assert_eq!(intensity(Some(90), Some(74), Some(90)), Intensity::Moderate);
+        // Low thresholds are inclusive of moderate; one value below forces recovery.
+        assert_eq!(intensity(Some(20), Some(50), Some(30)), Intensity::Moderate);
+        assert_eq!(intensity(Some(19), Some(90), Some(90)), Intensity::Recovery);
+        assert_eq!(intensity(Some(90), Some(49), Some(90)), Intensity::Recovery);
+        assert_eq!(intensity(Some(90), Some(90), Some(29)), Intensity::Recovery);
+        // Missing markers are skipped rather than counted as low.
+        assert_eq!(intensity(Some(80), None, None), Intensity::High);
+        assert_eq!(intensity(None, None, Some(10)), Intensity::Recovery);
+        assert_eq!(intensity(None, None, None), Intensity::Moderate);
+
+        let strict = IntensityThresholds {
+            high_body_battery: 90,
+            ..IntensityThresholds::default()
+        };
+        let recovery: crate::models::GarminRecoveryMetrics =
+            serde_json::from_value(serde_json::json!({ "current_body_battery": 80 })).unwrap();
+        assert_eq!(
+            recommended_intensity(&recovery, &strict),
+            Intensity::Moderate
+        );
+
+        let directive = intensity_directive(Intensity::Recovery, "2026-03-14");
+        assert!(directive.contains("Today's readiness is LOW"));
+        assert!(directive.contains("prescribe technique/volume-reduced work"));
+    }
+
+    #[test]
+    fn rest_day_rule_lists_configured_days() {
+        let rule = rest_day_rule(&["Sun".to_string(), "Wed".to_string()]).unwrap();
+        assert!(rule.contains("Do NOT schedule any workout on these weekdays: Sun, Wed"));
+        assert_eq!(rest_day_rule(&[]), None);
+    }
+}
@@ -1,4 +1,4 @@
-use chrono::Weekday;
+use chrono::{DateTime, FixedOffset, Utc, Weekday};
 use figment::{
     providers::{Env, Format, Json, Toml},
     Figment,
@@ -17,6 +17,20 @@ pub fn parse_weekday(day: &str) -> Weekday {
     }
 }
 
+/// Converts `utc` into the IANA `timezone` (e.g. "Europe/Berlin"), or the
+/// server's local zone when unset or unknown.
+pub fn in_timezone(timezone: Option<&str>, utc: DateTime<Utc>) -> DateTime<FixedOffset> {
+    match timezone.and_then(|tz| tz.parse::<chrono_tz::Tz>().ok()) {
+        Some(tz) => utc.with_timezone(&tz).fixed_offset(),
+        None => utc.with_timezone(&chrono::Local).fixed_offset(),
+    }
+}
+
+/// Current time in the configured zone; see `in_timezone`.
+pub fn now_in(timezone: Option<&str>) -> DateTime<FixedOffset> {
+    in_timezone(timezone, Utc::now())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     pub database_url: String,
@@ -27,15 +41,39 @@ pub struct AppConfig {
     pub signal_api_host: String,
     #[serde(default)]
     pub signal_subscribers: String,
+    /// Minutes within which a broadcast identical to the previous one is
+    /// suppressed (0 disables).
+    pub broadcast_dedup_mins: u64,
+    /// Free-text messages each sender may send the conversational coach per
+    /// minute; must be at least 1.
+    pub bot_chat_rate_limit_per_minute: usize,
+    /// How long the bot reuses the live Garmin part of its conversation context.
+    pub bot_context_cache_secs: u64,
     pub morning_message_time: String,
     pub readiness_message_time: String,
+    /// Days before the next race on which the readiness assessment is sent;
+    /// sorted farthest first and deduplicated on load.
+    pub race_readiness_offsets_days: Vec<i64>,
     pub weekly_review_day: String,
     pub weekly_review_time: String,
     pub monthly_review_day: u32,
     pub monthly_review_time: String,
     pub force_monthly_debrief: bool,
     pub strength_validation_time: String,
+    /// Days without any recorded activity before the coach checks in once (0 disables).
+    pub stale_checkin_days: u32,
+    pub stale_checkin_time: String,
     pub week_start_day: String,
+    /// Daily time for the daemon's database cleanup (`Database::vacuum_old_data`).
+    pub maintenance_time: String,
+    /// Days of analyses, resolution logs, generated-workout records and AI
+    /// caches kept by the daily cleanup (0 disables pruning).
+    pub retention_days: u32,
+    /// Days of `exercise_history` kept; 0 keeps it forever for long-term PRs.
+    /// When set it must be at least `retention_days`.
+    pub exercise_history_retention_days: u32,
+    /// IANA zone used for notifier times and "today"; unset uses the server's local time.
+    pub timezone: Option<String>,
 
     // API Settings
     pub cors_allowed_origins: String,
@@ -43,6 +81,53 @@ pub struct AppConfig {
     pub api_bind_addr: String,
     pub chat_rate_limit_per_minute: usize,
     pub generate_rate_limit_per_hour: usize,
+    /// Attempts per hour for `POST /api/garmin/login` and its MFA step.
+    pub garmin_login_rate_limit_per_hour: usize,
+    /// A plan generation that started less than this long ago blocks new ones
+    /// from the API, the Signal bot and the daemon loop.
+    pub generation_guard_secs: u64,
+
+    // Garmin Settings
+    /// Named Garmin account whose tokens live in `secrets/<account>/`.
+    /// Unset uses the default `secrets/oauth*.json` tokens.
+    pub garmin_account: Option<String>,
+    /// How long fetched Garmin data is served from the SQLite cache. The legacy
+    /// `GARMIN_CACHE_TTL_SECONDS` variable is still read, below the config files.
+    pub garmin_cache_ttl_secs: u64,
+    /// Months of Garmin calendar (starting with the current month) scanned for
+    /// scheduled workouts and races.
+    pub garmin_calendar_months: u32,
+    /// Short "Bench Press 80kg" step descriptions when a workout has to be
+    /// uploaded with the generic (robust) payload.
+    pub concise_generic_descriptions: bool,
+    /// Seconds for warmup/cooldown steps the AI left without a duration (0 = lap button).
+    pub default_warmup_secs: i64,
+    pub default_cooldown_secs: i64,
+    /// Name prefix tagging the workouts and courses the coach creates.
+    pub ai_workout_tag: String,
+    /// Extra name prefixes cleanup also deletes, for workouts created before
+    /// `ai_workout_tag` was introduced or changed.
+    pub cleanup_name_prefixes: Vec<String>,
+
+    // Athlete Settings
+    /// Max HR estimate used when only the athlete's age is known (`fox` = 220 - age,
+    /// `tanaka` = 208 - 0.7 * age).
+    pub max_hr_formula: crate::models::MaxHrFormula,
+    /// AI-proposed working weights above this multiple of the best recorded
+    /// weight for an exercise are capped (0 disables).
+    pub max_weight_ratio: f64,
+    /// Below this morning Body Battery no high-intensity session is scheduled
+    /// for today (0 disables).
+    pub min_body_battery_for_hard: i32,
+    /// Hours the brief asks the AI to leave between sessions training the same
+    /// muscle group (0 disables).
+    pub min_muscle_recovery_hours: u32,
+    /// Sleep score / training readiness / high Body Battery cut-offs for today's
+    /// recommended intensity; read them through `effective_intensity_thresholds`.
+    pub intensity_thresholds: crate::coaching::IntensityThresholds,
+    /// Insert a 5-minute warmup/stretching cooldown into strength workouts the
+    /// AI returned without one.
+    pub enforce_warmup_cooldown: bool,
 
     // Course / Location Settings
     pub default_start_latitude: Option<f64>,
@@ -50,6 +135,38 @@ pub struct AppConfig {
 
     // AI/Gemini Settings
     pub gemini_api_key: String,
+    /// Opening line of every Gemini system instruction; a profile's
+    /// `coach_persona` in `profiles.json` takes precedence.
+    pub coach_persona: String,
+    /// Number of days (starting tomorrow) the AI plans ahead.
+    pub planning_horizon_days: u32,
+    /// Workout types the AI plans and uploads: `any` (no restriction),
+    /// `strength`, `endurance` (runs and pool swims) or `both`.
+    pub generation_scope: crate::coaching::GenerationScope,
+    /// Gemini sampling for plans, analyses and other one-shot prompts; a low
+    /// `temperature` keeps exercise names closer to the database.
+    pub workout_generation: crate::ai_client::GenerationSettings,
+    /// Gemini sampling for the Signal and dashboard chat.
+    pub chat_generation: crate::ai_client::GenerationSettings,
+    /// Skip AI generation when Garmin already has something scheduled on every
+    /// day of the planning horizon.
+    pub skip_if_scheduled: bool,
+    /// Completed activities from the last this-many days (today included) are
+    /// auto-analyzed for `auto_analyze_sports`.
+    pub auto_analyze_days: u32,
+    /// Max analyses on the first run, before any activity has been analyzed.
+    pub auto_analyze_initial_cap: usize,
+    /// Also write each auto-analysis into the activity's Garmin description
+    /// (modifies the athlete's Garmin data, so off by default).
+    pub write_analysis_to_garmin: bool,
+    /// Create and schedule generated workouts on Garmin. When off, the plan is
+    /// only sent via Signal and saved to `GENERATED_WORKOUTS_PATH` for review.
+    pub auto_upload: bool,
+    /// Receives a JSON `{status, count, workouts}` POST after every pipeline run.
+    pub generation_webhook_url: Option<String>,
+    /// Days an AI workout duration prediction is reused before Gemini is asked
+    /// again (0 keeps predictions forever).
+    pub predicted_duration_ttl_days: u32,
     pub fitness_debug_prompt: bool,
 }
 
@@ -60,23 +177,61 @@ impl Default for AppConfig {
             signal_phone_number: "".to_string(),
             signal_api_host: "fitness-coach-signal-api".to_string(),
             signal_subscribers: "".to_string(),
+            broadcast_dedup_mins: 10,
+            bot_chat_rate_limit_per_minute: 6,
+            bot_context_cache_secs: 300,
             morning_message_time: "07:00".to_string(),
             readiness_message_time: "08:00".to_string(),
+            race_readiness_offsets_days: vec![14, 7, 2],
             weekly_review_day: "Sun".to_string(),
             weekly_review_time: "18:00".to_string(),
             monthly_review_day: 1,
             monthly_review_time: "18:00".to_string(),
             force_monthly_debrief: false,
             strength_validation_time: "04:00".to_string(),
+            stale_checkin_days: 4,
+            stale_checkin_time: "12:00".to_string(),
             week_start_day: "Mon".to_string(),
+            maintenance_time: "03:30".to_string(),
+            retention_days: 365,
+            exercise_history_retention_days: 0,
+            timezone: None,
             cors_allowed_origins: "http://localhost:3000".to_string(),
             api_auth_token: None,
             api_bind_addr: "127.0.0.1:3001".to_string(),
             chat_rate_limit_per_minute: 30,
             generate_rate_limit_per_hour: 6,
+            garmin_login_rate_limit_per_hour: 5,
+            generation_guard_secs: 900,
+            garmin_account: None,
+            garmin_cache_ttl_secs: DEFAULT_GARMIN_CACHE_TTL_SECS,
+            garmin_calendar_months: 6,
+            concise_generic_descriptions: false,
+            default_warmup_secs: crate::workout_builder::DEFAULT_WARMUP_SECS,
+            default_cooldown_secs: crate::workout_builder::DEFAULT_COOLDOWN_SECS,
+            ai_workout_tag: crate::garmin_client::AI_WORKOUT_PREFIX.to_string(),
+            cleanup_name_prefixes: Vec::new(),
+            max_hr_formula: crate::models::MaxHrFormula::Fox,
+            max_weight_ratio: 1.1,
+            min_body_battery_for_hard: crate::coaching::DEFAULT_MIN_BODY_BATTERY_FOR_HARD,
+            min_muscle_recovery_hours: 48,
+            intensity_thresholds: crate::coaching::IntensityThresholds::default(),
+            enforce_warmup_cooldown: true,
             default_start_latitude: None,
             default_start_longitude: None,
             gemini_api_key: "".to_string(),
+            coach_persona: crate::ai_client::DEFAULT_COACH_PERSONA.to_string(),
+            planning_horizon_days: 7,
+            generation_scope: crate::coaching::GenerationScope::Any,
+            workout_generation: crate::ai_client::GenerationSettings::default(),
+            chat_generation: crate::ai_client::GenerationSettings::default(),
+            skip_if_scheduled: false,
+            auto_analyze_days: 2,
+            auto_analyze_initial_cap: 5,
+            write_analysis_to_garmin: false,
+            auto_upload: true,
+            generation_webhook_url: None,
+            predicted_duration_ttl_days: 30,
             fitness_debug_prompt: false,
         }
     }
@@ -88,18 +243,289 @@ impl AppConfig {
         let mut config: AppConfig = Figment::from(figment::providers::Serialized::defaults(
             AppConfig::default(),
         ))
+        .merge(legacy_cache_ttl_env())
         .merge(Toml::file("Fitness.toml"))
         .merge(Json::file("Fitness.json"))
         .merge(Env::raw().ignore(&["SIGNAL_PHONE_NUMBER", "SIGNAL_SUBSCRIBERS"]))
         .extract()?;
 
+        if std::env::var_os(LEGACY_CACHE_TTL_VAR).is_some() {
+            tracing::warn!(
+                "{} is deprecated; set garmin_cache_ttl_secs (or GARMIN_CACHE_TTL_SECS) instead",
+                LEGACY_CACHE_TTL_VAR
+            );
+        }
+
         if let Ok(num) = std::env::var("SIGNAL_PHONE_NUMBER") {
             config.signal_phone_number = num;
         }
         if let Ok(subs) = std::env::var("SIGNAL_SUBSCRIBERS") {
             config.signal_subscribers = subs;
         }
+        if let Some(tz) = &config.timezone {
+            if tz.parse::<chrono_tz::Tz>().is_err() {
+                return Err(figment::Error::from(format!(
+                    "unknown timezone '{}', expected an IANA name like Europe/Berlin",
+                    tz
+                )));
+            }
+        }
+
+        if config.exercise_history_retention_days > 0
+            && config.exercise_history_retention_days < config.retention_days
+        {
+            return Err(figment::Error::from(format!(
+                "exercise_history_retention_days must be 0 (keep forever) or at least retention_days ({}), got {}",
+                config.retention_days, config.exercise_history_retention_days
+            )));
+        }
+
+        normalize_race_readiness_offsets(&mut config.race_readiness_offsets_days)
+            .map_err(figment::Error::from)?;
+
+        if !crate::profiles::PLANNING_HORIZON_RANGE.contains(&config.planning_horizon_days) {
+            return Err(figment::Error::from(format!(
+                "planning_horizon_days must be between {} and {}, got {}",
+                crate::profiles::PLANNING_HORIZON_RANGE.start(),
+                crate::profiles::PLANNING_HORIZON_RANGE.end(),
+                config.planning_horizon_days
+            )));
+        }
+
+        // A zero-capacity limiter would answer every chat message with "slow down".
+        if config.bot_chat_rate_limit_per_minute == 0 {
+            return Err(figment::Error::from(
+                "bot_chat_rate_limit_per_minute must be at least 1".to_string(),
+            ));
+        }
 
         Ok(config)
     }
+
+    /// `intensity_thresholds` with the low Body Battery cut-off taken from
+    /// `min_body_battery_for_hard`, so the guardrail and the recommendation agree.
+    pub fn effective_intensity_thresholds(&self) -> crate::coaching::IntensityThresholds {
+        crate::coaching::IntensityThresholds {
+            low_body_battery: self.min_body_battery_for_hard,
+            ..self.intensity_thresholds
+        }
+    }
+
+    /// Current time in the configured `timezone`.
+    pub fn now(&self) -> DateTime<FixedOffset> {
+        now_in(self.timezone.as_deref())
+    }
+
+    /// Serializes the effective config with secrets masked by [`mask_secret`].
+    pub fn redacted(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        let secrets = [
+            ("gemini_api_key", Some(self.gemini_api_key.as_str())),
+            ("api_auth_token", self.api_auth_token.as_deref()),
+            (
+                "signal_phone_number",
+                Some(self.signal_phone_number.as_str()),
+            ),
+            // Webhook URLs usually embed their credential (webhook id, token).
+            (
+                "generation_webhook_url",
+                self.generation_webhook_url.as_deref(),
+            ),
+        ];
+        if let Some(obj) = value.as_object_mut() {
+            for (key, secret) in secrets {
+                obj.insert(key.to_string(), serde_json::json!(mask_secret(secret)));
+            }
+        }
+        value
+    }
+}
+
+/// `"unset"` for a missing secret, otherwise asterisks followed by its last
+/// 4 characters. Secrets of 8 characters or fewer are masked entirely.
+fn mask_secret(secret: Option<&str>) -> String {
+    let Some(secret) = secret.filter(|s| !s.is_empty()) else {
+        return "unset".to_string();
+    };
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() <= 8 {
+        return "****".to_string();
+    }
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("****{}", tail)
+}
+
+/// Default for `garmin_cache_ttl_secs`: Garmin data changes a few times a day,
+/// so an hour keeps the daemon's 5 minute cycle from hitting the API each time.
+pub const DEFAULT_GARMIN_CACHE_TTL_SECS: u64 = 3600;
+
+/// Environment variable that set the cache TTL before `garmin_cache_ttl_secs`.
+const LEGACY_CACHE_TTL_VAR: &str = "GARMIN_CACHE_TTL_SECONDS";
+
+/// `GARMIN_CACHE_TTL_SECONDS` mapped onto `garmin_cache_ttl_secs`. Merged before
+/// the config files so the new key wins when both are set.
+fn legacy_cache_ttl_env() -> Env {
+    Env::raw()
+        .only(&[LEGACY_CACHE_TTL_VAR])
+        .map(|_| "garmin_cache_ttl_secs".into())
+}
+
+/// Readiness offsets must be positive; they may be listed in any order and are
+/// sorted farthest first with duplicates dropped, so each day fires once.
+fn normalize_race_readiness_offsets(offsets: &mut Vec<i64>) -> Result<(), String> {
+    if let Some(bad) = offsets.iter().find(|&&days| days <= 0) {
+        return Err(format!(
+            "race_readiness_offsets_days must be positive, got {}",
+            bad
+        ));
+    }
+    offsets.sort_unstable_by(|a, b| b.cmp(a));
+    offsets.dedup();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        in_timezone, legacy_cache_ttl_env, normalize_race_readiness_offsets, AppConfig,
+        DEFAULT_GARMIN_CACHE_TTL_SECS, LEGACY_CACHE_TTL_VAR,
+    };
+    use chrono::TimeZone;
+
+    #[test]
+    fn today_is_computed_in_the_configured_zone_around_midnight() {
+        let late_utc = chrono::Utc
+            .with_ymd_and_hms(2026, 3, 14, 23, 30, 0)
+            .unwrap();
+        let today = |tz| {
+            in_timezone(Some(tz), late_utc)
+                .format("%Y-%m-%d")
+                .to_string()
+        };
+        assert_eq!(today("Europe/Berlin"), "2026-03-15");
+        assert_eq!(today("America/New_York"), "2026-03-14");
+        assert_eq!(today("UTC"), "2026-03-14");
+
+        let early_utc = chrono::Utc.with_ymd_and_hms(2026, 3, 15, 0, 30, 0).unwrap();
+        let berlin = in_timezone(Some("Europe/Berlin"), early_utc);
+        assert_eq!(
+            berlin.format("%Y-%m-%d %H:%M").to_string(),
+            "2026-03-15 01:30"
+        );
+        let la = in_timezone(Some("America/Los_Angeles"), early_utc);
+        assert_eq!(la.format("%Y-%m-%d").to_string(), "2026-03-14");
+    }
+
+    #[test]
+    fn redacted_config_masks_secrets_to_their_last_four_chars() {
+        let config = AppConfig {
+            gemini_api_key: "super-secret-gemini-key".to_string(),
+            api_auth_token: Some("super-secret-token".to_string()),
+            signal_phone_number: "+4915112345678".to_string(),
+            generation_webhook_url: Some(
+                "https://ha.local/api/webhook/super-secret-hook-1234".to_string(),
+            ),
+            monthly_review_day: 15,
+            ..AppConfig::default()
+        };
+
+        let redacted = config.redacted();
+        let serialized = redacted.to_string();
+        assert!(!serialized.contains("super-secret"));
+        assert!(!serialized.contains("4915112"));
+        assert_eq!(redacted["gemini_api_key"], "****-key");
+        assert_eq!(redacted["api_auth_token"], "****oken");
+        assert_eq!(redacted["signal_phone_number"], "****5678");
+        assert_eq!(redacted["generation_webhook_url"], "****1234");
+        assert_eq!(redacted["week_start_day"], "Mon");
+        assert_eq!(redacted["monthly_review_day"], 15);
+
+        let defaults = AppConfig::default().redacted();
+        assert_eq!(defaults["gemini_api_key"], "unset");
+        assert_eq!(defaults["api_auth_token"], "unset");
+        assert_eq!(defaults["generation_webhook_url"], "unset");
+        let short = AppConfig {
+            api_auth_token: Some("abc123".to_string()),
+            ..AppConfig::default()
+        };
+        assert_eq!(short.redacted()["api_auth_token"], "****");
+    }
+
+    #[test]
+    #[allow(clippy::result_large_err)]
+    fn a_zero_chat_rate_limit_is_rejected() {
+        figment::Jail::expect_with(|jail| {
+            jail.set_env("BOT_CHAT_RATE_LIMIT_PER_MINUTE", "0");
+            let err = AppConfig::load().unwrap_err();
+            assert!(err.to_string().contains("bot_chat_rate_limit_per_minute"));
+            jail.set_env("BOT_CHAT_RATE_LIMIT_PER_MINUTE", "1");
+            assert_eq!(AppConfig::load()?.bot_chat_rate_limit_per_minute, 1);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn race_readiness_offsets_are_sorted_and_must_be_positive() {
+        let normalized = |offsets: &[i64]| {
+            let mut offsets = offsets.to_vec();
+            normalize_race_readiness_offsets(&mut offsets).map(|_| offsets)
+        };
+
+        let defaults = AppConfig::default().race_readiness_offsets_days;
+        assert_eq!(normalized(&defaults), Ok(defaults));
+        assert_eq!(normalized(&[]), Ok(vec![]));
+        assert_eq!(normalized(&[2, 7, 14]), Ok(vec![14, 7, 2]));
+        assert_eq!(normalized(&[7, 14, 7, 1]), Ok(vec![14, 7, 1]));
+        assert!(normalized(&[7, 0]).is_err());
+        assert!(normalized(&[-3, 7]).is_err());
+    }
+
+    #[test]
+    #[allow(clippy::result_large_err)]
+    fn legacy_cache_ttl_variable_is_still_honored() {
+        let figment = || {
+            figment::Figment::from(figment::providers::Serialized::defaults(
+                AppConfig::default(),
+            ))
+            .merge(legacy_cache_ttl_env())
+        };
+        let ttl = |figment: figment::Figment| {
+            figment
+                .extract::<AppConfig>()
+                .unwrap()
+                .garmin_cache_ttl_secs
+        };
+
+        // The jail restores the environment when it ends, even if an assertion fails.
+        figment::Jail::expect_with(|jail| {
+            assert_eq!(ttl(figment()), DEFAULT_GARMIN_CACHE_TTL_SECS);
+            jail.set_env(LEGACY_CACHE_TTL_VAR, "600");
+            assert_eq!(ttl(figment()), 600);
+            // An explicit garmin_cache_ttl_secs, merged later, still wins.
+            let explicit = figment().merge(("garmin_cache_ttl_secs", 120));
+            assert_eq!(ttl(explicit), 120);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn low_body_battery_cutoff_follows_the_hard_session_floor() {
+        let thresholds: crate::coaching::IntensityThresholds = serde_json::from_value(
+            serde_json::json!({ "low_body_battery": 5, "low_sleep_score": 40 }),
+        )
+        .unwrap();
+        let config = AppConfig {
+            min_body_battery_for_hard: 35,
+            intensity_thresholds: thresholds,
+            ..AppConfig::default()
+        };
+
+        let effective = config.effective_intensity_thresholds();
+        assert_eq!(effective.low_body_battery, 35);
+        assert_eq!(effective.low_sleep_score, 40);
+        assert_eq!(
+            AppConfig::default().effective_intensity_thresholds(),
+            crate::coaching::IntensityThresholds::default()
+        );
+    }
 }
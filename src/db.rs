@@ -3,6 +3,14 @@ use rusqlite::{params, Connection, Result};
 
 const MAX_CHAT_HISTORY: i64 = 200;
 const MAX_CHAT_MESSAGE_LEN: usize = 65_536;
+/// Coach briefs (plan generations and dashboard chat exchanges) kept in `coach_briefs`.
+const MAX_COACH_BRIEFS: i64 = 50;
+/// `kv_store` key naming the batch `/undo` acts on; cleared once that batch is undone.
+const LAST_GENERATION_BATCH_KEY: &str = "last_generation_batch";
+/// `kv_store` key claimed while a generation runs, by any process sharing the
+/// database. Its value is the owner's token and `updated_at` the start in unix
+/// milliseconds.
+const GENERATION_MARKER_KEY: &str = "generation_in_progress";
 
 pub type TrendHistoryItem = (f64, i32, String);
 pub type ProgressionHistoryEntry = (String, f64, i32, String, Vec<TrendHistoryItem>);
@@ -20,6 +28,63 @@ pub struct RecoveryHistoryEntry {
     pub rhr: Option<i32>,
 }
 
+/// Best set of an exercise in a window that beat every earlier recorded weight.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct PersonalRecord {
+    pub exercise_name: String,
+    pub weight: f64,
+    pub reps: i32,
+    pub date: String,
+    pub previous_best: f64,
+}
+
+/// A single recorded working set, with an Epley estimated one-rep max.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ExerciseSet {
+    pub date: String,
+    pub weight: f64,
+    pub reps: i32,
+    pub set_index: i32,
+    pub estimated_1rm: f64,
+}
+
+/// Epley formula: `weight * (1 + reps / 30)`; a single rep is the 1RM itself.
+pub fn estimated_1rm(weight: f64, reps: i32) -> f64 {
+    if reps <= 1 {
+        return weight;
+    }
+    weight * (1.0 + reps as f64 / 30.0)
+}
+
+/// Undelivered Signal messages kept for a later retry; the oldest are dropped beyond this.
+pub const MAX_PENDING_MESSAGES: i64 = 100;
+
+/// Exercise name resolutions kept in `exercise_resolution_log`; the oldest are dropped beyond this.
+pub const MAX_EXERCISE_RESOLUTIONS: i64 = 1000;
+
+/// A recorded `WorkoutBuilder::resolve_exercise_logged` decision.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ExerciseResolutionEntry {
+    pub input: String,
+    pub matched_key: Option<String>,
+    pub distance: Option<i64>,
+    /// `exact`, `manual`, `fuzzy`, `fallback` or `none`.
+    pub method: String,
+    /// Seconds since the Unix epoch.
+    pub created_at: i64,
+}
+
+/// Rows removed per table by `Database::vacuum_old_data`.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct PruneReport {
+    pub activity_analyses: usize,
+    pub exercise_resolutions: usize,
+    pub generated_workouts: usize,
+    /// Expired `pred_dur:` / `upcoming_analysis:` / `activity_temps:` entries in `kv_store`.
+    pub cache_entries: usize,
+    pub exercise_sets: usize,
+}
+
 pub struct Database {
     conn: Connection,
 }
@@ -111,6 +176,49 @@ impl Database {
             [],
         )?;
 
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS rpe_logs (
+                id INTEGER PRIMARY KEY,
+                date TEXT NOT NULL,
+                exercise TEXT NOT NULL,
+                rpe INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS pending_messages (
+                id INTEGER PRIMARY KEY,
+                recipient TEXT NOT NULL,
+                body TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS exercise_resolution_log (
+                id INTEGER PRIMARY KEY,
+                input TEXT NOT NULL,
+                matched_key TEXT,
+                distance INTEGER,
+                method TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS generated_workouts (
+                id INTEGER PRIMARY KEY,
+                batch_id TEXT NOT NULL,
+                workout_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                scheduled_date TEXT
+            )",
+            [],
+        )?;
+
         // Migrations: add max columns for existing databases
         let _ = conn.execute(
             "ALTER TABLE recovery_metrics_history ADD COLUMN body_battery_max INTEGER",
@@ -124,6 +232,11 @@ impl Database {
             "ALTER TABLE recovery_metrics_history ADD COLUMN training_readiness_max INTEGER",
             [],
         );
+        // Dashboard chat exchanges share `coach_briefs` with plan generations.
+        let _ = conn.execute(
+            "ALTER TABLE coach_briefs ADD COLUMN is_plan INTEGER NOT NULL DEFAULT 1",
+            [],
+        );
 
         Ok(Database { conn })
     }
@@ -154,6 +267,275 @@ impl Database {
         Ok(None)
     }
 
+    pub fn log_rpe(&self, date: &str, exercise: &str, rpe: i32) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO rpe_logs (date, exercise, rpe) VALUES (?1, ?2, ?3)",
+            params![date, exercise, rpe],
+        )?;
+        Ok(())
+    }
+
+    /// Returns `(date, exercise, rpe)` entries logged within the last `days` days, oldest first.
+    pub fn get_recent_rpe(&self, days: u32) -> Result<Vec<(String, String, i32)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT date, exercise, rpe FROM rpe_logs
+             WHERE date >= date('now', ?1)
+             ORDER BY date ASC, id ASC",
+        )?;
+        let modifier = format!("-{} days", days);
+        let mut rows = stmt.query(params![modifier])?;
+        let mut logs = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            logs.push((row.get(0)?, row.get(1)?, row.get(2)?));
+        }
+        Ok(logs)
+    }
+
+    /// Remembers a workout uploaded by the generation run `batch_id` so the run can be undone.
+    pub fn record_generated_workout(
+        &self,
+        batch_id: &str,
+        workout_id: i64,
+        name: &str,
+        scheduled_date: Option<&str>,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO generated_workouts (batch_id, workout_id, name, scheduled_date)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![batch_id, workout_id, name, scheduled_date],
+        )?;
+        self.set_kv(LAST_GENERATION_BATCH_KEY, batch_id)
+    }
+
+    /// The most recent generation batch and the Garmin workout ids it created;
+    /// `None` once that batch has been undone, so older batches are never picked.
+    pub fn get_last_generation_batch(&self) -> Result<Option<(String, Vec<i64>)>> {
+        let Some(batch_id) = self.get_kv(LAST_GENERATION_BATCH_KEY)? else {
+            return Ok(None);
+        };
+
+        let mut stmt = self.conn.prepare(
+            "SELECT workout_id FROM generated_workouts WHERE batch_id = ?1 ORDER BY id ASC",
+        )?;
+        let ids = stmt
+            .query_map(params![batch_id], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<i64>>>()?;
+        if ids.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some((batch_id, ids)))
+    }
+
+    /// Forgets a recorded workout once it is gone from Garmin, so `/undo` never reports it again.
+    pub fn forget_generated_workout(&self, workout_id: i64) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM generated_workouts WHERE workout_id = ?1",
+            params![workout_id],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_generation_batch(&self, batch_id: &str) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM generated_workouts WHERE batch_id = ?1",
+            params![batch_id],
+        )?;
+        self.conn.execute(
+            "DELETE FROM kv_store WHERE key = ?1 AND value = ?2",
+            params![LAST_GENERATION_BATCH_KEY, batch_id],
+        )?;
+        Ok(())
+    }
+
+    /// Claims the generation marker for `token` in one statement, unless another
+    /// claim started after `now_ms - stale_after_ms`. Returns whether it was claimed.
+    pub fn try_claim_generation(
+        &self,
+        token: &str,
+        now_ms: u64,
+        stale_after_ms: u64,
+    ) -> Result<bool> {
+        let claimed = self.conn.execute(
+            "INSERT INTO kv_store (key, value, updated_at)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(key) DO UPDATE SET
+             value = excluded.value,
+             updated_at = excluded.updated_at
+             WHERE kv_store.updated_at <= ?4",
+            params![
+                GENERATION_MARKER_KEY,
+                token,
+                now_ms,
+                now_ms.saturating_sub(stale_after_ms)
+            ],
+        )?;
+        Ok(claimed == 1)
+    }
+
+    /// Clears the generation marker if `token` still owns it.
+    pub fn release_generation(&self, token: &str) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM kv_store WHERE key = ?1 AND value = ?2",
+            params![GENERATION_MARKER_KEY, token],
+        )?;
+        Ok(())
+    }
+
+    pub fn queue_pending_message(&self, recipient: &str, body: &str) -> Result<()> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.conn.execute(
+            "INSERT INTO pending_messages (recipient, body, created_at) VALUES (?1, ?2, ?3)",
+            params![recipient, body, now],
+        )?;
+        self.conn.execute(
+            "DELETE FROM pending_messages WHERE id NOT IN (
+                SELECT id FROM pending_messages ORDER BY id DESC LIMIT ?1
+             )",
+            params![MAX_PENDING_MESSAGES],
+        )?;
+        Ok(())
+    }
+
+    /// Returns `(id, recipient, body)` for queued messages, oldest first.
+    pub fn get_pending_messages(&self, limit: u32) -> Result<Vec<(i64, String, String)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, recipient, body FROM pending_messages ORDER BY id ASC LIMIT ?1")?;
+        let messages = stmt
+            .query_map(params![limit], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(messages)
+    }
+
+    pub fn delete_pending_message(&self, id: i64) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM pending_messages WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    pub fn count_pending_messages(&self) -> Result<i64> {
+        let count = self
+            .conn
+            .query_row("SELECT COUNT(*) FROM pending_messages", [], |row| {
+                row.get(0)
+            })?;
+        Ok(count)
+    }
+
+    pub fn log_exercise_resolutions(
+        &self,
+        resolutions: &[crate::workout_builder::ExerciseResolution],
+    ) -> Result<()> {
+        if resolutions.is_empty() {
+            return Ok(());
+        }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        for resolution in resolutions {
+            self.conn.execute(
+                "INSERT INTO exercise_resolution_log (input, matched_key, distance, method, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    resolution.input,
+                    resolution.matched_key,
+                    resolution.distance.map(|d| d as i64),
+                    resolution.method.as_str(),
+                    now
+                ],
+            )?;
+        }
+        self.conn.execute(
+            "DELETE FROM exercise_resolution_log WHERE id NOT IN (
+                SELECT id FROM exercise_resolution_log ORDER BY id DESC LIMIT ?1
+             )",
+            params![MAX_EXERCISE_RESOLUTIONS],
+        )?;
+        Ok(())
+    }
+
+    /// The most recent resolutions, newest first.
+    pub fn get_exercise_resolutions(&self, limit: u32) -> Result<Vec<ExerciseResolutionEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT input, matched_key, distance, method, created_at
+             FROM exercise_resolution_log ORDER BY id DESC LIMIT ?1",
+        )?;
+        let entries = stmt
+            .query_map(params![limit], |row| {
+                Ok(ExerciseResolutionEntry {
+                    input: row.get(0)?,
+                    matched_key: row.get(1)?,
+                    distance: row.get(2)?,
+                    method: row.get(3)?,
+                    created_at: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    /// Empties the resolution log; returns the number of removed rows.
+    pub fn clear_exercise_resolutions(&self) -> Result<usize> {
+        self.conn.execute("DELETE FROM exercise_resolution_log", [])
+    }
+
+    /// Prunes analyses, resolution log entries, generated-workout records and
+    /// AI cache entries dated before `today - retention_days`, then runs `VACUUM`.
+    /// `exercise_history` feeds long-term PRs and is only pruned when
+    /// `exercise_history_days` is set; 0 disables either window.
+    pub fn vacuum_old_data(
+        &self,
+        retention_days: u32,
+        exercise_history_days: u32,
+        today: chrono::NaiveDate,
+    ) -> Result<PruneReport> {
+        let cutoff = |days: u32| {
+            let day = today - chrono::Duration::days(days as i64);
+            let secs = day.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp();
+            (day.format("%Y-%m-%d").to_string(), secs)
+        };
+
+        let mut report = PruneReport::default();
+        if retention_days > 0 {
+            let (date, secs) = cutoff(retention_days);
+            report.activity_analyses = self.conn.execute(
+                "DELETE FROM activity_analysis WHERE date < ?1",
+                params![date],
+            )?;
+            report.exercise_resolutions = self.conn.execute(
+                "DELETE FROM exercise_resolution_log WHERE created_at < ?1",
+                params![secs],
+            )?;
+            report.generated_workouts = self.conn.execute(
+                "DELETE FROM generated_workouts WHERE scheduled_date < ?1",
+                params![date],
+            )?;
+            report.cache_entries = self.conn.execute(
+                "DELETE FROM kv_store
+                 WHERE (key LIKE 'pred_dur:%' OR key LIKE 'upcoming_analysis:%'
+                        OR key LIKE 'activity_temps:%')
+                 AND updated_at < ?1",
+                params![secs],
+            )?;
+        }
+        if exercise_history_days > 0 {
+            let (date, _) = cutoff(exercise_history_days);
+            report.exercise_sets = self.conn.execute(
+                "DELETE FROM exercise_history WHERE date < ?1",
+                params![date],
+            )?;
+        }
+        self.conn.execute("VACUUM", [])?;
+        Ok(report)
+    }
+
     pub fn clear_ai_chat(&self) -> Result<()> {
         self.conn.execute("DELETE FROM ai_chats", [])?;
         Ok(())
@@ -202,7 +584,20 @@ impl Database {
         Ok(history)
     }
 
+    /// Stores a plan generation: the brief sent to Gemini and its plan response.
+    /// The latest one is fed back into the next brief as coaching memory.
     pub fn add_coach_brief(&self, prompt: &str, response: &str) -> Result<()> {
+        self.insert_coach_brief(prompt, response, true)
+    }
+
+    /// Stores a dashboard chat exchange. It continues the thread started by the
+    /// last plan (see `get_coach_briefs`) but is never treated as a plan. The
+    /// Signal bot's free-text chat lives separately in `ai_chats`.
+    pub fn add_chat_exchange(&self, prompt: &str, response: &str) -> Result<()> {
+        self.insert_coach_brief(prompt, response, false)
+    }
+
+    fn insert_coach_brief(&self, prompt: &str, response: &str, is_plan: bool) -> Result<()> {
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .map(|d| d.as_secs())
@@ -212,38 +607,41 @@ impl Database {
         let safe_response: String = response.chars().take(MAX_CHAT_MESSAGE_LEN).collect();
 
         self.conn.execute(
-            "INSERT INTO coach_briefs (created_at, prompt, response) VALUES (?1, ?2, ?3)",
-            params![now, safe_prompt, safe_response],
+            "INSERT INTO coach_briefs (created_at, prompt, response, is_plan) VALUES (?1, ?2, ?3, ?4)",
+            params![now, safe_prompt, safe_response, is_plan],
         )?;
 
-        // Keep only top 50 briefs to avoid massive db bloat since they are huge
+        // Keep only the latest briefs to avoid massive db bloat since they are huge
         self.conn.execute(
             "DELETE FROM coach_briefs 
              WHERE id NOT IN (
-                SELECT id FROM coach_briefs ORDER BY id DESC LIMIT 50
+                SELECT id FROM coach_briefs ORDER BY id DESC LIMIT ?1
              )",
-            [],
+            params![MAX_COACH_BRIEFS],
         )?;
 
         Ok(())
     }
 
-    pub fn clear_coach_briefs(&self) -> Result<()> {
-        self.conn.execute("DELETE FROM coach_briefs", [])?;
-        Ok(())
+    /// Empties the dashboard chat thread, plan generations included, and returns
+    /// how many briefs were removed. The Signal bot's `ai_chats` is left alone.
+    pub fn clear_coach_briefs(&self) -> Result<usize> {
+        self.conn.execute("DELETE FROM coach_briefs", [])
     }
 
+    /// The dashboard chat thread as `(prompt, response, created_at)`, oldest first:
+    /// plan generations and chat exchanges, bounded to the last `MAX_COACH_BRIEFS`.
     pub fn get_coach_briefs(&self) -> Result<Vec<(String, String, u64)>> {
         let mut stmt = self.conn.prepare(
             "SELECT prompt, response, created_at FROM (
                 SELECT id, prompt, response, created_at
                 FROM coach_briefs
                 ORDER BY id DESC
-                LIMIT 50
+                LIMIT ?1
              )
              ORDER BY id ASC",
         )?;
-        let mut rows = stmt.query([])?;
+        let mut rows = stmt.query(params![MAX_COACH_BRIEFS])?;
         let mut history = Vec::new();
 
         while let Some(row) = rows.next()? {
@@ -327,6 +725,40 @@ impl Database {
         Ok(history)
     }
 
+    pub fn get_exercise_sets(&self, category: &str) -> Result<Vec<ExerciseSet>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT date, weight, reps, set_index
+             FROM exercise_history
+             WHERE exercise_name = ?1
+             ORDER BY date ASC, set_index ASC",
+        )?;
+        let mut rows = stmt.query(params![category])?;
+        let mut sets = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            let weight: f64 = row.get(1)?;
+            let reps: i32 = row.get(2)?;
+            sets.push(ExerciseSet {
+                date: row.get(0)?,
+                weight,
+                reps,
+                set_index: row.get(3)?,
+                estimated_1rm: estimated_1rm(weight, reps),
+            });
+        }
+        Ok(sets)
+    }
+
+    pub fn get_exercise_names(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT exercise_name FROM exercise_history ORDER BY exercise_name ASC",
+        )?;
+        let names = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(names)
+    }
+
     pub fn get_progression_history_raw(&self) -> Result<Vec<ProgressionHistoryEntry>> {
         let mut stmt = self.conn.prepare(
             "SELECT exercise_name, weight, reps, date
@@ -382,6 +814,17 @@ impl Database {
         Ok(result)
     }
 
+    /// Proposed minus best recorded weight (kg) for `category`, or `None` when
+    /// the exercise has no weighted history yet.
+    pub fn progression_delta(&self, category: &str, proposed_weight: f64) -> Result<Option<f64>> {
+        let best: Option<f64> = self.conn.query_row(
+            "SELECT MAX(weight) FROM exercise_history WHERE exercise_name = ?1 AND weight > 0",
+            params![category],
+            |row| row.get(0),
+        )?;
+        Ok(best.map(|best| proposed_weight - best))
+    }
+
     pub fn get_recent_muscle_heatmap(
         &self,
         days: u32,
@@ -407,47 +850,10 @@ impl Database {
             let frequency: i32 = row.get(1)?;
 
             // Map the exercise category back to react-body-highlighter muscles
-            let muscles = match name.as_str() {
-                "BENCH_PRESS" | "PUSH_UP" => vec![
-                    "chest".to_string(),
-                    "triceps".to_string(),
-                    "front-deltoids".to_string(),
-                ],
-                "ROW" => vec![
-                    "upper-back".to_string(),
-                    "lower-back".to_string(),
-                    "biceps".to_string(),
-                    "back-deltoids".to_string(),
-                ],
-                "PULL_UP" | "PULL_DOWN" => vec![
-                    "upper-back".to_string(),
-                    "biceps".to_string(),
-                    "back-deltoids".to_string(),
-                ],
-                "SQUAT" | "LUNGE" => vec![
-                    "quadriceps".to_string(),
-                    "gluteal".to_string(),
-                    "hamstring".to_string(),
-                    "calves".to_string(),
-                ],
-                "DEADLIFT" => vec![
-                    "hamstring".to_string(),
-                    "gluteal".to_string(),
-                    "lower-back".to_string(),
-                    "forearm".to_string(),
-                    "trapezius".to_string(),
-                ],
-                "CALF_RAISE" => vec!["calves".to_string()],
-                "SHOULDER_PRESS" | "FRONT_RAISE" | "LATERAL_RAISE" => vec![
-                    "front-deltoids".to_string(),
-                    "back-deltoids".to_string(),
-                    "triceps".to_string(),
-                ],
-                "TRICEPS_EXTENSION" => vec!["triceps".to_string()],
-                "BICEP_CURL" => vec!["biceps".to_string()],
-                "CORE" | "PLANK" | "SIT_UP" => vec!["abs".to_string(), "obliques".to_string()],
-                _ => vec![],
-            };
+            let muscles: Vec<String> = crate::models::muscle_groups_for(&name)
+                .into_iter()
+                .map(str::to_string)
+                .collect();
 
             if !muscles.is_empty() {
                 heatmap.push(crate::models::ExerciseMuscleMap {
@@ -461,19 +867,37 @@ impl Database {
         Ok(heatmap)
     }
 
-    pub fn get_garmin_cache(&self) -> Result<Option<(String, u64)>> {
+    pub fn get_kv(&self, key: &str) -> Result<Option<String>> {
         let mut stmt = self
             .conn
-            .prepare("SELECT value, updated_at FROM kv_store WHERE key = 'garmin_cache'")?;
-        let mut rows = stmt.query([])?;
+            .prepare("SELECT value FROM kv_store WHERE key = ?1")?;
+        let mut rows = stmt.query(params![key])?;
         if let Some(row) = rows.next()? {
-            let value: String = row.get(0)?;
-            let updated_at: u64 = row.get(1)?;
-            return Ok(Some((value, updated_at)));
+            return Ok(Some(row.get(0)?));
         }
         Ok(None)
     }
 
+    pub fn set_kv(&self, key: &str, value: &str) -> Result<()> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        self.conn.execute(
+            "INSERT INTO kv_store (key, value, updated_at)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(key) DO UPDATE SET
+             value = excluded.value,
+             updated_at = excluded.updated_at",
+            params![key, value, now],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_garmin_cache(&self) -> Result<Option<(String, u64)>> {
+        self.get_kv_with_timestamp("garmin_cache")
+    }
+
     pub fn set_garmin_cache(&self, value: &str) -> Result<()> {
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -496,14 +920,102 @@ impl Database {
         Ok(())
     }
 
-    pub fn get_predicted_duration(&self, cache_key: &str) -> Result<Option<i32>> {
+    /// The last complete Garmin fetch with its Unix timestamp. Unlike the TTL
+    /// cache it is never cleared, so it survives a refresh that fails.
+    pub fn get_garmin_last_good(&self) -> Result<Option<(String, u64)>> {
+        self.get_kv_with_timestamp("garmin_last_good")
+    }
+
+    pub fn set_garmin_last_good(&self, value: &str) -> Result<()> {
+        self.set_kv("garmin_last_good", value)
+    }
+
+    /// The Garmin display name last seen in a profile fetch, used when the
+    /// profile endpoint fails.
+    pub fn get_display_name(&self) -> Result<Option<String>> {
+        Ok(self
+            .get_kv("garmin_display_name")?
+            .filter(|name| !name.is_empty()))
+    }
+
+    pub fn set_display_name(&self, name: &str) -> Result<()> {
+        self.set_kv("garmin_display_name", name)
+    }
+
+    /// Temperatures (min, max) read from an activity's detail payload, so it is
+    /// fetched once per activity; `None` when the activity was never looked up.
+    pub fn get_activity_temperatures(
+        &self,
+        activity_id: i64,
+    ) -> Result<Option<(Option<f64>, Option<f64>)>> {
+        Ok(self
+            .get_kv(&format!("activity_temps:{}", activity_id))?
+            .and_then(|value| serde_json::from_str(&value).ok()))
+    }
+
+    pub fn set_activity_temperatures(
+        &self,
+        activity_id: i64,
+        min: Option<f64>,
+        max: Option<f64>,
+    ) -> Result<()> {
+        self.set_kv(
+            &format!("activity_temps:{}", activity_id),
+            &serde_json::json!([min, max]).to_string(),
+        )
+    }
+
+    /// Records `body_hash` as the latest Signal broadcast unless the same hash
+    /// was recorded less than `window_secs` ago, in which case it returns false.
+    pub fn record_broadcast(&self, body_hash: &str, window_secs: u64) -> Result<bool> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        if let Some((last_hash, sent_at)) = self.get_kv_with_timestamp("last_broadcast")? {
+            if last_hash == body_hash && now.saturating_sub(sent_at) < window_secs {
+                return Ok(false);
+            }
+        }
+        self.set_kv("last_broadcast", body_hash)?;
+        Ok(true)
+    }
+
+    fn get_kv_with_timestamp(&self, key: &str) -> Result<Option<(String, u64)>> {
         let mut stmt = self
             .conn
-            .prepare("SELECT value FROM kv_store WHERE key = ?1")?;
+            .prepare("SELECT value, updated_at FROM kv_store WHERE key = ?1")?;
+        let mut rows = stmt.query(params![key])?;
+        if let Some(row) = rows.next()? {
+            let value: String = row.get(0)?;
+            let updated_at: u64 = row.get(1)?;
+            return Ok(Some((value, updated_at)));
+        }
+        Ok(None)
+    }
+
+    /// Cached AI duration prediction for `cache_key`. Entries older than
+    /// `max_age_secs` count as a miss so Gemini is asked again; `None` never expires.
+    pub fn get_predicted_duration(
+        &self,
+        cache_key: &str,
+        max_age_secs: Option<u64>,
+    ) -> Result<Option<i32>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT value, updated_at FROM kv_store WHERE key = ?1")?;
         let key = format!("pred_dur:{}", cache_key);
         let mut rows = stmt.query([key])?;
         if let Some(row) = rows.next()? {
             let value: String = row.get(0)?;
+            let updated_at: u64 = row.get(1)?;
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default();
+            if max_age_secs.is_some_and(|max_age| now.saturating_sub(updated_at) > max_age) {
+                return Ok(None);
+            }
             if let Ok(duration) = value.parse::<i32>() {
                 return Ok(Some(duration));
             }
@@ -567,6 +1079,14 @@ impl Database {
         Ok(exists)
     }
 
+    /// Whether any activity has been analyzed yet (false on a fresh database).
+    pub fn has_activity_analyses(&self) -> Result<bool> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT 1 FROM activity_analysis LIMIT 1")?;
+        stmt.exists([])
+    }
+
     pub fn get_activity_analysis(&self, activity_id: i64) -> Result<Option<String>> {
         let mut stmt = self
             .conn
@@ -593,6 +1113,29 @@ impl Database {
         Ok(())
     }
 
+    /// Stores `summary`, overwriting any earlier analysis of the activity.
+    pub fn replace_activity_analysis(
+        &self,
+        activity_id: i64,
+        date: &str,
+        summary: &str,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO activity_analysis (activity_id, date, summary) VALUES (?1, ?2, ?3)",
+            params![activity_id, date, summary],
+        )?;
+        Ok(())
+    }
+
+    /// Drops a stored analysis; returns whether one existed.
+    pub fn delete_activity_analysis(&self, activity_id: i64) -> Result<bool> {
+        let removed = self.conn.execute(
+            "DELETE FROM activity_analysis WHERE activity_id = ?1",
+            params![activity_id],
+        )?;
+        Ok(removed > 0)
+    }
+
     pub fn get_recent_activity_analyses(&self, days: u32) -> Result<Vec<(String, String)>> {
         let mut stmt = self.conn.prepare(
             "SELECT date, summary FROM activity_analysis 
@@ -613,9 +1156,9 @@ impl Database {
 
     pub fn save_recovery_metrics(
         &self,
+        today: &str,
         metrics: &crate::models::GarminRecoveryMetrics,
     ) -> Result<()> {
-        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
         let rhr = metrics.rhr_trend.last().copied();
 
         self.conn.execute(
@@ -650,9 +1193,9 @@ impl Database {
 
     /// Returns the last coach brief's AI response text (the previous plan), if any.
     pub fn get_last_coach_plan_response(&self) -> Result<Option<String>> {
-        let mut stmt = self
-            .conn
-            .prepare("SELECT response FROM coach_briefs ORDER BY id DESC LIMIT 1")?;
+        let mut stmt = self.conn.prepare(
+            "SELECT response FROM coach_briefs WHERE is_plan = 1 ORDER BY id DESC LIMIT 1",
+        )?;
         let mut rows = stmt.query([])?;
         if let Some(row) = rows.next()? {
             let response: String = row.get(0)?;
@@ -720,6 +1263,42 @@ impl Database {
             .collect())
     }
 
+    /// Weight PRs set on or after `since`: exercises whose best set in that window
+    /// is heavier than anything logged before it. First-ever lifts don't count.
+    pub fn get_personal_records_since(&self, since: &str) -> Result<Vec<PersonalRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT r.exercise_name, r.weight, r.reps, r.date, p.previous_best
+             FROM (
+                SELECT exercise_name, weight, reps, date,
+                       ROW_NUMBER() OVER (PARTITION BY exercise_name ORDER BY weight DESC, reps DESC, date ASC) AS rn
+                FROM exercise_history
+                WHERE date >= ?1
+             ) r
+             JOIN (
+                SELECT exercise_name, MAX(weight) AS previous_best
+                FROM exercise_history
+                WHERE date < ?1
+                GROUP BY exercise_name
+             ) p ON p.exercise_name = r.exercise_name
+             WHERE r.rn = 1 AND r.weight > p.previous_best
+             ORDER BY r.date ASC, r.exercise_name ASC",
+        )?;
+
+        let mut rows = stmt.query(params![since])?;
+        let mut records = Vec::new();
+        while let Some(row) = rows.next()? {
+            records.push(PersonalRecord {
+                exercise_name: row.get(0)?,
+                weight: row.get(1)?,
+                reps: row.get(2)?,
+                date: row.get(3)?,
+                previous_best: row.get(4)?,
+            });
+        }
+
+        Ok(records)
+    }
+
     pub fn get_recovery_history(&self, days: u32) -> Result<Vec<RecoveryHistoryEntry>> {
         let mut stmt = self.conn.prepare(
             "SELECT date, COALESCE(body_battery_max, body_battery), COALESCE(sleep_score_max, sleep_score), COALESCE(training_readiness_max, training_readiness), hrv_last_night_avg, hrv_status, rhr
@@ -747,3 +1326,425 @@ impl Database {
         Ok(history)
     }
 }
+
+/// A fresh in-memory database with the full schema, for tests.
+#[cfg(test)]
+pub(crate) fn memory_db() -> Database {
+    Database::new(&crate::config::AppConfig {
+        database_url: ":memory:".to_string(),
+        ..crate::config::AppConfig::default()
+    })
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{memory_db, PruneReport};
+    use rusqlite::params;
+
+    #[test]
+    fn personal_records_only_count_lifts_beating_earlier_bests() {
+        let db = memory_db();
+        let sets = [
+            (1, "2026-03-01 08:00:00", "BENCH_PRESS", 80.0, 5),
+            (1, "2026-03-01 08:00:00", "SQUAT", 100.0, 5),
+            (2, "2026-03-10 08:00:00", "BENCH_PRESS", 82.5, 3),
+            (2, "2026-03-10 08:00:00", "BENCH_PRESS", 85.0, 1),
+            (2, "2026-03-10 08:00:00", "SQUAT", 100.0, 6),
+            (2, "2026-03-10 08:00:00", "DEADLIFT", 140.0, 5),
+        ];
+        for (i, (activity_id, date, name, weight, reps)) in sets.iter().enumerate() {
+            db.conn
+                .execute(
+                    "INSERT INTO exercise_history (activity_id, date, exercise_name, weight, reps, set_index)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    rusqlite::params![activity_id, date, name, weight, reps, i as i32],
+                )
+                .unwrap();
+        }
+
+        let records = db.get_personal_records_since("2026-03-07").unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].exercise_name, "BENCH_PRESS");
+        assert_eq!(records[0].weight, 85.0);
+        assert_eq!(records[0].reps, 1);
+        assert_eq!(records[0].previous_best, 80.0);
+    }
+
+    #[test]
+    fn vacuum_prunes_rows_before_the_retention_cutoff() {
+        let db = memory_db();
+        let today = chrono::NaiveDate::from_ymd_opt(2026, 3, 31).unwrap();
+        // 30 days before today: the cutoff day itself is kept.
+        let cutoff_secs = 1_772_323_200; // 2026-03-01T00:00:00Z
+        for (id, date) in [(1, "2026-02-28 20:00:00"), (2, "2026-03-01 06:00:00")] {
+            db.conn
+                .execute(
+                    "INSERT INTO activity_analysis (activity_id, date, summary) VALUES (?1, ?2, 'ok')",
+                    params![id, date],
+                )
+                .unwrap();
+            db.conn
+                .execute(
+                    "INSERT INTO exercise_history (activity_id, date, exercise_name, weight, reps, set_index)
+                     VALUES (?1, ?2, 'BENCH_PRESS', 80, 5, 0)",
+                    params![id, date],
+                )
+                .unwrap();
+        }
+        for created_at in [cutoff_secs - 1, cutoff_secs] {
+            db.conn
+                .execute(
+                    "INSERT INTO exercise_resolution_log (input, method, created_at) VALUES ('x', 'none', ?1)",
+                    params![created_at],
+                )
+                .unwrap();
+        }
+        for (key, updated_at) in [
+            ("pred_dur:Old Run|running", cutoff_secs - 1),
+            ("pred_dur:New Run|running", cutoff_secs),
+            ("upcoming_analysis:old", cutoff_secs - 1),
+            ("activity_temps:1", cutoff_secs - 1),
+            ("activity_temps:2", cutoff_secs),
+            ("garmin_last_good", cutoff_secs - 1),
+        ] {
+            db.conn
+                .execute(
+                    "INSERT INTO kv_store (key, value, updated_at) VALUES (?1, '1', ?2)",
+                    params![key, updated_at],
+                )
+                .unwrap();
+        }
+        db.record_generated_workout("old", 1, "A", Some("2026-02-28"))
+            .unwrap();
+        db.record_generated_workout("new", 2, "B", Some("2026-03-01"))
+            .unwrap();
+
+        let report = db.vacuum_old_data(30, 0, today).unwrap();
+        assert_eq!(
+            report,
+            PruneReport {
+                activity_analyses: 1,
+                exercise_resolutions: 1,
+                generated_workouts: 1,
+                cache_entries: 3,
+                exercise_sets: 0,
+            }
+        );
+        assert!(db.is_activity_analyzed(2).unwrap());
+        assert!(!db.is_activity_analyzed(1).unwrap());
+        assert!(db.get_kv("garmin_last_good").unwrap().is_some());
+        assert!(db.get_kv("activity_temps:1").unwrap().is_none());
+        assert!(db.get_kv("activity_temps:2").unwrap().is_some());
+        assert_eq!(
+            db.get_predicted_duration("New Run|running", None).unwrap(),
+            Some(1)
+        );
+        // PR history survives the default window and needs its own, longer one.
+        assert_eq!(db.get_progression_history_raw().unwrap()[0].4.len(), 2);
+
+        let report = db.vacuum_old_data(30, 30, today).unwrap();
+        assert_eq!(report.exercise_sets, 1);
+        assert_eq!(report.activity_analyses, 0);
+        assert_eq!(db.get_progression_history_raw().unwrap()[0].4.len(), 1);
+        assert_eq!(
+            db.vacuum_old_data(0, 0, today).unwrap(),
+            PruneReport::default()
+        );
+    }
+
+    #[test]
+    fn progression_delta_compares_against_best_recorded_weight() {
+        let db = memory_db();
+        for (i, (name, weight)) in [("BENCH_PRESS", 80.0), ("BENCH_PRESS", 85.0), ("PLANK", 0.0)]
+            .iter()
+            .enumerate()
+        {
+            db.conn
+                .execute(
+                    "INSERT INTO exercise_history (activity_id, date, exercise_name, weight, reps, set_index)
+                     VALUES (1, '2026-03-01 08:00:00', ?1, ?2, 5, ?3)",
+                    rusqlite::params![name, weight, i as i32],
+                )
+                .unwrap();
+        }
+
+        assert_eq!(
+            db.progression_delta("BENCH_PRESS", 87.5).unwrap(),
+            Some(2.5)
+        );
+        assert_eq!(
+            db.progression_delta("BENCH_PRESS", 80.0).unwrap(),
+            Some(-5.0)
+        );
+        assert_eq!(db.progression_delta("PLANK", 10.0).unwrap(), None);
+        assert_eq!(db.progression_delta("SQUAT", 100.0).unwrap(), None);
+    }
+
+    #[test]
+    fn exercise_resolutions_are_capped_newest_first_and_clearable() {
+        use crate::workout_builder::{ExerciseResolution, ResolutionMethod};
+
+        let db = memory_db();
+        let resolution = |input: &str, method| ExerciseResolution {
+            input: input.to_string(),
+            matched_key: None,
+            distance: None,
+            method,
+        };
+        let batch: Vec<_> = (0..super::MAX_EXERCISE_RESOLUTIONS + 5)
+            .map(|i| resolution(&format!("LIFT_{}", i), ResolutionMethod::Fallback))
+            .collect();
+        db.log_exercise_resolutions(&batch).unwrap();
+        db.log_exercise_resolutions(&[ExerciseResolution {
+            matched_key: Some("BARBELL_ROW".to_string()),
+            distance: Some(2),
+            ..resolution("Barbel Row", ResolutionMethod::Fuzzy)
+        }])
+        .unwrap();
+
+        let latest = db.get_exercise_resolutions(2).unwrap();
+        assert_eq!(latest[0].input, "Barbel Row");
+        assert_eq!(latest[0].matched_key.as_deref(), Some("BARBELL_ROW"));
+        assert_eq!(latest[0].distance, Some(2));
+        assert_eq!(latest[0].method, "fuzzy");
+        assert_eq!(
+            latest[1].input,
+            format!("LIFT_{}", super::MAX_EXERCISE_RESOLUTIONS + 4)
+        );
+
+        let all = db.get_exercise_resolutions(u32::MAX).unwrap();
+        assert_eq!(all.len() as i64, super::MAX_EXERCISE_RESOLUTIONS);
+        assert_eq!(
+            db.clear_exercise_resolutions().unwrap() as i64,
+            super::MAX_EXERCISE_RESOLUTIONS
+        );
+        assert!(db.get_exercise_resolutions(10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn recent_rpe_excludes_entries_outside_window() {
+        let db = memory_db();
+        let today = chrono::Local::now().date_naive();
+        let recent = (today - chrono::Duration::days(2)).to_string();
+        let old = (today - chrono::Duration::days(30)).to_string();
+        db.log_rpe(&old, "SQUAT", 9).unwrap();
+        db.log_rpe(&recent, "BENCH_PRESS", 8).unwrap();
+
+        let logs = db.get_recent_rpe(7).unwrap();
+        assert_eq!(logs, vec![(recent, "BENCH_PRESS".to_string(), 8)]);
+    }
+
+    #[test]
+    fn activity_analysis_is_saved_once_and_read_back() {
+        let db = memory_db();
+        assert!(!db.is_activity_analyzed(42).unwrap());
+        assert!(!db.has_activity_analyses().unwrap());
+        assert_eq!(db.get_activity_analysis(42).unwrap(), None);
+
+        db.save_activity_analysis(42, "2026-03-14 07:00:00", "Strong negative split.")
+            .unwrap();
+        db.save_activity_analysis(42, "2026-03-14 07:00:00", "Ignored duplicate.")
+            .unwrap();
+
+        assert!(db.is_activity_analyzed(42).unwrap());
+        assert!(db.has_activity_analyses().unwrap());
+        assert_eq!(
+            db.get_activity_analysis(42).unwrap().as_deref(),
+            Some("Strong negative split.")
+        );
+    }
+
+    #[test]
+    fn recent_activity_analyses_exclude_entries_outside_window() {
+        let db = memory_db();
+        let today = chrono::Utc::now().date_naive();
+        let recent = format!("{} 07:00:00", today - chrono::Duration::days(2));
+        let old = format!("{} 07:00:00", today - chrono::Duration::days(30));
+        db.save_activity_analysis(1, &old, "Old run.").unwrap();
+        db.save_activity_analysis(2, &recent, "Recent run.")
+            .unwrap();
+
+        let analyses = db.get_recent_activity_analyses(7).unwrap();
+        assert_eq!(analyses, vec![(recent, "Recent run.".to_string())]);
+    }
+
+    #[test]
+    fn chat_exchanges_join_the_thread_but_not_the_plan_memory() {
+        let db = memory_db();
+        assert!(db.get_coach_briefs().unwrap().is_empty());
+        assert_eq!(db.get_last_coach_plan_response().unwrap(), None);
+
+        db.add_coach_brief("brief", "plan").unwrap();
+        db.add_chat_exchange("Swap Friday?", "Sure, moved to Saturday.")
+            .unwrap();
+
+        let thread = db.get_coach_briefs().unwrap();
+        let pairs: Vec<_> = thread
+            .iter()
+            .map(|(p, r, _)| (p.as_str(), r.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("brief", "plan"),
+                ("Swap Friday?", "Sure, moved to Saturday.")
+            ]
+        );
+        assert_eq!(
+            db.get_last_coach_plan_response().unwrap().as_deref(),
+            Some("plan")
+        );
+    }
+
+    #[test]
+    fn display_name_is_remembered_and_blank_names_ignored() {
+        let db = memory_db();
+        assert_eq!(db.get_display_name().unwrap(), None);
+        db.set_display_name("runner42").unwrap();
+        assert_eq!(db.get_display_name().unwrap().as_deref(), Some("runner42"));
+        db.set_display_name("").unwrap();
+        assert_eq!(db.get_display_name().unwrap(), None);
+    }
+
+    #[test]
+    fn coach_briefs_are_bounded_to_the_latest() {
+        let db = memory_db();
+        for i in 0..(super::MAX_COACH_BRIEFS + 3) {
+            db.add_coach_brief(&format!("brief {}", i), "plan").unwrap();
+        }
+        let thread = db.get_coach_briefs().unwrap();
+        assert_eq!(thread.len() as i64, super::MAX_COACH_BRIEFS);
+        assert_eq!(thread[0].0, "brief 3");
+    }
+
+    #[test]
+    fn predicted_durations_hit_miss_overwrite_and_expire() {
+        let db = memory_db();
+        assert_eq!(
+            db.get_predicted_duration("Long Run|running", None).unwrap(),
+            None
+        );
+
+        db.set_predicted_duration("Long Run|running", 90).unwrap();
+        db.set_predicted_duration("|running", 45).unwrap();
+        assert_eq!(
+            db.get_predicted_duration("Long Run|running", None).unwrap(),
+            Some(90)
+        );
+        assert_eq!(
+            db.get_predicted_duration("|running", None).unwrap(),
+            Some(45)
+        );
+        assert_eq!(db.get_predicted_duration("Long Run|", None).unwrap(), None);
+
+        db.set_predicted_duration("Long Run|running", 100).unwrap();
+        assert_eq!(
+            db.get_predicted_duration("Long Run|running", Some(3600))
+                .unwrap(),
+            Some(100)
+        );
+
+        db.conn
+            .execute(
+                "UPDATE kv_store SET updated_at = updated_at - 7200 WHERE key = 'pred_dur:Long Run|running'",
+                [],
+            )
+            .unwrap();
+        assert_eq!(
+            db.get_predicted_duration("Long Run|running", Some(3600))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            db.get_predicted_duration("Long Run|running", None).unwrap(),
+            Some(100)
+        );
+    }
+
+    #[test]
+    fn exercise_sets_are_ordered_with_estimated_1rm() {
+        let db = memory_db();
+        let sets = [
+            (2, "2026-03-10 08:00:00", "BENCH_PRESS", 90.0, 1, 0),
+            (1, "2026-03-01 08:00:00", "BENCH_PRESS", 80.0, 6, 1),
+            (1, "2026-03-01 08:00:00", "BENCH_PRESS", 75.0, 10, 0),
+            (1, "2026-03-01 08:00:00", "SQUAT", 100.0, 5, 2),
+        ];
+        for (activity_id, date, name, weight, reps, set_index) in sets {
+            db.conn
+                .execute(
+                    "INSERT INTO exercise_history (activity_id, date, exercise_name, weight, reps, set_index)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    rusqlite::params![activity_id, date, name, weight, reps, set_index],
+                )
+                .unwrap();
+        }
+
+        let history = db.get_exercise_sets("BENCH_PRESS").unwrap();
+        let summary: Vec<_> = history
+            .iter()
+            .map(|s| (s.weight, s.reps, s.estimated_1rm))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![(75.0, 10, 100.0), (80.0, 6, 96.0), (90.0, 1, 90.0)]
+        );
+        assert_eq!(
+            db.get_exercise_names().unwrap(),
+            vec!["BENCH_PRESS", "SQUAT"]
+        );
+    }
+
+    #[test]
+    fn pending_messages_are_capped_and_flushed_oldest_first() {
+        let db = memory_db();
+        for i in 0..(super::MAX_PENDING_MESSAGES + 5) {
+            db.queue_pending_message("+100", &format!("message {}", i))
+                .unwrap();
+        }
+        assert_eq!(
+            db.count_pending_messages().unwrap(),
+            super::MAX_PENDING_MESSAGES
+        );
+
+        let pending = db.get_pending_messages(2).unwrap();
+        assert_eq!(pending[0].2, "message 5");
+        assert_eq!(pending[1].2, "message 6");
+
+        db.delete_pending_message(pending[0].0).unwrap();
+        assert_eq!(
+            db.count_pending_messages().unwrap(),
+            super::MAX_PENDING_MESSAGES - 1
+        );
+    }
+
+    #[test]
+    fn undoing_the_last_batch_leaves_older_batches_alone() {
+        let db = memory_db();
+        assert_eq!(db.get_last_generation_batch().unwrap(), None);
+
+        db.record_generated_workout("older", 1, "FJ-AI: Strength A", Some("2026-03-10"))
+            .unwrap();
+        db.record_generated_workout("latest", 2, "FJ-AI: Strength B", Some("2026-03-16"))
+            .unwrap();
+        db.record_generated_workout("latest", 3, "FJ-AI: Strength C", None)
+            .unwrap();
+
+        let (batch, ids) = db.get_last_generation_batch().unwrap().unwrap();
+        assert_eq!(batch, "latest");
+        assert_eq!(ids, vec![2, 3]);
+
+        db.delete_generation_batch(&batch).unwrap();
+        assert_eq!(db.get_last_generation_batch().unwrap(), None);
+        let older: i64 = db
+            .conn
+            .query_row(
+                "SELECT COUNT(*) FROM generated_workouts WHERE batch_id = 'older'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(older, 1);
+    }
+}
@@ -0,0 +1,90 @@
+use thiserror::Error;
+
+/// Crate-level error used at module boundaries (pipeline, bot commands, API
+/// handlers) so callers can react differently to e.g. an expired Garmin
+/// session vs. a Gemini outage. Leaf code keeps using `anyhow` and is
+/// converted with `AppError::from_garmin` / `AppError::from_ai`.
+#[derive(Debug, Error)]
+pub enum AppError {
+    /// Garmin rejected our tokens (401/403) or the OAuth2 refresh failed.
+    #[error("Garmin authentication failed: {0}")]
+    GarminAuth(String),
+    #[error("Garmin API error: {0}")]
+    GarminApi(String),
+    /// Garmin answered 404: the workout or schedule no longer exists.
+    #[error("Garmin resource not found: {0}")]
+    GarminNotFound(String),
+    #[error("AI request failed: {0}")]
+    Ai(String),
+    #[error("Database error: {0}")]
+    Db(#[from] rusqlite::Error),
+    #[error("Configuration error: {0}")]
+    Config(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+pub type AppResult<T> = std::result::Result<T, AppError>;
+
+impl AppError {
+    /// Converts an `anyhow` error from the Garmin layer, preserving a typed
+    /// `AppError` (such as `GarminAuth`) raised at the call site.
+    pub fn from_garmin(err: anyhow::Error) -> Self {
+        match err.downcast::<AppError>() {
+            Ok(app_err) => app_err,
+            Err(other) => AppError::GarminApi(format!("{:#}", other)),
+        }
+    }
+
+    pub fn from_ai(err: anyhow::Error) -> Self {
+        AppError::Ai(format!("{:#}", err))
+    }
+
+    /// True when an `anyhow` error wraps `AppError::GarminAuth`.
+    pub fn is_garmin_auth(err: &anyhow::Error) -> bool {
+        matches!(
+            err.downcast_ref::<AppError>(),
+            Some(AppError::GarminAuth(_))
+        )
+    }
+
+    /// True when an `anyhow` error wraps `AppError::GarminNotFound`.
+    pub fn is_garmin_not_found(err: &anyhow::Error) -> bool {
+        matches!(
+            err.downcast_ref::<AppError>(),
+            Some(AppError::GarminNotFound(_))
+        )
+    }
+
+    /// Message for Signal/API users; auth failures get an actionable hint.
+    pub fn user_message(&self) -> String {
+        match self {
+            AppError::GarminAuth(_) => {
+                "🔐 Your Garmin session has expired or is invalid. Please re-run `fitness_journal login` to sign in again.".to_string()
+            }
+            other => other.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AppError;
+
+    #[test]
+    fn from_garmin_preserves_auth_errors() {
+        let auth: anyhow::Error = AppError::GarminAuth("401".to_string()).into();
+        assert!(AppError::is_garmin_auth(&auth));
+        assert!(matches!(
+            AppError::from_garmin(auth),
+            AppError::GarminAuth(_)
+        ));
+
+        let other = anyhow::anyhow!("Garmin API GET returned 500");
+        assert!(!AppError::is_garmin_auth(&other));
+        assert!(matches!(
+            AppError::from_garmin(other),
+            AppError::GarminApi(_)
+        ));
+    }
+}
@@ -1,8 +1,60 @@
+use crate::error::AppError;
 use crate::models::*;
 use anyhow::{anyhow, Context, Result};
-use reqwest::{Client, Method, RequestBuilder};
+use lazy_static::lazy_static;
+use regex::Regex;
+use reqwest::{Client, Method, RequestBuilder, StatusCode};
 use serde::{Deserialize, Serialize};
-use tracing::{error, info};
+use std::path::{Path, PathBuf};
+use tracing::{debug, error, info};
+
+/// Production Garmin Connect API host.
+pub const GARMIN_CONNECT_API_BASE: &str = "https://connectapi.garmin.com";
+
+/// Expired or revoked tokens; retrying won't help, the user has to log in again.
+fn is_auth_failure(status: StatusCode) -> bool {
+    status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN
+}
+
+lazy_static! {
+    static ref SECRET_FIELD_RE: Regex = Regex::new(
+        r#"(?i)("?(?:access_token|refresh_token|oauth_token_secret|oauth_token|mfa_token)"?\s*[:=]\s*"?)[^"&,\s}]+"#
+    )
+    .unwrap();
+    static ref EMAIL_RE: Regex =
+        Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap();
+}
+
+/// Masks token values and email addresses in a Garmin response body so it can
+/// be logged or surfaced in an error.
+pub fn redact(text: &str) -> String {
+    let text = SECRET_FIELD_RE.replace_all(text, "${1}[REDACTED]");
+    EMAIL_RE.replace_all(&text, "[REDACTED_EMAIL]").into_owned()
+}
+
+/// Root directory holding the Garmin OAuth token files.
+pub const SECRETS_DIR: &str = "secrets";
+
+/// Returns the token directory for a named Garmin account.
+/// `None` keeps the legacy single-account layout (`secrets/oauth*.json`),
+/// a name maps to `secrets/<account>/oauth*.json`.
+pub fn token_dir_for_account(account: Option<&str>) -> Result<PathBuf> {
+    match account.map(str::trim).filter(|a| !a.is_empty()) {
+        None => Ok(PathBuf::from(SECRETS_DIR)),
+        Some(name) => {
+            if !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(anyhow!(
+                    "Invalid Garmin account name '{}'. Use letters, digits, '-' or '_' only.",
+                    name
+                ));
+            }
+            Ok(Path::new(SECRETS_DIR).join(name))
+        }
+    }
+}
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct OAuth1Token {
@@ -27,29 +79,63 @@ pub struct OAuth2Token {
 }
 
 pub struct GarminApi {
-    oauth1: OAuth1Token,
+    oauth1: tokio::sync::RwLock<OAuth1Token>,
     oauth2: tokio::sync::RwLock<OAuth2Token>,
     client: Client,
+    token_dir: PathBuf,
+    base_url: String,
 }
 
 impl GarminApi {
     pub fn new() -> Result<Self> {
-        let o1_str = std::fs::read_to_string("secrets/oauth1_token.json")
-            .context("Failed to read secrets/oauth1_token.json. Please ensure it exists.")?;
+        Self::new_for_account(None)
+    }
+
+    /// Loads the tokens of a named Garmin account (see `token_dir_for_account`).
+    pub fn new_for_account(account: Option<&str>) -> Result<Self> {
+        Self::from_token_dir(token_dir_for_account(account)?)
+    }
+
+    pub fn from_token_dir(token_dir: impl Into<PathBuf>) -> Result<Self> {
+        Self::from_token_dir_with_base_url(token_dir, GARMIN_CONNECT_API_BASE)
+    }
+
+    /// Same as `from_token_dir`, but sends every request to `base_url`
+    /// (e.g. a local mock server) instead of the production Garmin host.
+    pub fn from_token_dir_with_base_url(
+        token_dir: impl Into<PathBuf>,
+        base_url: &str,
+    ) -> Result<Self> {
+        let token_dir = token_dir.into();
+
+        let o1_path = token_dir.join("oauth1_token.json");
+        let o1_str = std::fs::read_to_string(&o1_path).with_context(|| {
+            format!(
+                "Failed to read {}. Please ensure it exists.",
+                o1_path.display()
+            )
+        })?;
         let oauth1: OAuth1Token =
             serde_json::from_str(&o1_str).context("Failed to parse oauth1_token.json")?;
 
-        let o2_str = std::fs::read_to_string("secrets/oauth2_token.json")
-            .context("Failed to read secrets/oauth2_token.json. Please ensure it exists.")?;
+        let o2_path = token_dir.join("oauth2_token.json");
+        let o2_str = std::fs::read_to_string(&o2_path).with_context(|| {
+            format!(
+                "Failed to read {}. Please ensure it exists.",
+                o2_path.display()
+            )
+        })?;
         let oauth2: OAuth2Token =
             serde_json::from_str(&o2_str).context("Failed to parse oauth2_token.json")?;
 
         let client = Client::builder().user_agent("GCM-iOS-5.7.2.1").build()?;
 
         Ok(Self {
-            oauth1,
+            oauth1: tokio::sync::RwLock::new(oauth1),
             oauth2: tokio::sync::RwLock::new(oauth2),
             client,
+            token_dir,
+            base_url: base_url.trim_end_matches('/').to_string(),
         })
     }
 
@@ -66,16 +152,38 @@ impl GarminApi {
             refresh_token_expires_at: None,
         };
         Ok(Self {
-            oauth1,
+            oauth1: tokio::sync::RwLock::new(oauth1),
             oauth2: tokio::sync::RwLock::new(dummy_oauth2),
             client,
+            token_dir: PathBuf::from(SECRETS_DIR),
+            base_url: GARMIN_CONNECT_API_BASE.to_string(),
         })
     }
 
+    /// Sends every request to `base_url` (e.g. a local mock server).
+    #[cfg(test)]
+    pub(crate) fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Marks the refresh token as past its lifetime.
+    #[cfg(test)]
+    pub(crate) async fn expire_refresh_token(&self) {
+        self.oauth2.write().await.refresh_token_expires_at = Some(1);
+    }
+
     pub async fn get_oauth2_cloned(&self) -> Result<OAuth2Token> {
         Ok(self.oauth2.read().await.clone())
     }
 
+    /// Swaps in freshly issued tokens (e.g. after a login through the API) so
+    /// requests use them without a restart.
+    pub async fn replace_tokens(&self, oauth1: OAuth1Token, oauth2: OAuth2Token) {
+        *self.oauth1.write().await = oauth1;
+        *self.oauth2.write().await = oauth2;
+    }
+
     /// Helper to attach OAuth2 Bearer token
     async fn attach_oauth2(&self, mut req: RequestBuilder) -> RequestBuilder {
         let token = self.oauth2.read().await.access_token.clone();
@@ -98,20 +206,38 @@ impl GarminApi {
         }
     }
 
+    /// True once the OAuth2 refresh token's own lifetime has run out, at which
+    /// point only a fresh `login` will restore access.
+    pub async fn is_refresh_token_expired(&self) -> bool {
+        let oauth2 = self.oauth2.read().await;
+        match oauth2.refresh_token_expires_at {
+            Some(expires_at) => {
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default();
+                now >= expires_at
+            }
+            None => false,
+        }
+    }
+
     /// Refresh the OAuth2 token natively via the Garmin OAuth1 token exchange.
     pub async fn refresh_oauth2(&self) -> Result<()> {
         let consumer_key = "fc3e99d2-118c-44b8-8ae3-03370dde24c0";
         let consumer_secret = "E08WAR897WEy2knn7aFBrvegVAf0AFdWBBF";
-        let url = "https://connectapi.garmin.com/oauth-service/oauth/exchange/user/2.0";
+        let url = format!("{}/oauth-service/oauth/exchange/user/2.0", self.base_url);
+        let url = url.as_str();
+        let oauth1 = self.oauth1.read().await.clone();
 
         let token = oauth1_request::Token::from_parts(
             consumer_key,
             consumer_secret,
-            &self.oauth1.oauth_token,
-            &self.oauth1.oauth_token_secret,
+            &oauth1.oauth_token,
+            &oauth1.oauth_token_secret,
         );
 
-        let authorization = if let Some(mfa) = &self.oauth1.mfa_token {
+        let authorization = if let Some(mfa) = &oauth1.mfa_token {
             let request =
                 oauth1_request::ParameterList::new([("mfa_token", mfa as &dyn std::fmt::Display)]);
             oauth1_request::post(
@@ -135,19 +261,24 @@ impl GarminApi {
             .header("Authorization", authorization.to_string())
             .header("Content-Type", "application/x-www-form-urlencoded");
 
-        if let Some(mfa) = &self.oauth1.mfa_token {
+        if let Some(mfa) = &oauth1.mfa_token {
             b = b.form(&[("mfa_token", mfa)]);
         }
 
         let res = b.send().await?;
         if !res.status().is_success() {
             let status = res.status();
-            let text = res.text().await.unwrap_or_default();
-            return Err(anyhow!(
-                "Failed to refresh OAuth2 token {}: {}",
-                status,
-                text
-            ));
+            let text = redact(&res.text().await.unwrap_or_default());
+            let hint = if self.is_refresh_token_expired().await {
+                " (refresh token expired)"
+            } else {
+                ""
+            };
+            return Err(AppError::GarminAuth(format!(
+                "Failed to refresh OAuth2 token{} {}: {}",
+                hint, status, text
+            ))
+            .into());
         }
 
         let mut new_oauth2: OAuth2Token = res.json().await?;
@@ -161,18 +292,13 @@ impl GarminApi {
         let to_save = new_oauth2.clone();
         *self.oauth2.write().await = new_oauth2;
 
-        // Save the new token locally
-        std::fs::write(
-            "secrets/oauth2_token.json",
-            serde_json::to_string_pretty(&to_save)?,
-        )?;
+        // Save the new token next to the account's OAuth1 token
+        let o2_path = self.token_dir.join("oauth2_token.json");
+        std::fs::write(&o2_path, serde_json::to_string_pretty(&to_save)?)?;
         #[cfg(unix)]
         {
             use std::os::unix::fs::PermissionsExt;
-            std::fs::set_permissions(
-                "secrets/oauth2_token.json",
-                std::fs::Permissions::from_mode(0o600),
-            )?;
+            std::fs::set_permissions(&o2_path, std::fs::Permissions::from_mode(0o600))?;
         }
 
         info!("Successfully refreshed Garmin OAuth2 Token natively!");
@@ -186,7 +312,7 @@ impl GarminApi {
             if self.is_oauth2_expired().await {
                 self.refresh_oauth2().await?;
             }
-            let url = format!("https://connectapi.garmin.com{}", endpoint);
+            let url = format!("{}{}", self.base_url, endpoint);
             let mut req = self.client.request(Method::GET, &url);
             req = self.attach_oauth2(req).await;
 
@@ -196,7 +322,15 @@ impl GarminApi {
                 }
                 Ok(res) => {
                     let status = res.status();
-                    let text = res.text().await.unwrap_or_default();
+                    crate::metrics::record_garmin_error(status.as_str());
+                    let text = redact(&res.text().await.unwrap_or_default());
+                    if is_auth_failure(status) {
+                        return Err(AppError::GarminAuth(format!(
+                            "Garmin API GET {} returned {}",
+                            endpoint, status
+                        ))
+                        .into());
+                    }
                     if attempt == max_retries {
                         return Err(anyhow!("Garmin API GET returned {}: {}", status, text));
                     }
@@ -210,6 +344,7 @@ impl GarminApi {
                     );
                 }
                 Err(e) => {
+                    crate::metrics::record_garmin_error("network");
                     if attempt == max_retries {
                         return Err(anyhow::anyhow!("Garmin API GET request failed: {}", e));
                     }
@@ -238,7 +373,7 @@ impl GarminApi {
             if self.is_oauth2_expired().await {
                 self.refresh_oauth2().await?;
             }
-            let url = format!("https://connectapi.garmin.com{}", endpoint);
+            let url = format!("{}{}", self.base_url, endpoint);
             let mut req = self.client.request(Method::POST, &url);
             req = self.attach_oauth2(req).await;
             req = req.json(payload);
@@ -257,7 +392,15 @@ impl GarminApi {
                 }
                 Ok(res) => {
                     let status = res.status();
-                    let text = res.text().await.unwrap_or_default();
+                    crate::metrics::record_garmin_error(status.as_str());
+                    let text = redact(&res.text().await.unwrap_or_default());
+                    if is_auth_failure(status) {
+                        return Err(AppError::GarminAuth(format!(
+                            "Garmin API POST {} returned {}",
+                            endpoint, status
+                        ))
+                        .into());
+                    }
                     if attempt == max_retries {
                         return Err(anyhow!("Garmin API POST returned {}: {}", status, text));
                     }
@@ -271,6 +414,7 @@ impl GarminApi {
                     );
                 }
                 Err(e) => {
+                    crate::metrics::record_garmin_error("network");
                     if attempt == max_retries {
                         return Err(anyhow::anyhow!("Garmin API POST request failed: {}", e));
                     }
@@ -295,7 +439,7 @@ impl GarminApi {
             if self.is_oauth2_expired().await {
                 self.refresh_oauth2().await?;
             }
-            let url = format!("https://connectapi.garmin.com{}", endpoint);
+            let url = format!("{}{}", self.base_url, endpoint);
             let mut req = self.client.request(Method::DELETE, &url);
             req = self.attach_oauth2(req).await;
 
@@ -305,7 +449,22 @@ impl GarminApi {
                 }
                 Ok(res) => {
                     let status = res.status();
-                    let text = res.text().await.unwrap_or_default();
+                    crate::metrics::record_garmin_error(status.as_str());
+                    let text = redact(&res.text().await.unwrap_or_default());
+                    if is_auth_failure(status) {
+                        return Err(AppError::GarminAuth(format!(
+                            "Garmin API DELETE {} returned {}",
+                            endpoint, status
+                        ))
+                        .into());
+                    }
+                    if status == StatusCode::NOT_FOUND {
+                        return Err(AppError::GarminNotFound(format!(
+                            "Garmin API DELETE {} returned {}",
+                            endpoint, status
+                        ))
+                        .into());
+                    }
                     if attempt == max_retries {
                         return Err(anyhow!("Garmin API DELETE returned {}: {}", status, text));
                     }
@@ -319,6 +478,7 @@ impl GarminApi {
                     );
                 }
                 Err(e) => {
+                    crate::metrics::record_garmin_error("network");
                     if attempt == max_retries {
                         return Err(anyhow::anyhow!("Garmin API DELETE request failed: {}", e));
                     }
@@ -336,6 +496,64 @@ impl GarminApi {
         unreachable!()
     }
 
+    /// Generic connectapi PUT request
+    pub async fn connectapi_put(&self, endpoint: &str, payload: &serde_json::Value) -> Result<()> {
+        let max_retries = 3;
+        for attempt in 1..=max_retries {
+            if self.is_oauth2_expired().await {
+                self.refresh_oauth2().await?;
+            }
+            let url = format!("{}{}", self.base_url, endpoint);
+            let mut req = self.client.request(Method::PUT, &url);
+            req = self.attach_oauth2(req).await;
+            req = req.json(payload);
+
+            match req.send().await {
+                Ok(res) if res.status().is_success() => {
+                    return Ok(());
+                }
+                Ok(res) => {
+                    let status = res.status();
+                    crate::metrics::record_garmin_error(status.as_str());
+                    let text = redact(&res.text().await.unwrap_or_default());
+                    if is_auth_failure(status) {
+                        return Err(AppError::GarminAuth(format!(
+                            "Garmin API PUT {} returned {}",
+                            endpoint, status
+                        ))
+                        .into());
+                    }
+                    if attempt == max_retries {
+                        return Err(anyhow!("Garmin API PUT returned {}: {}", status, text));
+                    }
+                    tracing::warn!(
+                        "Garmin API PUT {} failed with {}: {}. Retrying {}/{}",
+                        endpoint,
+                        status,
+                        text,
+                        attempt,
+                        max_retries
+                    );
+                }
+                Err(e) => {
+                    crate::metrics::record_garmin_error("network");
+                    if attempt == max_retries {
+                        return Err(anyhow::anyhow!("Garmin API PUT request failed: {}", e));
+                    }
+                    tracing::warn!(
+                        "Garmin API PUT {} request failed: {}. Retrying {}/{}",
+                        endpoint,
+                        e,
+                        attempt,
+                        max_retries
+                    );
+                }
+            }
+            tokio::time::sleep(tokio::time::Duration::from_secs(2 * attempt)).await;
+        }
+        unreachable!()
+    }
+
     pub async fn get_activities(&self, start: u32, limit: u32) -> Result<Vec<GarminActivity>> {
         let endpoint = format!(
             "/activitylist-service/activities/search/activities?start={}&limit={}",
@@ -346,6 +564,28 @@ impl GarminApi {
         Ok(activities)
     }
 
+    /// Full activity summary, including the user-editable `description`.
+    pub async fn get_activity(&self, activity_id: i64) -> Result<serde_json::Value> {
+        self.connectapi_get(&format!("/activity-service/activity/{}", activity_id))
+            .await
+    }
+
+    /// Updates the fields in `patch` (e.g. `description`) on a recorded activity.
+    pub async fn update_activity(
+        &self,
+        activity_id: i64,
+        mut patch: serde_json::Value,
+    ) -> Result<()> {
+        if let Some(fields) = patch.as_object_mut() {
+            fields.insert("activityId".to_string(), serde_json::json!(activity_id));
+        }
+        self.connectapi_put(
+            &format!("/activity-service/activity/{}", activity_id),
+            &patch,
+        )
+        .await
+    }
+
     pub async fn get_activity_exercise_sets(
         &self,
         activity_id: i64,
@@ -359,9 +599,12 @@ impl GarminApi {
                 Ok(sets) => Ok(Some(sets)),
                 Err(e) => {
                     error!(
-                        "Failed to deserialize exercise sets for activity {}: {}. Raw JSON: {}",
+                        "Failed to deserialize exercise sets for activity {}: {}",
+                        activity_id, e
+                    );
+                    debug!(
+                        "Raw exercise sets JSON for activity {}: {}",
                         activity_id,
-                        e,
                         serde_json::to_string(&val).unwrap_or_default()
                     );
                     Ok(None)
@@ -436,6 +679,18 @@ impl GarminApi {
         self.connectapi_get(&endpoint).await
     }
 
+    pub async fn get_daily_summary(
+        &self,
+        display_name: &str,
+        date_iso: &str,
+    ) -> Result<serde_json::Value> {
+        let endpoint = format!(
+            "/usersummary-service/usersummary/daily/{}?calendarDate={}",
+            display_name, date_iso
+        );
+        self.connectapi_get(&endpoint).await
+    }
+
     pub async fn get_body_battery(
         &self,
         date_iso: &str,
@@ -539,3 +794,132 @@ impl GarminApi {
         self.connectapi_get(&endpoint).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{redact, token_dir_for_account, GarminApi};
+    use std::path::{Path, PathBuf};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn write_tokens(dir: &Path, oauth_token: &str, access_token: &str) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(
+            dir.join("oauth1_token.json"),
+            format!(
+                r#"{{"oauth_token":"{oauth_token}","oauth_token_secret":"s","mfa_token":null,"mfa_expiration_timestamp":null,"domain":"garmin.com"}}"#
+            ),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("oauth2_token.json"),
+            format!(
+                r#"{{"scope":"","jti":"","token_type":"Bearer","access_token":"{access_token}","refresh_token":"r","expires_in":3600,"expires_at":null,"refresh_token_expires_in":7200,"refresh_token_expires_at":null}}"#
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn redact_masks_tokens_and_emails() {
+        let body = r#"{"error":"invalid_grant","access_token":"eyJhbGciOi.abc","refresh_token": "r-123","user":"jane.doe@example.com"}"#;
+        let redacted = redact(body);
+        assert!(!redacted.contains("eyJhbGciOi"));
+        assert!(!redacted.contains("r-123"));
+        assert!(!redacted.contains("jane.doe@example.com"));
+        assert!(redacted.contains(r#""access_token":"[REDACTED]""#));
+        assert!(redacted.contains("invalid_grant"));
+
+        let form = "oauth_token=abc&oauth_token_secret=xyz&mfa_token=m1";
+        assert_eq!(
+            redact(form),
+            "oauth_token=[REDACTED]&oauth_token_secret=[REDACTED]&mfa_token=[REDACTED]"
+        );
+    }
+
+    #[tokio::test]
+    async fn named_accounts_load_distinct_tokens() {
+        let root = std::env::temp_dir().join(format!("fj-accounts-{}", std::process::id()));
+        write_tokens(&root.join("alice"), "alice-o1", "alice-o2");
+        write_tokens(&root.join("bob"), "bob-o1", "bob-o2");
+
+        let alice = GarminApi::from_token_dir(root.join("alice")).unwrap();
+        let bob = GarminApi::from_token_dir(root.join("bob")).unwrap();
+
+        assert_eq!(alice.oauth1.read().await.oauth_token, "alice-o1");
+        assert_eq!(bob.oauth1.read().await.oauth_token, "bob-o1");
+        assert_eq!(
+            alice.get_oauth2_cloned().await.unwrap().access_token,
+            "alice-o2"
+        );
+        assert_eq!(
+            bob.get_oauth2_cloned().await.unwrap().access_token,
+            "bob-o2"
+        );
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn refresh_token_expiry_follows_refresh_token_expires_at() {
+        let dir = std::env::temp_dir().join(format!("fj-refresh-exp-{}", std::process::id()));
+        write_tokens(&dir, "o1", "o2");
+        let api = GarminApi::from_token_dir(&dir).unwrap();
+        // Untracked expiry never counts as expired.
+        assert!(!api.is_refresh_token_expired().await);
+
+        api.oauth2.write().await.refresh_token_expires_at = Some(1);
+        assert!(api.is_refresh_token_expired().await);
+        api.oauth2.write().await.refresh_token_expires_at = Some(u64::MAX);
+        assert!(!api.is_refresh_token_expired().await);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn account_names_map_to_secret_subdirectories() {
+        assert_eq!(
+            token_dir_for_account(None).unwrap(),
+            PathBuf::from("secrets")
+        );
+        assert_eq!(
+            token_dir_for_account(Some("")).unwrap(),
+            PathBuf::from("secrets")
+        );
+        assert_eq!(
+            token_dir_for_account(Some("partner")).unwrap(),
+            PathBuf::from("secrets/partner")
+        );
+        assert!(token_dir_for_account(Some("../etc")).is_err());
+    }
+
+    #[tokio::test]
+    async fn connectapi_get_retries_after_server_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/userprofile-service/socialProfile"))
+            .respond_with(ResponseTemplate::new(500).set_body_string("upstream hiccup"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/userprofile-service/socialProfile"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({ "displayName": "runner" })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let dir = std::env::temp_dir().join(format!("fj-retry-{}", std::process::id()));
+        write_tokens(&dir, "o1", "o2");
+        let api = GarminApi::from_token_dir_with_base_url(&dir, &server.uri()).unwrap();
+
+        let profile = api.get_user_profile().await.unwrap();
+        assert_eq!(profile["displayName"], "runner");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
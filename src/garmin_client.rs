@@ -1,7 +1,8 @@
 use crate::garmin_api::GarminApi;
-use crate::models::GarminResponse;
+use crate::models::{GarminResponse, Sport};
 use anyhow::{Context, Result};
-use tracing::{error, info};
+use chrono::Datelike;
+use tracing::{error, info, warn};
 
 use crate::db::Database;
 use std::sync::Arc;
@@ -17,64 +18,507 @@ fn haversine_distance(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
     r * 2.0 * a.sqrt().atan2((1.0 - a).sqrt())
 }
 
+/// Default tag (`ai_workout_tag`) marking workouts and courses the coach created;
+/// only these are ever cleaned up, validated or undone.
 pub const AI_WORKOUT_PREFIX: &str = "FJ-AI:";
 
-pub fn is_ai_managed_workout(name: &str) -> bool {
-    name.starts_with(AI_WORKOUT_PREFIX)
+/// Name rules for the workouts the coach owns: the configured tag, plus the
+/// `cleanup_name_prefixes` that cleanup still honors while older workouts migrate.
+#[derive(Debug, Clone)]
+pub struct AiWorkoutTags {
+    tag: String,
+    legacy_prefixes: Vec<String>,
 }
 
-pub fn ensure_ai_workout_name(name: &str) -> String {
-    if is_ai_managed_workout(name) {
-        name.to_string()
+impl Default for AiWorkoutTags {
+    fn default() -> Self {
+        Self {
+            tag: AI_WORKOUT_PREFIX.to_string(),
+            legacy_prefixes: Vec::new(),
+        }
+    }
+}
+
+impl AiWorkoutTags {
+    pub fn from_config(config: &crate::config::AppConfig) -> Self {
+        let tag = config.ai_workout_tag.trim();
+        Self {
+            tag: if tag.is_empty() {
+                AI_WORKOUT_PREFIX.to_string()
+            } else {
+                tag.to_string()
+            },
+            legacy_prefixes: config
+                .cleanup_name_prefixes
+                .iter()
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect(),
+        }
+    }
+
+    /// True when `name` carries the coach's tag.
+    pub fn is_tagged(&self, name: &str) -> bool {
+        name.trim_start().starts_with(&self.tag)
+    }
+
+    /// The tag or legacy prefix that makes `name` eligible for cleanup, if any.
+    pub fn cleanup_rule(&self, name: &str) -> Option<&str> {
+        if self.is_tagged(name) {
+            return Some(&self.tag);
+        }
+        let name = name.trim_start();
+        self.legacy_prefixes
+            .iter()
+            .find(|p| name.starts_with(p.as_str()))
+            .map(String::as_str)
+    }
+
+    /// Tags `name` as `<tag> <name>`; already tagged names are returned unchanged.
+    pub fn tag_name(&self, name: &str) -> String {
+        if self.is_tagged(name) {
+            name.trim_start().to_string()
+        } else {
+            format!("{} {}", self.tag, name.trim())
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Higher is better: activities with strength sets win, then the one with
+/// more recorded metrics.
+fn activity_richness(act: &crate::models::GarminActivity) -> usize {
+    let has_sets = matches!(
+        &act.sets,
+        Some(crate::models::GarminSetsData::Details(d)) if !d.exercise_sets.is_empty()
+    );
+    let metrics = [act.distance, act.duration, act.average_hr, act.max_hr]
+        .iter()
+        .filter(|m| m.is_some())
+        .count();
+    usize::from(has_sets) * 100 + metrics * 10 + act.raw_fields.len().min(9)
+}
+
+/// Same sport, starts within a minute and durations within 10% of each other,
+/// e.g. a manually entered activity next to the device recording of it.
+fn is_near_duplicate(a: &crate::models::GarminActivity, b: &crate::models::GarminActivity) -> bool {
+    if a.get_activity_type() != b.get_activity_type() {
+        return false;
+    }
+    let (Some(start_a), Some(start_b)) = (a.start_local(), b.start_local()) else {
+        return false;
+    };
+    if (start_a - start_b).num_seconds().abs() > 60 {
+        return false;
+    }
+    match (a.duration, b.duration) {
+        (Some(da), Some(db)) => (da - db).abs() <= 0.1 * da.max(db),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Collapses activities Garmin returns twice (same `id`, or a near-identical
+/// pair), keeping the richer copy in the position of the first occurrence.
+fn dedup_activities(
+    activities: Vec<crate::models::GarminActivity>,
+) -> Vec<crate::models::GarminActivity> {
+    let mut kept: Vec<crate::models::GarminActivity> = Vec::with_capacity(activities.len());
+    for act in activities {
+        let existing = kept
+            .iter()
+            .position(|k| (k.id.is_some() && k.id == act.id) || is_near_duplicate(k, &act));
+        match existing {
+            Some(idx) => {
+                info!(
+                    "Merging duplicate activity {:?} ({:?}) into {:?}",
+                    act.id, act.name, kept[idx].id
+                );
+                if activity_richness(&act) > activity_richness(&kept[idx]) {
+                    kept[idx] = act;
+                }
+            }
+            None => kept.push(act),
+        }
+    }
+    kept
+}
+
+/// `(year, zero-based month)` pairs for `count` consecutive months starting at
+/// the given month, as expected by `GarminApi::get_calendar`.
+fn calendar_months(year: i32, month0: i32, count: u32) -> Vec<(i32, i32)> {
+    (0..count as i32)
+        .map(|offset| {
+            let total = month0 + offset;
+            (year + total / 12, total % 12)
+        })
+        .collect()
+}
+
+/// A calendar entry whose workout no longer exists in the workout library.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrphanedSchedule {
+    pub schedule_id: i64,
+    pub workout_id: i64,
+    pub title: String,
+    pub date: String,
+}
+
+/// Cross-references scheduled `workout` calendar items against the workout
+/// library and returns the entries pointing at missing workouts.
+fn orphaned_schedules(
+    calendar_items: &[serde_json::Value],
+    workouts: &[serde_json::Value],
+) -> Vec<OrphanedSchedule> {
+    let known: std::collections::HashSet<i64> = workouts
+        .iter()
+        .filter_map(|w| w.get("workoutId").and_then(|i| i.as_i64()))
+        .collect();
+
+    calendar_items
+        .iter()
+        .filter(|item| item.get("itemType").and_then(|t| t.as_str()) == Some("workout"))
+        .filter_map(|item| {
+            let workout_id = item.get("workoutId").and_then(|i| i.as_i64())?;
+            if known.contains(&workout_id) {
+                return None;
+            }
+            Some(OrphanedSchedule {
+                schedule_id: item.get("id").and_then(|i| i.as_i64())?,
+                workout_id,
+                title: item
+                    .get("title")
+                    .and_then(|t| t.as_str())
+                    .unwrap_or("Untitled")
+                    .to_string(),
+                date: item
+                    .get("date")
+                    .and_then(|d| d.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Maps Garmin's `usersummary/daily` response; absent or null fields stay `None`.
+pub fn parse_daily_summary(json: &serde_json::Value) -> crate::models::DailySummary {
+    let int = |key: &str| json.get(key).and_then(|v| v.as_f64()).map(|v| v as i64);
+    let intensity_minutes = match (
+        int("moderateIntensityMinutes"),
+        int("vigorousIntensityMinutes"),
+    ) {
+        (None, None) => None,
+        (moderate, vigorous) => Some(moderate.unwrap_or(0) + 2 * vigorous.unwrap_or(0)),
+    };
+    crate::models::DailySummary {
+        steps: int("totalSteps"),
+        floors: json.get("floorsAscended").and_then(|v| v.as_f64()),
+        intensity_minutes,
+        // Garmin reports -1/-2 when there was not enough stress data.
+        stress_avg: int("averageStressLevel")
+            .filter(|v| *v >= 0)
+            .map(|v| v as i32),
+        active_calories: int("activeKilocalories"),
+    }
+}
+
+/// Overall sleep score of a `dailySleepData` response, `None` for nights without one.
+pub fn parse_sleep_score(json: &serde_json::Value) -> Option<i32> {
+    json.get("dailySleepDTO")
+        .and_then(|d| d.get("sleepScores"))
+        .and_then(|s| s.get("overall"))
+        .and_then(|o| o.get("value"))
+        .and_then(|v| v.as_i64())
+        .map(|v| v as i32)
+}
+
+/// Maps the stage totals of a `dailySleepData` response. Returns `None` for
+/// nights without a sleep record (no DTO or no stage/duration values at all).
+pub fn parse_sleep_detail(json: &serde_json::Value) -> Option<crate::models::SleepDetail> {
+    let dto = json.get("dailySleepDTO")?;
+    let secs = |key: &str| dto.get(key).and_then(|v| v.as_f64()).map(|v| v as i64);
+    let detail = crate::models::SleepDetail {
+        total_seconds: secs("sleepTimeSeconds"),
+        deep_seconds: secs("deepSleepSeconds"),
+        rem_seconds: secs("remSleepSeconds"),
+        light_seconds: secs("lightSleepSeconds"),
+        awake_seconds: secs("awakeSleepSeconds"),
+    };
+    (detail != crate::models::SleepDetail::default()).then_some(detail)
+}
+
+/// Separates the athlete's own activity notes from the coach analysis written
+/// back to Garmin; everything after it is replaced on the next write.
+const ANALYSIS_MARKER: &str = "--- Coach analysis ---";
+
+/// `existing` description with `analysis` appended under `ANALYSIS_MARKER`,
+/// replacing an earlier analysis rather than adding a second one.
+fn description_with_analysis(existing: Option<&str>, analysis: &str) -> String {
+    let notes = existing
+        .map(|d| d.split(ANALYSIS_MARKER).next().unwrap_or(d).trim_end())
+        .unwrap_or("");
+    if notes.is_empty() {
+        format!("{}\n{}", ANALYSIS_MARKER, analysis.trim())
     } else {
-        format!("{AI_WORKOUT_PREFIX}{name}")
+        format!("{}\n\n{}\n{}", notes, ANALYSIS_MARKER, analysis.trim())
     }
 }
 
 pub struct GarminClient {
     pub api: GarminApi,
     pub db: Arc<Mutex<Database>>,
+    cache_ttl_secs: u64,
+    /// Number of calendar months (starting with the current one) scanned for scheduled workouts.
+    calendar_months: u32,
+    /// Loaded once and shared, so `/api/reload_exercises` also affects generation.
+    workout_builder: Arc<std::sync::RwLock<crate::workout_builder::WorkoutBuilder>>,
+    ai_tags: AiWorkoutTags,
+    /// IANA zone for "today" (see `AppConfig::timezone`).
+    timezone: Option<String>,
+    /// Seconds since the Unix epoch; injectable so cache expiry can be tested.
+    clock: fn() -> u64,
 }
 
 impl GarminClient {
-    pub fn new(db: Arc<Mutex<Database>>) -> Self {
+    /// Builds a client for the configured Garmin account (`secrets/<account>/`,
+    /// or the default tokens when `garmin_account` is unset).
+    pub fn new(db: Arc<Mutex<Database>>, config: &crate::config::AppConfig) -> Self {
+        Self {
+            api: GarminApi::new_for_account(config.garmin_account.as_deref())
+                .expect("Failed to initialize GarminApi"),
+            db,
+            cache_ttl_secs: config.garmin_cache_ttl_secs,
+            calendar_months: config.garmin_calendar_months,
+            workout_builder: Arc::new(std::sync::RwLock::new(
+                crate::workout_builder::WorkoutBuilder::new()
+                    .with_concise_generic_descriptions(config.concise_generic_descriptions)
+                    .with_default_phase_durations(
+                        config.default_warmup_secs,
+                        config.default_cooldown_secs,
+                    ),
+            )),
+            ai_tags: AiWorkoutTags::from_config(config),
+            timezone: config.timezone.clone(),
+            clock: unix_now,
+        }
+    }
+
+    /// A client without stored tokens, for tests that point `api` at a mock server.
+    #[cfg(test)]
+    pub(crate) fn offline(db: Arc<Mutex<Database>>, clock: fn() -> u64) -> Self {
+        let oauth1 = crate::garmin_api::OAuth1Token {
+            oauth_token: String::new(),
+            oauth_token_secret: String::new(),
+            mfa_token: None,
+            mfa_expiration_timestamp: None,
+            domain: "garmin.com".to_string(),
+        };
         Self {
-            api: GarminApi::new().expect("Failed to initialize GarminApi"),
+            api: GarminApi::from_oauth1_for_exchange(oauth1, reqwest::Client::new()).unwrap(),
             db,
+            cache_ttl_secs: crate::config::DEFAULT_GARMIN_CACHE_TTL_SECS,
+            calendar_months: 6,
+            workout_builder: Arc::new(std::sync::RwLock::new(
+                crate::workout_builder::WorkoutBuilder::new(),
+            )),
+            ai_tags: AiWorkoutTags::default(),
+            timezone: None,
+            clock,
+        }
+    }
+
+    pub fn ai_tags(&self) -> &AiWorkoutTags {
+        &self.ai_tags
+    }
+
+    fn now(&self) -> chrono::DateTime<chrono::FixedOffset> {
+        crate::config::now_in(self.timezone.as_deref())
+    }
+
+    /// Sleep (today and the 7-day trend), daily summary and resting HR trend:
+    /// the recovery endpoints addressed by the user's display name.
+    async fn fetch_named_recovery(
+        &self,
+        display_name: &str,
+        today: chrono::DateTime<chrono::FixedOffset>,
+        recovery_metrics: &mut crate::models::GarminRecoveryMetrics,
+    ) {
+        let today_str = today.format("%Y-%m-%d").to_string();
+        match self.api.get_sleep_data(display_name, &today_str).await {
+            Ok(sleep_json) => {
+                recovery_metrics.sleep_score = parse_sleep_score(&sleep_json);
+                recovery_metrics.sleep_detail = parse_sleep_detail(&sleep_json);
+            }
+            Err(e) => info!("Error fetching Sleep Data: {}", e),
+        }
+
+        // 7-day sleep trend (oldest first): the six previous nights concurrently,
+        // then today. Nights without a score are left out.
+        let trend_dates: Vec<String> = (1..7)
+            .rev()
+            .map(|days| {
+                (today - chrono::Duration::days(days))
+                    .format("%Y-%m-%d")
+                    .to_string()
+            })
+            .collect();
+        let past_nights = futures_util::future::join_all(
+            trend_dates
+                .iter()
+                .map(|date| self.api.get_sleep_data(display_name, date)),
+        )
+        .await;
+        for (night, date) in past_nights.into_iter().zip(&trend_dates) {
+            match night {
+                Ok(json) => {
+                    if let Some(score) = parse_sleep_score(&json) {
+                        recovery_metrics
+                            .recent_sleep_scores
+                            .push(crate::models::SleepScore {
+                                date: date.clone(),
+                                score,
+                            });
+                    }
+                }
+                Err(e) => info!("Error fetching Sleep Data for {}: {}", date, e),
+            }
+        }
+        if let Some(score) = recovery_metrics.sleep_score {
+            recovery_metrics
+                .recent_sleep_scores
+                .push(crate::models::SleepScore {
+                    date: today_str.clone(),
+                    score,
+                });
+        }
+
+        match self.api.get_daily_summary(display_name, &today_str).await {
+            Ok(summary_json) => {
+                recovery_metrics.daily_summary = Some(parse_daily_summary(&summary_json));
+            }
+            Err(e) => info!("Error fetching Daily Summary: {}", e),
+        }
+
+        let seven_days_ago_str = (today - chrono::Duration::days(7))
+            .format("%Y-%m-%d")
+            .to_string();
+
+        match self
+            .api
+            .get_rhr_trend(display_name, &seven_days_ago_str, &today_str)
+            .await
+        {
+            Ok(rhr_json) => {
+                if let Some(arr) = rhr_json.as_array() {
+                    let mut trend = Vec::new();
+                    for item in arr {
+                        // The actual field name will be discovered in debug print, but "value" or "values" is common.
+                        // For rhr, it's often { "value": 50 }
+                        if let Some(val) = item.get("value").and_then(|v| v.as_i64()) {
+                            trend.push(val as i32);
+                        } else if let Some(val) = item
+                            .get("values")
+                            .and_then(|v| v.get("restingHR"))
+                            .and_then(|r| r.as_i64())
+                        {
+                            trend.push(val as i32);
+                        }
+                    }
+                    recovery_metrics.rhr_trend = trend;
+                } else if let Some(all_metrics) = rhr_json
+                    .get("allMetrics")
+                    .and_then(|m| m.get("metricsMap"))
+                    .and_then(|m| m.get("WELLNESS_RESTING_HEART_RATE"))
+                    .and_then(|a| a.as_array())
+                {
+                    let mut trend = Vec::new();
+                    for item in all_metrics {
+                        if let Some(val) = item.get("value").and_then(|v| v.as_f64()) {
+                            trend.push(val as i32);
+                        } else if let Some(val) = item.get("value").and_then(|v| v.as_i64()) {
+                            trend.push(val as i32);
+                        }
+                    }
+                    recovery_metrics.rhr_trend = trend;
+                }
+            }
+            Err(e) => info!("Error fetching RHR TREND: {}", e),
+        }
+    }
+
+    /// The display name from this fetch's profile, remembered in the database
+    /// so a failed profile fetch still reaches the sleep and RHR endpoints.
+    async fn resolve_display_name(&self, fetched: Option<&str>) -> Option<String> {
+        let db = self.db.lock().await;
+        match fetched.filter(|name| !name.is_empty()) {
+            Some(name) => {
+                if let Err(e) = db.set_display_name(name) {
+                    error!("Failed to cache Garmin display name: {}", e);
+                }
+                Some(name.to_string())
+            }
+            None => {
+                let cached = db.get_display_name().ok().flatten();
+                if cached.is_some() {
+                    info!("Using cached Garmin display name");
+                }
+                cached
+            }
         }
     }
 
     pub async fn fetch_data(&self) -> Result<GarminResponse> {
+        self.fetch_data_with_opts(false).await
+    }
+
+    /// Like `fetch_data`, but `force_refresh` ignores the SQLite cache and always
+    /// pulls fresh data from Garmin (the cache is still updated afterwards).
+    pub async fn fetch_data_with_opts(&self, force_refresh: bool) -> Result<GarminResponse> {
         // 1. Check Cache
-        let is_test = std::env::args().any(|a| a == "--test");
-        if !is_test {
+        if !force_refresh {
             if let Ok(Some((cached_data, updated_at))) = self.db.lock().await.get_garmin_cache() {
-                let now = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs();
-                let elapsed = now.saturating_sub(updated_at);
-
-                let cache_ttl = std::env::var("GARMIN_CACHE_TTL_SECONDS")
-                    .unwrap_or_else(|_| "300".to_string())
-                    .parse::<u64>()
-                    .unwrap_or(300);
-
-                if elapsed < cache_ttl {
-                    // Check Cache
+                let elapsed = (self.clock)().saturating_sub(updated_at);
+
+                if elapsed < self.cache_ttl_secs {
                     info!("Using cached Garmin data ({} mins old)...", elapsed / 60);
                     let response: GarminResponse = serde_json::from_str(&cached_data)
                         .context("Failed to parse cached Garmin JSON output")?;
+                    crate::metrics::record_garmin_cache(true);
                     return Ok(response);
                 }
             }
         }
+        crate::metrics::record_garmin_cache(false);
+
+        // Refreshing cannot recover from this; fail before any request so the
+        // caller raises the re-login alert.
+        if self.api.is_refresh_token_expired().await {
+            return Err(crate::error::AppError::GarminAuth(
+                "Garmin refresh token expired".to_string(),
+            )
+            .into());
+        }
 
         // 2. Fetch Fresh Data natively via Rust GarminApi
         let activities = match self.api.get_activities(0, 100).await {
             Ok(acts) => acts,
             Err(e) => {
+                // Every other endpoint would fail the same way; surface it instead of
+                // caching an empty response.
+                if crate::error::AppError::is_garmin_auth(&e) {
+                    return Err(e);
+                }
                 error!("Failed to fetch activities from Garmin: {}", e);
+                if let Some(response) = self.last_good_response().await {
+                    return Ok(response);
+                }
                 Vec::new()
             }
         };
@@ -91,12 +535,12 @@ impl GarminClient {
             Vec::new()
         };
 
-        let mut display_name = String::new();
+        let mut fetched_name = None;
         let user_profile: Option<crate::models::GarminProfile> =
             match self.api.get_user_profile().await {
                 Ok(v) => {
                     if let Some(dn) = v.get("displayName").and_then(|val| val.as_str()) {
-                        display_name = dn.to_string();
+                        fetched_name = Some(dn.to_string());
                     }
                     serde_json::from_value(v).unwrap_or(None)
                 }
@@ -105,106 +549,113 @@ impl GarminClient {
                     None
                 }
             };
+        let display_name = self.resolve_display_name(fetched_name.as_deref()).await;
 
-        let today = chrono::Local::now();
+        let today = self.now();
         let today_str = today.format("%Y-%m-%d").to_string();
         let max_metrics = match self.api.get_max_metrics(&today_str).await {
             Ok(v) => serde_json::from_value(v).unwrap_or(None),
             Err(_) => None,
         };
 
-        // Fetch Calendar for Scheduled Workouts
+        // Fetch Calendar for Scheduled Workouts (all months concurrently)
         let mut scheduled_workouts = Vec::new();
         let mut seen_keys = std::collections::HashSet::new();
-        let mut tz_year = today
-            .format("%Y")
-            .to_string()
-            .parse::<i32>()
-            .unwrap_or(2025);
-        let mut tz_month = today.format("%m").to_string().parse::<i32>().unwrap_or(1) - 1;
+        let months = calendar_months(today.year(), today.month0() as i32, self.calendar_months);
+        let calendar_start = std::time::Instant::now();
+        let calendars = futures_util::future::join_all(
+            months
+                .iter()
+                .map(|&(year, month)| self.api.get_calendar(year, month)),
+        )
+        .await;
+        info!(
+            "Fetched {} calendar months in {} ms",
+            months.len(),
+            calendar_start.elapsed().as_millis()
+        );
 
-        for _ in 0..6 {
-            if let Ok(calendar_json) = self.api.get_calendar(tz_year, tz_month).await {
-                if let Some(items) = calendar_json
-                    .get("calendarItems")
-                    .and_then(|i| i.as_array())
-                {
-                    for item in items {
-                        // Item type can be "workout" or "activity" maybe?
-                        match serde_json::from_value::<crate::models::ScheduledWorkout>(
-                            item.clone(),
-                        ) {
-                            Ok(mut sw) => {
-                                if let Some(ref it) = sw.item_type {
-                                    if it == "workout"
-                                        || it == "fbtAdaptiveWorkout"
-                                        || it == "race"
-                                        || it == "event"
-                                        || it == "primaryEvent"
-                                    {
-                                        let key = format!(
-                                            "{}_{}",
-                                            sw.date,
-                                            sw.title.as_deref().unwrap_or("")
-                                        );
-                                        if seen_keys.insert(key) {
-                                            if it == "fbtAdaptiveWorkout" {
-                                                // Try workoutUuid first, then uuid, then id
-                                                let target = sw.raw_fields.get("workoutUuid").and_then(|v| v.as_str()).map(|s| s.to_string())
-                                                    .or_else(|| sw.raw_fields.get("uuid").and_then(|v| v.as_str()).map(|s| s.to_string()))
-                                                    .or_else(|| sw.raw_fields.get("id").and_then(|v| v.as_str()).map(|s| s.to_string()))
-                                                    .or_else(|| sw.raw_fields.get("id").and_then(|v| v.as_u64()).map(|n| n.to_string()));
-
-                                                if let Some(target_id) = target {
-                                                    match self.api.get_adaptive_workout_details(&target_id).await {
-                                                        Ok(details) => sw.adaptive_details = Some(details),
-                                                        Err(e) => info!("Failed to get adaptive details for {}: {}", target_id, e),
-                                                    }
+        for (calendar, (year, month)) in calendars.into_iter().zip(&months) {
+            let calendar_json = match calendar {
+                Ok(json) => json,
+                Err(e) => {
+                    info!("Failed to fetch calendar {}-{:02}: {}", year, month + 1, e);
+                    continue;
+                }
+            };
+            if let Some(items) = calendar_json
+                .get("calendarItems")
+                .and_then(|i| i.as_array())
+            {
+                for item in items {
+                    // Item type can be "workout" or "activity" maybe?
+                    match serde_json::from_value::<crate::models::ScheduledWorkout>(
+                        item.clone(),
+                    ) {
+                        Ok(mut sw) => {
+                            if let Some(ref it) = sw.item_type {
+                                if it == "workout"
+                                    || it == "fbtAdaptiveWorkout"
+                                    || it == "race"
+                                    || it == "event"
+                                    || it == "primaryEvent"
+                                {
+                                    let key = format!(
+                                        "{}_{}",
+                                        sw.date,
+                                        sw.title.as_deref().unwrap_or("")
+                                    );
+                                    if seen_keys.insert(key) {
+                                        if it == "fbtAdaptiveWorkout" {
+                                            // Try workoutUuid first, then uuid, then id
+                                            let target = sw.raw_fields.get("workoutUuid").and_then(|v| v.as_str()).map(|s| s.to_string())
+                                                .or_else(|| sw.raw_fields.get("uuid").and_then(|v| v.as_str()).map(|s| s.to_string()))
+                                                .or_else(|| sw.raw_fields.get("id").and_then(|v| v.as_str()).map(|s| s.to_string()))
+                                                .or_else(|| sw.raw_fields.get("id").and_then(|v| v.as_u64()).map(|n| n.to_string()));
+
+                                            if let Some(target_id) = target {
+                                                match self.api.get_adaptive_workout_details(&target_id).await {
+                                                    Ok(details) => sw.adaptive_details = Some(details),
+                                                    Err(e) => info!("Failed to get adaptive details for {}: {}", target_id, e),
                                                 }
                                             }
+                                        }
 
-                                            // Fetch full workout detail (with segments/steps) for workouts with a workoutId.
-                                            // Check: raw calendar item → adaptive_details top-level → nested workout/adaptiveWorkout objects
-                                            let wid_val = sw.raw_fields.get("workoutId")
-                                                .or_else(|| sw.adaptive_details.as_ref().and_then(|ad| {
-                                                    ad.get("workoutId")
-                                                        .or_else(|| ad.get("workout").and_then(|w| w.get("workoutId")))
-                                                        .or_else(|| ad.get("adaptiveWorkout").and_then(|w| w.get("workoutId")))
-                                                }));
-                                            let wid_i64 = wid_val.and_then(|v| v.as_i64()).or_else(|| wid_val.and_then(|v| v.as_u64()).map(|u| u as i64));
-
-                                            if let Some(wid) = wid_i64 {
-                                                if wid > 0 {
-                                                    info!("Fetching workout detail for '{}' (workoutId={})", sw.title.as_deref().unwrap_or("?"), wid);
-                                                    match self.api.get_workout_by_id(wid).await {
-                                                        Ok(detail) => sw.workout_detail = Some(detail),
-                                                        Err(e) => info!("Failed to get workout detail for {}: {}", wid, e),
-                                                    }
+                                        // Fetch full workout detail (with segments/steps) for workouts with a workoutId.
+                                        // Check: raw calendar item → adaptive_details top-level → nested workout/adaptiveWorkout objects
+                                        let wid_val = sw.raw_fields.get("workoutId")
+                                            .or_else(|| sw.adaptive_details.as_ref().and_then(|ad| {
+                                                ad.get("workoutId")
+                                                    .or_else(|| ad.get("workout").and_then(|w| w.get("workoutId")))
+                                                    .or_else(|| ad.get("adaptiveWorkout").and_then(|w| w.get("workoutId")))
+                                            }));
+                                        let wid_i64 = wid_val.and_then(|v| v.as_i64()).or_else(|| wid_val.and_then(|v| v.as_u64()).map(|u| u as i64));
+
+                                        if let Some(wid) = wid_i64 {
+                                            if wid > 0 {
+                                                info!("Fetching workout detail for '{}' (workoutId={})", sw.title.as_deref().unwrap_or("?"), wid);
+                                                match self.api.get_workout_by_id(wid).await {
+                                                    Ok(detail) => sw.workout_detail = Some(detail),
+                                                    Err(e) => info!("Failed to get workout detail for {}: {}", wid, e),
                                                 }
                                             }
-
-
-                                            scheduled_workouts.push(sw);
                                         }
+
+                                        sw.fill_nested_targets();
+                                        scheduled_workouts.push(sw);
                                     }
                                 }
                             }
-                            Err(e) => info!(
-                                "Failed to parse calendar item (type: {:?}): {}. Raw: {:?}",
-                                item.get("itemType"),
-                                e,
-                                item
-                            ),
                         }
+                        Err(e) => info!(
+                            "Failed to parse calendar item (type: {:?}): {}. Raw: {:?}",
+                            item.get("itemType"),
+                            e,
+                            item
+                        ),
                     }
                 }
             }
-
-            tz_month += 1;
-            if tz_month > 11 {
-                tz_month = 0;
-                tz_year += 1;
-            }
         }
 
         // Fetch Recovery Metrics
@@ -217,6 +668,8 @@ impl GarminClient {
             hrv_last_night_avg: None,
             hrv_weekly_avg: None,
             rhr_trend: Vec::new(),
+            daily_summary: None,
+            sleep_detail: None,
         };
 
         match self.api.get_body_battery(&today_str).await {
@@ -241,17 +694,14 @@ impl GarminClient {
             Err(e) => info!("Error fetching Body Battery: {}", e),
         }
 
-        match self.api.get_sleep_data(&display_name, &today_str).await {
-            Ok(sleep_json) => {
-                recovery_metrics.sleep_score = sleep_json
-                    .get("dailySleepDTO")
-                    .and_then(|d| d.get("sleepScores"))
-                    .and_then(|s| s.get("overall"))
-                    .and_then(|o| o.get("value"))
-                    .and_then(|v| v.as_i64())
-                    .map(|v| v as i32);
+        match display_name.as_deref() {
+            Some(name) => {
+                self.fetch_named_recovery(name, today, &mut recovery_metrics)
+                    .await
             }
-            Err(e) => info!("Error fetching Sleep Data: {}", e),
+            None => info!(
+                "Garmin display name unknown (profile fetch failed, none cached): skipping sleep, daily summary and RHR fetches."
+            ),
         }
 
         match self.api.get_training_readiness(&today_str).await {
@@ -288,55 +738,45 @@ impl GarminClient {
             Err(e) => info!("Error fetching HRV JSON: {}", e),
         }
 
-        let seven_days_ago_str = (today - chrono::Duration::days(7))
-            .format("%Y-%m-%d")
-            .to_string();
+        let heat_window_start =
+            today.date_naive() - chrono::Duration::days(crate::coaching::HEAT_EXPOSURE_DAYS);
+        let mut final_activities = Vec::new();
+        for mut act in activities {
+            let is_strength = act.get_activity_type() == Some("strength_training");
 
-        match self
-            .api
-            .get_rhr_trend(&display_name, &seven_days_ago_str, &today_str)
-            .await
-        {
-            Ok(rhr_json) => {
-                if let Some(arr) = rhr_json.as_array() {
-                    let mut trend = Vec::new();
-                    for item in arr {
-                        // The actual field name will be discovered in debug print, but "value" or "values" is common.
-                        // For rhr, it's often { "value": 50 }
-                        if let Some(val) = item.get("value").and_then(|v| v.as_i64()) {
-                            trend.push(val as i32);
-                        } else if let Some(val) = item
-                            .get("values")
-                            .and_then(|v| v.get("restingHR"))
-                            .and_then(|r| r.as_i64())
-                        {
-                            trend.push(val as i32);
-                        }
-                    }
-                    recovery_metrics.rhr_trend = trend;
-                } else if let Some(all_metrics) = rhr_json
-                    .get("allMetrics")
-                    .and_then(|m| m.get("metricsMap"))
-                    .and_then(|m| m.get("WELLNESS_RESTING_HEART_RATE"))
-                    .and_then(|a| a.as_array())
-                {
-                    let mut trend = Vec::new();
-                    for item in all_metrics {
-                        if let Some(val) = item.get("value").and_then(|v| v.as_f64()) {
-                            trend.push(val as i32);
-                        } else if let Some(val) = item.get("value").and_then(|v| v.as_i64()) {
-                            trend.push(val as i32);
+            // The activity list usually carries temperatures; fall back to the
+            // detail payload for recent outdoor sessions that lack them. The
+            // lookup is remembered per activity so each detail is fetched once.
+            if act.max_temperature.is_none()
+                && act.is_outdoor_endurance()
+                && act.start_date().is_some_and(|d| d >= heat_window_start)
+            {
+                if let Some(id) = act.id {
+                    let known = self.db.lock().await.get_activity_temperatures(id);
+                    match known {
+                        Ok(Some((min, max))) => {
+                            act.min_temperature = act.min_temperature.or(min);
+                            act.max_temperature = max;
                         }
+                        _ => match self.api.get_activity(id).await {
+                            Ok(detail) => {
+                                act.fill_temperatures_from_detail(&detail);
+                                if let Err(e) = self.db.lock().await.set_activity_temperatures(
+                                    id,
+                                    act.min_temperature,
+                                    act.max_temperature,
+                                ) {
+                                    error!(
+                                        "Failed to cache temperatures for activity {}: {}",
+                                        id, e
+                                    );
+                                }
+                            }
+                            Err(e) => info!("Failed to fetch detail for activity {}: {}", id, e),
+                        },
                     }
-                    recovery_metrics.rhr_trend = trend;
                 }
             }
-            Err(e) => info!("Error fetching RHR TREND: {}", e),
-        }
-
-        let mut final_activities = Vec::new();
-        for mut act in activities {
-            let is_strength = act.get_activity_type() == Some("strength_training");
 
             if is_strength {
                 if let Some(id) = act.id {
@@ -359,6 +799,8 @@ impl GarminClient {
             final_activities.push(act);
         }
 
+        let final_activities = dedup_activities(final_activities);
+
         let response = GarminResponse {
             activities: final_activities,
             plans: plans_vec,
@@ -366,30 +808,71 @@ impl GarminClient {
             max_metrics,
             scheduled_workouts,
             recovery_metrics: Some(recovery_metrics),
+            stale_since: None,
         };
 
         let stdout = serde_json::to_string(&response)?;
 
         // 3. Save to Cache
-        if let Err(e) = self.db.lock().await.set_garmin_cache(&stdout) {
+        let db = self.db.lock().await;
+        if let Err(e) = db.set_garmin_cache(&stdout) {
             error!("Warning: Failed to write to Garmin cache in DB: {}", e);
         }
+        if let Err(e) = db.set_garmin_last_good(&stdout) {
+            error!("Warning: Failed to write last good Garmin data: {}", e);
+        }
 
         Ok(response)
     }
 
+    /// The last successful fetch, regardless of the cache TTL, marked stale.
+    async fn last_good_response(&self) -> Option<GarminResponse> {
+        let (cached, fetched_at) = self.db.lock().await.get_garmin_last_good().ok()??;
+        let mut response: GarminResponse = match serde_json::from_str(&cached) {
+            Ok(response) => response,
+            Err(e) => {
+                error!("Failed to parse last good Garmin data: {}", e);
+                return None;
+            }
+        };
+        let age_hours = (self.clock)().saturating_sub(fetched_at) / 3600;
+        warn!(
+            "Garmin is unreachable; using cached data from {} hours ago",
+            age_hours
+        );
+        response.stale_since = Some(fetched_at);
+        Some(response)
+    }
+
+    /// Writes `analysis` into the activity's Garmin description, keeping the
+    /// athlete's own notes and replacing any analysis written before.
+    pub async fn write_activity_analysis(&self, activity_id: i64, analysis: &str) -> Result<()> {
+        let activity = self.api.get_activity(activity_id).await?;
+        let existing = activity.get("description").and_then(|d| d.as_str());
+        let description = description_with_analysis(existing, analysis);
+        if existing == Some(description.as_str()) {
+            return Ok(());
+        }
+        self.api
+            .update_activity(
+                activity_id,
+                serde_json::json!({ "description": description }),
+            )
+            .await
+    }
+
     pub async fn cleanup_ai_workouts(&self) -> Result<()> {
         info!("Fetching workouts to delete (future only)...");
         let workouts = self.api.get_workouts().await?;
-        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let today = self.now().format("%Y-%m-%d").to_string();
 
         if let Some(arr) = workouts.as_array() {
             let mut to_delete = Vec::new();
             for w in arr {
                 if let Some(name) = w.get("workoutName").and_then(|n| n.as_str()) {
-                    if is_ai_managed_workout(name) {
+                    if let Some(rule) = self.ai_tags.cleanup_rule(name) {
                         if let Some(wid) = w.get("workoutId").and_then(|i| i.as_i64()) {
-                            to_delete.push((wid, name.to_string()));
+                            to_delete.push((wid, name.to_string(), rule.to_string()));
                         }
                     }
                 }
@@ -399,7 +882,7 @@ impl GarminClient {
             let calendar_dates = self.get_ai_workout_schedule_dates().await;
 
             info!("Found {} AI workouts total.", to_delete.len());
-            for (wid, name) in to_delete {
+            for (wid, name, rule) in to_delete {
                 // Only delete if scheduled today or in the future, or if we can't determine the date
                 let scheduled_date = calendar_dates.get(&name);
                 let is_future = match scheduled_date {
@@ -410,7 +893,13 @@ impl GarminClient {
                 if is_future {
                     let endpoint = format!("/workout-service/workout/{}", wid);
                     match self.api.connectapi_delete(&endpoint).await {
-                        Ok(_) => info!("Deleted {} ({})", wid, name),
+                        Ok(_) => {
+                            info!("Deleted {} ({}, matched '{}')", wid, name, rule);
+                            self.forget_generated_workout(wid).await;
+                        }
+                        Err(e) if crate::error::AppError::is_garmin_not_found(&e) => {
+                            self.forget_generated_workout(wid).await;
+                        }
                         Err(e) => info!("Failed to delete {}: {}", wid, e),
                     }
                 } else {
@@ -421,10 +910,72 @@ impl GarminClient {
         Ok(())
     }
 
+    /// Scheduled workouts in the configured calendar window whose workout has
+    /// been deleted. Candidates are confirmed with a direct lookup so a
+    /// truncated workout list never flags a live workout.
+    pub async fn find_orphaned_schedules(&self) -> Result<Vec<OrphanedSchedule>> {
+        let workouts = self.api.get_workouts().await?;
+        let workouts = workouts.as_array().cloned().unwrap_or_default();
+
+        let today = self.now();
+        let months = calendar_months(today.year(), today.month0() as i32, self.calendar_months);
+        let calendars = futures_util::future::join_all(
+            months
+                .iter()
+                .map(|&(year, month)| self.api.get_calendar(year, month)),
+        )
+        .await;
+        let mut calendar_items = Vec::new();
+        for calendar in calendars {
+            let calendar_json = calendar?;
+            if let Some(items) = calendar_json
+                .get("calendarItems")
+                .and_then(|i| i.as_array())
+            {
+                calendar_items.extend(items.iter().cloned());
+            }
+        }
+
+        let mut orphans = Vec::new();
+        for candidate in orphaned_schedules(&calendar_items, &workouts) {
+            if self
+                .api
+                .get_workout_by_id(candidate.workout_id)
+                .await
+                .is_err()
+            {
+                orphans.push(candidate);
+            }
+        }
+        Ok(orphans)
+    }
+
+    /// Deletes the schedule entries reported by `find_orphaned_schedules()`.
+    pub async fn remove_orphaned_schedules(&self) -> Result<Vec<OrphanedSchedule>> {
+        let mut removed = Vec::new();
+        for orphan in self.find_orphaned_schedules().await? {
+            let endpoint = format!("/workout-service/schedule/{}", orphan.schedule_id);
+            match self.api.connectapi_delete(&endpoint).await {
+                Ok(_) => {
+                    info!(
+                        "Removed orphaned schedule {} ({} on {})",
+                        orphan.schedule_id, orphan.title, orphan.date
+                    );
+                    removed.push(orphan);
+                }
+                Err(e) => info!(
+                    "Failed to remove orphaned schedule {}: {}",
+                    orphan.schedule_id, e
+                ),
+            }
+        }
+        Ok(removed)
+    }
+
     /// Helper: build a map of AI workout name -> scheduled date from the Garmin calendar
     async fn get_ai_workout_schedule_dates(&self) -> std::collections::HashMap<String, String> {
         let mut dates = std::collections::HashMap::new();
-        let today = chrono::Local::now();
+        let today = self.now();
         let mut tz_year = today
             .format("%Y")
             .to_string()
@@ -443,7 +994,7 @@ impl GarminClient {
                             item.get("title").and_then(|t| t.as_str()),
                             item.get("date").and_then(|d| d.as_str()),
                         ) {
-                            if is_ai_managed_workout(title) {
+                            if self.ai_tags.cleanup_rule(title).is_some() {
                                 dates.insert(title.to_string(), date.to_string());
                             }
                         }
@@ -459,12 +1010,57 @@ impl GarminClient {
         dates
     }
 
+    /// A snapshot of the shared workout builder.
+    pub(crate) fn workout_builder(&self) -> crate::workout_builder::WorkoutBuilder {
+        self.workout_builder
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Re-reads the exercise database CSV into the shared workout builder.
+    pub fn reload_exercises(&self) -> Result<usize> {
+        self.workout_builder
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .reload()
+    }
+
+    /// The Garmin workout payload `create_and_schedule_workout` would upload first.
+    pub fn build_workout_payload(&self, workout_spec: &serde_json::Value) -> serde_json::Value {
+        self.workout_builder()
+            .build_workout_payload(workout_spec, false)
+    }
+
     pub async fn create_and_schedule_workout(
         &self,
         workout_spec: &serde_json::Value,
     ) -> Result<String> {
-        let builder = crate::workout_builder::WorkoutBuilder::new();
-        let mut payload = builder.build_workout_payload(workout_spec, false);
+        self.create_and_schedule(workout_spec, None).await
+    }
+
+    /// Like `create_and_schedule_workout`, but records the new workout under
+    /// `batch_id` as soon as it exists, so `/undo` removes it even when
+    /// scheduling fails.
+    pub async fn create_and_schedule_workout_in_batch(
+        &self,
+        workout_spec: &serde_json::Value,
+        batch_id: &str,
+    ) -> Result<String> {
+        self.create_and_schedule(workout_spec, Some(batch_id)).await
+    }
+
+    async fn create_and_schedule(
+        &self,
+        workout_spec: &serde_json::Value,
+        batch_id: Option<&str>,
+    ) -> Result<String> {
+        let builder = self.workout_builder();
+        let (mut payload, resolutions) =
+            builder.build_workout_payload_with_resolutions(workout_spec, false);
+        if let Err(e) = self.db.lock().await.log_exercise_resolutions(&resolutions) {
+            error!("Failed to record exercise resolutions: {}", e);
+        }
         let mut workout_id = None;
         let mut msg = String::new();
 
@@ -480,6 +1076,8 @@ impl GarminClient {
                 }
             }
             Err(e) => {
+                // Garmin rejects exercise category/name pairs it doesn't know with a
+                // 400; retry once with the generic payload (see `build_workout_payload`).
                 if e.to_string().contains("400") {
                     payload = builder.build_workout_payload(workout_spec, true);
                     match self
@@ -503,6 +1101,22 @@ impl GarminClient {
             }
         }
 
+        if let (Some(id), Some(batch_id)) = (workout_id, batch_id) {
+            let name = workout_spec
+                .get("workoutName")
+                .and_then(|n| n.as_str())
+                .unwrap_or_default();
+            let date = workout_spec.get("scheduledDate").and_then(|d| d.as_str());
+            if let Err(e) = self
+                .db
+                .lock()
+                .await
+                .record_generated_workout(batch_id, id, name, date)
+            {
+                error!("Failed to record generated workout {}: {}", id, e);
+            }
+        }
+
         if let (Some(id), Some(sch_date)) = (
             workout_id,
             workout_spec.get("scheduledDate").and_then(|d| d.as_str()),
@@ -527,6 +1141,47 @@ impl GarminClient {
         }
     }
 
+    /// Deletes the workouts created by the most recent generation run. Returns
+    /// `None` when no run is recorded or it was already undone, otherwise how
+    /// many workouts were removed. Workouts that are already gone in Garmin are
+    /// forgotten but not counted.
+    pub async fn undo_last_generation(&self) -> Result<Option<usize>> {
+        let Some((batch_id, workout_ids)) = self.db.lock().await.get_last_generation_batch()?
+        else {
+            return Ok(None);
+        };
+
+        let mut removed = 0;
+        for &id in &workout_ids {
+            let endpoint = format!("/workout-service/workout/{}", id);
+            match self.api.connectapi_delete(&endpoint).await {
+                Ok(_) => {
+                    removed += 1;
+                    self.forget_generated_workout(id).await;
+                }
+                Err(e) if crate::error::AppError::is_garmin_not_found(&e) => {
+                    self.forget_generated_workout(id).await;
+                }
+                Err(e) => {
+                    return Err(e.context(format!(
+                        "Removed {} of {} workouts before failing",
+                        removed,
+                        workout_ids.len()
+                    )))
+                }
+            }
+        }
+        self.db.lock().await.delete_generation_batch(&batch_id)?;
+        info!("Undid generation batch {} ({} workouts)", batch_id, removed);
+        Ok(Some(removed))
+    }
+
+    async fn forget_generated_workout(&self, workout_id: i64) {
+        if let Err(e) = self.db.lock().await.forget_generated_workout(workout_id) {
+            error!("Failed to forget generated workout {}: {}", workout_id, e);
+        }
+    }
+
     /// Creates a loop course on Garmin Connect for a run workout.
     /// Uses Garmin's round-trip route API for real road/trail routes,
     /// falling back to a synthetic circle if the API fails.
@@ -701,15 +1356,7 @@ impl GarminClient {
         };
 
         for act in &activities {
-            let is_running = act
-                .get_activity_type()
-                .map(|t| {
-                    let lower = t.to_lowercase();
-                    lower.contains("run") || lower.contains("trail")
-                })
-                .unwrap_or(false);
-
-            if !is_running {
+            if act.normalized_sport() != Sport::Run {
                 continue;
             }
 
@@ -759,7 +1406,7 @@ impl GarminClient {
         }
 
         // Only validate workouts scheduled today or in the future
-        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let today = self.now().format("%Y-%m-%d").to_string();
         let expected_future: Vec<&serde_json::Value> = expected
             .iter()
             .filter(|w| {
@@ -787,7 +1434,7 @@ impl GarminClient {
                 gw.get("workoutName").and_then(|n| n.as_str()),
                 gw.get("workoutId").and_then(|i| i.as_i64()),
             ) {
-                if is_ai_managed_workout(name) {
+                if self.ai_tags.is_tagged(name) {
                     garmin_map.insert(name.to_string(), (id, gw.clone()));
                 }
             }
@@ -800,7 +1447,7 @@ impl GarminClient {
                 .get("workoutName")
                 .and_then(|n| n.as_str())
                 .unwrap_or("Unknown");
-            let workout_name = ensure_ai_workout_name(raw_name);
+            let workout_name = self.ai_tags.tag_name(raw_name);
             let scheduled_date = expected_workout
                 .get("scheduledDate")
                 .and_then(|d| d.as_str())
@@ -941,3 +1588,502 @@ impl GarminClient {
         true
     }
 }
+
+/// Id grouping the workouts one generation or import run uploads: the local
+/// start time plus a random suffix, so two runs in the same second never share it.
+pub fn new_batch_id(prefix: &str, now: chrono::DateTime<chrono::FixedOffset>) -> String {
+    let mut suffix = [0u8; 4];
+    if getrandom::getrandom(&mut suffix).is_err() {
+        suffix = now.timestamp_subsec_nanos().to_be_bytes();
+    }
+    format!(
+        "{}{}-{:08x}",
+        prefix,
+        now.format("%Y%m%dT%H%M%S"),
+        u32::from_be_bytes(suffix)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        calendar_months, dedup_activities, description_with_analysis, orphaned_schedules,
+        parse_daily_summary, parse_sleep_detail, parse_sleep_score, unix_now, AiWorkoutTags,
+        GarminClient,
+    };
+    use crate::config::AppConfig;
+    use crate::db::Database;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn offline_client(db: Arc<Mutex<Database>>, clock: fn() -> u64) -> GarminClient {
+        GarminClient::offline(db, clock)
+    }
+
+    #[tokio::test]
+    async fn expired_refresh_token_fails_with_auth_before_fetching() {
+        let server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([])))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let db = Arc::new(Mutex::new(crate::db::memory_db()));
+        let mut client = offline_client(db, unix_now);
+        client.api = client.api.with_base_url(&server.uri());
+        client.api.expire_refresh_token().await;
+
+        let err = client.fetch_data_with_opts(true).await.unwrap_err();
+        assert!(crate::error::AppError::is_garmin_auth(&err));
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn undo_removes_a_workout_that_failed_to_schedule_and_forgets_missing_ones() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/workout-service/workout"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "workoutId": 7
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/workout-service/schedule/7"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/workout-service/workout/7"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/workout-service/workout/8"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+        let db = Arc::new(Mutex::new(crate::db::memory_db()));
+        let mut client = offline_client(db.clone(), unix_now);
+        client.api = client.api.with_base_url(&server.uri());
+        let config = AppConfig::default();
+
+        let spec = serde_json::json!({
+            "workoutName": "FJ-AI: Easy Run",
+            "sport": "running",
+            "scheduledDate": "2026-10-18",
+            "steps": [{ "phase": "interval", "duration": "30min" }]
+        });
+        let batch_id = super::new_batch_id("", config.now());
+        assert!(client
+            .create_and_schedule_workout_in_batch(&spec, &batch_id)
+            .await
+            .is_err());
+        db.lock()
+            .await
+            .record_generated_workout(&batch_id, 8, "FJ-AI: Gone", None)
+            .unwrap();
+
+        assert_eq!(client.undo_last_generation().await.unwrap(), Some(1));
+        assert_eq!(client.undo_last_generation().await.unwrap(), None);
+        assert_ne!(batch_id, super::new_batch_id("", config.now()));
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn a_second_undo_leaves_the_older_batch_alone() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/workout-service/workout/2"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/workout-service/workout/1"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let db = Arc::new(Mutex::new(crate::db::memory_db()));
+        let mut client = offline_client(db.clone(), unix_now);
+        client.api = client.api.with_base_url(&server.uri());
+        {
+            let db = db.lock().await;
+            db.record_generated_workout("older", 1, "FJ-AI: Strength A", Some("2026-03-10"))
+                .unwrap();
+            db.record_generated_workout("latest", 2, "FJ-AI: Strength B", Some("2026-03-16"))
+                .unwrap();
+        }
+
+        assert_eq!(client.undo_last_generation().await.unwrap(), Some(1));
+        assert_eq!(client.undo_last_generation().await.unwrap(), None);
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn a_server_error_mentioning_404_is_not_treated_as_gone() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/workout-service/workout/5"))
+            .respond_with(ResponseTemplate::new(500).set_body_string("upstream 404 lookup failed"))
+            .mount(&server)
+            .await;
+        let db = Arc::new(Mutex::new(crate::db::memory_db()));
+        let mut client = offline_client(db.clone(), unix_now);
+        client.api = client.api.with_base_url(&server.uri());
+        db.lock()
+            .await
+            .record_generated_workout("latest", 5, "FJ-AI: Easy Run", None)
+            .unwrap();
+
+        assert!(client.undo_last_generation().await.is_err());
+        let batch = db.lock().await.get_last_generation_batch().unwrap();
+        assert_eq!(batch.map(|(_, ids)| ids), Some(vec![5]));
+    }
+
+    #[tokio::test]
+    async fn fetch_within_ttl_is_served_from_cache() {
+        let db = Arc::new(Mutex::new(crate::db::memory_db()));
+        let cached = serde_json::json!({
+            "activities": [{ "activityId": 42, "activityName": "Cached Run", "startTimeLocal": "2026-03-01 07:00:00" }],
+            "plans": []
+        });
+        db.lock()
+            .await
+            .set_garmin_cache(&cached.to_string())
+            .unwrap();
+
+        // Two minutes later, still inside the one hour TTL. A network fetch would
+        // fail without tokens and return no activities.
+        let client = offline_client(db, || unix_now() + 120);
+        let response = client.fetch_data().await.unwrap();
+        assert_eq!(response.activities.len(), 1);
+        assert_eq!(response.activities[0].id, Some(42));
+    }
+
+    #[tokio::test]
+    async fn failed_fetch_falls_back_to_the_last_good_copy() {
+        let db = Arc::new(Mutex::new(crate::db::memory_db()));
+        let good = serde_json::json!({
+            "activities": [{ "activityId": 7, "activityName": "Last Good Run", "startTimeLocal": "2026-03-01 07:00:00" }],
+            "plans": []
+        });
+        {
+            let db = db.lock().await;
+            db.set_garmin_cache(&good.to_string()).unwrap();
+            db.set_garmin_last_good(&good.to_string()).unwrap();
+            // A manual refresh clears the TTL cache but must not lose the last good copy.
+            db.clear_garmin_cache().unwrap();
+        }
+
+        // Three hours later the cache has expired and Garmin is down.
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        let mut client = offline_client(db, || unix_now() + 3 * 3600 + 60);
+        client.api = client.api.with_base_url(&server.uri());
+        let response = client.fetch_data().await.unwrap();
+        assert_eq!(response.activities.len(), 1);
+        assert_eq!(response.activities[0].id, Some(7));
+        let fetched_at = response.stale_since.expect("fallback data is marked stale");
+        assert_eq!(response.stale_hours(fetched_at + 3 * 3600 + 60), Some(3));
+    }
+
+    #[tokio::test]
+    async fn activity_temperatures_are_looked_up_once() {
+        let config = AppConfig::default();
+        let db = Arc::new(Mutex::new(crate::db::memory_db()));
+        let started = config.now().format("%Y-%m-%d 07:00:00").to_string();
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/activitylist-service/activities/search/activities"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!([{
+                    "activityId": 11,
+                    "activityName": "Hot Run",
+                    "activityType": { "typeKey": "running" },
+                    "startTimeLocal": started
+                }])),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/activity-service/activity/11"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "summaryDTO": { "minTemperature": 24.0, "maxTemperature": 31.0 }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+            .mount(&server)
+            .await;
+        let mut client = offline_client(db, unix_now);
+        client.api = client.api.with_base_url(&server.uri());
+
+        for _ in 0..2 {
+            let response = client.fetch_data_with_opts(true).await.unwrap();
+            assert_eq!(response.activities[0].max_temperature, Some(31.0));
+            assert_eq!(response.activities[0].min_temperature, Some(24.0));
+        }
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn cached_display_name_covers_a_failed_profile_fetch() {
+        let db = Arc::new(Mutex::new(crate::db::memory_db()));
+        // The profile fetch fails; every other endpoint answers with empty data.
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/userprofile-service/socialProfile"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/activitylist-service/activities/search/activities"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([])))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+            .mount(&server)
+            .await;
+        let mut client = offline_client(db.clone(), unix_now);
+        client.api = client.api.with_base_url(&server.uri());
+        let named_requests = || async {
+            server
+                .received_requests()
+                .await
+                .unwrap()
+                .iter()
+                .filter(|r| r.url.path().ends_with("/runner42"))
+                .map(|r| r.url.path().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // Without a known name the dependent calls are skipped entirely.
+        client.fetch_data_with_opts(true).await.unwrap();
+        assert!(!server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .any(|r| r.url.path().contains("dailySleepData")));
+
+        db.lock().await.set_display_name("runner42").unwrap();
+        client.fetch_data_with_opts(true).await.unwrap();
+        let paths = named_requests().await;
+        assert_eq!(
+            paths
+                .iter()
+                .filter(|p| p.contains("/dailySleepData/"))
+                .count(),
+            7
+        );
+        assert!(paths.contains(&"/usersummary-service/usersummary/daily/runner42".to_string()));
+        assert!(paths.contains(&"/userstats-service/wellness/daily/runner42".to_string()));
+    }
+
+    #[test]
+    fn analysis_replaces_an_earlier_one_and_keeps_athlete_notes() {
+        let first = description_with_analysis(None, "Solid aerobic run.");
+        assert_eq!(first, "--- Coach analysis ---\nSolid aerobic run.");
+
+        let with_notes = description_with_analysis(Some("Felt heavy legs"), "Good pacing.\n");
+        assert_eq!(
+            with_notes,
+            "Felt heavy legs\n\n--- Coach analysis ---\nGood pacing."
+        );
+        let rewritten = description_with_analysis(Some(&with_notes), "Better pacing.");
+        assert_eq!(
+            rewritten,
+            "Felt heavy legs\n\n--- Coach analysis ---\nBetter pacing."
+        );
+        assert_eq!(rewritten.matches("Coach analysis").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn analysis_is_written_to_the_activity_description() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/activity-service/activity/42"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "activityId": 42,
+                "description": "Windy\n\n--- Coach analysis ---\nOld take."
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/activity-service/activity/42"))
+            .and(body_json(serde_json::json!({
+                "activityId": 42,
+                "description": "Windy\n\n--- Coach analysis ---\nNew take."
+            })))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let db = Arc::new(Mutex::new(crate::db::memory_db()));
+        let mut client = offline_client(db, unix_now);
+        client.api = client.api.with_base_url(&server.uri());
+        client
+            .write_activity_analysis(42, "New take.")
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn calendar_months_roll_over_the_year() {
+        assert_eq!(
+            calendar_months(2026, 10, 4),
+            vec![(2026, 10), (2026, 11), (2027, 0), (2027, 1)]
+        );
+        assert_eq!(calendar_months(2026, 0, 1), vec![(2026, 0)]);
+        assert!(calendar_months(2026, 0, 0).is_empty());
+    }
+
+    #[test]
+    fn duplicate_activities_collapse_to_the_richer_copy() {
+        let activities: Vec<crate::models::GarminActivity> = serde_json::from_value(
+            serde_json::json!([
+                { "activityId": 1, "activityName": "Manual Strength", "startTimeLocal": "2026-03-10 18:00:00",
+                  "activityType": { "typeKey": "strength_training" }, "duration": 3600.0 },
+                { "activityId": 2, "activityName": "Morning Run", "startTimeLocal": "2026-03-11 07:00:00",
+                  "activityType": { "typeKey": "running" }, "duration": 1800.0, "distance": 5000.0 },
+                { "activityId": 3, "activityName": "Strength", "startTimeLocal": "2026-03-10 18:00:40",
+                  "activityType": { "typeKey": "strength_training" }, "duration": 3500.0, "averageHR": 110.0,
+                  "sets": { "exerciseSets": [
+                      { "setType": "ACTIVE", "exercises": [{ "category": "SQUAT" }], "repetitionCount": 5, "weight": 100000.0 }
+                  ] } },
+                { "activityId": 2, "activityName": "Morning Run", "startTimeLocal": "2026-03-11 07:00:00",
+                  "activityType": { "typeKey": "running" }, "duration": 1800.0, "distance": 5000.0 },
+                { "activityId": 4, "activityName": "Evening Run", "startTimeLocal": "2026-03-11 07:00:30",
+                  "activityType": { "typeKey": "running" }, "duration": 900.0 }
+            ]),
+        )
+        .unwrap();
+
+        let deduped = dedup_activities(activities);
+        let ids: Vec<Option<i64>> = deduped.iter().map(|a| a.id).collect();
+        // The device copy with sets replaces the manual entry; the repeated id 2 is
+        // dropped; the much shorter run at the same time is kept.
+        assert_eq!(ids, vec![Some(3), Some(2), Some(4)]);
+    }
+
+    #[test]
+    fn schedules_pointing_at_deleted_workouts_are_orphans() {
+        let workouts = vec![
+            serde_json::json!({ "workoutId": 10, "workoutName": "FJ-AI: Push" }),
+            serde_json::json!({ "workoutId": 11, "workoutName": "Easy Run" }),
+        ];
+        let calendar_items = vec![
+            serde_json::json!({ "id": 100, "itemType": "workout", "workoutId": 10, "title": "FJ-AI: Push", "date": "2026-03-10" }),
+            serde_json::json!({ "id": 101, "itemType": "workout", "workoutId": 12, "title": "FJ-AI: Pull", "date": "2026-03-11" }),
+            serde_json::json!({ "id": 102, "itemType": "race", "title": "City Marathon", "date": "2026-04-01" }),
+            serde_json::json!({ "id": 103, "itemType": "activity", "title": "Morning Run", "date": "2026-03-09" }),
+        ];
+
+        let orphans = orphaned_schedules(&calendar_items, &workouts);
+        assert_eq!(orphans.len(), 1);
+        assert_eq!(orphans[0].schedule_id, 101);
+        assert_eq!(orphans[0].workout_id, 12);
+        assert_eq!(orphans[0].title, "FJ-AI: Pull");
+        assert_eq!(orphans[0].date, "2026-03-11");
+    }
+
+    #[test]
+    fn daily_summary_maps_fields_and_tolerates_missing_ones() {
+        let summary = parse_daily_summary(&serde_json::json!({
+            "totalSteps": 11234,
+            "floorsAscended": 8.6,
+            "moderateIntensityMinutes": 20,
+            "vigorousIntensityMinutes": 15,
+            "averageStressLevel": 31,
+            "activeKilocalories": 640.0
+        }));
+        assert_eq!(summary.steps, Some(11234));
+        assert_eq!(summary.intensity_minutes, Some(50));
+        assert_eq!(summary.stress_avg, Some(31));
+        assert_eq!(summary.active_calories, Some(640));
+
+        let sparse = parse_daily_summary(&serde_json::json!({
+            "totalSteps": null,
+            "averageStressLevel": -1
+        }));
+        assert_eq!(sparse, crate::models::DailySummary::default());
+    }
+
+    #[test]
+    fn sleep_detail_maps_stages_and_skips_nights_without_data() {
+        let detail = parse_sleep_detail(&serde_json::json!({
+            "dailySleepDTO": {
+                "sleepTimeSeconds": 27000,
+                "deepSleepSeconds": 5400,
+                "remSleepSeconds": 6300,
+                "lightSleepSeconds": 15300,
+                "awakeSleepSeconds": 900
+            }
+        }))
+        .unwrap();
+        assert_eq!(detail.total_seconds, Some(27000));
+        assert_eq!(detail.deep_seconds, Some(5400));
+        assert_eq!(detail.awake_seconds, Some(900));
+
+        let empty = serde_json::json!({
+            "dailySleepDTO": { "sleepTimeSeconds": null, "deepSleepSeconds": null }
+        });
+        assert_eq!(parse_sleep_detail(&empty), None);
+        assert_eq!(parse_sleep_detail(&serde_json::json!({})), None);
+    }
+
+    #[test]
+    fn sleep_score_is_none_for_nights_without_a_score() {
+        let scored = serde_json::json!({
+            "dailySleepDTO": { "sleepScores": { "overall": { "value": 78 } } }
+        });
+        assert_eq!(parse_sleep_score(&scored), Some(78));
+        let unscored = serde_json::json!({ "dailySleepDTO": { "sleepScores": null } });
+        assert_eq!(parse_sleep_score(&unscored), None);
+    }
+
+    #[test]
+    fn ai_workout_names_are_tagged_once() {
+        let tags = AiWorkoutTags::default();
+        let tagged = tags.tag_name("Upper Body Strength");
+        assert_eq!(tagged, "FJ-AI: Upper Body Strength");
+        assert_eq!(tags.tag_name(&tagged), tagged);
+        assert_eq!(tags.tag_name("FJ-AI:Test"), "FJ-AI:Test");
+
+        assert!(tags.is_tagged(&tagged));
+        assert!(tags.is_tagged(" FJ-AI: Push"));
+        assert!(!tags.is_tagged("Push Day (FJ-AI: copy)"));
+        assert!(!tags.is_tagged("Garmin Coach Run"));
+    }
+
+    #[test]
+    fn cleanup_honors_the_configured_tag_and_legacy_prefixes() {
+        let config = crate::config::AppConfig {
+            ai_workout_tag: "Coach:".to_string(),
+            cleanup_name_prefixes: vec!["AI -".to_string(), " ".to_string()],
+            ..Default::default()
+        };
+        let tags = AiWorkoutTags::from_config(&config);
+
+        assert_eq!(tags.tag_name("Tempo"), "Coach: Tempo");
+        assert_eq!(tags.cleanup_rule("Coach: Tempo"), Some("Coach:"));
+        assert_eq!(tags.cleanup_rule("AI - Leg Day"), Some("AI -"));
+        // Legacy names are only cleaned up, never treated as freshly tagged.
+        assert!(!tags.is_tagged("AI - Leg Day"));
+        // Blank prefixes are dropped instead of matching every workout.
+        assert_eq!(tags.cleanup_rule("My Own Run"), None);
+        assert_eq!(tags.cleanup_rule("FJ-AI: Old Default"), None);
+    }
+}
@@ -4,6 +4,9 @@ use lazy_static::lazy_static;
 use regex::Regex;
 use reqwest::Client;
 use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tracing::info;
 
 lazy_static! {
     static ref CSRF_RE: Regex = Regex::new(r#"name="_csrf"\s+value="(.+?)""#).unwrap();
@@ -21,6 +24,9 @@ pub struct GarminLoginSession {
     pub signin_params: HashMap<&'static str, &'static str>,
 }
 
+/// How long an MFA login started through the API may wait for its code.
+pub const MFA_SESSION_TTL: Duration = Duration::from_secs(10 * 60);
+
 pub enum LoginResult {
     Success(OAuth1Token, Box<OAuth2Token>),
     MfaRequired(GarminLoginSession),
@@ -257,3 +263,94 @@ async fn complete_login(client: Client, ticket: String) -> Result<(OAuth1Token,
 
     Ok((oauth1, final_oauth2))
 }
+
+/// API logins waiting for their MFA code, keyed by an opaque session id.
+/// Sessions expire after `ttl` and can only be redeemed once.
+pub struct PendingLogins {
+    ttl: Duration,
+    sessions: HashMap<String, (Instant, GarminLoginSession)>,
+}
+
+impl PendingLogins {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            sessions: HashMap::new(),
+        }
+    }
+
+    /// Stores `session` and returns the id the client submits with the MFA code.
+    pub fn insert(&mut self, session: GarminLoginSession) -> Result<String> {
+        self.purge_expired();
+        let id = new_session_id()?;
+        self.sessions.insert(id.clone(), (Instant::now(), session));
+        Ok(id)
+    }
+
+    /// Removes and returns the session for `id` unless it is unknown or expired.
+    pub fn take(&mut self, id: &str) -> Option<GarminLoginSession> {
+        self.purge_expired();
+        self.sessions.remove(id).map(|(_, session)| session)
+    }
+
+    fn purge_expired(&mut self) {
+        let ttl = self.ttl;
+        self.sessions
+            .retain(|_, (started, _)| started.elapsed() < ttl);
+    }
+}
+
+/// 128 random bits from the OS CSPRNG, as hex.
+fn new_session_id() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).map_err(|e| anyhow!("No OS randomness available: {}", e))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Writes both token files to `token_dir` (readable by the owner only).
+pub fn save_tokens(token_dir: &Path, oauth1: &OAuth1Token, oauth2: &OAuth2Token) -> Result<()> {
+    std::fs::create_dir_all(token_dir)?;
+    let o1_path = token_dir.join("oauth1_token.json");
+    let o2_path = token_dir.join("oauth2_token.json");
+    write_secret_json_file(&o1_path, oauth1)?;
+    write_secret_json_file(&o2_path, oauth2)?;
+    info!(
+        "Saved credentials to {} and {}",
+        o1_path.display(),
+        o2_path.display()
+    );
+    Ok(())
+}
+
+fn write_secret_json_file<T: serde::Serialize>(path: &Path, value: &T) -> Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(value)?)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GarminLoginSession, PendingLogins};
+    use std::time::Duration;
+
+    #[test]
+    fn pending_login_is_redeemed_once_and_expires() {
+        let mut pending = PendingLogins::new(Duration::from_secs(600));
+        let id = pending.insert(GarminLoginSession::new().unwrap()).unwrap();
+        let other = pending.insert(GarminLoginSession::new().unwrap()).unwrap();
+        assert_eq!(id.len(), 32);
+        assert_ne!(id, other);
+
+        assert!(pending.take("unknown").is_none());
+        assert!(pending.take(&id).is_some());
+        assert!(pending.take(&id).is_none());
+
+        let mut expiring = PendingLogins::new(Duration::ZERO);
+        let id = expiring.insert(GarminLoginSession::new().unwrap()).unwrap();
+        assert!(expiring.take(&id).is_none());
+    }
+}
@@ -0,0 +1,151 @@
+use crate::db::Database;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::error;
+
+/// "Generation in progress" marker kept in the database's `kv_store`, so the
+/// API server, the Signal bot and the daemon never run cleanup + create at the
+/// same time, even when they run as separate processes.
+#[derive(Clone)]
+pub struct GenerationGuard {
+    db: Arc<Mutex<Database>>,
+    /// A run that started longer ago than this is treated as abandoned.
+    stale_after: Duration,
+}
+
+/// Held for the duration of one generation; clears the marker when dropped,
+/// including when the pipeline returns early with an error. A permit that
+/// outlived `stale_after` leaves a newer run's marker alone.
+pub struct GenerationPermit {
+    db: Arc<Mutex<Database>>,
+    token: String,
+}
+
+impl GenerationGuard {
+    /// `db` is a connection of its own, so the marker can be released from
+    /// `Drop` without waiting on the shared async database lock.
+    pub fn new(db: Database, stale_after: Duration) -> Self {
+        Self {
+            db: Arc::new(Mutex::new(db)),
+            stale_after,
+        }
+    }
+
+    /// Marks a generation as started, or returns `None` if another one started
+    /// less than `stale_after` ago.
+    pub fn try_start(&self) -> Option<GenerationPermit> {
+        let token = new_token();
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        let claimed = self
+            .db
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .try_claim_generation(&token, now_ms, self.stale_after.as_millis() as u64);
+        match claimed {
+            Ok(true) => Some(GenerationPermit {
+                db: self.db.clone(),
+                token,
+            }),
+            Ok(false) => None,
+            Err(e) => {
+                error!("Failed to claim the generation marker: {}", e);
+                None
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for GenerationPermit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GenerationPermit")
+            .field("token", &self.token)
+            .finish()
+    }
+}
+
+impl Drop for GenerationPermit {
+    fn drop(&mut self) {
+        let db = self.db.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = db.release_generation(&self.token) {
+            error!("Failed to release the generation marker: {}", e);
+        }
+    }
+}
+
+/// Identifies one permit's claim; unique across processes.
+fn new_token() -> String {
+    let mut suffix = [0u8; 8];
+    if getrandom::getrandom(&mut suffix).is_err() {
+        suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default()
+            .to_be_bytes();
+    }
+    format!("{}-{:016x}", std::process::id(), u64::from_be_bytes(suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GenerationGuard;
+    use crate::db::memory_db;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn overlapping_generations_run_once_and_release_on_error() {
+        let guard = GenerationGuard::new(memory_db(), Duration::from_secs(600));
+
+        let run = |guard: GenerationGuard| async move {
+            let _permit = guard.try_start()?;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Some(Err::<(), &str>("pipeline failed"))
+        };
+        let (a, b) = tokio::join!(run(guard.clone()), run(guard.clone()));
+        assert_eq!([a.is_some(), b.is_some()].iter().filter(|r| **r).count(), 1);
+
+        // The failed run above dropped its permit, so a new one can start.
+        assert!(guard.try_start().is_some());
+    }
+
+    #[test]
+    fn stale_generation_does_not_block_forever() {
+        let guard = GenerationGuard::new(memory_db(), Duration::ZERO);
+        let _stuck = guard.try_start().unwrap();
+        assert!(guard.try_start().is_some());
+    }
+
+    #[test]
+    fn a_stale_permit_does_not_release_a_newer_run() {
+        let guard = GenerationGuard::new(memory_db(), Duration::from_millis(20));
+        let stale = guard.try_start().unwrap();
+        std::thread::sleep(Duration::from_millis(30));
+        let _current = guard.try_start().unwrap();
+
+        drop(stale);
+        assert!(guard.try_start().is_none());
+    }
+
+    #[test]
+    fn guards_in_separate_processes_share_the_marker() {
+        let path = std::env::temp_dir().join(format!("fj-generation-{}.db", std::process::id()));
+        let open = || {
+            crate::db::Database::new(&crate::config::AppConfig {
+                database_url: path.to_string_lossy().into_owned(),
+                ..crate::config::AppConfig::default()
+            })
+            .unwrap()
+        };
+        // Two guards with their own connections, as `serve` and `daemon` have.
+        let api = GenerationGuard::new(open(), Duration::from_secs(600));
+        let daemon = GenerationGuard::new(open(), Duration::from_secs(600));
+
+        let permit = api.try_start().unwrap();
+        assert!(daemon.try_start().is_none());
+        drop(permit);
+        assert!(daemon.try_start().is_some());
+        let _ = std::fs::remove_file(&path);
+    }
+}
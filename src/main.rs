@@ -4,17 +4,25 @@ mod bot;
 mod coaching;
 pub mod config;
 mod db;
+mod error;
 mod garmin_api;
 mod garmin_client;
 mod garmin_login;
+mod generation;
+mod metrics;
 mod models;
+mod parsing;
+mod plan_export;
+mod plan_import;
+mod profiles;
 mod workout_builder;
 
 use crate::coaching::Coach;
 use crate::db::Database;
+use crate::error::{AppError, AppResult};
 use crate::garmin_client::GarminClient;
 use chrono::Datelike;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tracing::{error, info};
@@ -24,32 +32,85 @@ use tracing::{error, info};
 struct Cli {
     #[arg(
         long,
-        help = "Run as a background daemon calculating workloads every 5min"
+        global = true,
+        help = "Garmin account to use (tokens in secrets/<account>/), overrides GARMIN_ACCOUNT"
     )]
-    daemon: bool,
-    #[arg(long, help = "Start Signal bot listener")]
-    signal: bool,
-    #[arg(long, help = "Start the web dashboard REST API")]
-    api: bool,
-    #[arg(long, help = "Login to Garmin Connect globally")]
-    login: bool,
-    #[arg(long, help = "Test uploading a local JSON file to Garmin")]
-    test_upload: Option<String>,
-    #[arg(long, help = "Test fetching and printing a specific workout ID")]
-    test_fetch: Option<String>,
-    #[arg(long, help = "Test fetching an arbitrary Garmin URL")]
-    test_fetch_url: Option<String>,
-    #[arg(long, help = "Delete ALL previously generated AI workouts in Garmin")]
-    delete_workouts: bool,
-    #[arg(long, help = "Test force-refreshing OAuth2 Garmin tokens")]
-    test_refresh: bool,
-    #[arg(long, help = "Debug: dump all activities from last 7 days with distances")]
-    debug_weekly: bool,
+    account: Option<String>,
+    /// Runs the pipeline once (`run`) when omitted.
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    #[command(about = "Login to Garmin Connect (interactive, with MFA)")]
+    Login,
+    #[command(about = "Start the web dashboard REST API")]
+    Serve,
+    #[command(about = "Start the Signal bot listener")]
+    Signal,
+    #[command(about = "Run as a background daemon calculating workloads every 5min")]
+    Daemon {
+        #[arg(long, help = "Also start the Signal bot listener")]
+        signal: bool,
+    },
+    #[command(about = "Run the coach pipeline once")]
+    Run {
+        #[arg(
+            long,
+            help = "Print the Garmin payloads and Signal messages instead of sending them"
+        )]
+        dry_run: bool,
+    },
+    #[command(
+        about = "Create and schedule a JSON plan of workouts (with scheduledDate) on Garmin"
+    )]
+    ImportPlan {
+        path: String,
+        #[arg(long, help = "Print the Garmin payloads instead of uploading them")]
+        dry_run: bool,
+    },
+    #[command(about = "Garmin Connect debugging utilities")]
+    Garmin {
+        #[command(subcommand)]
+        command: GarminCommand,
+    },
+    #[command(about = "Manage AI-generated workouts in Garmin")]
+    Workouts {
+        #[command(subcommand)]
+        command: WorkoutsCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum GarminCommand {
+    #[command(about = "Test uploading a local JSON file to Garmin")]
+    TestUpload { file: String },
+    #[command(about = "Test fetching and printing a specific workout ID")]
+    TestFetch { workout_id: String },
+    #[command(about = "Test fetching an arbitrary Garmin URL")]
+    TestFetchUrl { url: String },
+    #[command(about = "Test force-refreshing OAuth2 Garmin tokens")]
+    TestRefresh,
+    #[command(about = "Debug: dump all activities from last 7 days with distances")]
+    DebugWeekly,
+}
+
+#[derive(Subcommand, Debug)]
+enum WorkoutsCommand {
+    #[command(about = "Delete ALL previously generated AI workouts in Garmin")]
+    Delete,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tracing_subscriber::fmt::init();
+    // RUST_LOG overrides the level; without it a bare EnvFilter would only show errors.
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .init();
     dotenvy::dotenv().ok();
     info!("Starting Fitness Coach...");
 
@@ -87,271 +148,409 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let coach = Arc::new(Coach::new());
 
     let args = Cli::parse();
-    let is_daemon = args.daemon;
-    let is_signal = args.signal;
-    let is_api = args.api;
-
-    if args.login {
-        use std::io::{self, Write};
-
-        print!("Garmin Email: ");
-        io::stdout().flush()?;
-        let mut email = String::new();
-        io::stdin().read_line(&mut email)?;
-        let email = email.trim();
-
-        let password = rpassword::prompt_password("Garmin Password: ")?;
-
-        info!("Logging into Garmin Connect...");
-        match crate::garmin_login::login_step_1(email, &password).await {
-            Ok(crate::garmin_login::LoginResult::Success(o1, o2)) => {
-                info!("Login successful!");
-                write_secret_json_file("secrets/oauth1_token.json", &o1)?;
-                write_secret_json_file("secrets/oauth2_token.json", &o2)?;
-                info!(
-                    "Saved credentials to secrets/oauth1_token.json and secrets/oauth2_token.json"
-                );
-            }
-            Ok(crate::garmin_login::LoginResult::MfaRequired(session)) => {
-                print!("Garmin MFA Code (Enter to submit): ");
-                io::stdout().flush()?;
-                let mut mfa_code = String::new();
-                io::stdin().read_line(&mut mfa_code)?;
-                let mfa_code = mfa_code.trim();
-
-                info!("Submitting MFA code...");
-                match crate::garmin_login::login_step_2_mfa(session, mfa_code).await {
-                    Ok((o1, o2)) => {
-                        info!("MFA successful!");
-                        write_secret_json_file("secrets/oauth1_token.json", &o1)?;
-                        write_secret_json_file("secrets/oauth2_token.json", &o2)?;
-                        info!("Saved credentials to secrets/oauth1_token.json and secrets/oauth2_token.json");
-                    }
-                    Err(e) => info!("MFA login failed: {}", e),
-                }
+    let config = match &args.account {
+        Some(account) => Arc::new(crate::config::AppConfig {
+            garmin_account: Some(account.clone()),
+            ..(*config).clone()
+        }),
+        None => config,
+    };
+    let token_dir = crate::garmin_api::token_dir_for_account(config.garmin_account.as_deref())
+        .map_err(|e| AppError::Config(e.to_string()))?;
+    let command = args.command.unwrap_or(Command::Run { dry_run: false });
+
+    if let Command::Login = command {
+        return login(&token_dir).await;
+    }
+
+    let garmin_client = Arc::new(GarminClient::new(database.clone(), &config));
+    let guard_window = std::time::Duration::from_secs(config.generation_guard_secs);
+    let generation_guard =
+        crate::generation::GenerationGuard::new(Database::new(&config)?, guard_window);
+
+    match command {
+        Command::Login => unreachable!("login runs before the Garmin client is created"),
+        Command::ImportPlan { path, dry_run } => {
+            import_plan(&path, dry_run, &garmin_client, &database, &config).await?;
+        }
+        Command::Garmin { command } => {
+            run_garmin_command(command, &garmin_client, &config).await?;
+        }
+        Command::Workouts {
+            command: WorkoutsCommand::Delete,
+        } => delete_ai_workouts(&garmin_client).await?,
+        Command::Serve => {
+            info!("Starting Fitness Coach in API mode.");
+            if let Err(e) = api::run_server(
+                config.clone(),
+                database.clone(),
+                garmin_client.clone(),
+                coach.clone(),
+                generation_guard.clone(),
+            )
+            .await
+            {
+                error!("API Server crashed: {}", e);
             }
-            Err(e) => info!("Login failed: {}", e),
         }
-        return Ok(());
+        Command::Signal => {
+            crate::bot::start_pending_message_flusher(database.clone(), config.clone());
+            bot::BotController::new(
+                config.clone(),
+                garmin_client.clone(),
+                coach.clone(),
+                database.clone(),
+                generation_guard.clone(),
+            )
+            .run()
+            .await;
+        }
+        Command::Daemon { signal } => {
+            run_daemon(
+                signal,
+                config.clone(),
+                garmin_client.clone(),
+                coach.clone(),
+                database.clone(),
+                generation_guard,
+            )
+            .await;
+        }
+        Command::Run { dry_run } => {
+            run_coach_pipeline(
+                config.clone(),
+                garmin_client.clone(),
+                coach.clone(),
+                database.clone(),
+                true,
+                dry_run,
+            )
+            .await?;
+        }
     }
 
-    let garmin_client = Arc::new(GarminClient::new(database.clone()));
+    Ok(())
+}
 
-    if let Some(file) = args.test_upload {
-        info!("Testing workout upload with file: {}", file);
-        let json_str = std::fs::read_to_string(&file)?;
-        let builder = crate::workout_builder::WorkoutBuilder::new();
-        let parsed: serde_json::Value = serde_json::from_str(&json_str)?;
-        let workouts = if let Some(arr) = parsed.as_array() {
-            arr.clone()
-        } else {
-            vec![parsed]
-        };
+/// Interactive Garmin Connect login; saves the tokens to `token_dir`.
+async fn login(token_dir: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::{self, Write};
 
-        for w in workouts {
-            let payload = builder.build_workout_payload(&w, false);
-            info!(
-                "Sending payload: {}",
-                serde_json::to_string_pretty(&payload)?
-            );
-            match garmin_client
-                .api
-                .connectapi_post("/workout-service/workout", &payload)
-                .await
-            {
-                Ok(res) => info!("Success! Workout ID: {:?}", res.get("workoutId")),
-                Err(e) => info!("Failed to create workout: {}", e),
+    print!("Garmin Email: ");
+    io::stdout().flush()?;
+    let mut email = String::new();
+    io::stdin().read_line(&mut email)?;
+    let email = email.trim();
+
+    let password = rpassword::prompt_password("Garmin Password: ")?;
+
+    info!("Logging into Garmin Connect...");
+    match crate::garmin_login::login_step_1(email, &password).await {
+        Ok(crate::garmin_login::LoginResult::Success(o1, o2)) => {
+            info!("Login successful!");
+            crate::garmin_login::save_tokens(token_dir, &o1, &o2)?;
+        }
+        Ok(crate::garmin_login::LoginResult::MfaRequired(session)) => {
+            print!("Garmin MFA Code (Enter to submit): ");
+            io::stdout().flush()?;
+            let mut mfa_code = String::new();
+            io::stdin().read_line(&mut mfa_code)?;
+            let mfa_code = mfa_code.trim();
+
+            info!("Submitting MFA code...");
+            match crate::garmin_login::login_step_2_mfa(session, mfa_code).await {
+                Ok((o1, o2)) => {
+                    info!("MFA successful!");
+                    crate::garmin_login::save_tokens(token_dir, &o1, &o2)?;
+                }
+                Err(e) => info!("MFA login failed: {}", e),
             }
         }
+        Err(e) => info!("Login failed: {}", e),
+    }
+    Ok(())
+}
+
+/// Validates a plan file and creates and schedules its workouts on Garmin.
+async fn import_plan(
+    path: &str,
+    dry_run: bool,
+    garmin_client: &GarminClient,
+    database: &Arc<Mutex<Database>>,
+    config: &crate::config::AppConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!("Importing training plan from {}", path);
+    let json_str = std::fs::read_to_string(path)?;
+    let today = config.now().date_naive();
+    let plan =
+        match crate::plan_import::parse_plan(&json_str, &garmin_client.workout_builder(), today) {
+            Ok(plan) => plan,
+            Err(e) => {
+                error!("Cannot import {}: {:#}", path, e);
+                std::process::exit(1);
+            }
+        };
+    if !plan.unresolved.is_empty() {
+        info!(
+            "{} exercise name(s) did not resolve and will upload without a specific exercise: {}",
+            plan.unresolved.len(),
+            plan.unresolved.join(", ")
+        );
     }
 
-    if let Some(workout_id) = args.test_fetch {
-        info!("Fetching workout ID '{}' from Garmin...", workout_id);
-        let endpoint = format!("/workout-service/workout/{}", workout_id);
-        match garmin_client.api.connectapi_get(&endpoint).await {
-            Ok(res) => info!("Response Payload:\n{}", serde_json::to_string_pretty(&res)?),
-            Err(e) => info!("Failed: {}", e),
+    if dry_run {
+        for payload in &plan.payloads {
+            info!(
+                "[dry-run] Would upload and schedule:\n{}",
+                serde_json::to_string_pretty(payload)?
+            );
         }
         return Ok(());
     }
 
-    if let Some(url) = args.test_fetch_url {
-        info!("Fetching URL '{}' from Garmin...", url);
-        match garmin_client.api.connectapi_get(&url).await {
-            Ok(res) => info!("Response Payload:\n{}", serde_json::to_string_pretty(&res)?),
-            Err(e) => info!("Failed: {}", e),
+    // Imported workouts keep their names untagged so the next AI run's
+    // cleanup leaves the plan alone; /undo still removes the batch.
+    let batch_id = crate::garmin_client::new_batch_id("import-", config.now());
+    let mut imported = 0;
+    for w in &plan.workouts {
+        match garmin_client
+            .create_and_schedule_workout_in_batch(w, &batch_id)
+            .await
+        {
+            Ok(msg) => {
+                info!("{}", msg);
+                imported += 1;
+            }
+            Err(e) => error!("{}", e),
         }
-        return Ok(());
     }
+    info!(
+        "Imported {} of {} workouts from {}",
+        imported,
+        plan.workouts.len(),
+        path
+    );
+    let _ = database.lock().await.clear_garmin_cache();
+    Ok(())
+}
 
-    if args.delete_workouts {
-        info!("Fetching workouts to delete...");
-        match garmin_client.api.get_workouts().await {
-            Ok(workouts) => {
-                if let Some(arr) = workouts.as_array() {
-                    let mut to_delete = Vec::new();
-                    for w in arr {
-                        if let Some(name) = w.get("workoutName").and_then(|n| n.as_str()) {
-                            if crate::garmin_client::is_ai_managed_workout(name) {
-                                if let Some(wid) = w.get("workoutId").and_then(|i| i.as_i64()) {
-                                    to_delete.push((wid, name.to_string()));
-                                }
-                            }
-                        }
-                    }
+async fn run_garmin_command(
+    command: GarminCommand,
+    garmin_client: &GarminClient,
+    config: &crate::config::AppConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        GarminCommand::TestUpload { file } => {
+            info!("Testing workout upload with file: {}", file);
+            let json_str = std::fs::read_to_string(&file)?;
+            let builder = garmin_client.workout_builder();
+            let parsed: serde_json::Value = serde_json::from_str(&json_str)?;
+            let workouts = if let Some(arr) = parsed.as_array() {
+                arr.clone()
+            } else {
+                vec![parsed]
+            };
 
-                    info!("Found {} workouts to delete.", to_delete.len());
-                    for (wid, name) in to_delete {
-                        let endpoint = format!("/workout-service/workout/{}", wid);
-                        match garmin_client.api.connectapi_delete(&endpoint).await {
-                            Ok(_) => info!("Deleted {} ({})", wid, name),
-                            Err(e) => info!("Failed to delete {}: {}", wid, e),
-                        }
-                    }
+            for w in workouts {
+                let payload = builder.build_workout_payload(&w, false);
+                info!(
+                    "Sending payload: {}",
+                    serde_json::to_string_pretty(&payload)?
+                );
+                match garmin_client
+                    .api
+                    .connectapi_post("/workout-service/workout", &payload)
+                    .await
+                {
+                    Ok(res) => info!("Success! Workout ID: {:?}", res.get("workoutId")),
+                    Err(e) => info!("Failed to create workout: {}", e),
                 }
             }
-            Err(e) => info!("Failed to fetch workouts: {}", e),
         }
-        return Ok(());
-    }
+        GarminCommand::TestFetch { workout_id } => {
+            info!("Fetching workout ID '{}' from Garmin...", workout_id);
+            let endpoint = format!("/workout-service/workout/{}", workout_id);
+            match garmin_client.api.connectapi_get(&endpoint).await {
+                Ok(res) => info!("Response Payload:\n{}", serde_json::to_string_pretty(&res)?),
+                Err(e) => info!("Failed: {}", e),
+            }
+        }
+        GarminCommand::TestFetchUrl { url } => {
+            info!("Fetching URL '{}' from Garmin...", url);
+            match garmin_client.api.connectapi_get(&url).await {
+                Ok(res) => info!("Response Payload:\n{}", serde_json::to_string_pretty(&res)?),
+                Err(e) => info!("Failed: {}", e),
+            }
+        }
+        GarminCommand::TestRefresh => {
+            info!("Testing OAuth2 Token Refresh...");
+            let temp_db = Arc::new(Mutex::new(
+                Database::new(config).expect("Failed to initialize SQLite database"),
+            ));
+            let garmin_client_refresh = crate::garmin_client::GarminClient::new(temp_db, config);
+            match garmin_client_refresh.api.refresh_oauth2().await {
+                Ok(_) => info!("Successfully refreshed token!"),
+                Err(e) => info!("Failed to refresh: {}", e),
+            }
+        }
+        GarminCommand::DebugWeekly => {
+            info!("=== DEBUG WEEKLY: Fetching Garmin data ===");
+            match garmin_client.fetch_data().await {
+                Ok(data) => {
+                    let now = config.now().naive_local();
+                    let today_str = now.format("%Y-%m-%d").to_string();
+                    let seven_days_ago = now - chrono::Duration::days(7);
+                    let seven_days_ago_str = seven_days_ago.format("%Y-%m-%d").to_string();
+
+                    info!("Date range: {} to {}", seven_days_ago_str, today_str);
+                    info!("Total activities in Garmin response: {}", data.activities.len());
+
+                    let recent: Vec<_> = data.activities.iter()
+                        .filter(|a| a.start_date().is_some_and(|d| d >= seven_days_ago.date()))
+                        .collect();
+
+                    info!("Activities in last 7 days: {}", recent.len());
+                    info!("---");
+
+                    let mut type_stats: std::collections::HashMap<String, (f64, f64, usize)> =
+                        std::collections::HashMap::new();
+
+                    for a in &recent {
+                        let atype = a.get_activity_type().unwrap_or("other").replace('_', " ");
+                        let dist_m = a.distance.unwrap_or(0.0);
+                        let dur_s = a.duration.unwrap_or(0.0);
+                        info!(
+                            "  [{:>20}] {:>50} | date={} | dist={:>8.0}m ({:>6.2}km) | dur={:>6.0}s ({:>5.1}min)",
+                            atype,
+                            a.name.as_deref().unwrap_or("?"),
+                            &a.start_time[..10.min(a.start_time.len())],
+                            dist_m, dist_m / 1000.0,
+                            dur_s, dur_s / 60.0
+                        );
+                        let entry = type_stats.entry(atype).or_insert((0.0, 0.0, 0));
+                        entry.0 += dist_m / 1000.0;
+                        entry.1 += dur_s / 60.0;
+                        entry.2 += 1;
+                    }
 
-    if args.test_refresh {
-        info!("Testing OAuth2 Token Refresh...");
-        let temp_db = Arc::new(Mutex::new(
-            Database::new(&config).expect("Failed to initialize SQLite database"),
-        ));
-        let garmin_client_refresh = crate::garmin_client::GarminClient::new(temp_db);
-        match garmin_client_refresh.api.refresh_oauth2().await {
-            Ok(_) => info!("Successfully refreshed token!"),
-            Err(e) => info!("Failed to refresh: {}", e),
+                    info!("---");
+                    info!("=== PER-TYPE TOTALS ===");
+                    let mut sorted: Vec<_> = type_stats.iter().collect();
+                    sorted.sort_by(|a, b| b.1.0.partial_cmp(&a.1.0).unwrap_or(std::cmp::Ordering::Equal));
+                    let mut grand_dist = 0.0;
+                    let mut grand_dur = 0.0;
+                    for (atype, (dist, dur, count)) in &sorted {
+                        info!("  {} (×{}): {:.1} km, {:.0} mins", atype, count, dist, dur);
+                        grand_dist += dist;
+                        grand_dur += dur;
+                    }
+                    info!("---");
+                    info!("GRAND TOTAL: {:.1} km, {:.0} mins across {} activities", grand_dist, grand_dur, recent.len());
+                }
+                Err(e) => error!("Failed to fetch Garmin data: {}", e),
+            }
         }
-        return Ok(());
     }
+    Ok(())
+}
 
-    if args.debug_weekly {
-        info!("=== DEBUG WEEKLY: Fetching Garmin data ===");
-        match garmin_client.fetch_data().await {
-            Ok(data) => {
-                let now = chrono::Local::now().naive_local();
-                let today_str = now.format("%Y-%m-%d").to_string();
-                let seven_days_ago = now - chrono::Duration::days(7);
-                let seven_days_ago_str = seven_days_ago.format("%Y-%m-%d").to_string();
-
-                info!("Date range: {} to {}", seven_days_ago_str, today_str);
-                info!("Total activities in Garmin response: {}", data.activities.len());
-
-                let recent: Vec<_> = data.activities.iter()
-                    .filter(|a| a.start_time >= seven_days_ago_str)
-                    .collect();
-
-                info!("Activities in last 7 days: {}", recent.len());
-                info!("---");
-
-                let mut type_stats: std::collections::HashMap<String, (f64, f64, usize)> =
-                    std::collections::HashMap::new();
-
-                for a in &recent {
-                    let atype = a.get_activity_type().unwrap_or("other").replace('_', " ");
-                    let dist_m = a.distance.unwrap_or(0.0);
-                    let dur_s = a.duration.unwrap_or(0.0);
-                    info!(
-                        "  [{:>20}] {:>50} | date={} | dist={:>8.0}m ({:>6.2}km) | dur={:>6.0}s ({:>5.1}min)",
-                        atype,
-                        a.name.as_deref().unwrap_or("?"),
-                        &a.start_time[..10.min(a.start_time.len())],
-                        dist_m, dist_m / 1000.0,
-                        dur_s, dur_s / 60.0
-                    );
-                    let entry = type_stats.entry(atype).or_insert((0.0, 0.0, 0));
-                    entry.0 += dist_m / 1000.0;
-                    entry.1 += dur_s / 60.0;
-                    entry.2 += 1;
+/// Deletes every Garmin workout carrying the AI tag or a legacy cleanup prefix.
+async fn delete_ai_workouts(
+    garmin_client: &GarminClient,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!("Fetching workouts to delete...");
+    match garmin_client.api.get_workouts().await {
+        Ok(workouts) => {
+            if let Some(arr) = workouts.as_array() {
+                let mut to_delete = Vec::new();
+                for w in arr {
+                    if let Some(name) = w.get("workoutName").and_then(|n| n.as_str()) {
+                        if let Some(rule) = garmin_client.ai_tags().cleanup_rule(name) {
+                            if let Some(wid) = w.get("workoutId").and_then(|i| i.as_i64()) {
+                                to_delete.push((wid, name.to_string(), rule.to_string()));
+                            }
+                        }
+                    }
                 }
 
-                info!("---");
-                info!("=== PER-TYPE TOTALS ===");
-                let mut sorted: Vec<_> = type_stats.iter().collect();
-                sorted.sort_by(|a, b| b.1.0.partial_cmp(&a.1.0).unwrap_or(std::cmp::Ordering::Equal));
-                let mut grand_dist = 0.0;
-                let mut grand_dur = 0.0;
-                for (atype, (dist, dur, count)) in &sorted {
-                    info!("  {} (×{}): {:.1} km, {:.0} mins", atype, count, dist, dur);
-                    grand_dist += dist;
-                    grand_dur += dur;
+                info!("Found {} workouts to delete.", to_delete.len());
+                for (wid, name, rule) in to_delete {
+                    let endpoint = format!("/workout-service/workout/{}", wid);
+                    match garmin_client.api.connectapi_delete(&endpoint).await {
+                        Ok(_) => info!("Deleted {} ({}, matched '{}')", wid, name, rule),
+                        Err(e) => info!("Failed to delete {}: {}", wid, e),
+                    }
                 }
-                info!("---");
-                info!("GRAND TOTAL: {:.1} km, {:.0} mins across {} activities", grand_dist, grand_dur, recent.len());
             }
-            Err(e) => error!("Failed to fetch Garmin data: {}", e),
-        }
-        return Ok(());
-    }
-
-    if is_api {
-        info!("Starting Fitness Coach in API mode.");
-        if let Err(e) = api::run_server(
-            config.clone(),
-            database.clone(),
-            garmin_client.clone(),
-            coach.clone(),
-        )
-        .await
-        {
-            error!("API Server crashed: {}", e);
         }
-        return Ok(());
+        Err(e) => info!("Failed to fetch workouts: {}", e),
     }
+    Ok(())
+}
 
-    if is_signal {
+/// Runs the pipeline every 5 minutes plus the scheduled notifiers, optionally
+/// with the Signal bot alongside.
+async fn run_daemon(
+    with_signal: bool,
+    config: Arc<crate::config::AppConfig>,
+    garmin_client: Arc<GarminClient>,
+    coach: Arc<Coach>,
+    database: Arc<Mutex<Database>>,
+    generation_guard: crate::generation::GenerationGuard,
+) {
+    crate::bot::start_pending_message_flusher(database.clone(), config.clone());
+    if with_signal {
         let bot = bot::BotController::new(
             config.clone(),
             garmin_client.clone(),
             coach.clone(),
             database.clone(),
+            generation_guard.clone(),
         );
-        if is_daemon {
-            tokio::spawn(async move {
-                bot.run().await;
-            });
-        } else {
+        tokio::spawn(async move {
             bot.run().await;
-            return Ok(());
-        }
+        });
     }
 
-    if is_daemon {
-        info!("Starting Fitness Coach in DAEMON mode. Will run every 5 minutes.");
-        crate::bot::start_morning_notifier(garmin_client.clone(), config.clone());
-        if !config.gemini_api_key.is_empty() {
-            crate::bot::start_weekly_review_notifier(garmin_client.clone(), config.clone());
-            crate::bot::start_monthly_debrief_notifier(garmin_client.clone(), config.clone());
-            crate::bot::start_race_readiness_notifier(garmin_client.clone(), config.clone());
-            crate::bot::start_strength_validation_notifier(garmin_client.clone(), config.clone());
-        }
-        loop {
-            run_coach_pipeline(
+    info!("Starting Fitness Coach in DAEMON mode. Will run every 5 minutes.");
+    crate::bot::start_morning_notifier(garmin_client.clone(), config.clone());
+    crate::bot::start_maintenance_task(database.clone(), config.clone());
+    crate::bot::start_stale_data_checkin_notifier(
+        garmin_client.clone(),
+        database.clone(),
+        config.clone(),
+    );
+    if !config.gemini_api_key.is_empty() {
+        crate::bot::start_weekly_review_notifier(
+            garmin_client.clone(),
+            database.clone(),
+            config.clone(),
+        );
+        crate::bot::start_monthly_debrief_notifier(garmin_client.clone(), config.clone());
+        crate::bot::start_race_readiness_notifier(garmin_client.clone(), config.clone());
+        crate::bot::start_strength_validation_notifier(garmin_client.clone(), config.clone());
+    }
+    loop {
+        if let Some(_generation) = generation_guard.try_start() {
+            let result = run_coach_pipeline(
                 config.clone(),
                 garmin_client.clone(),
                 coach.clone(),
                 database.clone(),
                 false,
+                false,
             )
-            .await?;
-            info!("Sleeping for 5 minutes... zzz");
-            tokio::time::sleep(tokio::time::Duration::from_secs(300)).await;
+            .await;
+            if let Err(e) = &result {
+                error!("Coach pipeline failed: {}", e);
+            }
+            crate::bot::report_garmin_auth_status(&database, &config, &result).await;
+        } else {
+            info!("A generation is already running. Skipping this cycle.");
         }
-    } else {
-        run_coach_pipeline(
-            config.clone(),
-            garmin_client.clone(),
-            coach.clone(),
-            database.clone(),
-            true,
-        )
-        .await?;
+        info!("Sleeping for 5 minutes... zzz");
+        tokio::time::sleep(tokio::time::Duration::from_secs(300)).await;
     }
-
-    Ok(())
 }
 
 pub async fn run_coach_pipeline(
@@ -360,7 +559,133 @@ pub async fn run_coach_pipeline(
     coach: Arc<Coach>,
     database: Arc<Mutex<Database>>,
     force_generation: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
+    dry_run: bool,
+) -> AppResult<()> {
+    let result = coach_pipeline(
+        config.clone(),
+        garmin_client,
+        coach,
+        database,
+        force_generation,
+        dry_run,
+    )
+    .await;
+    let result = match result {
+        Ok(outcome) => outcome,
+        Err(e) => PipelineOutcome::Failed(e),
+    };
+    crate::metrics::record_generation(!matches!(
+        result,
+        PipelineOutcome::Failed(_) | PipelineOutcome::Generated(Err(_))
+    ));
+    if let (Some(url), false) = (config.generation_webhook_url.as_deref(), dry_run) {
+        if let Some(payload) = GenerationWebhook::for_outcome(&result, force_generation) {
+            notify_generation_webhook(url, &payload).await;
+        }
+    }
+    match result {
+        PipelineOutcome::Skipped => Ok(()),
+        PipelineOutcome::Generated(published) => published.map(|_| ()),
+        PipelineOutcome::Failed(e) => Err(e),
+    }
+}
+
+/// What a pipeline run did about workout generation.
+#[derive(Debug)]
+enum PipelineOutcome {
+    /// No generation was needed (already scheduled, horizon covered, no API key).
+    Skipped,
+    /// `generate_and_publish_plan` ran; the workouts it created or proposed.
+    Generated(AppResult<Vec<PublishedWorkout>>),
+    /// The run failed before deciding whether to generate (e.g. Garmin auth).
+    Failed(crate::error::AppError),
+}
+
+/// Seconds to wait for `generation_webhook_url` before giving up.
+const GENERATION_WEBHOOK_TIMEOUT_SECS: u64 = 5;
+
+/// A workout created (or, in review mode, proposed) by a pipeline run.
+#[derive(Debug, Clone, serde::Serialize)]
+struct PublishedWorkout {
+    name: String,
+    scheduled_date: Option<String>,
+}
+
+/// JSON body POSTed to `generation_webhook_url` when a pipeline run finishes.
+#[derive(Debug, serde::Serialize)]
+struct GenerationWebhook {
+    status: &'static str,
+    count: usize,
+    workouts: Vec<PublishedWorkout>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl GenerationWebhook {
+    /// The notification for a pipeline run, if any: a generation finished or
+    /// failed, or an explicitly requested run failed before generating. Skipped
+    /// and failed daemon cycles stay silent so the receiver isn't hit every 5 minutes.
+    fn for_outcome(outcome: &PipelineOutcome, force_generation: bool) -> Option<Self> {
+        match outcome {
+            PipelineOutcome::Skipped => None,
+            PipelineOutcome::Generated(result) => Some(Self::from_result(result)),
+            PipelineOutcome::Failed(e) if force_generation => Some(Self {
+                status: "error",
+                count: 0,
+                workouts: Vec::new(),
+                error: Some(e.to_string()),
+            }),
+            PipelineOutcome::Failed(_) => None,
+        }
+    }
+
+    fn from_result(result: &AppResult<Vec<PublishedWorkout>>) -> Self {
+        match result {
+            Ok(workouts) => Self {
+                status: "success",
+                count: workouts.len(),
+                workouts: workouts.clone(),
+                error: None,
+            },
+            Err(e) => Self {
+                status: "error",
+                count: 0,
+                workouts: Vec::new(),
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+/// Posts `payload` to `url`. Failures are logged; they never fail the run.
+async fn notify_generation_webhook(url: &str, payload: &GenerationWebhook) {
+    let client = match reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(
+            GENERATION_WEBHOOK_TIMEOUT_SECS,
+        ))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            error!("Failed to build generation webhook client: {}", e);
+            return;
+        }
+    };
+    match client.post(url).json(payload).send().await {
+        Ok(res) if res.status().is_success() => info!("Generation webhook notified"),
+        Ok(res) => error!("Generation webhook returned {}", res.status()),
+        Err(e) => error!("Generation webhook failed: {}", e),
+    }
+}
+
+async fn coach_pipeline(
+    config: Arc<crate::config::AppConfig>,
+    garmin_client: Arc<GarminClient>,
+    coach: Arc<Coach>,
+    database: Arc<Mutex<Database>>,
+    force_generation: bool,
+    dry_run: bool,
+) -> AppResult<PipelineOutcome> {
     // 1. Fetch Detailed Data from Garmin Connect (Native Rust)
     info!("\nFetching detailed stats from Garmin Connect...");
     let (
@@ -370,6 +695,7 @@ pub async fn run_coach_pipeline(
         max_metrics,
         scheduled_workouts,
         recovery,
+        stale_data_hours,
     ) = match garmin_client.fetch_data().await {
         Ok(response) => {
             info!(
@@ -378,6 +704,7 @@ pub async fn run_coach_pipeline(
                 response.plans.len(),
                 response.scheduled_workouts.len()
             );
+            let stale_data_hours = response.stale_hours(chrono::Utc::now().timestamp() as u64);
             (
                 response.activities,
                 response.plans,
@@ -385,44 +712,59 @@ pub async fn run_coach_pipeline(
                 response.max_metrics,
                 response.scheduled_workouts,
                 response.recovery_metrics,
+                stale_data_hours,
             )
         }
         Err(e) => {
-            error!("Failed to fetch detailed Garmin data: {}", e);
-            (Vec::new(), Vec::new(), None, None, Vec::new(), None)
+            let err = AppError::from_garmin(e);
+            if matches!(err, AppError::GarminAuth(_)) {
+                return Err(err);
+            }
+            error!("Failed to fetch detailed Garmin data: {}", err);
+            (Vec::new(), Vec::new(), None, None, Vec::new(), None, None)
         }
     };
 
     // 2. Save Recovery Metrics & Sync Garmin Strength Sets to Local Database & Fetch History
-    if let Some(ref metrics) = recovery {
-        if let Err(e) = database.lock().await.save_recovery_metrics(metrics) {
+    // (stale fallback data would record old markers under today's date).
+    if let Some(metrics) = recovery.as_ref().filter(|_| stale_data_hours.is_none()) {
+        let today = config.now().format("%Y-%m-%d").to_string();
+        if let Err(e) = database.lock().await.save_recovery_metrics(&today, metrics) {
             error!("Failed to save recovery metrics to DB: {}", e);
         }
     }
     let progression_history = sync_workouts_to_db(&detailed_activities, &database).await;
 
     // 3. Load Active Profile
-    let (context, auto_analyze_sports) = load_profile_context();
+    let (context, auto_analysis) = crate::profiles::load_active_context();
 
     // 4. Auto-Analyze Activities (Signal Cheerleader)
-    if !config.gemini_api_key.is_empty() && !auto_analyze_sports.is_empty() {
-        auto_analyze_recent_activities(
+    if dry_run {
+        info!("[dry-run] Skipping activity auto-analysis and its Signal broadcasts.");
+    } else if !config.gemini_api_key.is_empty() && !auto_analysis.sports.is_empty() {
+        let ai_client = crate::ai_client::AiClient::from_config(&config);
+        auto_analyze_activities(
             &detailed_activities,
-            &auto_analyze_sports,
+            &garmin_client,
+            &ai_client,
+            &auto_analysis,
             &database,
             &config,
+            AnalysisRun::Recent,
         )
         .await;
     }
 
     // 5. Fetch coaching memory data from DB (single lock acquisition)
-    let (previous_plan_response, recent_analyses, weekly_deltas) = {
+    let (previous_plan_response, recent_analyses, recent_rpe, weekly_deltas, recovery_history) = {
         let db = database.lock().await;
         let prev = db.get_last_coach_plan_response().unwrap_or(None);
         let analyses = db.get_recent_activity_analyses(7).unwrap_or_default();
+        let rpe = db.get_recent_rpe(7).unwrap_or_default();
+        let recovery_history = db.get_recovery_history(14).unwrap_or_default();
 
         // Compute week boundaries for progression deltas
-        let now_local = chrono::Local::now();
+        let now_local = config.now();
         let week_start_chrono = crate::config::parse_weekday(&config.week_start_day);
         let today_weekday = now_local.date_naive().weekday();
         let days_since_week_start = (today_weekday.num_days_from_monday() as i64
@@ -438,11 +780,15 @@ pub async fn run_coach_pipeline(
         let deltas = db
             .get_weekly_progression_deltas(&this_week_start_str, &last_week_start_str)
             .unwrap_or_default();
-        (prev, analyses, deltas)
+        (prev, analyses, rpe, deltas, recovery_history)
     };
 
     // Build adherence summary: compare generated_workouts.json against exercise_history
-    let adherence_summary = build_adherence_summary(&detailed_activities, &config.week_start_day);
+    let adherence_summary = build_adherence_summary(&detailed_activities, &config);
+
+    let planning_horizon_days = context
+        .planning_horizon_days
+        .unwrap_or(config.planning_horizon_days);
 
     // 6. Generate Brief
     info!("\nGenerating Coach Brief...");
@@ -458,8 +804,18 @@ pub async fn run_coach_pipeline(
         week_start_day: &config.week_start_day,
         previous_plan_response: &previous_plan_response,
         recent_analyses: &recent_analyses,
+        recent_rpe: &recent_rpe,
         adherence_summary: &adherence_summary,
         weekly_deltas: &weekly_deltas,
+        recovery_history: &recovery_history,
+        max_hr_formula: config.max_hr_formula,
+        min_body_battery_for_hard: config.min_body_battery_for_hard,
+        min_muscle_recovery_hours: config.min_muscle_recovery_hours,
+        intensity_thresholds: config.effective_intensity_thresholds(),
+        stale_data_hours,
+        now: config.now(),
+        planning_horizon_days,
+        generation_scope: config.generation_scope,
     });
 
     info!("Coach brief generated ({} characters).", brief.len());
@@ -473,7 +829,7 @@ pub async fn run_coach_pipeline(
     if !config.gemini_api_key.is_empty() {
         let has_ai_workouts = scheduled_workouts.iter().any(|w| {
             if let Some(name) = w.title.as_deref() {
-                crate::garmin_client::is_ai_managed_workout(name)
+                garmin_client.ai_tags().is_tagged(name)
             } else {
                 false
             }
@@ -486,7 +842,7 @@ pub async fn run_coach_pipeline(
             let workouts_path = std::env::var("GENERATED_WORKOUTS_PATH")
                 .unwrap_or_else(|_| "generated_workouts.json".to_string());
             if let Ok(json_str) = std::fs::read_to_string(&workouts_path) {
-                let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+                let today = config.now().format("%Y-%m-%d").to_string();
                 if let Ok(workouts) = serde_json::from_str::<Vec<serde_json::Value>>(&json_str) {
                     let has_future = workouts.iter().any(|w| {
                         w.get("scheduledDate")
@@ -508,8 +864,29 @@ pub async fn run_coach_pipeline(
             false
         };
 
-        if force_generation || (!has_ai_workouts && !has_local_plan) {
-            generate_and_publish_plan(&brief, &garmin_client, &database, &config).await;
+        let horizon_covered = config.skip_if_scheduled
+            && crate::coaching::schedule_covers_horizon(
+                &scheduled_workouts,
+                config.now().date_naive(),
+                planning_horizon_days,
+            );
+
+        if !force_generation && horizon_covered {
+            info!(
+                "\nThe next {} days are already fully scheduled in Garmin. Skipping automatic workout generation.",
+                planning_horizon_days
+            );
+        } else if force_generation || (!has_ai_workouts && !has_local_plan) {
+            let published = generate_and_publish_plan(
+                &brief,
+                &recovery,
+                &garmin_client,
+                &database,
+                &config,
+                dry_run,
+            )
+            .await;
+            return Ok(PipelineOutcome::Generated(published));
         } else {
             info!("\nAI Workouts already scheduled. Skipping automatic workout generation.");
         }
@@ -517,7 +894,7 @@ pub async fn run_coach_pipeline(
         info!("\nNo GEMINI_API_KEY set. Skipping automatic workout generation.");
     }
 
-    Ok(())
+    Ok(PipelineOutcome::Skipped)
 }
 
 async fn sync_workouts_to_db(
@@ -546,79 +923,11 @@ async fn sync_workouts_to_db(
     progression_history
 }
 
-pub fn load_profile_context() -> (crate::coaching::CoachContext, Vec<String>) {
-    let mut context = crate::coaching::CoachContext {
-        goals: vec![
-            "Improve Marathon Time (Sub 4h)".to_string(),
-            "Maintain Upper Body Strength (Hypertrophy)".to_string(),
-            "Increase VO2Max".to_string(),
-        ],
-        constraints: vec![],
-        available_equipment: vec![],
-    };
-
-    let mut auto_analyze_sports = Vec::new();
-
-    // Load active profile from profiles.json
-    let profiles_path =
-        std::env::var("PROFILES_PATH").unwrap_or_else(|_| "data/profiles.json".to_string());
-    if let Ok(profile_data) = std::fs::read_to_string(&profiles_path) {
-        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&profile_data) {
-            if let Some(active_name) = json.get("active_profile").and_then(|v| v.as_str()) {
-                info!("Loaded active equipment profile: {}", active_name);
-                if let Some(profile) = json.get("profiles").and_then(|p| p.get(active_name)) {
-                    if let Some(goals) = profile.get("goals").and_then(|g| g.as_array()) {
-                        let parsed_goals: Vec<String> = goals
-                            .iter()
-                            .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                            .collect();
-                        if !parsed_goals.is_empty() {
-                            context.goals = parsed_goals;
-                        } else {
-                            info!(
-                                "Warning: profile '{}' has no valid goals. Falling back to default goals.",
-                                active_name
-                            );
-                        }
-                    }
-                    if let Some(constraints) = profile.get("constraints").and_then(|c| c.as_array())
-                    {
-                        context.constraints = constraints
-                            .iter()
-                            .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                            .collect();
-                    }
-                    if let Some(equipment) = profile
-                        .get("available_equipment")
-                        .and_then(|e| e.as_array())
-                    {
-                        context.available_equipment = equipment
-                            .iter()
-                            .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                            .collect();
-                    }
-                    if let Some(sports) = profile
-                        .get("auto_analyze_sports")
-                        .and_then(|s| s.as_array())
-                    {
-                        auto_analyze_sports = sports
-                            .iter()
-                            .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                            .collect();
-                    }
-                }
-            }
-        }
-    }
-
-    (context, auto_analyze_sports)
-}
-
 /// Compare the last generated plan (generated_workouts.json) against actual activities
 /// to produce a human-readable adherence summary for the AI brief.
 fn build_adherence_summary(
     detailed_activities: &[crate::models::GarminActivity],
-    _week_start_day: &str,
+    config: &crate::config::AppConfig,
 ) -> Vec<String> {
     let workouts_path = std::env::var("GENERATED_WORKOUTS_PATH")
         .unwrap_or_else(|_| "generated_workouts.json".to_string());
@@ -636,7 +945,7 @@ fn build_adherence_summary(
     }
 
     // Only report adherence for planned workouts whose scheduled date has already passed
-    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let today = config.now().format("%Y-%m-%d").to_string();
 
     let mut summary = Vec::new();
     let mut completed_count = 0;
@@ -662,15 +971,11 @@ fn build_adherence_summary(
         // Check if there's an actual strength activity on that date
         let actual_on_date: Vec<&crate::models::GarminActivity> = detailed_activities
             .iter()
-            .filter(|a| a.start_time.starts_with(scheduled_date))
             .filter(|a| {
-                a.get_activity_type()
-                    .map(|t| {
-                        let lower = t.to_lowercase();
-                        lower.contains("strength") || lower.contains("fitness")
-                    })
-                    .unwrap_or(false)
+                a.start_date()
+                    .is_some_and(|d| d.format("%Y-%m-%d").to_string() == scheduled_date)
             })
+            .filter(|a| a.normalized_sport() == crate::models::Sport::Strength)
             .collect();
 
         if actual_on_date.is_empty() {
@@ -735,89 +1040,150 @@ fn build_adherence_summary(
     summary
 }
 
-async fn auto_analyze_recent_activities(
+/// Which activities `auto_analyze_activities` covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalysisRun {
+    /// The pipeline's pass: activities within `auto_analyze_days` not yet analyzed.
+    Recent,
+    /// A deep force-pull: today's activities, replacing any stored analysis.
+    TodayForced,
+}
+
+/// Analyzes `auto_analyze_sports` activities, broadcasting and storing each
+/// result, and returns how many were analyzed.
+pub async fn auto_analyze_activities(
     detailed_activities: &[crate::models::GarminActivity],
-    auto_analyze_sports: &[String],
+    garmin_client: &GarminClient,
+    ai_client: &crate::ai_client::AiClient,
+    auto_analysis: &crate::profiles::AutoAnalysis,
     database: &Arc<Mutex<Database>>,
     config: &crate::config::AppConfig,
-) {
-    let gemini_model =
-        std::env::var("GEMINI_MODEL").unwrap_or_else(|_| "gemini-3-flash-preview".to_string());
-    let ai_client = crate::ai_client::AiClient::new(config.gemini_api_key.clone(), gemini_model);
-    let db = database.lock().await;
+    run: AnalysisRun,
+) -> usize {
+    let today = config.now().date_naive();
 
-    // Only analyze recent activities (from today or yesterday) to avoid spamming 50+ backlogs
-    let today = chrono::Local::now();
-    let yesterday = today - chrono::Duration::days(1);
-    let today_str = today.format("%Y-%m-%d").to_string();
-    let yesterday_str = yesterday.format("%Y-%m-%d").to_string();
+    // Only hold the database while reading; the Gemini calls and broadcasts
+    // below must not block other users of it.
+    let db = database.lock().await;
+    let candidates = match run {
+        AnalysisRun::Recent => {
+            // The first run (nothing analyzed yet) is capped so a backlog doesn't spam Signal.
+            let cap = if db.has_activity_analyses().unwrap_or(true) {
+                None
+            } else {
+                Some(config.auto_analyze_initial_cap)
+            };
+            crate::coaching::auto_analyze_candidates(
+                detailed_activities,
+                &auto_analysis.sports,
+                today,
+                config.auto_analyze_days,
+                cap,
+                |id| db.is_activity_analyzed(id).unwrap_or(false),
+            )
+        }
+        AnalysisRun::TodayForced => crate::coaching::auto_analyze_candidates(
+            detailed_activities,
+            &auto_analysis.sports,
+            today,
+            1,
+            None,
+            |_| false,
+        ),
+    };
+    drop(db);
 
-    for act in detailed_activities {
-        if !act.start_time.starts_with(&today_str) && !act.start_time.starts_with(&yesterday_str) {
+    let mut analyzed = 0;
+    for act in candidates {
+        let (Some(id), Some(act_type)) = (act.id, act.get_activity_type()) else {
             continue;
-        }
+        };
+        info!(
+            "Activity {} ({}) matches auto_analyze_sports. Requesting analysis...",
+            id, act_type
+        );
 
-        if let (Some(id), Some(act_type)) = (act.id, act.get_activity_type()) {
-            if auto_analyze_sports.contains(&act_type.to_string()) {
-                let is_analyzed = db.is_activity_analyzed(id).unwrap_or(false);
-                if !is_analyzed {
-                    info!(
-                        "Activity {} ({}) matches auto_analyze_sports. Requesting analysis...",
-                        id, act_type
-                    );
-
-                    let prompt = format!(
-                        "Please provide an in-depth analysis of this completed fitness activity. Be encouraging but highly analytical.\n\nYou have been provided with the complete, raw JSON payload direct from Garmin. It contains many undocumented fields, extra metrics, recovery data, elevation, stress, cadence, temperatures, or detailed exercise sets.\n\nPlease actively hunt through this raw JSON and surface interesting insights, anomalies, or performance correlations that wouldn't be obvious from just the basic time/distance metrics. Explain what these deeper metrics mean for the athlete's progress.\n\nKeep the response concise enough for a messaging app (max 2-3 short paragraphs) and format it directly as text without any markdown wrappers.\n\nHere is the raw activity data:\n\n{}",
-                        serde_json::to_string(act).unwrap_or_default()
-                    );
-
-                    match ai_client.generate_workout(&prompt).await {
-                        Ok(analysis) => {
-                            info!("Analysis generated! Broadcasting via Signal...");
-                            let msg = format!(
-                                "📊 **Activity Analysis: {}**\n\n{}",
-                                act.name.as_deref().unwrap_or("Untitled Workout"),
-                                analysis
-                            );
-                            crate::bot::broadcast_message(&msg, config).await;
+        let prompt = format!(
+            "{}\n\nKeep the response concise enough for a messaging app (max 2-3 short paragraphs) and format it directly as text without any markdown wrappers.\n\n{}Here is the raw activity data:\n\n{}",
+            auto_analysis.analysis_prompt_for(act_type),
+            crate::coaching::terrain_note(Some(act)),
+            serde_json::to_string(act).unwrap_or_default()
+        );
 
-                            if let Err(e) =
-                                db.save_activity_analysis(id, &act.start_time, &analysis)
-                            {
-                                error!("Failed to save activity analysis to DB: {}", e);
-                            }
-                        }
-                        Err(e) => {
-                            error!("Failed to generate analysis for {}: {}", id, e)
-                        }
+        match ai_client.generate_workout(&prompt).await {
+            Ok(analysis) => {
+                info!("Analysis generated! Broadcasting via Signal...");
+                let msg = format!(
+                    "📊 **Activity Analysis: {}**\n\n{}",
+                    act.name.as_deref().unwrap_or("Untitled Workout"),
+                    analysis
+                );
+                crate::bot::broadcast_message(&msg, config).await;
+                analyzed += 1;
+
+                let saved = {
+                    let db = database.lock().await;
+                    if run == AnalysisRun::TodayForced {
+                        db.replace_activity_analysis(id, &act.start_time, &analysis)
+                    } else {
+                        db.save_activity_analysis(id, &act.start_time, &analysis)
                     }
+                };
+                if let Err(e) = saved {
+                    error!("Failed to save activity analysis to DB: {}", e);
                 }
+                if config.write_analysis_to_garmin {
+                    if let Err(e) = garmin_client.write_activity_analysis(id, &analysis).await {
+                        error!("Failed to write analysis to Garmin activity {}: {}", id, e);
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to generate analysis for {}: {}", id, e)
             }
         }
     }
+    analyzed
 }
 
 async fn generate_and_publish_plan(
     brief: &str,
+    recovery: &Option<crate::models::GarminRecoveryMetrics>,
     garmin_client: &Arc<GarminClient>,
     database: &Arc<Mutex<Database>>,
     config: &crate::config::AppConfig,
-) {
+    dry_run: bool,
+) -> AppResult<Vec<PublishedWorkout>> {
+    let mut published = Vec::new();
     info!("\nGEMINI_API_KEY found! Generating workout via Gemini...");
 
     // Initialize AI Client
-    let gemini_model =
-        std::env::var("GEMINI_MODEL").unwrap_or_else(|_| "gemini-3-flash-preview".to_string());
-    let ai_client = crate::ai_client::AiClient::new(config.gemini_api_key.clone(), gemini_model);
+    let ai_client = crate::ai_client::AiClient::from_config(config);
+    let units = crate::profiles::active_units();
 
-    info!("Cleaning up previously generated workouts before generating a new plan...");
-    if let Err(e) = garmin_client.cleanup_ai_workouts().await {
-        info!("Warning: failed to cleanup old AI workouts: {}", e);
-    }
+    let mode = PublishMode::new(dry_run, config);
+
+    if dry_run {
+        info!(
+            "[dry-run] Skipping cleanup of previously generated Garmin workouts and chat context."
+        );
+    } else {
+        if mode == PublishMode::Review {
+            info!("auto_upload is off: leaving previously generated Garmin workouts in place.");
+        } else {
+            info!("Cleaning up previously generated workouts before generating a new plan...");
+            if let Err(e) = garmin_client.cleanup_ai_workouts().await {
+                if AppError::is_garmin_auth(&e) {
+                    return Err(AppError::from_garmin(e));
+                }
+                info!("Warning: failed to cleanup old AI workouts: {}", e);
+            }
+        }
 
-    info!("Wiping previous chat context...");
-    if let Err(e) = database.lock().await.clear_ai_chat() {
-        info!("Warning: failed to clear AI chat log: {}", e);
+        info!("Wiping previous chat context...");
+        if let Err(e) = database.lock().await.clear_ai_chat() {
+            info!("Warning: failed to clear AI chat log: {}", e);
+        }
     }
 
     // Note: we no longer clear coach_briefs here — the previous plan response
@@ -827,40 +1193,71 @@ async fn generate_and_publish_plan(
         Ok(markdown_response) => {
             info!("Received response from AI!");
 
-            if let Err(e) = database
-                .lock()
-                .await
-                .add_coach_brief(brief, &markdown_response)
-            {
-                info!("Warning: failed to save coach brief to db: {}", e);
+            if !dry_run {
+                if let Err(e) = database
+                    .lock()
+                    .await
+                    .add_coach_brief(brief, &markdown_response)
+                {
+                    info!("Warning: failed to save coach brief to db: {}", e);
+                }
             }
 
-            match crate::ai_client::AiClient::extract_json_block(&markdown_response) {
-                Ok(json_str) => {
+            match crate::ai_client::AiClient::extract_workouts(&markdown_response) {
+                Ok(mut workouts) => {
+                    workouts.retain(|w| {
+                        let in_scope = config.generation_scope.includes(w);
+                        if !in_scope {
+                            info!(
+                                "Skipping '{}': outside generation_scope {:?}",
+                                w.get("workoutName")
+                                    .and_then(|n| n.as_str())
+                                    .unwrap_or("unnamed"),
+                                config.generation_scope
+                            );
+                        }
+                        in_scope
+                    });
+                    let json_str = serde_json::to_string_pretty(&workouts).unwrap_or_default();
                     let out_file = std::env::var("GENERATED_WORKOUTS_PATH")
                         .unwrap_or_else(|_| "generated_workouts.json".to_string());
-                    if let Err(e) = std::fs::write(&out_file, &json_str) {
+                    if dry_run {
+                        info!("[dry-run] Not writing {}", out_file);
+                    } else if let Err(e) = std::fs::write(&out_file, &json_str) {
                         error!("Failed to write to {}: {}", out_file, e);
                     } else {
                         info!("Saved structured workout to {}", out_file);
                     }
 
-                    // Upload to Garmin
-                    info!("Uploading to Garmin Connect...");
-                    let parsed: serde_json::Value = match serde_json::from_str(&json_str) {
-                        Ok(v) => v,
-                        Err(e) => {
-                            error!("Failed to parse generated JSON: {}", e);
-                            return;
-                        }
-                    };
+                    if mode == PublishMode::Upload {
+                        info!("Uploading to Garmin Connect...");
+                    }
 
-                    let workouts = if let Some(arr) = parsed.as_array() {
-                        arr.clone()
+                    let builder = garmin_client.workout_builder();
+                    let weight_caps =
+                        cap_workout_weights(&mut workouts, &builder, database, config).await;
+                    let downregulated =
+                        downregulate_first_day_intervals(&mut workouts, recovery, config);
+                    let phase_fixes = if config.enforce_warmup_cooldown {
+                        enforce_warmup_cooldown(&mut workouts)
                     } else {
-                        vec![parsed]
+                        Vec::new()
                     };
+                    if !dry_run
+                        && (!weight_caps.is_empty()
+                            || !downregulated.is_empty()
+                            || !phase_fixes.is_empty())
+                    {
+                        if let Ok(capped_json) = serde_json::to_string_pretty(&workouts) {
+                            if let Err(e) = std::fs::write(&out_file, capped_json) {
+                                error!("Failed to write capped workouts to {}: {}", out_file, e);
+                            }
+                        }
+                    }
 
+                    annotate_progression(&mut workouts, &builder, database).await;
+
+                    let batch_id = crate::garmin_client::new_batch_id("", config.now());
                     let mut generated_count = 0;
                     let mut scheduled_details = Vec::new();
                     for w in workouts {
@@ -873,15 +1270,14 @@ async fn generate_and_publish_plan(
                             obj.insert(
                                 "workoutName".to_string(),
                                 serde_json::Value::String(
-                                    crate::garmin_client::ensure_ai_workout_name(current_name),
+                                    garmin_client.ai_tags().tag_name(current_name),
                                 ),
                             );
                         }
 
-                        match garmin_client
-                            .create_and_schedule_workout(&workout_spec)
-                            .await
-                        {
+                        let result =
+                            publish_workout(&workout_spec, garmin_client, &batch_id, mode).await;
+                        match result {
                             Ok(msg) => {
                                 info!("{}", msg);
                                 let sch_date = workout_spec
@@ -889,30 +1285,65 @@ async fn generate_and_publish_plan(
                                     .and_then(|d| d.as_str())
                                     .unwrap_or("Unknown Date");
                                 generated_count += 1;
+                                published.push(PublishedWorkout {
+                                    name: workout_spec
+                                        .get("workoutName")
+                                        .and_then(|n| n.as_str())
+                                        .unwrap_or_default()
+                                        .to_string(),
+                                    scheduled_date: workout_spec
+                                        .get("scheduledDate")
+                                        .and_then(|d| d.as_str())
+                                        .map(str::to_string),
+                                });
                                 let detailed_str =
-                                    crate::bot::format_workout_details(&workout_spec);
-                                scheduled_details.push(format!(
-                                    "📅 Scheduled for: {}\n{}",
-                                    sch_date, detailed_str
-                                ));
+                                    crate::bot::format_workout_details(&workout_spec, units);
+                                let label = if mode == PublishMode::Review {
+                                    "Proposed for"
+                                } else {
+                                    "Scheduled for"
+                                };
+                                scheduled_details
+                                    .push(format!("📅 {}: {}\n{}", label, sch_date, detailed_str));
                             }
                             Err(e) => info!("{}", e),
                         }
                     }
 
                     if generated_count > 0 {
-                        let mut msg = format!(
-                            "✅ AI Coach has successfully generated and scheduled {} new workouts!",
-                            generated_count
-                        );
+                        let mut msg = if mode == PublishMode::Review {
+                            format!(
+                                "📝 AI Coach proposed {} new workouts for your review. They were not uploaded to Garmin.",
+                                generated_count
+                            )
+                        } else {
+                            format!(
+                                "✅ AI Coach has successfully generated and scheduled {} new workouts!",
+                                generated_count
+                            )
+                        };
                         if !scheduled_details.is_empty() {
                             msg.push_str("\n\n");
                             msg.push_str(&scheduled_details.join("\n\n"));
                         }
-                        crate::bot::broadcast_message(&msg, config).await;
+                        if !weight_caps.is_empty() {
+                            msg.push_str("\n\n⚠️ Weights capped to your recorded bests:\n");
+                            msg.push_str(&weight_caps.join("\n"));
+                        }
+                        if !downregulated.is_empty() {
+                            msg.push_str("\n\n🔋 Body Battery is low, so tomorrow's intervals (the plan's first day) became recovery runs:\n");
+                            msg.push_str(&downregulated.join("\n"));
+                        }
+                        if dry_run {
+                            info!("[dry-run] Would broadcast via Signal:\n{}", msg);
+                        } else {
+                            crate::bot::broadcast_message(&msg, config).await;
+                        }
                     }
 
-                    let _ = database.lock().await.clear_garmin_cache();
+                    if mode == PublishMode::Upload {
+                        let _ = database.lock().await.clear_garmin_cache();
+                    }
                 }
                 Err(e) => {
                     error!("Could not extract JSON from AI response: {}", e);
@@ -922,19 +1353,468 @@ async fn generate_and_publish_plan(
                 }
             }
         }
-        Err(e) => error!("Failed to call Gemini: {}", e),
+        Err(e) => {
+            error!("Failed to call Gemini: {}", e);
+            return Err(AppError::from_ai(e));
+        }
     }
+
+    Ok(published)
 }
 
-fn write_secret_json_file<T: serde::Serialize>(
-    path: &str,
-    value: &T,
-) -> Result<(), Box<dyn std::error::Error>> {
-    std::fs::write(path, serde_json::to_string_pretty(value)?)?;
-    #[cfg(unix)]
+/// What `generate_and_publish_plan` does with each generated workout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PublishMode {
+    /// Log the Garmin payload (`run --dry-run`).
+    DryRun,
+    /// Keep the workout off Garmin for manual approval (`auto_upload = false`).
+    Review,
+    /// Create and schedule the workout on Garmin.
+    Upload,
+}
+
+impl PublishMode {
+    fn new(dry_run: bool, config: &crate::config::AppConfig) -> Self {
+        if dry_run {
+            PublishMode::DryRun
+        } else if !config.auto_upload {
+            PublishMode::Review
+        } else {
+            PublishMode::Upload
+        }
+    }
+}
+
+/// Publishes one generated workout according to `mode`. Uploaded workouts are
+/// recorded under `batch_id` so the run can be undone.
+async fn publish_workout(
+    workout_spec: &serde_json::Value,
+    garmin_client: &GarminClient,
+    batch_id: &str,
+    mode: PublishMode,
+) -> anyhow::Result<String> {
+    match mode {
+        PublishMode::DryRun => {
+            let payload = garmin_client.build_workout_payload(workout_spec);
+            Ok(format!(
+                "[dry-run] Would upload and schedule:\n{}",
+                serde_json::to_string_pretty(&payload).unwrap_or_default()
+            ))
+        }
+        PublishMode::Review => Ok(format!(
+            "Proposed (not uploaded): {}",
+            workout_spec
+                .get("workoutName")
+                .and_then(|n| n.as_str())
+                .unwrap_or_default()
+        )),
+        PublishMode::Upload => {
+            garmin_client
+                .create_and_schedule_workout_in_batch(workout_spec, batch_id)
+                .await
+        }
+    }
+}
+
+/// Swaps interval-heavy workouts on the plan's first day (tomorrow) for easy
+/// recovery runs when this morning's Body Battery is below
+/// `config.min_body_battery_for_hard`. Returns the names of the adjusted workouts.
+fn downregulate_first_day_intervals(
+    workouts: &mut [serde_json::Value],
+    recovery: &Option<crate::models::GarminRecoveryMetrics>,
+    config: &crate::config::AppConfig,
+) -> Vec<String> {
+    let first_day = crate::coaching::plan_start(config.now())
+        .format("%Y-%m-%d")
+        .to_string();
+    if crate::coaching::low_body_battery_directive(
+        recovery,
+        config.min_body_battery_for_hard,
+        &first_day,
+    )
+    .is_none()
     {
-        use std::os::unix::fs::PermissionsExt;
-        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        return Vec::new();
+    }
+
+    let reason = format!(
+        "Body Battery below {}, hard session replaced.",
+        config.min_body_battery_for_hard
+    );
+    let mut adjusted = Vec::new();
+    for workout in workouts.iter_mut() {
+        let on_first_day =
+            workout.get("scheduledDate").and_then(|d| d.as_str()) == Some(&first_day);
+        if on_first_day
+            && crate::workout_builder::WorkoutBuilder::downregulate_to_recovery(workout, &reason)
+        {
+            let name = workout
+                .get("workoutName")
+                .and_then(|n| n.as_str())
+                .unwrap_or("Untitled")
+                .to_string();
+            info!("Down-regulated '{}' to a recovery run: {}", name, reason);
+            adjusted.push(name);
+        }
+    }
+    adjusted
+}
+
+/// Adds the warmup/cooldown a strength workout is missing (see
+/// `WorkoutBuilder::ensure_warmup_cooldown`).
+fn enforce_warmup_cooldown(workouts: &mut [serde_json::Value]) -> Vec<String> {
+    let mut added = Vec::new();
+    for workout in workouts.iter_mut() {
+        for fix in crate::workout_builder::WorkoutBuilder::ensure_warmup_cooldown(workout) {
+            info!("Warmup/cooldown enforced: {}", fix);
+            added.push(fix);
+        }
+    }
+    added
+}
+
+/// Records each weighted step's `progression_delta` (kg vs. the best recorded
+/// weight) so the confirmation can show the progression. The comparison uses the
+/// step's `progress_from` category when given, else the resolved exercise.
+async fn annotate_progression(
+    workouts: &mut [serde_json::Value],
+    builder: &crate::workout_builder::WorkoutBuilder,
+    database: &Arc<Mutex<Database>>,
+) {
+    let db = database.lock().await;
+    for workout in workouts.iter_mut() {
+        let Some(steps) = workout.get_mut("steps").and_then(|s| s.as_array_mut()) else {
+            continue;
+        };
+        for step in steps {
+            let Some(weight) = step
+                .get("weight")
+                .and_then(crate::workout_builder::WorkoutBuilder::parse_weight)
+                .filter(|w| *w > 0.0)
+            else {
+                continue;
+            };
+            let category = match step.get("progress_from").and_then(|c| c.as_str()) {
+                Some(from) => builder.resolve_exercise(from).0,
+                None => step
+                    .get("exercise")
+                    .and_then(|e| e.as_str())
+                    .and_then(|name| builder.resolve_exercise(name).0),
+            };
+            let Some(category) = category else {
+                continue;
+            };
+            match db.progression_delta(&category, weight) {
+                Ok(Some(delta)) => {
+                    step["progression_delta"] = serde_json::json!((delta * 100.0).round() / 100.0)
+                }
+                Ok(None) => {}
+                Err(e) => error!("Failed to compute progression for {}: {}", category, e),
+            }
+        }
+    }
+}
+
+/// Caps AI-proposed weights that exceed `config.max_weight_ratio` times the best
+/// recorded weight for each exercise. Returns the adjustments for the user.
+async fn cap_workout_weights(
+    workouts: &mut [serde_json::Value],
+    builder: &crate::workout_builder::WorkoutBuilder,
+    database: &Arc<Mutex<Database>>,
+    config: &crate::config::AppConfig,
+) -> Vec<String> {
+    let maxes: std::collections::HashMap<String, f64> = database
+        .lock()
+        .await
+        .get_progression_history_raw()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, weight, _, _, _)| (name, weight))
+        .collect();
+
+    let mut adjustments = Vec::new();
+    for workout in workouts.iter_mut() {
+        for adjustment in builder.cap_weights(workout, &maxes, config.max_weight_ratio) {
+            info!("Weight cap applied: {}", adjustment);
+            adjustments.push(adjustment);
+        }
+    }
+    adjustments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        auto_analyze_activities, downregulate_first_day_intervals, notify_generation_webhook,
+        publish_workout, AnalysisRun, Cli, Command, GarminCommand, GenerationWebhook,
+        PipelineOutcome, PublishMode, PublishedWorkout,
+    };
+    use crate::config::AppConfig;
+    use crate::db::Database;
+    use crate::error::AppError;
+    use crate::garmin_client::GarminClient;
+    use clap::Parser;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    async fn mock_garmin(expected_posts: u64) -> (MockServer, GarminClient, Arc<Mutex<Database>>) {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "workoutId": 7
+            })))
+            .expect(expected_posts)
+            .mount(&server)
+            .await;
+        let db = Arc::new(Mutex::new(crate::db::memory_db()));
+        let mut client = GarminClient::offline(db.clone(), || 0);
+        client.api = client.api.with_base_url(&server.uri());
+        (server, client, db)
+    }
+
+    fn workout() -> serde_json::Value {
+        serde_json::json!({
+            "workoutName": "FJ-AI: Easy Run",
+            "sport": "running",
+            "scheduledDate": "2026-10-18",
+            "steps": [{ "phase": "interval", "duration": "30min" }]
+        })
+    }
+
+    #[test]
+    fn low_body_battery_downregulates_intervals_on_the_plans_first_day() {
+        let config = AppConfig::default();
+        let recovery =
+            serde_json::from_value(serde_json::json!({ "current_body_battery": 5 })).unwrap();
+        let intervals = |name: &str, days: i64| {
+            serde_json::json!({
+                "workoutName": name,
+                "sport": "running",
+                "scheduledDate": (config.now().date_naive() + chrono::Duration::days(days))
+                    .format("%Y-%m-%d")
+                    .to_string(),
+                "steps": [{ "repeat": 5, "steps": [{ "duration": "3min", "pace": "4:00-4:10" }] }]
+            })
+        };
+        let mut workouts = vec![intervals("Today", 0), intervals("Tomorrow", 1)];
+
+        let adjusted = downregulate_first_day_intervals(&mut workouts, &recovery, &config);
+
+        assert_eq!(adjusted, vec!["Tomorrow".to_string()]);
+        let interval_heavy = crate::workout_builder::WorkoutBuilder::is_interval_heavy;
+        assert!(interval_heavy(&workouts[0]));
+        assert!(!interval_heavy(&workouts[1]));
+    }
+
+    #[tokio::test]
+    async fn deep_force_pull_reanalyzes_todays_activities() {
+        let gemini = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "candidates": [{ "content": { "parts": [{ "text": "Strong run." }] } }]
+            })))
+            .expect(2)
+            .mount(&gemini)
+            .await;
+        let ai_client = crate::ai_client::AiClient::new_with_base_url(
+            "test-key".to_string(),
+            "test-model".to_string(),
+            &gemini.uri(),
+        );
+        let (_server, client, db) = mock_garmin(0).await;
+        let config = AppConfig::default();
+
+        let now = config.now();
+        let today = now.format("%Y-%m-%d 07:00:00").to_string();
+        let yesterday = (now - chrono::Duration::days(1))
+            .format("%Y-%m-%d 07:00:00")
+            .to_string();
+        let activities: Vec<crate::models::GarminActivity> = [
+            (1, today.as_str(), "running"),
+            (2, today.as_str(), "running"),
+            (3, today.as_str(), "yoga"),
+            (4, yesterday.as_str(), "running"),
+        ]
+        .iter()
+        .map(|(id, start, sport)| {
+            serde_json::from_value(serde_json::json!({
+                "activityId": id,
+                "startTimeLocal": start,
+                "activityType": { "typeKey": sport }
+            }))
+            .unwrap()
+        })
+        .collect();
+        db.lock()
+            .await
+            .save_activity_analysis(2, &today, "Old take.")
+            .unwrap();
+        let auto_analysis = crate::profiles::AutoAnalysis {
+            sports: vec!["running".to_string()],
+            ..Default::default()
+        };
+
+        let analyzed = auto_analyze_activities(
+            &activities,
+            &client,
+            &ai_client,
+            &auto_analysis,
+            &db,
+            &config,
+            AnalysisRun::TodayForced,
+        )
+        .await;
+
+        assert_eq!(analyzed, 2);
+        let db = db.lock().await;
+        for id in [1, 2] {
+            assert_eq!(
+                db.get_activity_analysis(id).unwrap().as_deref(),
+                Some("Strong run.")
+            );
+        }
+        assert_eq!(db.get_activity_analysis(4).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn generation_webhook_receives_status_count_and_workouts() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let url = format!("{}/hook", server.uri());
+
+        let done = Ok(vec![PublishedWorkout {
+            name: "FJ-AI: Easy Run".to_string(),
+            scheduled_date: Some("2026-10-18".to_string()),
+        }]);
+        notify_generation_webhook(&url, &GenerationWebhook::from_result(&done)).await;
+        let failed = Err(AppError::Ai("quota exceeded".to_string()));
+        notify_generation_webhook(&url, &GenerationWebhook::from_result(&failed)).await;
+
+        let bodies: Vec<serde_json::Value> = server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.body_json().unwrap())
+            .collect();
+        assert_eq!(
+            bodies[0],
+            serde_json::json!({
+                "status": "success",
+                "count": 1,
+                "workouts": [{ "name": "FJ-AI: Easy Run", "scheduled_date": "2026-10-18" }]
+            })
+        );
+        assert_eq!(
+            bodies[1],
+            serde_json::json!({
+                "status": "error",
+                "count": 0,
+                "workouts": [],
+                "error": "AI request failed: quota exceeded"
+            })
+        );
+    }
+
+    #[test]
+    fn skipped_and_failed_daemon_cycles_send_no_webhook() {
+        let payload = GenerationWebhook::for_outcome;
+        assert!(payload(&PipelineOutcome::Skipped, false).is_none());
+        assert!(payload(&PipelineOutcome::Skipped, true).is_none());
+
+        let auth = || PipelineOutcome::Failed(AppError::GarminAuth("401".to_string()));
+        assert!(payload(&auth(), false).is_none());
+        let requested = payload(&auth(), true).expect("a requested run reports its failure");
+        assert_eq!(requested.status, "error");
+
+        let generated = PipelineOutcome::Generated(Ok(Vec::new()));
+        assert_eq!(payload(&generated, false).unwrap().status, "success");
+        let failed = PipelineOutcome::Generated(Err(AppError::Ai("quota".to_string())));
+        assert_eq!(payload(&failed, false).unwrap().status, "error");
+    }
+
+    #[tokio::test]
+    async fn unreachable_webhook_is_only_logged() {
+        let server = MockServer::start().await;
+        let url = format!("{}/hook", server.uri());
+        drop(server);
+
+        notify_generation_webhook(&url, &GenerationWebhook::from_result(&Ok(Vec::new()))).await;
+    }
+
+    #[test]
+    fn review_mode_follows_auto_upload_unless_dry_run() {
+        let review = AppConfig {
+            auto_upload: false,
+            ..AppConfig::default()
+        };
+        assert_eq!(PublishMode::new(false, &review), PublishMode::Review);
+        assert_eq!(PublishMode::new(true, &review), PublishMode::DryRun);
+        assert_eq!(
+            PublishMode::new(false, &AppConfig::default()),
+            PublishMode::Upload
+        );
+    }
+
+    #[tokio::test]
+    async fn review_mode_never_posts_to_garmin() {
+        let (server, client, db) = mock_garmin(0).await;
+
+        let msg = publish_workout(&workout(), &client, "batch", PublishMode::Review)
+            .await
+            .unwrap();
+
+        assert!(msg.contains("not uploaded"));
+        let batch = db.lock().await.get_last_generation_batch().unwrap();
+        assert!(batch.is_none());
+        server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn upload_mode_creates_schedules_and_records_the_workout() {
+        // One POST creates the workout, the second schedules it.
+        let (server, client, db) = mock_garmin(2).await;
+
+        publish_workout(&workout(), &client, "batch", PublishMode::Upload)
+            .await
+            .unwrap();
+
+        let batch = db.lock().await.get_last_generation_batch().unwrap();
+        let (batch_id, ids) = batch.unwrap();
+        assert_eq!(batch_id, "batch");
+        assert_eq!(ids, vec![7]);
+        server.verify().await;
+    }
+
+    #[test]
+    fn subcommands_parse_with_a_global_account() {
+        let cli = Cli::try_parse_from(["fitness_journal"]).unwrap();
+        assert!(cli.command.is_none());
+
+        let cli =
+            Cli::try_parse_from(["fitness_journal", "daemon", "--signal", "--account", "bob"])
+                .unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Daemon { signal: true })
+        ));
+        assert_eq!(cli.account.as_deref(), Some("bob"));
+
+        let cli = Cli::try_parse_from(["fitness_journal", "garmin", "test-fetch", "123"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Garmin {
+                command: GarminCommand::TestFetch { ref workout_id }
+            }) if workout_id == "123"
+        ));
+
+        assert!(Cli::try_parse_from(["fitness_journal", "--daemon"]).is_err());
     }
-    Ok(())
 }
@@ -0,0 +1,210 @@
+use axum::{extract::MatchedPath, extract::Request, middleware::Next, response::Response};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds (seconds) of the Gemini latency histogram buckets.
+const GEMINI_BUCKETS: [f64; 8] = [0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 30.0, 60.0];
+
+/// Process-wide counters rendered by `GET /metrics` in the Prometheus text format.
+struct Metrics {
+    api_requests: Mutex<BTreeMap<String, u64>>,
+    garmin_errors: Mutex<BTreeMap<String, u64>>,
+    gemini_buckets: [AtomicU64; GEMINI_BUCKETS.len()],
+    gemini_count: AtomicU64,
+    gemini_sum_micros: AtomicU64,
+    garmin_cache_hits: AtomicU64,
+    garmin_cache_misses: AtomicU64,
+    generation_successes: AtomicU64,
+    generation_failures: AtomicU64,
+}
+
+static METRICS: Metrics = Metrics {
+    api_requests: Mutex::new(BTreeMap::new()),
+    garmin_errors: Mutex::new(BTreeMap::new()),
+    gemini_buckets: [const { AtomicU64::new(0) }; GEMINI_BUCKETS.len()],
+    gemini_count: AtomicU64::new(0),
+    gemini_sum_micros: AtomicU64::new(0),
+    garmin_cache_hits: AtomicU64::new(0),
+    garmin_cache_misses: AtomicU64::new(0),
+    generation_successes: AtomicU64::new(0),
+    generation_failures: AtomicU64::new(0),
+};
+
+fn increment(map: &Mutex<BTreeMap<String, u64>>, label: &str) {
+    if let Ok(mut map) = map.lock() {
+        *map.entry(label.to_string()).or_insert(0) += 1;
+    }
+}
+
+/// Axum middleware counting requests per matched route (e.g. `/api/exercise/:name/history`).
+pub async fn track_request(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    increment(&METRICS.api_requests, &route);
+    next.run(request).await
+}
+
+pub fn record_gemini_call(elapsed: Duration) {
+    let secs = elapsed.as_secs_f64();
+    for (bucket, le) in METRICS.gemini_buckets.iter().zip(GEMINI_BUCKETS) {
+        if secs <= le {
+            bucket.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    METRICS.gemini_count.fetch_add(1, Ordering::Relaxed);
+    METRICS
+        .gemini_sum_micros
+        .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+}
+
+/// `status` is the HTTP status code, or `"network"` when no response arrived.
+pub fn record_garmin_error(status: &str) {
+    increment(&METRICS.garmin_errors, status);
+}
+
+pub fn record_garmin_cache(hit: bool) {
+    let counter = if hit {
+        &METRICS.garmin_cache_hits
+    } else {
+        &METRICS.garmin_cache_misses
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn record_generation(success: bool) {
+    let counter = if success {
+        &METRICS.generation_successes
+    } else {
+        &METRICS.generation_failures
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+fn write_labeled(out: &mut String, name: &str, label: &str, map: &Mutex<BTreeMap<String, u64>>) {
+    if let Ok(map) = map.lock() {
+        for (value, count) in map.iter() {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            let _ = writeln!(out, "{}{{{}=\"{}\"}} {}", name, label, value, count);
+        }
+    }
+}
+
+/// Renders every metric in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut out = String::new();
+
+    out.push_str("# HELP fitness_api_requests_total API requests by matched route.\n");
+    out.push_str("# TYPE fitness_api_requests_total counter\n");
+    write_labeled(
+        &mut out,
+        "fitness_api_requests_total",
+        "route",
+        &METRICS.api_requests,
+    );
+
+    out.push_str("# HELP fitness_gemini_request_duration_seconds Gemini API call latency.\n");
+    out.push_str("# TYPE fitness_gemini_request_duration_seconds histogram\n");
+    for (bucket, le) in METRICS.gemini_buckets.iter().zip(GEMINI_BUCKETS) {
+        let _ = writeln!(
+            out,
+            "fitness_gemini_request_duration_seconds_bucket{{le=\"{}\"}} {}",
+            le,
+            bucket.load(Ordering::Relaxed)
+        );
+    }
+    let count = METRICS.gemini_count.load(Ordering::Relaxed);
+    let sum = METRICS.gemini_sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+    let _ = writeln!(
+        out,
+        "fitness_gemini_request_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+        count
+    );
+    let _ = writeln!(out, "fitness_gemini_request_duration_seconds_sum {}", sum);
+    let _ = writeln!(
+        out,
+        "fitness_gemini_request_duration_seconds_count {}",
+        count
+    );
+
+    out.push_str("# HELP fitness_garmin_errors_total Failed Garmin API requests by status.\n");
+    out.push_str("# TYPE fitness_garmin_errors_total counter\n");
+    write_labeled(
+        &mut out,
+        "fitness_garmin_errors_total",
+        "status",
+        &METRICS.garmin_errors,
+    );
+
+    out.push_str(
+        "# HELP fitness_garmin_cache_requests_total Garmin data lookups by cache result.\n",
+    );
+    out.push_str("# TYPE fitness_garmin_cache_requests_total counter\n");
+    let _ = writeln!(
+        out,
+        "fitness_garmin_cache_requests_total{{result=\"hit\"}} {}",
+        METRICS.garmin_cache_hits.load(Ordering::Relaxed)
+    );
+    let _ = writeln!(
+        out,
+        "fitness_garmin_cache_requests_total{{result=\"miss\"}} {}",
+        METRICS.garmin_cache_misses.load(Ordering::Relaxed)
+    );
+
+    out.push_str("# HELP fitness_generations_total Coach pipeline runs by outcome.\n");
+    out.push_str("# TYPE fitness_generations_total counter\n");
+    let _ = writeln!(
+        out,
+        "fitness_generations_total{{result=\"success\"}} {}",
+        METRICS.generation_successes.load(Ordering::Relaxed)
+    );
+    let _ = writeln!(
+        out,
+        "fitness_generations_total{{result=\"failure\"}} {}",
+        METRICS.generation_failures.load(Ordering::Relaxed)
+    );
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(text: &str, series: &str) -> u64 {
+        text.lines()
+            .find_map(|l| l.strip_prefix(series))
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn render_reports_recorded_values() {
+        let before = render();
+        record_gemini_call(Duration::from_millis(1500));
+        record_garmin_error("429");
+        record_generation(false);
+        let after = render();
+
+        let series = [
+            "fitness_gemini_request_duration_seconds_bucket{le=\"2\"}",
+            "fitness_gemini_request_duration_seconds_bucket{le=\"+Inf\"}",
+            "fitness_gemini_request_duration_seconds_count",
+            "fitness_garmin_errors_total{status=\"429\"}",
+            "fitness_generations_total{result=\"failure\"}",
+        ];
+        for s in series {
+            assert!(
+                value(&after, s) > value(&before, s),
+                "{} did not increase",
+                s
+            );
+        }
+        assert!(after.contains("# TYPE fitness_gemini_request_duration_seconds histogram"));
+    }
+}
@@ -1,4 +1,4 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -51,6 +51,18 @@ pub struct GarminResponse {
     pub scheduled_workouts: Vec<ScheduledWorkout>,
     #[serde(default)]
     pub recovery_metrics: Option<GarminRecoveryMetrics>,
+    /// Unix time of the last successful fetch, set when Garmin was unreachable
+    /// and that copy is served instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stale_since: Option<u64>,
+}
+
+impl GarminResponse {
+    /// Whole hours since the data was fetched when it is a stale fallback copy.
+    pub fn stale_hours(&self, now_secs: u64) -> Option<u64> {
+        self.stale_since
+            .map(|fetched| now_secs.saturating_sub(fetched) / 3600)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -65,6 +77,33 @@ pub struct GarminRecoveryMetrics {
     pub hrv_last_night_avg: Option<i32>,
     #[serde(default)]
     pub rhr_trend: Vec<i32>,
+    /// All-day load outside of workouts (steps, stress, ...).
+    #[serde(default)]
+    pub daily_summary: Option<DailySummary>,
+    /// Last night's sleep architecture; `None` when Garmin has no sleep record.
+    #[serde(default)]
+    pub sleep_detail: Option<SleepDetail>,
+}
+
+/// Today's all-day activity totals from Garmin's daily user summary.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct DailySummary {
+    pub steps: Option<i64>,
+    pub floors: Option<f64>,
+    /// Moderate minutes plus vigorous minutes counted double, as Garmin does.
+    pub intensity_minutes: Option<i64>,
+    pub stress_avg: Option<i32>,
+    pub active_calories: Option<i64>,
+}
+
+/// Last night's sleep stages from Garmin's `dailySleepDTO`, in seconds.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct SleepDetail {
+    pub total_seconds: Option<i64>,
+    pub deep_seconds: Option<i64>,
+    pub rem_seconds: Option<i64>,
+    pub light_seconds: Option<i64>,
+    pub awake_seconds: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -85,7 +124,9 @@ pub struct ScheduledWorkout {
     pub is_race: Option<bool>,
     #[serde(default, alias = "primaryEvent")]
     pub primary_event: Option<bool>,
+    /// Planned duration in seconds.
     pub duration: Option<f64>,
+    /// Planned distance in meters.
     pub distance: Option<f64>,
     pub description: Option<String>,
 
@@ -98,6 +139,73 @@ pub struct ScheduledWorkout {
     pub raw_fields: std::collections::HashMap<String, serde_json::Value>,
 }
 
+/// Whether a calendar item is a race or event (Garmin's `race`, `event` and
+/// `primaryEvent` item types) rather than a workout.
+pub fn is_race_item(sw: &ScheduledWorkout) -> bool {
+    matches!(
+        sw.item_type.as_deref(),
+        Some("race" | "event" | "primaryEvent")
+    )
+}
+
+impl ScheduledWorkout {
+    /// Calendar items usually leave the top-level `duration`/`distance`/`description`
+    /// null and carry the targets on the item itself (`estimatedDurationInSecs`,
+    /// `estimatedDistanceInMeters`) or on a nested workout (`workout`, the adaptive
+    /// details, or the fetched workout detail). Fills the gaps from those sources,
+    /// never overwriting values Garmin already set.
+    pub fn fill_nested_targets(&mut self) {
+        let (duration, distance, description) = {
+            let nested_workout = self.raw_fields.get("workout");
+            let adaptive = self.adaptive_details.as_ref();
+            let adaptive_workout =
+                adaptive.and_then(|ad| ad.get("workout").or_else(|| ad.get("adaptiveWorkout")));
+            let sources: Vec<&serde_json::Value> = [
+                nested_workout,
+                self.workout_detail.as_ref(),
+                adaptive,
+                adaptive_workout,
+            ]
+            .into_iter()
+            .flatten()
+            .collect();
+
+            let top_level_f64 = |key: &str| self.raw_fields.get(key).and_then(|v| v.as_f64());
+            let nested_f64 = |key: &str| {
+                sources
+                    .iter()
+                    .find_map(|src| src.get(key).and_then(|v| v.as_f64()))
+            };
+            let positive = |v: f64| v > 0.0;
+
+            let duration = top_level_f64("estimatedDurationInSecs")
+                .or_else(|| nested_f64("estimatedDurationInSecs"))
+                .filter(|v| positive(*v));
+            let distance = top_level_f64("estimatedDistanceInMeters")
+                .or_else(|| nested_f64("estimatedDistanceInMeters"))
+                .filter(|v| positive(*v));
+            let description = sources.iter().find_map(|src| {
+                src.get("description")
+                    .and_then(|v| v.as_str())
+                    .map(str::trim)
+                    .filter(|d| !d.is_empty())
+                    .map(str::to_string)
+            });
+            (duration, distance, description)
+        };
+
+        if self.duration.is_none() {
+            self.duration = duration;
+        }
+        if self.distance.is_none() {
+            self.distance = distance;
+        }
+        if self.description.is_none() {
+            self.description = description;
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GarminProfile {
     pub weight: Option<f64>,
@@ -108,6 +216,75 @@ pub struct GarminProfile {
     pub vo2_max_running: Option<f64>,
 }
 
+/// Pounds to kilograms; Garmin payloads and the database always hold kilograms.
+pub const KG_PER_LB: f64 = 0.45359237;
+
+/// Unit system weights are shown in (`units` in `profiles.json`).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Units {
+    #[default]
+    Metric,
+    Imperial,
+}
+
+impl Units {
+    pub fn weight_label(self) -> &'static str {
+        match self {
+            Units::Metric => "kg",
+            Units::Imperial => "lb",
+        }
+    }
+
+    /// Converts a stored kilogram value into this unit system.
+    pub fn weight_from_kg(self, kg: f64) -> f64 {
+        match self {
+            Units::Metric => kg,
+            Units::Imperial => kg / KG_PER_LB,
+        }
+    }
+
+    /// `"61.2kg"` / `"135lb"`, with at most one decimal.
+    pub fn format_weight(self, kg: f64) -> String {
+        let value = (self.weight_from_kg(kg) * 10.0).round() / 10.0;
+        format!("{}{}", value, self.weight_label())
+    }
+}
+
+/// Formula used to estimate max heart rate from age when no measured value exists.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MaxHrFormula {
+    /// 220 - age
+    #[default]
+    Fox,
+    /// 208 - 0.7 * age
+    Tanaka,
+}
+
+impl MaxHrFormula {
+    pub fn max_hr(self, age: u32) -> f64 {
+        match self {
+            MaxHrFormula::Fox => 220.0 - age as f64,
+            MaxHrFormula::Tanaka => 208.0 - 0.7 * age as f64,
+        }
+    }
+}
+
+impl GarminProfile {
+    /// Age in whole years on `today`. `None` if `birthDate` is missing, unparseable
+    /// or in the future. Garmin sends `YYYY-MM-DD`, sometimes with a time suffix.
+    pub fn age(&self, today: NaiveDate) -> Option<u32> {
+        let raw = self.birth_date.as_deref()?;
+        let birth = NaiveDate::parse_from_str(raw.get(..10)?, "%Y-%m-%d").ok()?;
+        today.years_since(birth)
+    }
+
+    pub fn estimated_max_hr(&self, today: NaiveDate, formula: MaxHrFormula) -> Option<f64> {
+        self.age(today).map(|age| formula.max_hr(age))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GarminMaxMetrics {
     #[serde(rename = "vo2MaxPrecise")]
@@ -126,6 +303,36 @@ pub struct GarminPlan {
     pub description: Option<String>,
 }
 
+/// Wall-clock time of a Garmin timestamp, plus its offset when it carries one.
+fn parse_garmin_parts(raw: &str) -> Option<(NaiveDateTime, Option<FixedOffset>)> {
+    let raw = raw.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(raw) {
+        return Some((dt.naive_local(), Some(*dt.offset())));
+    }
+    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(raw, fmt).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+                .ok()
+                .map(|d| d.and_time(NaiveTime::MIN))
+        })
+        .map(|naive| (naive, None))
+}
+
+/// Parses a Garmin timestamp into `tz`. `startTimeLocal` looks like
+/// `"2026-02-21 06:30:00"`: wall-clock time without an offset, so it is read as
+/// local time in `tz`. Timestamps with an offset (RFC 3339) are converted.
+pub fn parse_garmin_datetime<Tz: TimeZone>(raw: &str, tz: &Tz) -> Option<DateTime<Tz>> {
+    match parse_garmin_parts(raw)? {
+        (naive, Some(offset)) => offset
+            .from_local_datetime(&naive)
+            .single()
+            .map(|dt| dt.with_timezone(tz)),
+        (naive, None) => tz.from_local_datetime(&naive).earliest(),
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GarminActivity {
     #[serde(alias = "activityId")]
@@ -144,12 +351,52 @@ pub struct GarminActivity {
     pub max_hr: Option<f64>,
     pub sets: Option<GarminSetsData>,
 
+    // Swim summary fields (pool and open-water swims)
+    /// Pool length in `unitOfPoolLength` units (Garmin reports centimeters for meter pools).
+    #[serde(rename = "poolLength", default)]
+    pub pool_length: Option<f64>,
+    #[serde(rename = "unitOfPoolLength", default)]
+    pub pool_length_unit: Option<serde_json::Value>,
+    #[serde(rename = "activeLengths", default)]
+    pub active_lengths: Option<i64>,
+    #[serde(rename = "averageSwolf", default)]
+    pub average_swolf: Option<f64>,
+    /// Strokes per minute.
+    #[serde(rename = "averageSwimCadenceInStrokesPerMinute", default)]
+    pub average_stroke_rate: Option<f64>,
+
+    // Elevation summary fields in meters (absent or zero for flat and indoor activities)
+    #[serde(rename = "elevationGain", default)]
+    pub elevation_gain: Option<f64>,
+    #[serde(rename = "elevationLoss", default)]
+    pub elevation_loss: Option<f64>,
+    #[serde(rename = "maxElevation", default)]
+    pub max_elevation: Option<f64>,
+
+    // Temperature range in °C recorded by the device (absent without a sensor reading)
+    #[serde(rename = "minTemperature", default)]
+    pub min_temperature: Option<f64>,
+    #[serde(rename = "maxTemperature", default)]
+    pub max_temperature: Option<f64>,
+
     // Capture all other raw Garmin properties
     #[serde(flatten)]
     pub raw_fields: std::collections::HashMap<String, serde_json::Value>,
 }
 
 impl GarminActivity {
+    /// Start as the athlete's local wall-clock time (`startTimeLocal`).
+    pub fn start_local(&self) -> Option<NaiveDateTime> {
+        parse_garmin_parts(&self.start_time).map(|(naive, _)| naive)
+    }
+
+    /// Local calendar day the activity started on.
+    pub fn start_date(&self) -> Option<NaiveDate> {
+        self.start_local().map(|t| t.date())
+    }
+
+    /// Garmin's activity type key (`activityType.typeKey`, or a plain string
+    /// when the activity came from the cache).
     pub fn get_activity_type(&self) -> Option<&str> {
         if let Some(ref val) = self.activity_type {
             if let Some(tk) = val.get("typeKey").and_then(|v| v.as_str()) {
@@ -160,6 +407,98 @@ impl GarminActivity {
         }
         None
     }
+
+    /// Pool length in meters, for pool swims that report one.
+    pub fn pool_length_meters(&self) -> Option<f64> {
+        let raw = self.pool_length.filter(|l| *l > 0.0)?;
+        let factor = self
+            .pool_length_unit
+            .as_ref()
+            .and_then(|u| u.get("factor"))
+            .and_then(|f| f.as_f64())
+            .filter(|f| *f > 0.0)
+            .unwrap_or(100.0);
+        Some(raw / factor)
+    }
+
+    /// Climbing summary such as `+420 m / -415 m, max 1230 m`; `None` for flat
+    /// or indoor activities that report no gain or loss.
+    pub fn elevation_summary(&self) -> Option<String> {
+        let gain = self.elevation_gain.unwrap_or(0.0);
+        let loss = self.elevation_loss.unwrap_or(0.0);
+        if gain < 1.0 && loss < 1.0 {
+            return None;
+        }
+        let mut summary = format!("+{:.0} m / -{:.0} m", gain, loss);
+        if let Some(max) = self.max_elevation {
+            summary.push_str(&format!(", max {:.0} m", max));
+        }
+        Some(summary)
+    }
+
+    /// Outdoor run, ride or open-water swim, where the weather matters.
+    /// Indoor, treadmill, virtual and pool sessions only see body heat.
+    pub fn is_outdoor_endurance(&self) -> bool {
+        let key = self.get_activity_type().unwrap_or_default();
+        matches!(
+            self.normalized_sport(),
+            Sport::Run | Sport::Bike | Sport::Swim
+        ) && !["indoor", "treadmill", "virtual", "lap_swimming"]
+            .iter()
+            .any(|k| key.contains(k))
+    }
+
+    /// Fills temperatures missing from the activity list from the activity
+    /// detail payload's `summaryDTO`, when it has them.
+    pub fn fill_temperatures_from_detail(&mut self, detail: &serde_json::Value) {
+        let summary = &detail["summaryDTO"];
+        self.min_temperature = self
+            .min_temperature
+            .or_else(|| summary["minTemperature"].as_f64());
+        self.max_temperature = self
+            .max_temperature
+            .or_else(|| summary["maxTemperature"].as_f64());
+    }
+
+    pub fn normalized_sport(&self) -> Sport {
+        self.get_activity_type()
+            .map(Sport::from_type_key)
+            .unwrap_or(Sport::Other)
+    }
+}
+
+/// Coarse sport bucket for Garmin activity type keys, used for counting and
+/// filtering activities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sport {
+    Run,
+    Bike,
+    Swim,
+    Strength,
+    Cardio,
+    Other,
+}
+
+impl Sport {
+    /// Classifies a type key such as `trail_running`, `indoor_cycling`,
+    /// `lap_swimming` or `strength_training`.
+    pub fn from_type_key(key: &str) -> Self {
+        let key = key.to_lowercase();
+        let has = |parts: &[&str]| parts.iter().any(|p| key.contains(p));
+        if has(&["swim"]) {
+            Sport::Swim
+        } else if has(&["run", "trail"]) {
+            Sport::Run
+        } else if has(&["cycl", "bik", "ride"]) {
+            Sport::Bike
+        } else if has(&["cardio", "hiit", "elliptical", "rowing", "stair"]) {
+            Sport::Cardio
+        } else if has(&["strength", "fitness"]) {
+            Sport::Strength
+        } else {
+            Sport::Other
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -212,3 +551,324 @@ pub struct ExerciseMuscleMap {
     pub muscles: Vec<String>,
     pub frequency: i32,
 }
+
+/// Maps a Garmin exercise category to react-body-highlighter muscle slugs,
+/// primary mover first. Unknown categories map to an empty list.
+pub fn muscle_groups_for(category: &str) -> Vec<&'static str> {
+    match category {
+        "BENCH_PRESS" | "PUSH_UP" => vec!["chest", "triceps", "front-deltoids"],
+        "CHEST_FLY" | "FLYE" => vec!["chest", "front-deltoids"],
+        "ROW" => vec!["upper-back", "lower-back", "biceps", "back-deltoids"],
+        "PULL_UP" | "PULL_DOWN" => vec!["upper-back", "biceps", "back-deltoids"],
+        "REVERSE_FLY" => vec!["back-deltoids", "upper-back", "trapezius"],
+        "SHRUG" => vec!["trapezius"],
+        "SQUAT" | "LUNGE" => vec!["quadriceps", "gluteal", "hamstring", "calves"],
+        "LEG_PRESS" => vec!["quadriceps", "gluteal", "hamstring"],
+        "LEG_EXTENSION" => vec!["quadriceps"],
+        "LEG_CURL" => vec!["hamstring", "calves"],
+        "DEADLIFT" => vec!["hamstring", "gluteal", "lower-back", "forearm", "trapezius"],
+        "HIP_THRUST" | "HIP_RAISE" => vec!["gluteal", "hamstring"],
+        "HYPEREXTENSION" => vec!["lower-back", "gluteal", "hamstring"],
+        "CALF_RAISE" => vec!["calves"],
+        "SHOULDER_PRESS" | "FRONT_RAISE" | "LATERAL_RAISE" => {
+            vec!["front-deltoids", "back-deltoids", "triceps"]
+        }
+        "TRICEPS_EXTENSION" => vec!["triceps"],
+        "BICEP_CURL" | "CURL" => vec!["biceps"],
+        "CORE" | "PLANK" | "SIT_UP" | "CRUNCH" | "LEG_RAISE" => vec!["abs", "obliques"],
+        "CARRY" => vec!["forearm", "trapezius", "abs"],
+        "OLYMPIC_LIFT" => vec![
+            "quadriceps",
+            "gluteal",
+            "hamstring",
+            "trapezius",
+            "front-deltoids",
+        ],
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        muscle_groups_for, parse_garmin_datetime, GarminActivity, GarminProfile, MaxHrFormula,
+        ScheduledWorkout, Sport,
+    };
+    use chrono::{FixedOffset, NaiveDate, TimeZone};
+
+    #[test]
+    fn garmin_timestamps_parse_as_local_wall_time() {
+        let berlin = FixedOffset::east_opt(3600).unwrap();
+        let expected = berlin.with_ymd_and_hms(2026, 2, 21, 6, 30, 0).unwrap();
+
+        for raw in [
+            "2026-02-21 06:30:00",
+            "2026-02-21 06:30:00.0",
+            "2026-02-21T06:30:00",
+            "2026-02-21T05:30:00Z",
+            "2026-02-21T06:30:00+01:00",
+        ] {
+            assert_eq!(
+                parse_garmin_datetime(raw, &berlin),
+                Some(expected),
+                "{}",
+                raw
+            );
+        }
+        assert_eq!(
+            parse_garmin_datetime("2026-02-21", &berlin),
+            Some(berlin.with_ymd_and_hms(2026, 2, 21, 0, 0, 0).unwrap())
+        );
+        assert_eq!(parse_garmin_datetime("", &berlin), None);
+        assert_eq!(parse_garmin_datetime("21.02.2026 06:30", &berlin), None);
+
+        let late: GarminActivity = serde_json::from_value(serde_json::json!({
+            "startTimeLocal": "2026-02-21 23:45:10"
+        }))
+        .unwrap();
+        assert_eq!(late.start_date(), NaiveDate::from_ymd_opt(2026, 2, 21));
+        assert_eq!(
+            late.start_local().unwrap().format("%H:%M:%S").to_string(),
+            "23:45:10"
+        );
+    }
+
+    #[test]
+    fn garmin_type_keys_map_to_sports() {
+        let cases = [
+            ("running", Sport::Run),
+            ("trail_running", Sport::Run),
+            ("treadmill_running", Sport::Run),
+            ("road_biking", Sport::Bike),
+            ("indoor_cycling", Sport::Bike),
+            ("virtual_ride", Sport::Bike),
+            ("lap_swimming", Sport::Swim),
+            ("open_water_swimming", Sport::Swim),
+            ("strength_training", Sport::Strength),
+            ("fitness_equipment", Sport::Strength),
+            ("hiit", Sport::Cardio),
+            ("indoor_rowing", Sport::Cardio),
+            ("elliptical", Sport::Cardio),
+            ("yoga", Sport::Other),
+            ("", Sport::Other),
+        ];
+        for (key, sport) in cases {
+            assert_eq!(Sport::from_type_key(key), sport, "{key}");
+        }
+    }
+
+    #[test]
+    fn swim_activities_expose_pool_and_stroke_metrics() {
+        let swim: GarminActivity = serde_json::from_value(serde_json::json!({
+            "activityId": 1,
+            "activityType": { "typeKey": "lap_swimming" },
+            "startTimeLocal": "2026-03-10 07:00:00",
+            "distance": 1500.0,
+            "poolLength": 2500.0,
+            "unitOfPoolLength": { "unitId": 1, "unitKey": "meter", "factor": 100.0 },
+            "activeLengths": 60,
+            "averageSwolf": 38.0,
+            "averageSwimCadenceInStrokesPerMinute": 27.0
+        }))
+        .unwrap();
+        assert_eq!(swim.normalized_sport(), Sport::Swim);
+        assert_eq!(swim.pool_length_meters(), Some(25.0));
+        assert_eq!(swim.active_lengths, Some(60));
+        assert_eq!(swim.average_swolf, Some(38.0));
+        assert_eq!(swim.average_stroke_rate, Some(27.0));
+
+        let open_water: GarminActivity = serde_json::from_value(serde_json::json!({
+            "activityType": { "typeKey": "open_water_swimming" },
+            "startTimeLocal": "2026-03-11 07:00:00"
+        }))
+        .unwrap();
+        assert_eq!(open_water.normalized_sport(), Sport::Swim);
+        assert_eq!(open_water.pool_length_meters(), None);
+    }
+
+    #[test]
+    fn detail_temperatures_fill_only_missing_values() {
+        let mut run: GarminActivity = serde_json::from_value(serde_json::json!({
+            "activityType": { "typeKey": "running" },
+            "startTimeLocal": "2026-07-11 12:30:00",
+            "maxTemperature": 31.0
+        }))
+        .unwrap();
+        run.fill_temperatures_from_detail(&serde_json::json!({
+            "activityId": 42,
+            "summaryDTO": { "minTemperature": 23.0, "maxTemperature": 29.0 }
+        }));
+        assert_eq!(run.min_temperature, Some(23.0));
+        assert_eq!(run.max_temperature, Some(31.0));
+        assert!(run.is_outdoor_endurance());
+
+        let indoor: GarminActivity = serde_json::from_value(serde_json::json!({
+            "activityType": { "typeKey": "indoor_cycling" },
+            "startTimeLocal": "2026-07-11 12:30:00"
+        }))
+        .unwrap();
+        assert!(!indoor.is_outdoor_endurance());
+    }
+
+    #[test]
+    fn elevation_summary_skips_flat_and_indoor_activities() {
+        let trail: GarminActivity = serde_json::from_value(serde_json::json!({
+            "activityType": { "typeKey": "trail_running" },
+            "startTimeLocal": "2026-03-10 07:00:00",
+            "elevationGain": 420.4,
+            "elevationLoss": 415.0,
+            "maxElevation": 1230.2
+        }))
+        .unwrap();
+        assert_eq!(
+            trail.elevation_summary().as_deref(),
+            Some("+420 m / -415 m, max 1230 m")
+        );
+
+        let treadmill: GarminActivity = serde_json::from_value(serde_json::json!({
+            "activityType": { "typeKey": "treadmill_running" },
+            "startTimeLocal": "2026-03-11 07:00:00",
+            "elevationGain": 0.0,
+            "elevationLoss": 0.0
+        }))
+        .unwrap();
+        assert_eq!(treadmill.elevation_summary(), None);
+
+        let indoor: GarminActivity = serde_json::from_value(serde_json::json!({
+            "activityType": { "typeKey": "indoor_cycling" },
+            "startTimeLocal": "2026-03-12 07:00:00"
+        }))
+        .unwrap();
+        assert_eq!(indoor.elevation_summary(), None);
+    }
+
+    #[test]
+    fn muscle_groups_cover_common_lifts() {
+        let expected: &[(&str, &[&str])] = &[
+            ("BENCH_PRESS", &["chest", "triceps", "front-deltoids"]),
+            ("CHEST_FLY", &["chest", "front-deltoids"]),
+            (
+                "ROW",
+                &["upper-back", "lower-back", "biceps", "back-deltoids"],
+            ),
+            ("REVERSE_FLY", &["back-deltoids", "upper-back", "trapezius"]),
+            ("SHRUG", &["trapezius"]),
+            ("SQUAT", &["quadriceps", "gluteal", "hamstring", "calves"]),
+            ("LEG_PRESS", &["quadriceps", "gluteal", "hamstring"]),
+            ("LEG_EXTENSION", &["quadriceps"]),
+            ("LEG_CURL", &["hamstring", "calves"]),
+            ("HIP_THRUST", &["gluteal", "hamstring"]),
+            ("CALF_RAISE", &["calves"]),
+            (
+                "LATERAL_RAISE",
+                &["front-deltoids", "back-deltoids", "triceps"],
+            ),
+            ("BICEP_CURL", &["biceps"]),
+            ("PLANK", &["abs", "obliques"]),
+        ];
+        for (category, muscles) in expected {
+            assert_eq!(
+                muscle_groups_for(category),
+                muscles.to_vec(),
+                "{}",
+                category
+            );
+        }
+        assert!(muscle_groups_for("WARM_UP").is_empty());
+        assert!(muscle_groups_for("UNKNOWN").is_empty());
+    }
+
+    #[test]
+    fn calendar_workout_targets_are_read_from_nested_fields() {
+        let item = serde_json::json!({
+            "id": 1234567890,
+            "itemType": "workout",
+            "activityTypeId": 1,
+            "title": "Tempo Run",
+            "date": "2026-02-21",
+            "workoutId": 987654321,
+            "sportTypeKey": "running",
+            "isRace": false,
+            "primaryEvent": null,
+            "duration": null,
+            "distance": null,
+            "workout": {
+                "workoutId": 987654321,
+                "description": "2km warmup, 5km @ tempo, 1km cooldown",
+                "estimatedDurationInSecs": 2700,
+                "estimatedDistanceInMeters": 8000.0
+            }
+        });
+
+        let mut sw: ScheduledWorkout = serde_json::from_value(item).unwrap();
+        assert_eq!(sw.duration, None);
+        sw.fill_nested_targets();
+
+        assert_eq!(sw.sport.as_deref(), Some("running"));
+        assert_eq!(sw.duration, Some(2700.0));
+        assert_eq!(sw.distance, Some(8000.0));
+        assert_eq!(
+            sw.description.as_deref(),
+            Some("2km warmup, 5km @ tempo, 1km cooldown")
+        );
+    }
+
+    #[test]
+    fn explicit_calendar_targets_are_kept() {
+        let item = serde_json::json!({
+            "itemType": "workout",
+            "title": "Long Ride",
+            "date": "2026-02-22",
+            "duration": 5400.0,
+            "estimatedDurationInSecs": 3600,
+            "estimatedDistanceInMeters": 0
+        });
+
+        let mut sw: ScheduledWorkout = serde_json::from_value(item).unwrap();
+        sw.fill_nested_targets();
+        assert_eq!(sw.duration, Some(5400.0));
+        assert_eq!(sw.distance, None);
+    }
+
+    fn profile_born(birth_date: Option<&str>) -> GarminProfile {
+        GarminProfile {
+            weight: None,
+            height: None,
+            birth_date: birth_date.map(str::to_string),
+            vo2_max_running: None,
+        }
+    }
+
+    #[test]
+    fn age_counts_completed_years() {
+        let today = NaiveDate::from_ymd_opt(2026, 3, 14).unwrap();
+        assert_eq!(profile_born(Some("1990-03-14")).age(today), Some(36));
+        assert_eq!(profile_born(Some("1990-03-15")).age(today), Some(35));
+        assert_eq!(
+            profile_born(Some("1990-03-14T00:00:00.0")).age(today),
+            Some(36)
+        );
+        assert_eq!(profile_born(Some("2030-01-01")).age(today), None);
+        assert_eq!(profile_born(Some("14.03.1990")).age(today), None);
+        assert_eq!(profile_born(None).age(today), None);
+    }
+
+    #[test]
+    fn max_hr_formulas() {
+        assert_eq!(MaxHrFormula::Fox.max_hr(40), 180.0);
+        assert_eq!(MaxHrFormula::Tanaka.max_hr(40), 180.0);
+        assert_eq!(MaxHrFormula::Fox.max_hr(30), 190.0);
+        assert!((MaxHrFormula::Tanaka.max_hr(30) - 187.0).abs() < 1e-9);
+
+        let today = NaiveDate::from_ymd_opt(2026, 3, 14).unwrap();
+        let profile = profile_born(Some("1996-01-01"));
+        assert_eq!(
+            profile.estimated_max_hr(today, MaxHrFormula::Fox),
+            Some(190.0)
+        );
+        assert_eq!(
+            profile_born(None).estimated_max_hr(today, MaxHrFormula::Tanaka),
+            None
+        );
+    }
+}
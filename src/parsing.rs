@@ -0,0 +1,160 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+const METERS_PER_MILE: f64 = 1609.344;
+
+lazy_static! {
+    static ref DURATION_RE: Regex = Regex::new(r"\d+").unwrap();
+    static ref CLOCK_RE: Regex = Regex::new(r"^\s*(\d+):([0-5]\d)\s*$").unwrap();
+    static ref WEIGHT_RE: Regex = Regex::new(r"(\d+(?:\.\d+)?)\s*(lbs?\b)?").unwrap();
+    static ref DISTANCE_RE: Regex =
+        Regex::new(r"^\s*(\d+(?:\.\d+)?)\s*(?:(km|kilometers?|mi|miles?|m|meters?)\b.*)?$")
+            .unwrap();
+    static ref PACE_RE: Regex = Regex::new(r"^\s*(\d+):([0-5]\d)\s*(?:/\s*(km|mi))?\s*$").unwrap();
+}
+
+/// Seconds from `"90"`, `"12min"`, `"10-12 min"` (first number wins) or a
+/// `"m:ss"` clock value like `"1:30"`.
+pub fn parse_duration(text: &str) -> Option<i64> {
+    if let Some(caps) = CLOCK_RE.captures(text) {
+        return Some(caps[1].parse::<i64>().ok()? * 60 + caps[2].parse::<i64>().ok()?);
+    }
+    let value = DURATION_RE.find(text)?.as_str().parse::<i64>().ok()?;
+    if text.to_lowercase().contains("min") {
+        Some(value * 60)
+    } else {
+        Some(value)
+    }
+}
+
+/// Kilograms from `"42.5"`, `"42.5kg"` or pounds (`"135lb"`, `"135 lbs"`).
+pub fn parse_weight(text: &str) -> Option<f64> {
+    let lower = text.to_lowercase();
+    let caps = WEIGHT_RE.captures(&lower)?;
+    let value = caps[1].parse::<f64>().ok()?;
+    if caps.get(2).is_some() {
+        Some(value * crate::models::KG_PER_LB)
+    } else {
+        Some(value)
+    }
+}
+
+/// Meters from `"800"`, `"800m"`, `"1.5km"`, `"2mi"` or `"3 miles easy"`.
+/// A bare number counts as meters; anything after it must start with a unit.
+pub fn parse_distance(text: &str) -> Option<f64> {
+    let lower = text.to_lowercase();
+    let caps = DISTANCE_RE.captures(&lower)?;
+    let value = caps[1].parse::<f64>().ok()?;
+    match caps.get(2).map(|m| m.as_str()) {
+        Some(unit) if unit.starts_with('k') => Some(value * 1000.0),
+        Some(unit) if unit.starts_with("mi") => Some(value * METERS_PER_MILE),
+        _ => Some(value),
+    }
+}
+
+/// Seconds per km from `"5:30"`, `"5:30/km"` or a per-mile pace (`"8:00/mi"`).
+pub fn parse_pace(text: &str) -> Option<f64> {
+    let lower = text.to_lowercase();
+    let caps = PACE_RE.captures(&lower)?;
+    let secs = caps[1].parse::<f64>().ok()? * 60.0 + caps[2].parse::<f64>().ok()?;
+    if secs <= 0.0 {
+        return None;
+    }
+    match caps.get(3).map(|m| m.as_str()) {
+        Some("mi") => Some(secs * 1000.0 / METERS_PER_MILE),
+        _ => Some(secs),
+    }
+}
+
+/// `(fastest, slowest)` seconds per km from a single pace or a range such as
+/// `"3:50-4:00/km"`; the unit suffix may sit on either end of the range.
+pub fn parse_pace_range(text: &str) -> Option<(f64, f64)> {
+    let text = text.trim();
+    let (a, b) = match text.split_once('-') {
+        Some((a, b)) => {
+            let unit = ["/km", "/mi"]
+                .into_iter()
+                .find(|u| b.trim_end().to_lowercase().ends_with(u))
+                .unwrap_or("");
+            let a = if a.contains('/') {
+                a.to_string()
+            } else {
+                format!("{}{}", a, unit)
+            };
+            (parse_pace(&a)?, parse_pace(b)?)
+        }
+        None => {
+            let pace = parse_pace(text)?;
+            (pace, pace)
+        }
+    };
+    Some((a.min(b), a.max(b)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_distance, parse_duration, parse_pace, parse_pace_range, parse_weight};
+
+    #[test]
+    fn durations_accept_seconds_minutes_and_clock_values() {
+        assert_eq!(parse_duration("90"), Some(90));
+        assert_eq!(parse_duration("12min"), Some(720));
+        assert_eq!(parse_duration("10-12 min"), Some(600));
+        assert_eq!(parse_duration("45 sec"), Some(45));
+        assert_eq!(parse_duration("1:30"), Some(90));
+
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("until failure"), None);
+    }
+
+    #[test]
+    fn weights_are_kilograms_and_pounds_are_converted() {
+        assert_eq!(parse_weight("42.5kg"), Some(42.5));
+        assert_eq!(parse_weight("80"), Some(80.0));
+        let kg = parse_weight("135lb").unwrap();
+        assert!((kg - 61.2).abs() < 0.05);
+        assert_eq!(parse_weight("135 LBS"), Some(kg));
+
+        assert_eq!(parse_weight("bodyweight"), None);
+        assert_eq!(parse_weight(""), None);
+    }
+
+    #[test]
+    fn distances_convert_to_meters() {
+        assert_eq!(parse_distance("800"), Some(800.0));
+        assert_eq!(parse_distance("800m"), Some(800.0));
+        assert_eq!(parse_distance("1.5km"), Some(1500.0));
+        assert_eq!(parse_distance("1 KM"), Some(1000.0));
+        assert_eq!(parse_distance("1mi"), Some(1609.344));
+        assert_eq!(parse_distance("2 miles easy"), Some(3218.688));
+        assert_eq!(parse_distance("400 meters"), Some(400.0));
+
+        assert_eq!(parse_distance("km"), None);
+        assert_eq!(parse_distance("5 laps"), None);
+        assert_eq!(parse_distance(""), None);
+    }
+
+    #[test]
+    fn paces_convert_to_seconds_per_km() {
+        assert_eq!(parse_pace("5:30/km"), Some(330.0));
+        assert_eq!(parse_pace("5:30"), Some(330.0));
+        assert_eq!(parse_pace(" 4:05 / km "), Some(245.0));
+        let per_mile = parse_pace("8:00/mi").unwrap();
+        assert!((per_mile - 298.26).abs() < 0.01);
+
+        assert_eq!(parse_pace("0:00"), None);
+        assert_eq!(parse_pace("5:75"), None);
+        assert_eq!(parse_pace("fast"), None);
+        assert_eq!(parse_pace("5:30/lap"), None);
+        assert_eq!(parse_pace(""), None);
+    }
+
+    #[test]
+    fn pace_ranges_are_ordered_fastest_first() {
+        assert_eq!(parse_pace_range("3:50-4:00"), Some((230.0, 240.0)));
+        assert_eq!(parse_pace_range("4:00-3:50/km"), Some((230.0, 240.0)));
+        assert_eq!(parse_pace_range("4:05"), Some((245.0, 245.0)));
+        assert_eq!(parse_pace_range("4:00-"), None);
+        assert_eq!(parse_pace_range("easy"), None);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::models::ScheduledWorkout;
+
+/// RFC 5545 limits content lines to 75 octets before folding.
+const ICS_LINE_LIMIT: usize = 75;
+
+/// Renders scheduled workouts as an iCalendar feed with one all-day VEVENT each.
+/// `stamp` is the feed's generation time, used for every DTSTAMP.
+pub fn render_ics(workouts: &[ScheduledWorkout], stamp: chrono::DateTime<chrono::Utc>) -> String {
+    let dtstamp = stamp.format("%Y%m%dT%H%M%SZ").to_string();
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, "PRODID:-//FitnessJournal//Training Plan//EN");
+    push_line(&mut out, "CALSCALE:GREGORIAN");
+    push_line(&mut out, "X-WR-CALNAME:Training Plan");
+
+    for (i, w) in workouts.iter().enumerate() {
+        let Ok(day) = chrono::NaiveDate::parse_from_str(w.date.get(..10).unwrap_or(""), "%Y-%m-%d")
+        else {
+            continue;
+        };
+        let end = day + chrono::Duration::days(1);
+
+        push_line(&mut out, "BEGIN:VEVENT");
+        push_line(&mut out, &format!("UID:{}", event_uid(w, i)));
+        push_line(&mut out, &format!("DTSTAMP:{}", dtstamp));
+        push_line(
+            &mut out,
+            &format!("DTSTART;VALUE=DATE:{}", day.format("%Y%m%d")),
+        );
+        push_line(
+            &mut out,
+            &format!("DTEND;VALUE=DATE:{}", end.format("%Y%m%d")),
+        );
+        push_line(
+            &mut out,
+            &format!("SUMMARY:{}", escape_ics_text(&display_title(w))),
+        );
+        let description = event_description(w);
+        if !description.is_empty() {
+            push_line(
+                &mut out,
+                &format!("DESCRIPTION:{}", escape_ics_text(&description)),
+            );
+        }
+        push_line(&mut out, "END:VEVENT");
+    }
+
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// Renders scheduled workouts as CSV with a header row.
+pub fn render_csv(workouts: &[ScheduledWorkout]) -> String {
+    let mut out = String::from("date,title,sport,duration_min,distance_km,description\n");
+    for w in workouts {
+        let fields = [
+            w.date.get(..10).unwrap_or(&w.date).to_string(),
+            display_title(w),
+            w.sport.clone().unwrap_or_default(),
+            w.duration
+                .map(|s| format!("{:.0}", s / 60.0))
+                .unwrap_or_default(),
+            w.distance
+                .map(|m| format!("{:.2}", m / 1000.0))
+                .unwrap_or_default(),
+            w.description.clone().unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| escape_csv_field(f)).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn display_title(w: &ScheduledWorkout) -> String {
+    w.title
+        .as_deref()
+        .filter(|t| !t.trim().is_empty())
+        .unwrap_or("Workout")
+        .to_string()
+}
+
+/// Stable across refreshes when Garmin supplies the calendar item id, so
+/// calendar apps update events in place instead of duplicating them.
+fn event_uid(w: &ScheduledWorkout, index: usize) -> String {
+    match w.raw_fields.get("id").filter(|v| !v.is_null()) {
+        Some(id) => format!("fj-{}@fitness-journal", id.to_string().trim_matches('"')),
+        None => format!("fj-{}-{}@fitness-journal", w.date, index),
+    }
+}
+
+fn event_description(w: &ScheduledWorkout) -> String {
+    let mut parts = Vec::new();
+    if let Some(sport) = w.sport.as_deref().filter(|s| !s.is_empty()) {
+        parts.push(format!("Sport: {}", sport));
+    }
+    if let Some(secs) = w.duration {
+        parts.push(format!("Duration: {:.0} min", secs / 60.0));
+    }
+    if let Some(meters) = w.distance {
+        parts.push(format!("Distance: {:.2} km", meters / 1000.0));
+    }
+    if let Some(desc) = w.description.as_deref().filter(|d| !d.trim().is_empty()) {
+        parts.push(desc.to_string());
+    }
+    parts.join("\n")
+}
+
+fn escape_ics_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Appends a CRLF-terminated content line, folding it into 75-octet chunks
+/// without splitting UTF-8 characters.
+fn push_line(out: &mut String, line: &str) {
+    let mut used = 0;
+    for ch in line.chars() {
+        let len = ch.len_utf8();
+        if used + len > ICS_LINE_LIMIT {
+            out.push_str("\r\n ");
+            // The leading space of a continuation line counts towards its limit.
+            used = 1;
+        }
+        out.push(ch);
+        used += len;
+    }
+    out.push_str("\r\n");
+}
+
+fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_csv, render_ics};
+    use crate::models::ScheduledWorkout;
+
+    fn workout(title: &str, date: &str, description: Option<&str>) -> ScheduledWorkout {
+        serde_json::from_value(serde_json::json!({
+            "title": title,
+            "date": date,
+            "sport": "running",
+            "duration": 2700.0,
+            "distance": 8000.0,
+            "description": description,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn ics_has_one_folded_vevent_per_workout() {
+        let long = "Warm up 15 min easy, then 5x1km at threshold with 2 min jog recoveries; cool down 10 min.";
+        let workouts = vec![
+            workout("FJ-AI: Tempo Run", "2026-03-10", Some(long)),
+            workout("FJ-AI: Long Run", "2026-03-12", None),
+        ];
+        let stamp = chrono::DateTime::from_timestamp(1_773_000_000, 0).unwrap();
+        let ics = render_ics(&workouts, stamp);
+
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+        assert_eq!(ics.matches("END:VEVENT").count(), 2);
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.contains("DTSTART;VALUE=DATE:20260310\r\n"));
+        assert!(ics.contains("DTEND;VALUE=DATE:20260313\r\n"));
+        assert!(ics.contains("UID:fj-2026-03-10-0@fitness-journal\r\n"));
+        assert!(ics.contains("SUMMARY:FJ-AI: Tempo Run\r\n"));
+        for line in ics.split("\r\n") {
+            assert!(line.len() <= 75, "line not folded: {line:?}");
+        }
+        // Unfolding restores the escaped description.
+        let unfolded = ics.replace("\r\n ", "");
+        assert!(unfolded.contains("5x1km at threshold with 2 min jog recoveries\\; cool down"));
+    }
+
+    #[test]
+    fn csv_quotes_fields_with_commas() {
+        let workouts = vec![workout(
+            "Intervals",
+            "2026-03-10T00:00:00",
+            Some("Easy, then fast"),
+        )];
+        let csv = render_csv(&workouts);
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("date,title,sport,duration_min,distance_km,description")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("2026-03-10,Intervals,running,45,8.00,\"Easy, then fast\"")
+        );
+    }
+}
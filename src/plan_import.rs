@@ -0,0 +1,153 @@
+use crate::workout_builder::{ResolutionMethod, WorkoutBuilder};
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use serde_json::Value;
+
+/// A validated plan file, ready to be created and scheduled on Garmin.
+#[derive(Debug)]
+pub struct ImportedPlan {
+    /// Workout specs in file order, each with a valid `scheduledDate`.
+    pub workouts: Vec<Value>,
+    /// The Garmin payload built for each entry of `workouts`.
+    pub payloads: Vec<Value>,
+    /// Exercise names that did not resolve to a known Garmin exercise, sorted
+    /// and deduplicated. They still upload, but without a specific exercise.
+    pub unresolved: Vec<String>,
+}
+
+/// Parses a plan file: a JSON array of workouts in the AI output schema, each
+/// with a `scheduledDate` (`YYYY-MM-DD`) no earlier than `today`. Every invalid
+/// entry is reported at once so the file can be fixed in a single pass.
+pub fn parse_plan(json: &str, builder: &WorkoutBuilder, today: NaiveDate) -> Result<ImportedPlan> {
+    let parsed: Value = serde_json::from_str(json)?;
+    let workouts = parsed
+        .as_array()
+        .ok_or_else(|| anyhow!("Plan must be a JSON array of workouts"))?;
+    if workouts.is_empty() {
+        return Err(anyhow!("Plan contains no workouts"));
+    }
+
+    let mut problems = Vec::new();
+    for (i, w) in workouts.iter().enumerate() {
+        let name = w
+            .get("workoutName")
+            .and_then(|n| n.as_str())
+            .unwrap_or("unnamed");
+        match w.get("scheduledDate").and_then(|d| d.as_str()) {
+            None => problems.push(format!("#{} '{}': missing scheduledDate", i + 1, name)),
+            Some(date) => match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                Err(_) => problems.push(format!(
+                    "#{} '{}': invalid scheduledDate '{}'",
+                    i + 1,
+                    name,
+                    date
+                )),
+                Ok(day) if day < today => problems.push(format!(
+                    "#{} '{}': scheduledDate {} is in the past",
+                    i + 1,
+                    name,
+                    date
+                )),
+                Ok(_) => {}
+            },
+        }
+    }
+    if !problems.is_empty() {
+        return Err(anyhow!("Invalid plan:\n{}", problems.join("\n")));
+    }
+
+    let mut payloads = Vec::new();
+    let mut unresolved = Vec::new();
+    for w in workouts {
+        let (payload, resolutions) = builder.build_workout_payload_with_resolutions(w, false);
+        payloads.push(payload);
+        unresolved.extend(
+            resolutions
+                .into_iter()
+                .filter(|r| {
+                    matches!(
+                        r.method,
+                        ResolutionMethod::None | ResolutionMethod::Fallback
+                    )
+                })
+                .map(|r| r.input),
+        );
+    }
+    unresolved.sort();
+    unresolved.dedup();
+
+    Ok(ImportedPlan {
+        workouts: workouts.clone(),
+        payloads,
+        unresolved,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_plan;
+    use crate::workout_builder::WorkoutBuilder;
+    use chrono::NaiveDate;
+
+    const PLAN: &str = r#"[
+        {
+            "workoutName": "Week 1 Lower",
+            "description": "Squat focus",
+            "scheduledDate": "2026-11-02",
+            "steps": [
+                {"phase": "warmup", "exercise": "CARDIO", "duration": "5min"},
+                {"exercise": "BARBELL_BACK_SQUAT", "sets": 4, "reps": 5, "weight": 100},
+                {"exercise": "Frobnicator Lunge", "sets": 3, "reps": 10}
+            ]
+        },
+        {
+            "workoutName": "Week 1 Easy Run",
+            "sport": "running",
+            "scheduledDate": "2026-11-04",
+            "steps": [
+                {"phase": "interval", "duration": "40min", "target_pace": "6:00"}
+            ]
+        },
+        {
+            "workoutName": "Week 2 Lower",
+            "scheduledDate": "2026-11-09",
+            "steps": [
+                {"exercise": "Frobnicator Lunge", "sets": 3, "reps": 8}
+            ]
+        }
+    ]"#;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, 17).unwrap()
+    }
+
+    #[test]
+    fn plan_builds_one_payload_per_workout() {
+        let plan = parse_plan(PLAN, &WorkoutBuilder::new(), today()).unwrap();
+
+        assert_eq!(plan.workouts.len(), 3);
+        assert_eq!(plan.payloads.len(), 3);
+        assert_eq!(plan.payloads[0]["workoutName"], "Week 1 Lower");
+        assert_eq!(plan.payloads[1]["sportType"]["sportTypeKey"], "running");
+        assert_eq!(plan.unresolved, vec!["Frobnicator Lunge".to_string()]);
+    }
+
+    #[test]
+    fn invalid_dates_are_all_reported_before_uploading() {
+        let plan = r#"[
+            {"workoutName": "A", "steps": []},
+            {"workoutName": "B", "scheduledDate": "next monday", "steps": []},
+            {"workoutName": "C", "scheduledDate": "2026-10-01", "steps": []},
+            {"workoutName": "D", "scheduledDate": "2026-10-20", "steps": []}
+        ]"#;
+
+        let err = parse_plan(plan, &WorkoutBuilder::new(), today())
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.contains("#1 'A': missing scheduledDate"));
+        assert!(err.contains("#2 'B': invalid scheduledDate 'next monday'"));
+        assert!(err.contains("#3 'C': scheduledDate 2026-10-01 is in the past"));
+        assert!(!err.contains("'D'"));
+    }
+}
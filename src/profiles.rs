@@ -0,0 +1,831 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+use tracing::{error, info};
+
+const MAX_PROFILE_NAME_LEN: usize = 64;
+const MAX_PROFILE_ITEMS: usize = 64;
+const MAX_PROFILE_ITEM_LEN: usize = 256;
+const MAX_COACH_PERSONA_LEN: usize = 2048;
+/// Valid range for a planning horizon (profile override and `AppConfig`).
+pub const PLANNING_HORIZON_RANGE: std::ops::RangeInclusive<u32> = 1..=28;
+const WEEKDAY_ABBREVIATIONS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Serializes in-process access to `profiles.json`: readers (pipeline, bot, API)
+/// never see a write in progress, and read-modify-write updates are not lost.
+/// The atomic rename covers other processes; its direct-write fallback does not.
+static PROFILES_LOCK: RwLock<()> = RwLock::new(());
+
+fn read_lock() -> RwLockReadGuard<'static, ()> {
+    PROFILES_LOCK.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write_lock() -> RwLockWriteGuard<'static, ()> {
+    PROFILES_LOCK.write().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn profiles_path() -> String {
+    std::env::var("PROFILES_PATH").unwrap_or_else(|_| "data/profiles.json".to_string())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfigPayload {
+    #[serde(default)]
+    pub goals: Vec<String>,
+    #[serde(default)]
+    pub constraints: Vec<String>,
+    #[serde(default)]
+    pub available_equipment: Vec<String>,
+    #[serde(default)]
+    pub auto_analyze_sports: Vec<String>,
+    /// Auto-analysis prompt templates keyed by an `auto_analyze_sports` entry.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub analysis_prompts: BTreeMap<String, String>,
+    /// Replaces `AppConfig::coach_persona` while this profile is active.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coach_persona: Option<String>,
+    /// Weekday abbreviations (`Mon`..`Sun`) on which no workout may be scheduled.
+    #[serde(default)]
+    pub rest_days: Vec<String>,
+    /// Replaces `AppConfig::planning_horizon_days` while this profile is active.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub planning_horizon_days: Option<u32>,
+    /// Unit system weights are displayed in; Garmin payloads stay metric.
+    #[serde(default)]
+    pub units: crate::models::Units,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfilesPayload {
+    pub active_profile: String,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ProfileConfigPayload>,
+}
+
+impl ProfilesPayload {
+    fn active_mut(&mut self) -> Result<&mut ProfileConfigPayload> {
+        let name = self.active_profile.clone();
+        self.profiles
+            .get_mut(&name)
+            .ok_or_else(|| anyhow!("Active profile '{}' not found", name))
+    }
+}
+
+/// One invalid field in a profiles payload, so the dashboard can point at it.
+/// `profile` is `None` for top-level fields such as `active_profile`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProfileValidationError {
+    pub field: String,
+    pub profile: Option<String>,
+    pub message: String,
+}
+
+impl ProfileValidationError {
+    fn new(field: &str, profile: Option<&str>, message: String) -> Self {
+        Self {
+            field: field.to_string(),
+            profile: profile.map(str::to_string),
+            message,
+        }
+    }
+}
+
+/// Joins validation errors into one message for logs and CLI output.
+pub fn describe_validation_errors(errors: &[ProfileValidationError]) -> String {
+    errors
+        .iter()
+        .map(|e| e.message.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn normalize_profile_list(
+    values: &[String],
+    profile_name: &str,
+    field_name: &str,
+    errors: &mut Vec<ProfileValidationError>,
+) -> Vec<String> {
+    if values.len() > MAX_PROFILE_ITEMS {
+        errors.push(ProfileValidationError::new(
+            field_name,
+            Some(profile_name),
+            format!(
+                "Profile '{}' has too many '{}' entries (max {}).",
+                profile_name, field_name, MAX_PROFILE_ITEMS
+            ),
+        ));
+    }
+
+    let mut normalized = Vec::new();
+    let mut too_long = false;
+    for value in values {
+        let trimmed = value.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        if trimmed.chars().count() > MAX_PROFILE_ITEM_LEN {
+            too_long = true;
+            continue;
+        }
+
+        normalized.push(trimmed.to_string());
+    }
+    if too_long {
+        errors.push(ProfileValidationError::new(
+            field_name,
+            Some(profile_name),
+            format!(
+                "Profile '{}' has an entry in '{}' that exceeds {} characters.",
+                profile_name, field_name, MAX_PROFILE_ITEM_LEN
+            ),
+        ));
+    }
+
+    normalized
+}
+
+/// Trims and checks a profiles payload, reporting every invalid field at once.
+pub fn validate_profiles_payload(
+    payload: ProfilesPayload,
+) -> Result<ProfilesPayload, Vec<ProfileValidationError>> {
+    let mut errors = Vec::new();
+    let active_profile = payload.active_profile.trim();
+    if active_profile.is_empty() {
+        errors.push(ProfileValidationError::new(
+            "active_profile",
+            None,
+            "active_profile cannot be empty.".to_string(),
+        ));
+    } else if active_profile.chars().count() > MAX_PROFILE_NAME_LEN {
+        errors.push(ProfileValidationError::new(
+            "active_profile",
+            None,
+            format!(
+                "active_profile exceeds {} characters.",
+                MAX_PROFILE_NAME_LEN
+            ),
+        ));
+    }
+    if payload.profiles.is_empty() {
+        errors.push(ProfileValidationError::new(
+            "profiles",
+            None,
+            "profiles must include at least one profile.".to_string(),
+        ));
+    }
+
+    let mut normalized_profiles = BTreeMap::new();
+    for (raw_name, profile) in payload.profiles {
+        let profile_name = raw_name.trim();
+        let name_error = if profile_name.is_empty() {
+            Some("Profile names cannot be empty.".to_string())
+        } else if profile_name.chars().count() > MAX_PROFILE_NAME_LEN {
+            Some(format!(
+                "Profile name '{}' exceeds {} characters.",
+                profile_name, MAX_PROFILE_NAME_LEN
+            ))
+        } else if normalized_profiles.contains_key(profile_name) {
+            Some(format!("Duplicate profile name '{}'.", profile_name))
+        } else {
+            None
+        };
+        if let Some(message) = name_error {
+            errors.push(ProfileValidationError::new(
+                "name",
+                Some(&raw_name),
+                message,
+            ));
+        }
+        let field_error = |field: &str, message: String| {
+            ProfileValidationError::new(field, Some(profile_name), message)
+        };
+
+        let mut rest_days = Vec::new();
+        for day in &profile.rest_days {
+            match parse_rest_day(day) {
+                Some(abbr) => rest_days.push(abbr.to_string()),
+                None => errors.push(field_error(
+                    "rest_days",
+                    format!(
+                        "Profile '{}' rest_days entry '{}' is not one of {}.",
+                        profile_name,
+                        day.trim(),
+                        WEEKDAY_ABBREVIATIONS.join(", ")
+                    ),
+                )),
+            }
+        }
+
+        let normalized_profile = ProfileConfigPayload {
+            goals: normalize_profile_list(&profile.goals, profile_name, "goals", &mut errors),
+            constraints: normalize_profile_list(
+                &profile.constraints,
+                profile_name,
+                "constraints",
+                &mut errors,
+            ),
+            available_equipment: normalize_profile_list(
+                &profile.available_equipment,
+                profile_name,
+                "available_equipment",
+                &mut errors,
+            ),
+            auto_analyze_sports: normalize_profile_list(
+                &profile.auto_analyze_sports,
+                profile_name,
+                "auto_analyze_sports",
+                &mut errors,
+            ),
+            analysis_prompts: profile
+                .analysis_prompts
+                .iter()
+                .map(|(sport, prompt)| (sport.trim().to_string(), prompt.trim().to_string()))
+                .filter(|(_, prompt)| !prompt.is_empty())
+                .collect(),
+            coach_persona: profile
+                .coach_persona
+                .as_deref()
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(str::to_string),
+            rest_days,
+            planning_horizon_days: profile.planning_horizon_days,
+            units: profile.units,
+        };
+
+        if let Some(days) = normalized_profile.planning_horizon_days {
+            if !PLANNING_HORIZON_RANGE.contains(&days) {
+                errors.push(field_error(
+                    "planning_horizon_days",
+                    format!(
+                        "Profile '{}' planning_horizon_days must be between {} and {}.",
+                        profile_name,
+                        PLANNING_HORIZON_RANGE.start(),
+                        PLANNING_HORIZON_RANGE.end()
+                    ),
+                ));
+            }
+        }
+
+        for (sport, prompt) in &normalized_profile.analysis_prompts {
+            if !normalized_profile.auto_analyze_sports.contains(sport) {
+                errors.push(field_error(
+                    "analysis_prompts",
+                    format!(
+                        "Profile '{}' has an analysis prompt for '{}', which is not in auto_analyze_sports.",
+                        profile_name, sport
+                    ),
+                ));
+            }
+            if prompt.chars().count() > MAX_COACH_PERSONA_LEN {
+                errors.push(field_error(
+                    "analysis_prompts",
+                    format!(
+                        "Profile '{}' analysis prompt for '{}' exceeds {} characters.",
+                        profile_name, sport, MAX_COACH_PERSONA_LEN
+                    ),
+                ));
+            }
+        }
+
+        if let Some(persona) = &normalized_profile.coach_persona {
+            if persona.chars().count() > MAX_COACH_PERSONA_LEN {
+                errors.push(field_error(
+                    "coach_persona",
+                    format!(
+                        "Profile '{}' coach_persona exceeds {} characters.",
+                        profile_name, MAX_COACH_PERSONA_LEN
+                    ),
+                ));
+            }
+        }
+
+        normalized_profiles
+            .entry(profile_name.to_string())
+            .or_insert(normalized_profile);
+    }
+
+    if !active_profile.is_empty() && !normalized_profiles.contains_key(active_profile) {
+        errors.push(ProfileValidationError::new(
+            "active_profile",
+            None,
+            format!(
+                "active_profile '{}' must reference an existing profile.",
+                active_profile
+            ),
+        ));
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(ProfilesPayload {
+        active_profile: active_profile.to_string(),
+        profiles: normalized_profiles,
+    })
+}
+
+/// Canonical abbreviation (`"Sun"`) for a rest day entry, case-insensitive.
+fn parse_rest_day(day: &str) -> Option<&'static str> {
+    let day = day.trim();
+    WEEKDAY_ABBREVIATIONS
+        .into_iter()
+        .find(|abbr| abbr.eq_ignore_ascii_case(day))
+}
+
+pub fn write_file_atomically(path: &Path, content: &str) -> std::io::Result<()> {
+    let mut tmp_path = path.to_path_buf();
+    tmp_path.set_extension("json.tmp");
+
+    std::fs::write(&tmp_path, content)?;
+    if let Err(err) = std::fs::rename(&tmp_path, path) {
+        // Docker file bind mounts can reject atomic replace with EBUSY/EXDEV.
+        // In that case we fall back to direct write to preserve functionality.
+        let needs_fallback = matches!(err.raw_os_error(), Some(16 | 18));
+        if needs_fallback {
+            error!(
+                "Atomic replace failed for {} ({}). Falling back to direct write.",
+                path.display(),
+                err
+            );
+            std::fs::write(path, content)?;
+            let _ = std::fs::remove_file(&tmp_path);
+            return Ok(());
+        }
+
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+/// Reads and validates the profiles file at `path`.
+pub fn load_profiles(path: &Path) -> Result<ProfilesPayload> {
+    let _guard = read_lock();
+    read_profiles(path)
+}
+
+fn read_profiles(path: &Path) -> Result<ProfilesPayload> {
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let parsed = serde_json::from_str::<ProfilesPayload>(&data)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    validate_profiles_payload(parsed).map_err(|errors| anyhow!(describe_validation_errors(&errors)))
+}
+
+/// Validates `payload` and atomically writes it to `path` as pretty JSON.
+pub fn save_profiles(path: &Path, payload: ProfilesPayload) -> Result<ProfilesPayload> {
+    let _guard = write_lock();
+    write_profiles(path, payload)
+}
+
+fn write_profiles(path: &Path, payload: ProfilesPayload) -> Result<ProfilesPayload> {
+    let validated = validate_profiles_payload(payload)
+        .map_err(|errors| anyhow!(describe_validation_errors(&errors)))?;
+    let mut json_str = serde_json::to_string_pretty(&validated)?;
+    json_str.push('\n');
+    write_file_atomically(path, &json_str)
+        .with_context(|| format!("Failed to atomically write {}", path.display()))?;
+    Ok(validated)
+}
+
+/// Makes `name` the active profile, leaving the rest of the file untouched.
+/// Returns `false` without writing when no profile is called `name`.
+pub fn set_active_profile(path: &Path, name: &str) -> Result<bool> {
+    let _guard = write_lock();
+    let mut payload = read_profiles(path)?;
+    let name = name.trim();
+    if !payload.profiles.contains_key(name) {
+        return Ok(false);
+    }
+    payload.active_profile = name.to_string();
+    write_profiles(path, payload)?;
+    Ok(true)
+}
+
+/// Appends a constraint to the active profile and returns the updated list.
+pub fn add_active_constraint(path: &Path, constraint: &str) -> Result<Vec<String>> {
+    let _guard = write_lock();
+    let mut payload = read_profiles(path)?;
+    payload
+        .active_mut()?
+        .constraints
+        .push(constraint.to_string());
+    let mut saved = write_profiles(path, payload)?;
+    Ok(saved.active_mut()?.constraints.clone())
+}
+
+/// Removes the 1-based `index` constraint from the active profile and returns it.
+pub fn remove_active_constraint(path: &Path, index: usize) -> Result<String> {
+    let _guard = write_lock();
+    let mut payload = read_profiles(path)?;
+    let constraints = &mut payload.active_mut()?.constraints;
+    if index == 0 || index > constraints.len() {
+        return Err(anyhow!(
+            "No constraint #{} (active profile has {}).",
+            index,
+            constraints.len()
+        ));
+    }
+    let removed = constraints.remove(index - 1);
+    write_profiles(path, payload)?;
+    Ok(removed)
+}
+
+/// Goals the coach works towards when no profile provides any.
+fn default_goals() -> Vec<String> {
+    vec![
+        "Improve Marathon Time (Sub 4h)".to_string(),
+        "Maintain Upper Body Strength (Hypertrophy)".to_string(),
+        "Increase VO2Max".to_string(),
+    ]
+}
+
+/// Which sports the active profile auto-analyzes, and how.
+#[derive(Debug, Default)]
+pub struct AutoAnalysis {
+    pub sports: Vec<String>,
+    pub prompts: BTreeMap<String, String>,
+}
+
+impl AutoAnalysis {
+    /// The profile's template for `sport`, or the generic analysis prompt.
+    pub fn analysis_prompt_for(&self, sport: &str) -> &str {
+        self.prompts
+            .get(sport)
+            .map(String::as_str)
+            .unwrap_or(crate::coaching::GENERIC_ANALYSIS_PROMPT)
+    }
+}
+
+/// The active profile as coaching context, plus its auto-analysis settings.
+/// Falls back to the default goals when `profiles.json` is missing or invalid.
+pub fn load_active_context() -> (crate::coaching::CoachContext, AutoAnalysis) {
+    load_active_context_from(Path::new(&profiles_path()))
+}
+
+fn load_active_context_from(path: &Path) -> (crate::coaching::CoachContext, AutoAnalysis) {
+    let mut context = crate::coaching::CoachContext {
+        goals: default_goals(),
+        constraints: Vec::new(),
+        available_equipment: Vec::new(),
+        rest_days: Vec::new(),
+        planning_horizon_days: None,
+    };
+    if !path.exists() {
+        return (context, AutoAnalysis::default());
+    }
+
+    let mut payload = match load_profiles(path) {
+        Ok(payload) => payload,
+        Err(e) => {
+            error!("Ignoring profiles, using default goals: {:#}", e);
+            return (context, AutoAnalysis::default());
+        }
+    };
+    let active_name = payload.active_profile.clone();
+    let Ok(profile) = payload.active_mut() else {
+        return (context, AutoAnalysis::default());
+    };
+    info!("Loaded active equipment profile: {}", active_name);
+
+    if profile.goals.is_empty() {
+        info!(
+            "Warning: profile '{}' has no valid goals. Falling back to default goals.",
+            active_name
+        );
+    } else {
+        context.goals = std::mem::take(&mut profile.goals);
+    }
+    context.constraints = std::mem::take(&mut profile.constraints);
+    context.available_equipment = std::mem::take(&mut profile.available_equipment);
+    context.rest_days = std::mem::take(&mut profile.rest_days);
+    context.planning_horizon_days = profile.planning_horizon_days;
+
+    let auto_analysis = AutoAnalysis {
+        sports: std::mem::take(&mut profile.auto_analyze_sports),
+        prompts: std::mem::take(&mut profile.analysis_prompts),
+    };
+    (context, auto_analysis)
+}
+
+/// The coaching persona for AI calls: the active profile's `coach_persona`
+/// when set, otherwise `config.coach_persona`.
+pub fn coach_persona(config: &crate::config::AppConfig) -> String {
+    active_coach_persona(Path::new(&profiles_path()))
+        .unwrap_or_else(|| config.coach_persona.clone())
+}
+
+fn active_coach_persona(path: &Path) -> Option<String> {
+    let payload = load_profiles(path).ok()?;
+    payload
+        .profiles
+        .get(&payload.active_profile)?
+        .coach_persona
+        .clone()
+        .filter(|p| !p.trim().is_empty())
+}
+
+/// Unit system of the active profile; metric when no profile is configured.
+pub fn active_units() -> crate::models::Units {
+    active_units_from(Path::new(&profiles_path()))
+}
+
+fn active_units_from(path: &Path) -> crate::models::Units {
+    load_profiles(path)
+        .ok()
+        .and_then(|payload| {
+            payload
+                .profiles
+                .get(&payload.active_profile)
+                .map(|p| p.units)
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_profiles_file(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "fitness_journal_profiles_{}_{}",
+            name,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("profiles.json");
+        std::fs::write(
+            &path,
+            r#"{
+  "active_profile": "home_gym",
+  "profiles": {
+    "home_gym": { "goals": ["Build strength"], "constraints": ["No overhead pressing"] },
+    "travel": { "constraints": ["Bodyweight only"] }
+  }
+}"#,
+        )
+        .unwrap();
+        path
+    }
+
+    #[test]
+    fn injury_constraints_persist_on_active_profile() {
+        let path = temp_profiles_file("injury");
+
+        let constraints = add_active_constraint(&path, "Injury: sore left knee").unwrap();
+        assert_eq!(constraints.len(), 2);
+
+        let reloaded = load_profiles(&path).unwrap();
+        assert_eq!(
+            reloaded.profiles["home_gym"].constraints,
+            vec!["No overhead pressing", "Injury: sore left knee"]
+        );
+        assert_eq!(
+            reloaded.profiles["travel"].constraints,
+            vec!["Bodyweight only"]
+        );
+
+        let removed = remove_active_constraint(&path, 1).unwrap();
+        assert_eq!(removed, "No overhead pressing");
+        assert!(remove_active_constraint(&path, 5).is_err());
+
+        let reloaded = load_profiles(&path).unwrap();
+        assert_eq!(
+            reloaded.profiles["home_gym"].constraints,
+            vec!["Injury: sore left knee"]
+        );
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn switching_profiles_only_changes_the_active_name() {
+        let path = temp_profiles_file("switch");
+
+        assert!(set_active_profile(&path, "travel").unwrap());
+        let reloaded = load_profiles(&path).unwrap();
+        assert_eq!(reloaded.active_profile, "travel");
+        assert_eq!(
+            reloaded.profiles["home_gym"].constraints,
+            vec!["No overhead pressing"]
+        );
+
+        let before = std::fs::read_to_string(&path).unwrap();
+        assert!(!set_active_profile(&path, "hotel").unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), before);
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn concurrent_updates_are_not_lost_and_reads_never_fail() {
+        let path = temp_profiles_file("concurrent");
+
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let path = &path;
+                scope.spawn(move || {
+                    add_active_constraint(path, &format!("Constraint {}", i)).unwrap();
+                });
+            }
+            for _ in 0..4 {
+                let path = &path;
+                scope.spawn(move || {
+                    for _ in 0..25 {
+                        load_profiles(path).expect("read during update");
+                    }
+                });
+            }
+        });
+
+        let constraints = &load_profiles(&path).unwrap().profiles["home_gym"].constraints;
+        assert_eq!(constraints.len(), 9);
+        for i in 0..8 {
+            assert!(constraints.contains(&format!("Constraint {}", i)));
+        }
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn active_profile_persona_overrides_default() {
+        let path = temp_profiles_file("persona");
+        assert_eq!(active_coach_persona(&path), None);
+
+        let mut payload = load_profiles(&path).unwrap();
+        payload.active_profile = "travel".to_string();
+        payload.profiles.get_mut("travel").unwrap().coach_persona =
+            Some("You are a drill sergeant.".to_string());
+        save_profiles(&path, payload).unwrap();
+        assert_eq!(
+            active_coach_persona(&path).as_deref(),
+            Some("You are a drill sergeant.")
+        );
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn active_profile_units_default_to_metric() {
+        let path = temp_profiles_file("units");
+        assert_eq!(active_units_from(&path), crate::models::Units::Metric);
+
+        let mut payload = load_profiles(&path).unwrap();
+        payload.profiles.get_mut("home_gym").unwrap().units = crate::models::Units::Imperial;
+        save_profiles(&path, payload).unwrap();
+        assert_eq!(active_units_from(&path), crate::models::Units::Imperial);
+        let raw = std::fs::read_to_string(&path).unwrap();
+        assert!(raw.contains(r#""units": "imperial""#));
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn rest_days_are_normalized_and_validated() {
+        let path = temp_profiles_file("rest_days");
+        let mut payload = load_profiles(&path).unwrap();
+        payload.profiles.get_mut("home_gym").unwrap().rest_days =
+            vec!["sun".to_string(), " Wed ".to_string()];
+        let validated = validate_profiles_payload(payload.clone()).unwrap();
+        assert_eq!(validated.profiles["home_gym"].rest_days, vec!["Sun", "Wed"]);
+
+        payload.profiles.get_mut("home_gym").unwrap().rest_days = vec!["Sunday".to_string()];
+        let err = validate_profiles_payload(payload).unwrap_err();
+        assert_eq!(err[0].field, "rest_days");
+        assert!(err[0].message.contains("'Sunday'"));
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn every_invalid_field_is_reported_at_once() {
+        let path = temp_profiles_file("all_errors");
+        let mut payload = load_profiles(&path).unwrap();
+        payload.active_profile = "missing".to_string();
+        let profile = payload.profiles.get_mut("home_gym").unwrap();
+        profile.rest_days = vec![
+            "Sunday".to_string(),
+            "Mon".to_string(),
+            "Fryday".to_string(),
+        ];
+        profile.planning_horizon_days = Some(0);
+        profile.coach_persona = Some("x".repeat(MAX_COACH_PERSONA_LEN + 1));
+        let long_name = "p".repeat(MAX_PROFILE_NAME_LEN + 1);
+        payload
+            .profiles
+            .insert(long_name.clone(), payload.profiles["home_gym"].clone());
+
+        let errors = validate_profiles_payload(payload).unwrap_err();
+        let fields: Vec<(&str, Option<&str>)> = errors
+            .iter()
+            .map(|e| (e.field.as_str(), e.profile.as_deref()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("rest_days", Some("home_gym")),
+                ("rest_days", Some("home_gym")),
+                ("planning_horizon_days", Some("home_gym")),
+                ("coach_persona", Some("home_gym")),
+                ("name", Some(long_name.as_str())),
+                ("rest_days", Some(long_name.as_str())),
+                ("rest_days", Some(long_name.as_str())),
+                ("planning_horizon_days", Some(long_name.as_str())),
+                ("coach_persona", Some(long_name.as_str())),
+                ("active_profile", None),
+            ]
+        );
+        assert!(errors[1].message.contains("'Fryday'"));
+        assert!(describe_validation_errors(&errors).contains("must reference an existing profile"));
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn planning_horizon_override_is_range_checked() {
+        let path = temp_profiles_file("horizon");
+        let mut payload = load_profiles(&path).unwrap();
+        let horizon = |payload: &mut ProfilesPayload, days| {
+            payload
+                .profiles
+                .get_mut("home_gym")
+                .unwrap()
+                .planning_horizon_days = days;
+        };
+        horizon(&mut payload, Some(14));
+        let validated = validate_profiles_payload(payload.clone()).unwrap();
+        assert_eq!(
+            validated.profiles["home_gym"].planning_horizon_days,
+            Some(14)
+        );
+
+        horizon(&mut payload, Some(29));
+        let err = validate_profiles_payload(payload).unwrap_err();
+        assert_eq!(err[0].field, "planning_horizon_days");
+        assert!(err[0].message.contains("between 1 and 28"));
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn active_context_is_loaded_from_the_shipped_profiles_file() {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("profiles.json");
+        let (context, auto_analyze) = load_active_context_from(&fixture);
+        assert_eq!(context.goals[0], "Improve Marathon Time (Sub 4h)");
+        assert!(context
+            .available_equipment
+            .iter()
+            .any(|e| e.starts_with("Kettlebell")));
+        assert!(auto_analyze.sports.contains(&"running".to_string()));
+
+        let path = temp_profiles_file("context");
+        std::fs::write(&path, r#"{ "active_profile": "missing", "profiles": {} }"#).unwrap();
+        let (fallback, auto_analyze) = load_active_context_from(&path);
+        assert_eq!(fallback.goals, default_goals());
+        assert!(fallback.constraints.is_empty() && auto_analyze.sports.is_empty());
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn analysis_prompt_is_chosen_per_sport() {
+        let path = temp_profiles_file("analysis_prompts");
+        let mut payload = load_profiles(&path).unwrap();
+        let profile = payload.profiles.get_mut("home_gym").unwrap();
+        profile.auto_analyze_sports = vec!["strength_training".into(), "running".into()];
+        profile.analysis_prompts = BTreeMap::from([(
+            " strength_training ".to_string(),
+            " Focus on volume and PRs. ".to_string(),
+        )]);
+        save_profiles(&path, payload.clone()).unwrap();
+
+        let (_, auto_analysis) = load_active_context_from(&path);
+        assert_eq!(
+            auto_analysis.analysis_prompt_for("strength_training"),
+            "Focus on volume and PRs."
+        );
+        assert_eq!(
+            auto_analysis.analysis_prompt_for("running"),
+            crate::coaching::GENERIC_ANALYSIS_PROMPT
+        );
+
+        let profile = payload.profiles.get_mut("home_gym").unwrap();
+        profile
+            .analysis_prompts
+            .insert("cycling".to_string(), "Look at power.".to_string());
+        let err = validate_profiles_payload(payload).unwrap_err();
+        assert_eq!(err[0].field, "analysis_prompts");
+        assert!(err[0].message.contains("'cycling'"));
+
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
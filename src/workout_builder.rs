@@ -1,5 +1,4 @@
 use lazy_static::lazy_static;
-use regex::Regex;
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use strsim::levenshtein;
@@ -11,6 +10,15 @@ use tracing::info;
 const SPORT_TYPE_STRENGTH: &str = "strength_training";
 const SPORT_TYPE_ID_STRENGTH: i32 = 5;
 
+const SPORT_TYPE_RUNNING: &str = "running";
+const SPORT_TYPE_ID_RUNNING: i32 = 1;
+
+const SPORT_TYPE_SWIMMING: &str = "swimming";
+const SPORT_TYPE_ID_SWIMMING: i32 = 4;
+
+/// Pool length used when a swim spec has no `pool_length`.
+const DEFAULT_POOL_LENGTH_M: f64 = 25.0;
+
 const STEP_TYPE_WARMUP: &str = "warmup";
 const STEP_TYPE_ID_WARMUP: i32 = 1;
 
@@ -20,9 +28,15 @@ const STEP_TYPE_ID_COOLDOWN: i32 = 2;
 const STEP_TYPE_INTERVAL: &str = "interval";
 const STEP_TYPE_ID_INTERVAL: i32 = 3;
 
+const STEP_TYPE_RECOVERY: &str = "recovery";
+const STEP_TYPE_ID_RECOVERY: i32 = 4;
+
 const STEP_TYPE_REST: &str = "rest";
 const STEP_TYPE_ID_REST: i32 = 5;
 
+const STEP_TYPE_REPEAT: &str = "repeat";
+const STEP_TYPE_ID_REPEAT: i32 = 6;
+
 const CONDITION_REPS: &str = "reps";
 const CONDITION_ID_REPS: i32 = 10;
 
@@ -32,9 +46,26 @@ const CONDITION_ID_TIME: i32 = 2;
 const CONDITION_LAP_BUTTON: &str = "lap.button";
 const CONDITION_ID_LAP_BUTTON: i32 = 1;
 
+const CONDITION_DISTANCE: &str = "distance";
+const CONDITION_ID_DISTANCE: i32 = 3;
+
+const CONDITION_ITERATIONS: &str = "iterations";
+const CONDITION_ID_ITERATIONS: i32 = 7;
+
+pub const EXERCISE_DB_PATH: &str = "Garmin Exercises Database - Exercises.csv";
+
+/// Used for warmup/cooldown steps that arrive without a duration or reps.
+pub const DEFAULT_WARMUP_SECS: i64 = 300;
+pub const DEFAULT_COOLDOWN_SECS: i64 = 300;
+/// Rest between a set and each of its drop sets (time to strip the plates).
+const DROPSET_REST_SECS: i64 = 10;
+
 const TARGET_NO_TARGET: &str = "no.target";
 const TARGET_ID_NO_TARGET: i32 = 1;
 
+const TARGET_PACE_ZONE: &str = "pace.zone";
+const TARGET_ID_PACE_ZONE: i32 = 6;
+
 const UNIT_KILOGRAM: &str = "kilogram";
 const UNIT_ID_KILOGRAM: i32 = 8;
 
@@ -73,116 +104,262 @@ lazy_static! {
     };
 }
 
+/// How `resolve_exercise` mapped an AI exercise name to a Garmin key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolutionMethod {
+    /// Found in the exercise CSV (verbatim or with separators stripped).
+    Exact,
+    /// Found in `MANUAL_OVERRIDES`.
+    Manual,
+    /// Closest exercise CSV key within 3 edits.
+    Fuzzy,
+    /// Unknown `SNAKE_CASE` name passed through as its own category.
+    Fallback,
+    /// No match; the step is uploaded without an exercise.
+    None,
+}
+
+impl ResolutionMethod {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ResolutionMethod::Exact => "exact",
+            ResolutionMethod::Manual => "manual",
+            ResolutionMethod::Fuzzy => "fuzzy",
+            ResolutionMethod::Fallback => "fallback",
+            ResolutionMethod::None => "none",
+        }
+    }
+}
+
+/// One `resolve_exercise` decision, recorded while building a payload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExerciseResolution {
+    pub input: String,
+    pub matched_key: Option<String>,
+    /// Levenshtein distance of a fuzzy match.
+    pub distance: Option<usize>,
+    pub method: ResolutionMethod,
+}
+
+#[derive(Clone)]
 pub struct WorkoutBuilder {
     exercise_db: HashMap<String, (String, String)>,
+    concise_generic_descriptions: bool,
+    default_warmup_secs: i64,
+    default_cooldown_secs: i64,
 }
 
 impl WorkoutBuilder {
     pub fn new() -> Self {
-        let mut builder = Self {
-            exercise_db: HashMap::new(),
-        };
-        builder.load_exercise_db("Garmin Exercises Database - Exercises.csv");
+        let mut builder = Self::empty();
+        builder.load_exercise_db(EXERCISE_DB_PATH);
         builder
     }
 
+    /// A builder with default settings and no exercise mappings loaded.
+    fn empty() -> Self {
+        Self {
+            exercise_db: HashMap::new(),
+            concise_generic_descriptions: false,
+            default_warmup_secs: DEFAULT_WARMUP_SECS,
+            default_cooldown_secs: DEFAULT_COOLDOWN_SECS,
+        }
+    }
+
+    /// Use a short "Bench Press 80kg" description instead of the verbose
+    /// "Exercise: BENCH_PRESS. note Target: 80kg" for generic (robust) steps.
+    pub fn with_concise_generic_descriptions(mut self, concise: bool) -> Self {
+        self.concise_generic_descriptions = concise;
+        self
+    }
+
+    /// Time end-conditions (in seconds) for warmup/cooldown steps that have no
+    /// explicit duration; `0` keeps the lap-button fallback.
+    pub fn with_default_phase_durations(mut self, warmup_secs: i64, cooldown_secs: i64) -> Self {
+        self.default_warmup_secs = warmup_secs;
+        self.default_cooldown_secs = cooldown_secs;
+        self
+    }
+
+    /// Turns an exercise key like `BENCH_PRESS` into `Bench Press`; names that
+    /// already contain lowercase letters are kept as written.
+    fn human_exercise_name(raw_name: &str) -> String {
+        let trimmed = raw_name.trim();
+        if trimmed.chars().any(|c| c.is_lowercase()) {
+            return trimmed.to_string();
+        }
+        trimmed
+            .split(|c: char| c == '_' || c.is_whitespace())
+            .filter(|w| !w.is_empty())
+            .map(|w| {
+                let lower = w.to_lowercase();
+                let mut chars = lower.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    fn generic_description(&self, raw_name: &str, note: &str, weight: Option<f64>) -> String {
+        if self.concise_generic_descriptions {
+            let name = Self::human_exercise_name(raw_name);
+            return match weight {
+                Some(w) => format!("{} {}kg", name, w),
+                None => name,
+            };
+        }
+
+        let mut desc = format!("Exercise: {}. {}", raw_name, note);
+        if let Some(w) = weight {
+            desc.push_str(&format!(" Target: {}kg", w));
+        }
+        desc.trim().to_string()
+    }
+
     fn load_exercise_db(&mut self, path: &str) {
+        match Self::read_exercise_db(path) {
+            Ok(db) => {
+                info!("Loaded {} elements into exercise DB from CSV", db.len());
+                self.exercise_db = db;
+            }
+            Err(e) => info!("Warning: {}. Using name as key.", e),
+        }
+    }
+
+    /// Re-reads the exercise database CSV, e.g. after the user edited it.
+    /// On error the previously loaded entries are kept.
+    pub fn reload(&mut self) -> anyhow::Result<usize> {
+        self.reload_from(EXERCISE_DB_PATH)
+    }
+
+    fn reload_from(&mut self, path: &str) -> anyhow::Result<usize> {
+        let db = Self::read_exercise_db(path)?;
+        info!(
+            "Reloaded {} elements into exercise DB from {}",
+            db.len(),
+            path
+        );
+        self.exercise_db = db;
+        Ok(self.exercise_db.len())
+    }
+
+    fn read_exercise_db(path: &str) -> anyhow::Result<HashMap<String, (String, String)>> {
         if !std::path::Path::new(path).exists() {
-            info!(
-                "Warning: Exercise DB CSV not found at {}. Using name as key.",
-                path
-            );
-            return;
+            anyhow::bail!("Exercise DB CSV not found at {}", path);
         }
 
-        if let Ok(mut rdr) = csv::ReaderBuilder::new()
+        let mut rdr = csv::ReaderBuilder::new()
             .flexible(true)
             .has_headers(false) // Handle them manually
             .from_path(path)
-        {
-            let mut records = rdr.records();
-            let mut name_idx = 0;
-            let mut cat_idx = 0;
-            let mut id_idx = 0;
-
-            // Search for the true header row (the one containing CATEGORY_GARMIN)
-            let mut found_headers = false;
-            while let Some(Ok(row)) = records.next() {
-                for (i, v) in row.iter().enumerate() {
-                    let h = v.to_uppercase();
-                    // Handle potential UTF-8 BOM on the first column or extraneous quotes
-                    if h.contains("NAME") && !h.contains("GARMIN") {
-                        name_idx = i;
-                    }
-                    if h.contains("CATEGORY_GARMIN") {
-                        cat_idx = i;
-                        found_headers = true;
-                    }
-                    if h.contains("NAME_GARMIN") {
-                        id_idx = i;
-                    }
+            .map_err(|e| anyhow::anyhow!("Could not read CSV at {}: {}", path, e))?;
+        let mut exercise_db = HashMap::new();
+        let mut records = rdr.records();
+        let mut name_idx = 0;
+        let mut cat_idx = 0;
+        let mut id_idx = 0;
+
+        // Search for the true header row (the one containing CATEGORY_GARMIN)
+        let mut found_headers = false;
+        while let Some(Ok(row)) = records.next() {
+            for (i, v) in row.iter().enumerate() {
+                let h = v.to_uppercase();
+                // Handle potential UTF-8 BOM on the first column or extraneous quotes
+                if h.contains("NAME") && !h.contains("GARMIN") {
+                    name_idx = i;
+                }
+                if h.contains("CATEGORY_GARMIN") {
+                    cat_idx = i;
+                    found_headers = true;
                 }
-                if found_headers {
-                    break;
+                if h.contains("NAME_GARMIN") {
+                    id_idx = i;
                 }
             }
+            if found_headers {
+                break;
+            }
+        }
 
-            for row in records.flatten() {
-                if let (Some(name), Some(cat), Some(id)) =
-                    (row.get(name_idx), row.get(cat_idx), row.get(id_idx))
-                {
-                    let human_name = name.trim().to_uppercase();
-                    let cat_key = cat.trim().to_string();
-                    let ex_key = id.trim().to_string();
-
-                    if !human_name.is_empty() && !cat_key.is_empty() && !ex_key.is_empty() {
-                        let val = (cat_key.clone(), ex_key.clone());
-
-                        self.exercise_db.insert(human_name.clone(), val.clone());
-                        self.exercise_db.insert(ex_key.clone(), val.clone());
-                        self.exercise_db
-                            .insert(human_name.replace(" ", "_"), val.clone());
-                        self.exercise_db
-                            .insert(ex_key.replace("_", " "), val.clone());
-                        self.exercise_db.insert(
-                            human_name
-                                .replace("-", "")
-                                .replace(" ", "")
-                                .replace("_", ""),
-                            val.clone(),
-                        );
-                        self.exercise_db
-                            .insert(ex_key.replace("_", "").replace("-", ""), val);
-                    }
+        for row in records.flatten() {
+            if let (Some(name), Some(cat), Some(id)) =
+                (row.get(name_idx), row.get(cat_idx), row.get(id_idx))
+            {
+                let human_name = name.trim().to_uppercase();
+                let cat_key = cat.trim().to_string();
+                let ex_key = id.trim().to_string();
+
+                if !human_name.is_empty() && !cat_key.is_empty() && !ex_key.is_empty() {
+                    let val = (cat_key.clone(), ex_key.clone());
+
+                    exercise_db.insert(human_name.clone(), val.clone());
+                    exercise_db.insert(ex_key.clone(), val.clone());
+                    exercise_db.insert(human_name.replace(" ", "_"), val.clone());
+                    exercise_db.insert(ex_key.replace("_", " "), val.clone());
+                    exercise_db.insert(
+                        human_name
+                            .replace("-", "")
+                            .replace(" ", "")
+                            .replace("_", ""),
+                        val.clone(),
+                    );
+                    exercise_db.insert(ex_key.replace("_", "").replace("-", ""), val);
                 }
             }
-            info!(
-                "Loaded {} elements into exercise DB from CSV",
-                self.exercise_db.len()
-            );
-        } else {
-            info!("Warning: Could not read CSV at {}", path);
         }
+
+        Ok(exercise_db)
     }
 
     pub fn resolve_exercise(&self, name: &str) -> (Option<String>, Option<String>) {
+        let (category, exercise, _) = self.resolve_exercise_logged(name);
+        (category, exercise)
+    }
+
+    /// `resolve_exercise`, plus a record of how the name was matched.
+    pub fn resolve_exercise_logged(
+        &self,
+        name: &str,
+    ) -> (Option<String>, Option<String>, ExerciseResolution) {
         let clean = name.trim().to_uppercase();
+        let resolution = |matched_key: Option<&str>, distance, method| ExerciseResolution {
+            input: name.to_string(),
+            matched_key: matched_key.map(str::to_string),
+            distance,
+            method,
+        };
 
         if let Some((cat, ex)) = MANUAL_OVERRIDES.get(clean.as_str()) {
-            return (Some(cat.to_string()), Some(ex.to_string()));
+            return (
+                Some(cat.to_string()),
+                Some(ex.to_string()),
+                resolution(Some(&clean), None, ResolutionMethod::Manual),
+            );
         }
 
         if let Some(val) = self.exercise_db.get(&clean) {
-            return (Some(val.0.clone()), Some(val.1.clone()));
+            return (
+                Some(val.0.clone()),
+                Some(val.1.clone()),
+                resolution(Some(&clean), None, ResolutionMethod::Exact),
+            );
         }
 
         let norm_input = clean.replace("_", "").replace(" ", "").replace("-", "");
         if let Some(val) = self.exercise_db.get(&norm_input) {
-            return (Some(val.0.clone()), Some(val.1.clone()));
+            return (
+                Some(val.0.clone()),
+                Some(val.1.clone()),
+                resolution(Some(&norm_input), None, ResolutionMethod::Exact),
+            );
         }
 
         if clean.contains("_") {
-            return (Some(clean.clone()), Some(clean));
+            let fallback = resolution(Some(&clean), None, ResolutionMethod::Fallback);
+            return (Some(clean.clone()), Some(clean), fallback);
         }
 
         // Fuzzy fallback
@@ -205,56 +382,621 @@ impl WorkoutBuilder {
                     "Fuzzy match: '{}' -> '{}' (distance: {})",
                     name, best_key, best_distance
                 );
-                return (Some(val.0.clone()), Some(val.1.clone()));
+                return (
+                    Some(val.0.clone()),
+                    Some(val.1.clone()),
+                    resolution(
+                        Some(&best_key),
+                        Some(best_distance),
+                        ResolutionMethod::Fuzzy,
+                    ),
+                );
             }
         }
 
-        (None, None)
+        (None, None, resolution(None, None, ResolutionMethod::None))
     }
 
     pub fn parse_duration(val: &Value) -> Option<i64> {
         match val {
             Value::Number(n) => n.as_i64(),
-            Value::String(s) => {
-                lazy_static! {
-                    static ref RE: Regex = Regex::new(r"\d+").unwrap();
-                }
-                if let Some(caps) = RE.captures(s) {
-                    if let Ok(parsed) = caps[0].parse::<i64>() {
-                        if s.to_lowercase().contains("min") {
-                            return Some(parsed * 60);
-                        }
-                        return Some(parsed);
-                    }
-                }
-                None
-            }
+            Value::String(s) => crate::parsing::parse_duration(s),
             _ => None,
         }
     }
 
+    /// Parses a weight in kilograms; strings ending in `lb`/`lbs` are
+    /// converted from pounds.
     pub fn parse_weight(val: &Value) -> Option<f64> {
         match val {
             Value::Number(n) => n.as_f64(),
-            Value::String(s) => {
-                lazy_static! {
-                    static ref RE: Regex = Regex::new(r"[\d\.]+").unwrap();
+            Value::String(s) => crate::parsing::parse_weight(s),
+            _ => None,
+        }
+    }
+
+    /// Parses a distance like `800`, `"800m"`, `"1.5km"` or `"2mi"` into meters.
+    pub fn parse_distance(val: &Value) -> Option<f64> {
+        match val {
+            Value::Number(n) => n.as_f64(),
+            Value::String(s) => crate::parsing::parse_distance(s),
+            _ => None,
+        }
+    }
+
+    /// Parses a per-km pace (`"4:05"` or a range `"3:50-4:00"`) into a
+    /// `(slowest, fastest)` speed range in m/s, as Garmin pace targets expect.
+    pub fn parse_pace(val: &Value) -> Option<(f64, f64)> {
+        let (fastest, slowest) = crate::parsing::parse_pace_range(val.as_str()?)?;
+        Some((1000.0 / slowest, 1000.0 / fastest))
+    }
+
+    fn is_swim_workout(data: &Value) -> bool {
+        data.get("sport")
+            .and_then(|s| s.as_str())
+            .map(|s| crate::models::Sport::from_type_key(s) == crate::models::Sport::Swim)
+            .unwrap_or(false)
+    }
+
+    /// Garmin stroke type for a swim step's `stroke` (`free`, `back`, `breast`,
+    /// `fly`, `im`, `drill`, `mixed`; anything else is "any stroke").
+    fn swim_stroke_type(stroke: &str) -> Value {
+        let (id, key) = match stroke.trim().to_lowercase().as_str() {
+            "back" | "backstroke" => (2, "backstroke"),
+            "breast" | "breaststroke" => (3, "breaststroke"),
+            "drill" => (4, "drill"),
+            "fly" | "butterfly" => (5, "fly"),
+            "free" | "freestyle" | "crawl" => (6, "free"),
+            "im" | "individual_medley" => (7, "individual_medley"),
+            "mixed" => (8, "mixed"),
+            _ => (1, "any_stroke"),
+        };
+        json!({ "strokeTypeId": id, "strokeTypeKey": key })
+    }
+
+    fn is_running_workout(data: &Value) -> bool {
+        data.get("sport")
+            .and_then(|s| s.as_str())
+            .map(|s| matches!(s.to_lowercase().as_str(), "running" | "run"))
+            .unwrap_or(false)
+    }
+
+    /// Builds running steps recursively: `{"repeat": n, "steps": [...]}` becomes a
+    /// repeat group (which may itself contain repeats), anything else an
+    /// executable step ended by `distance`, `duration`/`time`, or the lap button.
+    fn build_running_steps(&self, steps: &[Value], order: &mut i32) -> Vec<Value> {
+        let mut payload = Vec::new();
+
+        for step in steps {
+            if let (Some(iterations), Some(children)) = (
+                step.get("repeat").and_then(|r| r.as_i64()),
+                step.get("steps").and_then(|s| s.as_array()),
+            ) {
+                let repeat_order = *order;
+                *order += 1;
+                let child_steps = self.build_running_steps(children, order);
+                payload.push(json!({
+                    "type": "RepeatGroupDTO",
+                    "stepOrder": repeat_order,
+                    "stepType": {
+                        "stepTypeId": STEP_TYPE_ID_REPEAT,
+                        "stepTypeKey": STEP_TYPE_REPEAT,
+                    },
+                    "numberOfIterations": iterations,
+                    "endCondition": {
+                        "conditionTypeId": CONDITION_ID_ITERATIONS,
+                        "conditionTypeKey": CONDITION_ITERATIONS,
+                    },
+                    "endConditionValue": iterations,
+                    "smartRepeat": false,
+                    "workoutSteps": child_steps
+                }));
+                continue;
+            }
+
+            let phase = step
+                .get("phase")
+                .and_then(|p| p.as_str())
+                .unwrap_or("interval")
+                .to_lowercase();
+            let (step_type_id, step_type_key, default_secs) = match phase.as_str() {
+                "warmup" | "warm_up" => (
+                    STEP_TYPE_ID_WARMUP,
+                    STEP_TYPE_WARMUP,
+                    self.default_warmup_secs,
+                ),
+                "cooldown" | "cool_down" => (
+                    STEP_TYPE_ID_COOLDOWN,
+                    STEP_TYPE_COOLDOWN,
+                    self.default_cooldown_secs,
+                ),
+                "recovery" => (STEP_TYPE_ID_RECOVERY, STEP_TYPE_RECOVERY, 0),
+                "rest" => (STEP_TYPE_ID_REST, STEP_TYPE_REST, 0),
+                _ => (STEP_TYPE_ID_INTERVAL, STEP_TYPE_INTERVAL, 0),
+            };
+
+            let duration = step
+                .get("duration")
+                .or_else(|| step.get("time"))
+                .and_then(Self::parse_duration)
+                .or((default_secs > 0).then_some(default_secs));
+            let (end_cond_id, end_cond_key, end_val) =
+                if let Some(meters) = step.get("distance").and_then(Self::parse_distance) {
+                    (CONDITION_ID_DISTANCE, CONDITION_DISTANCE, json!(meters))
+                } else if let Some(secs) = duration {
+                    (CONDITION_ID_TIME, CONDITION_TIME, json!(secs))
+                } else {
+                    (CONDITION_ID_LAP_BUTTON, CONDITION_LAP_BUTTON, Value::Null)
+                };
+
+            let mut step_dict = json!({
+                "type": "ExecutableStepDTO",
+                "stepOrder": *order,
+                "stepType": {
+                    "stepTypeId": step_type_id,
+                    "stepTypeKey": step_type_key,
+                },
+                "childStepId": null,
+                "description": step.get("note").and_then(|n| n.as_str()),
+                "endCondition": {
+                    "conditionTypeId": end_cond_id,
+                    "conditionTypeKey": end_cond_key,
+                },
+                "endConditionValue": end_val,
+                "targetType": {
+                    "workoutTargetTypeId": TARGET_ID_NO_TARGET,
+                    "workoutTargetTypeKey": TARGET_NO_TARGET,
+                },
+            });
+
+            if let Some(stroke) = step.get("stroke").and_then(|s| s.as_str()) {
+                if let Some(step_obj) = step_dict.as_object_mut() {
+                    step_obj.insert("strokeType".to_string(), Self::swim_stroke_type(stroke));
+                }
+            }
+
+            if let Some((slowest, fastest)) = step.get("pace").and_then(Self::parse_pace) {
+                if let Some(step_obj) = step_dict.as_object_mut() {
+                    step_obj.insert(
+                        "targetType".to_string(),
+                        json!({
+                            "workoutTargetTypeId": TARGET_ID_PACE_ZONE,
+                            "workoutTargetTypeKey": TARGET_PACE_ZONE,
+                        }),
+                    );
+                    step_obj.insert("targetValueOne".to_string(), json!(slowest));
+                    step_obj.insert("targetValueTwo".to_string(), json!(fastest));
+                }
+            }
+
+            payload.push(step_dict);
+            *order += 1;
+        }
+
+        payload
+    }
+
+    /// Pool swim workout: the same step format as running specs (distance or
+    /// time steps, nested `repeat` blocks) plus an optional per-step `stroke`
+    /// and a workout-level `pool_length` in meters.
+    fn build_swim_workout_payload(&self, data: &Value) -> Value {
+        let mut order = 1;
+        let steps = data
+            .get("steps")
+            .and_then(|s| s.as_array())
+            .map(|steps| self.build_running_steps(steps, &mut order))
+            .unwrap_or_default();
+
+        let workout_name = data
+            .get("workoutName")
+            .and_then(|n| n.as_str())
+            .unwrap_or("Imported Swim Workout");
+        let description = data.get("description").and_then(|d| d.as_str());
+        let pool_length = data
+            .get("pool_length")
+            .and_then(Self::parse_distance)
+            .filter(|m| *m > 0.0)
+            .unwrap_or(DEFAULT_POOL_LENGTH_M);
+
+        json!({
+            "workoutName": workout_name,
+            "description": description,
+            "sportType": {
+                "sportTypeId": SPORT_TYPE_ID_SWIMMING,
+                "sportTypeKey": SPORT_TYPE_SWIMMING,
+            },
+            "poolLength": pool_length,
+            "poolLengthUnit": {
+                "unitId": 1,
+                "unitKey": "meter",
+                "factor": 100.0
+            },
+            "workoutSegments": [
+                {
+                    "segmentOrder": 1,
+                    "sportType": {
+                        "sportTypeId": SPORT_TYPE_ID_SWIMMING,
+                        "sportTypeKey": SPORT_TYPE_SWIMMING,
+                    },
+                    "workoutSteps": steps
+                }
+            ]
+        })
+    }
+
+    fn build_running_workout_payload(&self, data: &Value) -> Value {
+        let mut order = 1;
+        let steps = data
+            .get("steps")
+            .and_then(|s| s.as_array())
+            .map(|steps| self.build_running_steps(steps, &mut order))
+            .unwrap_or_default();
+
+        let workout_name = data
+            .get("workoutName")
+            .and_then(|n| n.as_str())
+            .unwrap_or("Imported Running Workout");
+        let description = data.get("description").and_then(|d| d.as_str());
+
+        json!({
+            "workoutName": workout_name,
+            "description": description,
+            "sportType": {
+                "sportTypeId": SPORT_TYPE_ID_RUNNING,
+                "sportTypeKey": SPORT_TYPE_RUNNING,
+            },
+            "workoutSegments": [
+                {
+                    "segmentOrder": 1,
+                    "sportType": {
+                        "sportTypeId": SPORT_TYPE_ID_RUNNING,
+                        "sportTypeKey": SPORT_TYPE_RUNNING,
+                    },
+                    "workoutSteps": steps
+                }
+            ]
+        })
+    }
+
+    /// True for running specs built around intervals: any `repeat` block or a
+    /// pace target on a work step.
+    pub fn is_interval_heavy(workout_spec: &Value) -> bool {
+        if !Self::is_running_workout(workout_spec) {
+            return false;
+        }
+        fn has_intervals(steps: &[Value]) -> bool {
+            steps.iter().any(|step| {
+                if step.get("repeat").is_some() {
+                    return true;
+                }
+                let phase = step
+                    .get("phase")
+                    .and_then(|p| p.as_str())
+                    .unwrap_or("interval")
+                    .to_lowercase();
+                phase == "interval" && step.get("pace").is_some()
+            })
+        }
+        workout_spec
+            .get("steps")
+            .and_then(|s| s.as_array())
+            .is_some_and(|steps| has_intervals(steps))
+    }
+
+    /// Replaces an interval-heavy running spec with an easy recovery run of
+    /// roughly the same length. Returns `false` when the spec was left as is.
+    pub fn downregulate_to_recovery(workout_spec: &mut Value, reason: &str) -> bool {
+        if !Self::is_interval_heavy(workout_spec) {
+            return false;
+        }
+        if let Some(obj) = workout_spec.as_object_mut() {
+            obj.insert(
+                "steps".to_string(),
+                json!([
+                    { "phase": "warmup", "duration": "10min" },
+                    { "phase": "interval", "duration": "20min", "note": format!("Easy recovery pace. {}", reason) },
+                    { "phase": "cooldown", "duration": "10min" }
+                ]),
+            );
+            obj.insert("description".to_string(), json!(reason));
+        }
+        true
+    }
+
+    /// Prepends a 5-minute warmup and appends a 5-minute stretching cooldown to
+    /// a strength spec whose first/last step lacks that phase. Running and swim
+    /// specs are left alone. Returns one human-readable line per inserted step.
+    pub fn ensure_warmup_cooldown(workout_spec: &mut Value) -> Vec<String> {
+        let mut added = Vec::new();
+        if Self::is_running_workout(workout_spec) || Self::is_swim_workout(workout_spec) {
+            return added;
+        }
+        let name = workout_spec
+            .get("workoutName")
+            .and_then(|n| n.as_str())
+            .unwrap_or("Workout")
+            .to_string();
+        let Some(steps) = workout_spec.get_mut("steps").and_then(|s| s.as_array_mut()) else {
+            return added;
+        };
+        if steps.is_empty() {
+            return added;
+        }
+
+        let phase = |step: Option<&Value>| {
+            step.and_then(|s| s.get("phase"))
+                .and_then(|p| p.as_str())
+                .map(str::to_lowercase)
+                .unwrap_or_default()
+        };
+        if !matches!(phase(steps.first()).as_str(), "warmup" | "warm_up") {
+            steps.insert(
+                0,
+                json!({
+                    "phase": "warmup",
+                    "exercise": "DYNAMIC_WARMUP",
+                    "duration": "5min",
+                    "note": "General warm-up: easy cardio and dynamic mobility."
+                }),
+            );
+            added.push(format!("{}: added a 5min warmup", name));
+        }
+        if !matches!(
+            phase(steps.last()).as_str(),
+            "cooldown" | "cool_down" | "stretching"
+        ) {
+            steps.push(json!({
+                "phase": "cooldown",
+                "exercise": "STRETCHING",
+                "duration": "5min",
+                "note": "Easy static stretching for the muscles worked."
+            }));
+            added.push(format!("{}: added a 5min stretching cooldown", name));
+        }
+        added
+    }
+
+    /// Caps working-set weights that exceed `ratio` times the best recorded
+    /// weight for that exercise (`maxes` is keyed by exercise category, as stored
+    /// in `exercise_history`). Exercises without history are left untouched.
+    /// Returns one human-readable line per adjusted weight.
+    pub fn cap_weights(
+        &self,
+        workout_spec: &mut Value,
+        maxes: &HashMap<String, f64>,
+        ratio: f64,
+    ) -> Vec<String> {
+        let mut adjustments = Vec::new();
+        if ratio <= 0.0 {
+            return adjustments;
+        }
+
+        let Some(steps) = workout_spec.get_mut("steps").and_then(|s| s.as_array_mut()) else {
+            return adjustments;
+        };
+
+        for step in steps {
+            let phase = step
+                .get("phase")
+                .and_then(|p| p.as_str())
+                .unwrap_or("interval")
+                .to_lowercase();
+            if phase != "interval" {
+                continue;
+            }
+
+            let targets: Vec<&mut Value> = if step.get("exercises").is_some_and(Value::is_array) {
+                step["exercises"]
+                    .as_array_mut()
+                    .into_iter()
+                    .flatten()
+                    .collect()
+            } else {
+                vec![step]
+            };
+
+            for target in targets {
+                let Some(name) = target
+                    .get("exercise")
+                    .or_else(|| target.get("name"))
+                    .and_then(|e| e.as_str())
+                else {
+                    continue;
+                };
+                let Some(weight) = target.get("weight").and_then(Self::parse_weight) else {
+                    continue;
+                };
+                let Some(category) = self.resolve_exercise(name).0 else {
+                    continue;
+                };
+                let Some(&best) = maxes.get(&category) else {
+                    continue;
+                };
+                if best <= 0.0 {
+                    continue;
+                }
+
+                // Round down to the nearest 0.5kg so the cap is a loadable weight.
+                let limit = (best * ratio * 2.0).floor() / 2.0;
+                if weight > limit {
+                    adjustments.push(format!(
+                        "{}: {}kg capped to {}kg (best recorded {}kg)",
+                        name, weight, limit, best
+                    ));
+                    if let Some(obj) = target.as_object_mut() {
+                        obj.insert("weight".to_string(), json!(limit));
+                    }
                 }
-                if let Some(caps) = RE.captures(s) {
-                    return caps[0].parse::<f64>().ok();
+            }
+        }
+
+        adjustments
+    }
+
+    /// Merges consecutive steps sharing a `"group"` tag (e.g. `"A"`) into one
+    /// step with an `exercises` list, so a superset becomes a single repeat group
+    /// with one rest after the last exercise. The group uses the highest `sets`
+    /// and the last `rest` given by its members.
+    fn group_supersets(steps: &[Value]) -> Vec<Value> {
+        let mut grouped: Vec<Value> = Vec::new();
+        let mut current_group: Option<String> = None;
+
+        for step in steps {
+            let group = step
+                .get("group")
+                .and_then(|g| g.as_str())
+                .map(|g| g.trim().to_string())
+                .filter(|g| !g.is_empty());
+
+            if group.is_some() && group == current_group {
+                if let Some(merged) = grouped.last_mut().and_then(|v| v.as_object_mut()) {
+                    let sets = step.get("sets").and_then(|s| s.as_i64()).unwrap_or(1);
+                    let merged_sets = merged.get("sets").and_then(|s| s.as_i64()).unwrap_or(1);
+                    merged.insert("sets".to_string(), json!(sets.max(merged_sets)));
+                    if let Some(rest) = step.get("rest") {
+                        merged.insert("rest".to_string(), rest.clone());
+                    }
+                    if let Some(Value::Array(exercises)) = merged.get_mut("exercises") {
+                        exercises.push(step.clone());
+                    }
+                    continue;
                 }
-                None
             }
+
+            current_group = group.clone();
+            match group {
+                Some(_) => {
+                    let mut merged = serde_json::Map::new();
+                    for key in ["phase", "sets", "rest", "group"] {
+                        if let Some(v) = step.get(key) {
+                            merged.insert(key.to_string(), v.clone());
+                        }
+                    }
+                    merged.insert("exercises".to_string(), json!([step.clone()]));
+                    grouped.push(Value::Object(merged));
+                }
+                None => grouped.push(step.clone()),
+            }
+        }
+
+        grouped
+    }
+
+    /// End condition for a `reps` value: a rep count, or the lap button for
+    /// `"AMRAP"` and anything else that is not a number.
+    fn reps_end_condition(reps: &Value) -> (i32, &'static str, Option<Value>) {
+        let count = match reps {
+            Value::String(s) if !s.to_uppercase().contains("AMRAP") => s.parse::<i64>().ok(),
+            Value::Number(n) => n.as_i64(),
             _ => None,
+        };
+        match count {
+            Some(n) => (CONDITION_ID_REPS, CONDITION_REPS, Some(json!(n))),
+            None => (CONDITION_ID_LAP_BUTTON, CONDITION_LAP_BUTTON, None),
+        }
+    }
+
+    /// `Tempo 3-1-1 | TUT 40s` style text for a step's tempo and time under tension.
+    pub fn tempo_text(tempo: Option<&str>, tut_seconds: Option<i64>) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(tempo) = tempo.map(str::trim).filter(|t| !t.is_empty()) {
+            parts.push(format!("Tempo {}", tempo));
+        }
+        if let Some(tut) = tut_seconds.filter(|t| *t > 0) {
+            parts.push(format!("TUT {}s", tut));
         }
+        (!parts.is_empty()).then(|| parts.join(" | "))
     }
 
+    /// A drop set's weight in kg: absolute (`60`, `"60kg"`, `"135lb"`) or a
+    /// percentage of the previous set (`"80%"`, or `"-20%"` for a 20% drop),
+    /// rounded to 0.5kg.
+    fn parse_drop_weight(val: &Value, previous_kg: Option<f64>) -> Option<f64> {
+        if let Some(pct) = val.as_str().and_then(|s| s.trim().strip_suffix('%')) {
+            let pct = pct.trim().parse::<f64>().ok()?;
+            let factor = if pct < 0.0 {
+                1.0 + pct / 100.0
+            } else {
+                pct / 100.0
+            };
+            let kg = previous_kg? * factor;
+            return (kg > 0.0).then(|| (kg * 2.0).round() / 2.0);
+        }
+        Self::parse_weight(val)
+    }
+
+    /// Rest step for a `rest` value in seconds (or "90s"/"2min"), or `"LAP"` for an
+    /// untimed rest that ends on the lap button. Like the exercise steps it carries
+    /// explicit null `category`/`exerciseName`, so normal and generic payloads have
+    /// the same shape.
+    fn rest_step(order: i32, rest: &Value) -> Option<Value> {
+        let (cond_id, cond_key, end_val) = match rest.as_str() {
+            Some(s) if s.trim().eq_ignore_ascii_case("lap") => {
+                (CONDITION_ID_LAP_BUTTON, CONDITION_LAP_BUTTON, Value::Null)
+            }
+            _ => (
+                CONDITION_ID_TIME,
+                CONDITION_TIME,
+                json!(Self::parse_duration(rest)?),
+            ),
+        };
+        Some(json!({
+            "type": "ExecutableStepDTO",
+            "stepOrder": order,
+            "stepType": {
+                "stepTypeId": STEP_TYPE_ID_REST,
+                "stepTypeKey": STEP_TYPE_REST,
+            },
+            "childStepId": null,
+            "description": null,
+            "endCondition": {
+                "conditionTypeId": cond_id,
+                "conditionTypeKey": cond_key,
+            },
+            "endConditionValue": end_val,
+            "targetType": {
+                "workoutTargetTypeId": TARGET_ID_NO_TARGET,
+                "workoutTargetTypeKey": TARGET_NO_TARGET,
+            },
+            "category": null,
+            "exerciseName": null,
+        }))
+    }
+
+    /// Builds the Garmin strength workout payload for an AI spec.
+    ///
+    /// With `robust` set, every step is generic (no `category`/`exerciseName`, no
+    /// `weightValue`) and the exercise and weight move into the step description.
+    /// `GarminClient::create_and_schedule_workout` retries with this variant when
+    /// Garmin answers the normal upload with a 400, which happens when a resolved
+    /// category/exercise pair is not accepted for the account (e.g. newer exercises
+    /// missing from its exercise catalog). Step count and `stepOrder` sequencing,
+    /// including rest steps, are identical in both variants.
     pub fn build_workout_payload(&self, data: &Value, robust: bool) -> Value {
+        self.build_workout_payload_with_resolutions(data, robust).0
+    }
+
+    /// `build_workout_payload`, plus how each strength exercise name was resolved
+    /// (running and swim workouts resolve none).
+    pub fn build_workout_payload_with_resolutions(
+        &self,
+        data: &Value,
+        robust: bool,
+    ) -> (Value, Vec<ExerciseResolution>) {
+        if Self::is_running_workout(data) {
+            return (self.build_running_workout_payload(data), Vec::new());
+        }
+        if Self::is_swim_workout(data) {
+            return (self.build_swim_workout_payload(data), Vec::new());
+        }
+
+        let mut resolutions = Vec::new();
         let mut steps_payload = Vec::new();
         let mut order = 1;
 
         if let Some(steps) = data.get("steps").and_then(|s| s.as_array()) {
-            for step in steps {
+            let steps = Self::group_supersets(steps);
+            for step in &steps {
                 let phase = step
                     .get("phase")
                     .and_then(|p| p.as_str())
@@ -302,10 +1044,20 @@ impl WorkoutBuilder {
                         .and_then(|e| e.as_str())
                         .unwrap_or("BENCH_PRESS");
 
-                    let (cat_key, ex_key) = self.resolve_exercise(raw_name);
+                    let (cat_key, ex_key, resolution) = self.resolve_exercise_logged(raw_name);
+                    resolutions.push(resolution);
                     let is_unrecognized = cat_key.is_none();
 
                     let reps = sub_ex.get("reps").or_else(|| step.get("reps"));
+                    let tempo = sub_ex
+                        .get("tempo")
+                        .or_else(|| step.get("tempo"))
+                        .and_then(|t| t.as_str());
+                    let tut_seconds = sub_ex
+                        .get("tut_seconds")
+                        .or_else(|| step.get("tut_seconds"))
+                        .and_then(|t| t.as_i64())
+                        .filter(|t| *t > 0);
                     let duration = sub_ex
                         .get("time")
                         .or_else(|| step.get("time"))
@@ -320,23 +1072,8 @@ impl WorkoutBuilder {
                         if step_type_id != STEP_TYPE_ID_WARMUP
                             && step_type_id != STEP_TYPE_ID_COOLDOWN
                         {
-                            if let Some(r_str) = reps_value.as_str() {
-                                if r_str.to_uppercase().contains("AMRAP") {
-                                    end_cond_id = CONDITION_ID_LAP_BUTTON;
-                                    end_cond_key = CONDITION_LAP_BUTTON;
-                                } else if let Ok(n) = r_str.parse::<i64>() {
-                                    end_val = Some(json!(n));
-                                    end_cond_id = CONDITION_ID_REPS;
-                                    end_cond_key = CONDITION_REPS;
-                                } else {
-                                    end_cond_id = CONDITION_ID_LAP_BUTTON;
-                                    end_cond_key = CONDITION_LAP_BUTTON;
-                                }
-                            } else if let Some(n) = reps_value.as_i64() {
-                                end_val = Some(json!(n));
-                                end_cond_id = CONDITION_ID_REPS;
-                                end_cond_key = CONDITION_REPS;
-                            }
+                            (end_cond_id, end_cond_key, end_val) =
+                                Self::reps_end_condition(reps_value);
                         }
                     } else if let Some(d) = duration {
                         if let Some(sec) = Self::parse_duration(d) {
@@ -344,6 +1081,25 @@ impl WorkoutBuilder {
                             end_cond_key = CONDITION_TIME;
                             end_val = Some(json!(sec));
                         }
+                    } else if let Some(tut) = tut_seconds {
+                        end_cond_id = CONDITION_ID_TIME;
+                        end_cond_key = CONDITION_TIME;
+                        end_val = Some(json!(tut));
+                    }
+
+                    if end_val.is_none() {
+                        let default_secs = if step_type_id == STEP_TYPE_ID_WARMUP {
+                            self.default_warmup_secs
+                        } else if step_type_id == STEP_TYPE_ID_COOLDOWN {
+                            self.default_cooldown_secs
+                        } else {
+                            0
+                        };
+                        if default_secs > 0 {
+                            end_cond_id = CONDITION_ID_TIME;
+                            end_cond_key = CONDITION_TIME;
+                            end_val = Some(json!(default_secs));
+                        }
                     }
 
                     let weight_val = sub_ex
@@ -359,21 +1115,23 @@ impl WorkoutBuilder {
                         .or_else(|| step.get("note"))
                         .and_then(|n| n.as_str())
                         .unwrap_or("");
+                    // Garmin has no tempo field, so tempo/TUT travel in the description.
+                    let note = match Self::tempo_text(tempo, tut_seconds) {
+                        Some(tempo) if note.is_empty() => tempo,
+                        Some(tempo) => format!("{} | {}", tempo, note),
+                        None => note.to_string(),
+                    };
 
                     let mut description = if note.is_empty() {
                         None
                     } else {
-                        Some(note.to_string())
+                        Some(note.clone())
                     };
 
                     if robust || is_unrecognized {
                         category_obj = None;
                         exercise_name_obj = None;
-                        let mut desc = format!("Exercise: {}. {}", raw_name, note);
-                        if let Some(w) = weight_val {
-                            desc.push_str(&format!(" Target: {}kg", w));
-                        }
-                        description = Some(desc.trim().to_string());
+                        description = Some(self.generic_description(raw_name, &note, weight_val));
                     }
 
                     let mut step_dict = json!({
@@ -414,44 +1172,81 @@ impl WorkoutBuilder {
                         }
                     }
 
-                    if use_repeat_group {
-                        group_steps.push(step_dict);
+                    let drops = sub_ex
+                        .get("dropsets")
+                        .or_else(|| step.get("dropsets"))
+                        .and_then(|d| d.as_array())
+                        .filter(|_| step_type_id == STEP_TYPE_ID_INTERVAL);
+                    let target = if use_repeat_group {
+                        &mut group_steps
                     } else {
-                        steps_payload.push(step_dict);
-                    }
+                        &mut steps_payload
+                    };
+                    target.push(step_dict.clone());
                     order += 1;
+
+                    // Each drop follows the set after a short transition rest.
+                    let mut previous_weight = weight_val;
+                    for (i, drop) in drops.into_iter().flatten().enumerate() {
+                        if let Some(rest_step) = Self::rest_step(order, &json!(DROPSET_REST_SECS)) {
+                            target.push(rest_step);
+                            order += 1;
+                        }
+
+                        let drop_weight = drop
+                            .get("weight")
+                            .and_then(|w| Self::parse_drop_weight(w, previous_weight));
+                        previous_weight = drop_weight;
+                        let (cond_id, cond_key, drop_end_val) = drop
+                            .get("reps")
+                            .map(Self::reps_end_condition)
+                            .unwrap_or((CONDITION_ID_LAP_BUTTON, CONDITION_LAP_BUTTON, None));
+                        let label = format!("Drop set {}", i + 1);
+
+                        let mut drop_step = step_dict.clone();
+                        drop_step["stepOrder"] = json!(order);
+                        drop_step["endCondition"] = json!({
+                            "conditionTypeId": cond_id,
+                            "conditionTypeKey": cond_key,
+                        });
+                        drop_step["endConditionValue"] = json!(drop_end_val);
+                        if robust || is_unrecognized {
+                            drop_step["description"] =
+                                json!(self.generic_description(raw_name, &label, drop_weight));
+                        } else {
+                            drop_step["description"] = json!(label);
+                        }
+                        if let Some(step_obj) = drop_step.as_object_mut() {
+                            step_obj.remove("weightValue");
+                            step_obj.remove("weightUnit");
+                            if let (Some(w), false) = (drop_weight, robust) {
+                                step_obj.insert("weightValue".to_string(), json!(w));
+                                step_obj.insert(
+                                    "weightUnit".to_string(),
+                                    json!({
+                                        "unitId": UNIT_ID_KILOGRAM,
+                                        "unitKey": UNIT_KILOGRAM,
+                                        "factor": 1000.0
+                                    }),
+                                );
+                            }
+                        }
+                        target.push(drop_step);
+                        order += 1;
+                    }
                 }
 
                 // Add rest if specified (only at the end of the block/superset)
                 if step_type_id == STEP_TYPE_ID_INTERVAL {
-                    if let Some(rest) = step.get("rest") {
-                        if let Some(rest_sec) = Self::parse_duration(rest) {
-                            let rest_step = json!({
-                                "type": "ExecutableStepDTO",
-                                "stepOrder": order,
-                                "stepType": {
-                                    "stepTypeId": STEP_TYPE_ID_REST,
-                                    "stepTypeKey": STEP_TYPE_REST,
-                                },
-                                "childStepId": null,
-                                "endCondition": {
-                                    "conditionTypeId": CONDITION_ID_TIME,
-                                    "conditionTypeKey": CONDITION_TIME,
-                                },
-                                "endConditionValue": rest_sec,
-                                "targetType": {
-                                    "workoutTargetTypeId": TARGET_ID_NO_TARGET,
-                                    "workoutTargetTypeKey": TARGET_NO_TARGET,
-                                }
-                            });
-
-                            if use_repeat_group {
-                                group_steps.push(rest_step);
-                            } else {
-                                steps_payload.push(rest_step);
-                            }
-                            order += 1;
+                    if let Some(rest_step) =
+                        step.get("rest").and_then(|r| Self::rest_step(order, r))
+                    {
+                        if use_repeat_group {
+                            group_steps.push(rest_step);
+                        } else {
+                            steps_payload.push(rest_step);
                         }
+                        order += 1;
                     }
                 }
 
@@ -460,8 +1255,8 @@ impl WorkoutBuilder {
                         "type": "RepeatGroupDTO",
                         "stepOrder": repeat_order,
                         "stepType": {
-                            "stepTypeId": 6,
-                            "stepTypeKey": "repeat"
+                            "stepTypeId": STEP_TYPE_ID_REPEAT,
+                            "stepTypeKey": STEP_TYPE_REPEAT
                         },
                         "numberOfIterations": sets,
                         "smartRepeat": false,
@@ -478,7 +1273,7 @@ impl WorkoutBuilder {
             .unwrap_or("Imported Strength Workout");
         let description = data.get("description").and_then(|d| d.as_str());
 
-        json!({
+        let payload = json!({
             "workoutName": workout_name,
             "description": description,
             "sportType": {
@@ -495,14 +1290,18 @@ impl WorkoutBuilder {
                     "workoutSteps": steps_payload
                 }
             ]
-        })
+        });
+        (payload, resolutions)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::WorkoutBuilder;
+    use crate::models::Units;
     use serde_json::json;
+    use serde_json::Value;
+    use std::collections::HashMap;
 
     #[test]
     fn parse_duration_handles_minutes_text() {
@@ -518,4 +1317,496 @@ mod tests {
     fn parse_weight_handles_numeric_string() {
         assert_eq!(WorkoutBuilder::parse_weight(&json!("42.5kg")), Some(42.5));
     }
+
+    #[test]
+    fn parse_weight_converts_pounds_to_kilograms() {
+        let kg = WorkoutBuilder::parse_weight(&json!("135lb")).unwrap();
+        assert!((kg - 61.2).abs() < 0.05, "{kg}");
+        assert_eq!(WorkoutBuilder::parse_weight(&json!("135 LBS")), Some(kg));
+
+        // Stored kilograms render back in the athlete's unit.
+        assert_eq!(Units::Imperial.format_weight(kg), "135lb");
+        assert_eq!(Units::Metric.format_weight(kg), "61.2kg");
+    }
+
+    #[test]
+    fn robust_descriptions_are_verbose_by_default_and_concise_when_enabled() {
+        let spec = json!({
+            "steps": [
+                { "exercise": "BENCH_PRESS", "reps": 8, "weight": 80, "note": "Pause at chest" }
+            ]
+        });
+        let description = |builder: &WorkoutBuilder| {
+            builder.build_workout_payload(&spec, true)["workoutSegments"][0]["workoutSteps"][0]
+                ["description"]
+                .clone()
+        };
+
+        let verbose = WorkoutBuilder::empty();
+        assert_eq!(
+            description(&verbose),
+            json!("Exercise: BENCH_PRESS. Pause at chest Target: 80kg")
+        );
+
+        let concise = verbose.with_concise_generic_descriptions(true);
+        assert_eq!(description(&concise), json!("Bench Press 80kg"));
+    }
+
+    #[test]
+    fn grouped_steps_become_one_superset_with_trailing_rest() {
+        let builder = WorkoutBuilder::empty();
+        let spec = json!({
+            "steps": [
+                { "phase": "interval", "group": "A", "exercise": "BICEP_CURL", "reps": 12, "sets": 3, "rest": 30 },
+                { "phase": "interval", "group": "A", "exercise": "TRICEPS_EXTENSION", "reps": 12, "sets": 3, "rest": 90 },
+                { "phase": "interval", "exercise": "PLANK", "duration": 60 }
+            ]
+        });
+
+        let payload = builder.build_workout_payload(&spec, false);
+        let steps = payload["workoutSegments"][0]["workoutSteps"]
+            .as_array()
+            .unwrap();
+        assert_eq!(steps.len(), 2);
+
+        let superset = &steps[0];
+        assert_eq!(superset["type"], "RepeatGroupDTO");
+        assert_eq!(superset["numberOfIterations"], 3);
+        let children = superset["workoutSteps"].as_array().unwrap();
+        assert_eq!(children.len(), 3);
+        assert_eq!(children[0]["endConditionValue"], 12);
+        assert_eq!(children[1]["endConditionValue"], 12);
+        assert_eq!(children[0]["stepType"]["stepTypeKey"], "interval");
+        assert_eq!(children[1]["stepType"]["stepTypeKey"], "interval");
+        assert_eq!(children[2]["stepType"]["stepTypeKey"], "rest");
+        assert_eq!(children[2]["endConditionValue"], 90);
+
+        assert_eq!(steps[1]["type"], "ExecutableStepDTO");
+        assert_eq!(steps[1]["endConditionValue"], 60);
+    }
+
+    #[test]
+    fn missing_warmup_and_cooldown_are_inserted_and_steps_renumbered() {
+        let builder = WorkoutBuilder::empty();
+        let mut spec = json!({
+            "workoutName": "Push Day",
+            "steps": [
+                { "phase": "interval", "exercise": "BENCH_PRESS", "reps": 8, "weight": 80 },
+                { "phase": "interval", "exercise": "PUSH_UP", "reps": 12 }
+            ]
+        });
+
+        let added = WorkoutBuilder::ensure_warmup_cooldown(&mut spec);
+        assert_eq!(added.len(), 2);
+        let steps = spec["steps"].as_array().unwrap();
+        assert_eq!(steps.len(), 4);
+        assert_eq!(steps[0]["phase"], "warmup");
+        assert_eq!(steps[3]["phase"], "cooldown");
+
+        let payload = builder.build_workout_payload(&spec, false);
+        let built = payload["workoutSegments"][0]["workoutSteps"]
+            .as_array()
+            .unwrap();
+        assert_eq!(built[0]["stepType"]["stepTypeKey"], "warmup");
+        assert_eq!(built[0]["endConditionValue"], 300);
+        let orders: Vec<i64> = built
+            .iter()
+            .map(|s| s["stepOrder"].as_i64().unwrap())
+            .collect();
+        assert_eq!(orders, (1..=orders.len() as i64).collect::<Vec<_>>());
+
+        // Already complete specs and running specs are left unchanged.
+        assert!(WorkoutBuilder::ensure_warmup_cooldown(&mut spec).is_empty());
+        let mut run = json!({ "sport": "running", "steps": [{ "distance": "5km" }] });
+        assert!(WorkoutBuilder::ensure_warmup_cooldown(&mut run).is_empty());
+    }
+
+    #[test]
+    fn duration_less_warmup_defaults_to_timed_step() {
+        let builder = WorkoutBuilder::empty();
+        let spec = json!({
+            "steps": [
+                { "phase": "warmup", "exercise": "DYNAMIC_WARMUP" },
+                { "phase": "cooldown", "exercise": "STRETCHING", "duration": "10min" }
+            ]
+        });
+
+        let payload = builder.build_workout_payload(&spec, true);
+        let steps = payload["workoutSegments"][0]["workoutSteps"]
+            .as_array()
+            .unwrap();
+        assert_eq!(steps[0]["endCondition"]["conditionTypeKey"], "time");
+        assert_eq!(steps[0]["endConditionValue"], 300);
+        assert_eq!(steps[1]["endConditionValue"], 600);
+
+        let lap_button = builder.with_default_phase_durations(0, 0);
+        let payload = lap_button.build_workout_payload(&spec, true);
+        let warmup = &payload["workoutSegments"][0]["workoutSteps"][0];
+        assert_eq!(warmup["endCondition"]["conditionTypeKey"], "lap.button");
+    }
+
+    #[test]
+    fn weights_above_recorded_max_are_capped() {
+        let builder = WorkoutBuilder::empty();
+        let maxes = HashMap::from([
+            ("BENCH_PRESS".to_string(), 80.0),
+            ("SQUAT".to_string(), 100.0),
+        ]);
+        let mut spec = json!({
+            "steps": [
+                { "phase": "interval", "exercise": "BENCH_PRESS", "reps": 5, "weight": "300kg" },
+                { "phase": "interval", "exercise": "SQUAT", "reps": 5, "weight": 105 },
+                { "phase": "interval", "exercises": [
+                    { "exercise": "BENCH_PRESS", "reps": 8, "weight": 90 },
+                    { "exercise": "DEADLIFT", "reps": 5, "weight": 500 }
+                ] }
+            ]
+        });
+
+        let adjustments = builder.cap_weights(&mut spec, &maxes, 1.1);
+        assert_eq!(adjustments.len(), 2);
+        assert_eq!(spec["steps"][0]["weight"], json!(88.0));
+        assert_eq!(spec["steps"][1]["weight"], json!(105));
+        assert_eq!(spec["steps"][2]["exercises"][0]["weight"], json!(88.0));
+        assert_eq!(spec["steps"][2]["exercises"][1]["weight"], json!(500));
+    }
+
+    #[test]
+    fn track_session_builds_nested_running_payload() {
+        let builder = WorkoutBuilder::empty();
+        let spec = json!({
+            "workoutName": "Track 6x800",
+            "sport": "running",
+            "steps": [
+                { "phase": "warmup", "duration": "15min", "pace": "5:30-6:00" },
+                { "repeat": 2, "steps": [
+                    { "repeat": 3, "steps": [
+                        { "phase": "interval", "distance": "800m", "pace": "3:50-4:00" },
+                        { "phase": "recovery", "distance": "400m" }
+                    ] },
+                    { "phase": "rest", "duration": 180 }
+                ] },
+                { "phase": "cooldown" }
+            ]
+        });
+
+        let payload = builder.build_workout_payload(&spec, false);
+        assert_eq!(payload["sportType"]["sportTypeKey"], "running");
+        let steps = payload["workoutSegments"][0]["workoutSteps"]
+            .as_array()
+            .unwrap();
+        assert_eq!(steps.len(), 3);
+
+        let warmup = &steps[0];
+        assert_eq!(warmup["stepOrder"], 1);
+        assert_eq!(warmup["endConditionValue"], 900);
+        assert_eq!(warmup["targetType"]["workoutTargetTypeKey"], "pace.zone");
+
+        let outer = &steps[1];
+        assert_eq!(outer["type"], "RepeatGroupDTO");
+        assert_eq!(outer["stepOrder"], 2);
+        assert_eq!(outer["numberOfIterations"], 2);
+        let inner = &outer["workoutSteps"][0];
+        assert_eq!(inner["type"], "RepeatGroupDTO");
+        assert_eq!(inner["stepOrder"], 3);
+        assert_eq!(inner["numberOfIterations"], 3);
+
+        let rep = &inner["workoutSteps"][0];
+        assert_eq!(rep["stepOrder"], 4);
+        assert_eq!(rep["endCondition"]["conditionTypeKey"], "distance");
+        assert_eq!(rep["endConditionValue"], 800.0);
+        let slowest = rep["targetValueOne"].as_f64().unwrap();
+        let fastest = rep["targetValueTwo"].as_f64().unwrap();
+        assert!((slowest - 1000.0 / 240.0).abs() < 1e-9);
+        assert!((fastest - 1000.0 / 230.0).abs() < 1e-9);
+
+        let recovery = &inner["workoutSteps"][1];
+        assert_eq!(recovery["stepType"]["stepTypeKey"], "recovery");
+        assert_eq!(recovery["targetType"]["workoutTargetTypeKey"], "no.target");
+        assert_eq!(outer["workoutSteps"][1]["stepOrder"], 6);
+        assert_eq!(outer["workoutSteps"][1]["endConditionValue"], 180);
+
+        let cooldown = &steps[2];
+        assert_eq!(cooldown["stepOrder"], 7);
+        assert_eq!(cooldown["endConditionValue"], 300);
+    }
+
+    #[test]
+    fn swim_spec_builds_pool_swim_payload_with_strokes() {
+        let builder = WorkoutBuilder::new();
+        let spec = json!({
+            "workoutName": "CSS Set",
+            "sport": "swimming",
+            "pool_length": "50m",
+            "steps": [
+                { "phase": "warmup", "distance": "400m", "stroke": "free" },
+                { "repeat": 4, "steps": [
+                    { "phase": "interval", "distance": "100m", "stroke": "fly" },
+                    { "phase": "rest", "duration": 20 }
+                ] },
+                { "phase": "cooldown", "distance": "200m", "stroke": "backstroke" }
+            ]
+        });
+
+        let payload = builder.build_workout_payload(&spec, false);
+        assert_eq!(payload["sportType"]["sportTypeKey"], "swimming");
+        assert_eq!(payload["poolLength"], 50.0);
+        let steps = payload["workoutSegments"][0]["workoutSteps"]
+            .as_array()
+            .unwrap();
+        assert_eq!(steps[0]["endConditionValue"], 400.0);
+        assert_eq!(steps[0]["strokeType"]["strokeTypeKey"], "free");
+        assert_eq!(steps[1]["numberOfIterations"], 4);
+        assert_eq!(
+            steps[1]["workoutSteps"][0]["strokeType"]["strokeTypeKey"],
+            "fly"
+        );
+        assert_eq!(steps[2]["strokeType"]["strokeTypeKey"], "backstroke");
+
+        let default_pool =
+            builder.build_workout_payload(&json!({ "sport": "lap_swimming", "steps": [] }), false);
+        assert_eq!(default_pool["poolLength"], 25.0);
+    }
+
+    #[test]
+    fn payload_build_records_how_each_exercise_resolved() {
+        let mut exercise_db = HashMap::new();
+        exercise_db.insert(
+            "BARBELL_ROW".to_string(),
+            ("ROW".to_string(), "BARBELL_ROW".to_string()),
+        );
+        let mut builder = WorkoutBuilder::empty();
+        builder.exercise_db = exercise_db;
+        let spec = json!({
+            "steps": [
+                { "phase": "interval", "exercise": "barbell_row", "reps": 8 },
+                { "phase": "interval", "exercise": "Barbel Row", "reps": 8 },
+                { "phase": "interval", "exercise": "goblet_squat", "reps": 10 },
+                { "phase": "interval", "exercise": "ZERCHER_CARRY", "reps": 10 },
+                { "phase": "interval", "exercise": "Handstand", "reps": 5 }
+            ]
+        });
+
+        let (_, resolutions) = builder.build_workout_payload_with_resolutions(&spec, false);
+        let summary: Vec<_> = resolutions
+            .iter()
+            .map(|r| {
+                (
+                    r.input.as_str(),
+                    r.matched_key.as_deref(),
+                    r.method.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("barbell_row", Some("BARBELL_ROW"), "exact"),
+                ("Barbel Row", Some("BARBELL_ROW"), "fuzzy"),
+                ("goblet_squat", Some("GOBLET_SQUAT"), "manual"),
+                ("ZERCHER_CARRY", Some("ZERCHER_CARRY"), "fallback"),
+                ("Handstand", None, "none"),
+            ]
+        );
+        assert_eq!(resolutions[1].distance, Some(2));
+        assert!(resolutions
+            .iter()
+            .enumerate()
+            .all(|(i, r)| i == 1 || r.distance.is_none()));
+    }
+
+    #[test]
+    fn tempo_goes_into_the_description_and_tut_without_reps_is_timed() {
+        let builder = WorkoutBuilder::new();
+        let spec = json!({
+            "steps": [
+                { "exercise": "BENCH_PRESS", "reps": 10, "tempo": "3-1-1", "note": "Control it" },
+                { "exercise": "BARBELL_BACK_SQUAT", "tempo": "4-0-1", "tut_seconds": 45 }
+            ]
+        });
+
+        let payload = builder.build_workout_payload(&spec, false);
+        let steps = &payload["workoutSegments"][0]["workoutSteps"];
+        assert_eq!(steps[0]["description"], "Tempo 3-1-1 | Control it");
+        assert_eq!(steps[0]["endCondition"]["conditionTypeKey"], "reps");
+        assert_eq!(steps[0]["endConditionValue"], 10);
+        assert_eq!(steps[1]["description"], "Tempo 4-0-1 | TUT 45s");
+        assert_eq!(steps[1]["endCondition"]["conditionTypeKey"], "time");
+        assert_eq!(steps[1]["endConditionValue"], 45);
+
+        // Generic (robust) uploads keep the tempo too.
+        let robust = builder.build_workout_payload(&spec, true);
+        let description = robust["workoutSegments"][0]["workoutSteps"][0]["description"]
+            .as_str()
+            .unwrap();
+        assert!(description.contains("Tempo 3-1-1"), "{description}");
+    }
+
+    #[test]
+    fn dropsets_follow_the_base_set_in_order() {
+        let builder = WorkoutBuilder::new();
+        let spec = json!({
+            "steps": [
+                {
+                    "phase": "interval",
+                    "exercise": "BENCH_PRESS",
+                    "weight": 80,
+                    "reps": 3,
+                    "dropsets": [
+                        { "reps": 6, "weight": "-20%" },
+                        { "reps": "AMRAP", "weight": 50 }
+                    ],
+                    "rest": 120
+                }
+            ]
+        });
+
+        let payload = builder.build_workout_payload(&spec, false);
+        let steps = payload["workoutSegments"][0]["workoutSteps"]
+            .as_array()
+            .unwrap();
+        let intervals: Vec<_> = steps
+            .iter()
+            .filter(|s| s["stepType"]["stepTypeKey"] == "interval")
+            .map(|s| {
+                (
+                    s["endConditionValue"].clone(),
+                    s["weightValue"].as_f64(),
+                    s["category"].clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            intervals,
+            [
+                (json!(3), Some(80.0), json!("BENCH_PRESS")),
+                (json!(6), Some(64.0), json!("BENCH_PRESS")),
+                (Value::Null, Some(50.0), json!("BENCH_PRESS")),
+            ]
+        );
+
+        let kinds: Vec<_> = steps
+            .iter()
+            .map(|s| s["stepType"]["stepTypeKey"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            kinds,
+            ["interval", "rest", "interval", "rest", "interval", "rest"]
+        );
+        assert_eq!(steps[1]["endConditionValue"], 10);
+        assert_eq!(steps[5]["endConditionValue"], 120);
+        let orders: Vec<_> = steps
+            .iter()
+            .map(|s| s["stepOrder"].as_i64().unwrap())
+            .collect();
+        assert_eq!(orders, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(steps[4]["endCondition"]["conditionTypeKey"], "lap.button");
+    }
+
+    #[test]
+    fn interval_sessions_are_downregulated_to_easy_runs() {
+        let mut intervals = json!({
+            "workoutName": "FJ-AI: Track 6x800",
+            "sport": "running",
+            "scheduledDate": "2026-03-14",
+            "steps": [
+                { "phase": "warmup", "duration": "15min" },
+                { "repeat": 6, "steps": [{ "phase": "interval", "distance": "800m", "pace": "3:50-4:00" }] }
+            ]
+        });
+        let mut strength =
+            json!({ "steps": [{ "exercise": "BENCH_PRESS", "reps": 5, "sets": 5 }] });
+
+        assert!(WorkoutBuilder::downregulate_to_recovery(
+            &mut intervals,
+            "Low Body Battery."
+        ));
+        assert!(!WorkoutBuilder::is_interval_heavy(&intervals));
+        assert_eq!(intervals["workoutName"], "FJ-AI: Track 6x800");
+        assert_eq!(intervals["steps"].as_array().unwrap().len(), 3);
+        assert!(!WorkoutBuilder::downregulate_to_recovery(
+            &mut strength,
+            "Low Body Battery."
+        ));
+    }
+
+    #[test]
+    fn reload_replaces_entries_and_keeps_them_when_csv_is_missing() {
+        let mut builder = WorkoutBuilder::empty();
+        let dir =
+            std::env::temp_dir().join(format!("fitness_journal_exercises_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let csv_path = dir.join("exercises.csv");
+        std::fs::write(
+            &csv_path,
+            "Garmin exercises\nName,CATEGORY_GARMIN,NAME_GARMIN\nZercher Squat,SQUAT,ZERCHER_SQUAT\n",
+        )
+        .unwrap();
+
+        let loaded = builder.reload_from(csv_path.to_str().unwrap()).unwrap();
+        assert_eq!(loaded, builder.exercise_db.len());
+        assert_eq!(
+            builder.resolve_exercise("zercher squat"),
+            (Some("SQUAT".to_string()), Some("ZERCHER_SQUAT".to_string()))
+        );
+
+        let missing = dir.join("missing.csv");
+        let err = builder.reload_from(missing.to_str().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("not found"));
+        assert_eq!(builder.exercise_db.len(), loaded);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    /// Flattens steps depth-first as (stepOrder, stepTypeKey, endConditionValue).
+    fn flatten_steps(steps: &[serde_json::Value], out: &mut Vec<(i64, String, serde_json::Value)>) {
+        for step in steps {
+            out.push((
+                step["stepOrder"].as_i64().unwrap(),
+                step["stepType"]["stepTypeKey"]
+                    .as_str()
+                    .unwrap()
+                    .to_string(),
+                step["endConditionValue"].clone(),
+            ));
+            if let Some(children) = step["workoutSteps"].as_array() {
+                flatten_steps(children, out);
+            }
+        }
+    }
+
+    #[test]
+    fn robust_payload_keeps_rest_steps_and_step_order() {
+        let builder = WorkoutBuilder::new();
+        let spec = json!({
+            "workoutName": "Strength A",
+            "steps": [
+                { "phase": "warmup", "exercise": "ROW", "duration": "5min" },
+                { "phase": "interval", "exercise": "BENCH_PRESS", "weight": 60, "reps": 8, "sets": 3, "rest": 90 },
+                { "phase": "interval", "exercise": "PULL_UP", "reps": "AMRAP", "rest": "LAP" },
+                { "phase": "cooldown", "exercise": "YOGA", "duration": "10min" }
+            ]
+        });
+
+        let mut flattened = Vec::new();
+        for robust in [false, true] {
+            let payload = builder.build_workout_payload(&spec, robust);
+            let steps = payload["workoutSegments"][0]["workoutSteps"]
+                .as_array()
+                .unwrap()
+                .clone();
+            let mut flat = Vec::new();
+            flatten_steps(&steps, &mut flat);
+            let orders: Vec<i64> = flat.iter().map(|(o, _, _)| *o).collect();
+            assert_eq!(orders, (1..=flat.len() as i64).collect::<Vec<_>>());
+            flattened.push(flat);
+        }
+
+        let (normal, robust) = (&flattened[0], &flattened[1]);
+        assert_eq!(normal, robust);
+        let rests: Vec<_> = robust.iter().filter(|(_, key, _)| key == "rest").collect();
+        assert_eq!(rests.len(), 2);
+        assert_eq!(rests[0].2, json!(90));
+        assert_eq!(rests[1].2, serde_json::Value::Null);
+    }
 }